@@ -0,0 +1,106 @@
+use crate::write::{file_integrity, list_package_files};
+use crate::PackageFiles;
+use anyhow::Result;
+use rayon::prelude::*;
+use ssri::Integrity;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Differences between the files of an unpacked package and its files index
+#[derive(Debug, Default, PartialEq)]
+pub struct FilesDiff {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl FilesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Re-hashes the files of the package at `pkg_dir` and compares them
+/// against the files recorded in the store index
+pub fn check_pkg_files<P: AsRef<Path>>(pkg_dir: P, files: &PackageFiles) -> Result<FilesDiff> {
+    let pkg_dir = pkg_dir.as_ref();
+    let on_disk = list_package_files(pkg_dir);
+    let on_disk_names = on_disk
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut added = on_disk_names
+        .iter()
+        .filter(|name| !files.contains_key(**name))
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+
+    let mut removed = vec![];
+    let mut modified = files
+        .par_iter()
+        .filter(|(name, _)| on_disk_names.contains(name.as_str()))
+        .map(|(name, info)| -> Result<Option<String>> {
+            let content = fs::read(pkg_dir.join(name))?;
+            if content.len() as u64 != info.size {
+                return Ok(Some(name.clone()));
+            }
+            let expected = info.integrity.parse::<Integrity>()?;
+            Ok(expected
+                .matches(&file_integrity(&content))
+                .is_none()
+                .then(|| name.clone()))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>>>()?;
+
+    for name in files.keys() {
+        if !on_disk_names.contains(name.as_str()) {
+            removed.push(name.clone());
+        }
+    }
+
+    added.sort();
+    modified.sort();
+    removed.sort();
+
+    Ok(FilesDiff {
+        added,
+        modified,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_files_from_dir;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn detect_edited_added_and_removed_files() {
+        let cafs_dir = tempdir().unwrap();
+        let pkg_dir = tempdir().unwrap();
+        fs::write(pkg_dir.path().join("package.json"), "{}").unwrap();
+        fs::write(pkg_dir.path().join("index.js"), "module.exports = 1").unwrap();
+        fs::write(pkg_dir.path().join("README.md"), "# foo").unwrap();
+
+        let files = add_files_from_dir(cafs_dir.path(), pkg_dir.path()).unwrap();
+        assert!(check_pkg_files(pkg_dir.path(), &files).unwrap().is_empty());
+
+        fs::write(pkg_dir.path().join("index.js"), "module.exports = 2").unwrap();
+        fs::write(pkg_dir.path().join("extra.js"), "").unwrap();
+        fs::remove_file(pkg_dir.path().join("README.md")).unwrap();
+
+        assert_eq!(
+            check_pkg_files(pkg_dir.path(), &files).unwrap(),
+            FilesDiff {
+                added: vec!["extra.js".to_string()],
+                modified: vec!["index.js".to_string()],
+                removed: vec!["README.md".to_string()],
+            }
+        );
+    }
+}
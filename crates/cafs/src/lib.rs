@@ -0,0 +1,147 @@
+mod check;
+mod tarball;
+mod verify;
+mod write;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use ssri::Integrity;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub use check::{check_pkg_files, FilesDiff};
+pub use tarball::add_files_from_tarball;
+pub use verify::{verify_store, CorruptedFile, VerifyStoreOptions, QUARANTINE_DIR};
+pub use write::{add_files_from_dir, file_integrity, write_buffer_to_cafs};
+
+/// Files are grouped by this directory inside the store
+pub const CAFS_DIR: &str = "files";
+const EXECUTABLE_MASK: u32 = 0o111;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileType {
+    Exec,
+    NonExec,
+    Index,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageFileInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<u128>,
+    pub integrity: String,
+    pub mode: u32,
+    pub size: u64,
+}
+
+pub type PackageFiles = HashMap<String, PackageFileInfo>;
+
+/// Contents of the `-index.json` file that is written next to the
+/// files of every package added to the store
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageFilesIndex {
+    pub files: PackageFiles,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side_effects: Option<HashMap<String, PackageFiles>>,
+}
+
+pub fn cafs_dir<P: AsRef<Path>>(store_dir: P) -> PathBuf {
+    store_dir.as_ref().join(CAFS_DIR)
+}
+
+pub fn mode_is_executable(mode: u32) -> bool {
+    mode & EXECUTABLE_MASK == EXECUTABLE_MASK
+}
+
+pub fn content_path_from_hex(file_type: FileType, hex: &str) -> PathBuf {
+    let path = Path::new(&hex[..2]).join(&hex[2..]);
+
+    match file_type {
+        FileType::Exec => PathBuf::from(format!("{}-exec", path.to_string_lossy())),
+        FileType::NonExec => path,
+        FileType::Index => PathBuf::from(format!("{}-index.json", path.to_string_lossy())),
+    }
+}
+
+pub fn get_file_path_in_cafs<P: AsRef<Path>>(
+    cafs_dir: P,
+    integrity: &Integrity,
+    file_type: FileType,
+) -> PathBuf {
+    let (_, hex) = integrity.to_hex();
+    cafs_dir
+        .as_ref()
+        .join(content_path_from_hex(file_type, &hex))
+}
+
+pub fn get_file_path_by_mode_in_cafs<P: AsRef<Path>>(
+    cafs_dir: P,
+    integrity: &Integrity,
+    mode: u32,
+) -> PathBuf {
+    get_file_path_in_cafs(
+        cafs_dir,
+        integrity,
+        if mode_is_executable(mode) {
+            FileType::Exec
+        } else {
+            FileType::NonExec
+        },
+    )
+}
+
+pub fn read_package_files_index<P: AsRef<Path>>(index_path: P) -> Result<PackageFilesIndex> {
+    let content = std::fs::read_to_string(index_path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn write_package_files_index<P: AsRef<Path>>(
+    index_path: P,
+    index: &PackageFilesIndex,
+) -> Result<()> {
+    let index_path = index_path.as_ref();
+    let parent = index_path.parent().unwrap_or_else(|| Path::new("."));
+    std::fs::create_dir_all(parent)?;
+
+    // written to a temp file first so that a crashed process
+    // never leaves a half written index behind
+    let temp_file = temp_path::temp_path_atomic(parent);
+    std::fs::write(&temp_file, serde_json::to_string(index)?)?;
+    std::fs::rename(temp_file, index_path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn content_path() {
+        assert_eq!(
+            content_path_from_hex(FileType::NonExec, "abcdef"),
+            Path::new("ab").join("cdef")
+        );
+        assert_eq!(
+            content_path_from_hex(FileType::Exec, "abcdef"),
+            PathBuf::from(format!("{}-exec", Path::new("ab").join("cdef").display()))
+        );
+        assert_eq!(
+            content_path_from_hex(FileType::Index, "abcdef"),
+            PathBuf::from(format!(
+                "{}-index.json",
+                Path::new("ab").join("cdef").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn executable_mode() {
+        assert!(mode_is_executable(0o755));
+        assert!(!mode_is_executable(0o644));
+        assert!(!mode_is_executable(0o744));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::write::{now_millis, write_buffer_to_cafs};
+use crate::{PackageFileInfo, PackageFiles};
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use std::io::{self, Read};
+use std::path::{Component, Path};
+use tar::{Archive, EntryType};
+
+/// Returns the path of a tarball entry without its first component,
+/// which is the `package/` directory in tarballs created by `npm pack`
+fn strip_first_component(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    components.next()?;
+
+    let mut parts = vec![];
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            // entries that point outside of the package are never extracted
+            _ => return None,
+        }
+    }
+
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Unpacks a gzipped tarball into the store, entry by entry, without
+/// buffering the whole archive. The reader is read to the end, so a
+/// hashing reader sees every byte of the tarball.
+pub fn add_files_from_tarball<P: AsRef<Path>, R: Read>(
+    cafs_dir: P,
+    reader: R,
+) -> Result<PackageFiles> {
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    let mut files = PackageFiles::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+        let relative_path = match strip_first_component(&entry.path()?) {
+            Some(relative_path) => relative_path,
+            None => continue,
+        };
+
+        let mode = entry.header().mode()? & 0o777;
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buffer)?;
+        let (integrity, _) = write_buffer_to_cafs(&cafs_dir, &buffer, mode)?;
+
+        files.insert(
+            relative_path,
+            PackageFileInfo {
+                checked_at: Some(now_millis()),
+                integrity: integrity.to_string(),
+                mode,
+                size: buffer.len() as u64,
+            },
+        );
+    }
+
+    // the tar archive may end before the gzip stream does
+    let mut decoder = archive.into_inner();
+    io::copy(&mut decoder, &mut io::sink())?;
+    io::copy(&mut decoder.into_inner(), &mut io::sink())?;
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_file_path_in_cafs, FileType};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tar::{Builder, Header};
+    use tempfile::tempdir;
+
+    fn append_file(
+        builder: &mut Builder<GzEncoder<Vec<u8>>>,
+        path: &str,
+        content: &[u8],
+        mode: u32,
+    ) {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content).unwrap();
+    }
+
+    #[test]
+    fn unpack_tarball() {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        append_file(&mut builder, "package/package.json", b"{}", 0o644);
+        append_file(
+            &mut builder,
+            "package/bin/cli.js",
+            b"#!/usr/bin/env node",
+            0o755,
+        );
+        append_file(&mut builder, "package", b"", 0o644);
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let cafs_dir = tempdir().unwrap();
+        let files = add_files_from_tarball(cafs_dir.path(), tarball.as_slice()).unwrap();
+
+        let mut names = files.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["bin/cli.js", "package.json"]);
+        assert_eq!(files["bin/cli.js"].mode, 0o755);
+
+        let cli_path = get_file_path_in_cafs(
+            cafs_dir.path(),
+            &files["bin/cli.js"].integrity.parse().unwrap(),
+            FileType::Exec,
+        );
+        assert_eq!(fs::read(cli_path).unwrap(), b"#!/usr/bin/env node");
+    }
+
+    #[test]
+    fn strip_first_path_component() {
+        assert_eq!(
+            strip_first_component(Path::new("package/lib/index.js")),
+            Some("lib/index.js".to_string())
+        );
+        assert_eq!(
+            strip_first_component(Path::new("foo-1.0.0/./index.js")),
+            Some("index.js".to_string())
+        );
+        assert_eq!(strip_first_component(Path::new("package")), None);
+        assert_eq!(
+            strip_first_component(Path::new("package/../../etc/passwd")),
+            None
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::write::file_integrity;
+use crate::CAFS_DIR;
+use anyhow::Result;
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Corrupted files are moved to this directory inside the store
+/// when verifying with `quarantine` turned on
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+#[derive(Default)]
+pub struct VerifyStoreOptions {
+    pub quarantine: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CorruptedFile {
+    pub path: PathBuf,
+    pub expected_hex: String,
+    pub actual_hex: String,
+    pub quarantined_to: Option<PathBuf>,
+}
+
+/// Returns the hex digest encoded in the path of a content file,
+/// or `None` for files that are not content files (index files, temp files)
+fn digest_from_content_path(cafs_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(cafs_dir).ok()?;
+    let mut components = relative.components();
+    let dir = components.next()?.as_os_str().to_str()?;
+    let file_name = components.next()?.as_os_str().to_str()?;
+    if components.next().is_some() || file_name.ends_with("-index.json") {
+        return None;
+    }
+
+    let rest = file_name.strip_suffix("-exec").unwrap_or(file_name);
+    let hex = format!("{}{}", dir, rest);
+    (hex.len() == 128 && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(hex)
+}
+
+/// Checks every content file of the store against the digest in its name
+pub fn verify_store<P: AsRef<Path>>(
+    store_dir: P,
+    opts: VerifyStoreOptions,
+) -> Result<Vec<CorruptedFile>> {
+    let store_dir = store_dir.as_ref();
+    let cafs_dir = store_dir.join(CAFS_DIR);
+    let quarantine_dir = store_dir.join(QUARANTINE_DIR);
+
+    let content_files = WalkDir::new(&cafs_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            digest_from_content_path(&cafs_dir, entry.path())
+                .map(|hex| (entry.path().to_path_buf(), hex))
+        })
+        .collect::<Vec<_>>();
+
+    let mut corrupted = content_files
+        .into_par_iter()
+        .map(|(path, expected_hex)| -> Result<Option<CorruptedFile>> {
+            let (_, actual_hex) = file_integrity(&fs::read(&path)?).to_hex();
+            if actual_hex == expected_hex {
+                return Ok(None);
+            }
+
+            let quarantined_to = if opts.quarantine {
+                fs::create_dir_all(&quarantine_dir)?;
+                let target = quarantine_dir.join(path.file_name().unwrap_or_default());
+                fs::rename(&path, &target)?;
+                Some(target)
+            } else {
+                None
+            };
+
+            Ok(Some(CorruptedFile {
+                path,
+                expected_hex,
+                actual_hex,
+                quarantined_to,
+            }))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>>>()?;
+
+    corrupted.sort_by(|file_1, file_2| file_1.path.cmp(&file_2.path));
+
+    Ok(corrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write_buffer_to_cafs;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn verify_valid_store() {
+        let store_dir = tempdir().unwrap();
+        write_buffer_to_cafs(store_dir.path().join(CAFS_DIR), b"foo", 0o644).unwrap();
+        write_buffer_to_cafs(store_dir.path().join(CAFS_DIR), b"bar", 0o755).unwrap();
+
+        assert_eq!(
+            verify_store(store_dir.path(), VerifyStoreOptions::default()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn quarantine_corrupted_files() {
+        let store_dir = tempdir().unwrap();
+        let (_, valid_file) =
+            write_buffer_to_cafs(store_dir.path().join(CAFS_DIR), b"foo", 0o644).unwrap();
+        let (_, corrupted_file) =
+            write_buffer_to_cafs(store_dir.path().join(CAFS_DIR), b"bar", 0o755).unwrap();
+        fs::write(&corrupted_file, "baz").unwrap();
+
+        let corrupted =
+            verify_store(store_dir.path(), VerifyStoreOptions { quarantine: true }).unwrap();
+
+        assert_eq!(corrupted.len(), 1);
+        assert_eq!(corrupted[0].path, corrupted_file);
+        assert_eq!(
+            corrupted[0].quarantined_to,
+            Some(
+                store_dir
+                    .path()
+                    .join(QUARANTINE_DIR)
+                    .join(corrupted_file.file_name().unwrap())
+            )
+        );
+        assert!(!corrupted_file.exists());
+        assert!(valid_file.exists());
+    }
+}
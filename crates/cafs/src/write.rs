@@ -0,0 +1,180 @@
+use crate::{get_file_path_by_mode_in_cafs, mode_is_executable, PackageFileInfo, PackageFiles};
+use anyhow::Result;
+use rayon::prelude::*;
+use ssri::{Algorithm, Integrity, IntegrityOpts};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+pub fn file_integrity(content: &[u8]) -> Integrity {
+    IntegrityOpts::new()
+        .algorithm(Algorithm::Sha512)
+        .chain(content)
+        .result()
+}
+
+pub(crate) fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+/// Writes the buffer to the store under a path derived from its digest.
+/// Existing files are left untouched since the path already proves their content.
+pub fn write_buffer_to_cafs<P: AsRef<Path>>(
+    cafs_dir: P,
+    buffer: &[u8],
+    mode: u32,
+) -> Result<(Integrity, PathBuf)> {
+    let integrity = file_integrity(buffer);
+    let file_path = get_file_path_by_mode_in_cafs(&cafs_dir, &integrity, mode);
+
+    if !file_path.exists() {
+        let parent = file_path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(parent)?;
+        let temp_file = temp_path::temp_path_atomic(parent);
+        fs::write(&temp_file, buffer)?;
+        set_file_mode(&temp_file, mode)?;
+        // another process might have written the same file in the meantime,
+        // which is fine since the content is the same
+        fs::rename(&temp_file, &file_path).or_else(|err| {
+            fs::remove_file(&temp_file).ok();
+            if file_path.exists() {
+                Ok(())
+            } else {
+                Err(err)
+            }
+        })?;
+    }
+
+    Ok((integrity, file_path))
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = if mode_is_executable(mode) {
+        0o755
+    } else {
+        0o644
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_mode(_metadata: &fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Lists the files of a package directory, relative to it and with `/` separators.
+/// The `node_modules` directory of the package is never part of the package.
+pub(crate) fn list_package_files<P: AsRef<Path>>(dir: P) -> Vec<(String, PathBuf)> {
+    let dir = dir.as_ref();
+
+    WalkDir::new(dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|entry| !(entry.depth() == 1 && entry.file_name() == "node_modules"))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(dir).ok()?;
+            let relative = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((relative, entry.path().to_path_buf()))
+        })
+        .collect()
+}
+
+/// Adds every file of `dir` to the store and returns the files index of the package
+pub fn add_files_from_dir<P: AsRef<Path>, S: AsRef<Path> + Sync>(
+    cafs_dir: S,
+    dir: P,
+) -> Result<PackageFiles> {
+    list_package_files(dir)
+        .into_par_iter()
+        .map(|(relative_path, absolute_path)| {
+            let metadata = fs::metadata(&absolute_path)?;
+            let mode = file_mode(&metadata);
+            let buffer = fs::read(&absolute_path)?;
+            let (integrity, _) = write_buffer_to_cafs(&cafs_dir, &buffer, mode)?;
+
+            Ok((
+                relative_path,
+                PackageFileInfo {
+                    checked_at: Some(now_millis()),
+                    integrity: integrity.to_string(),
+                    mode,
+                    size: buffer.len() as u64,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_file_path_in_cafs, FileType};
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn write_buffer() {
+        let cafs_dir = tempdir().unwrap();
+        let (integrity, file_path) =
+            write_buffer_to_cafs(cafs_dir.path(), b"hello world", 0o644).unwrap();
+
+        assert_eq!(
+            file_path,
+            get_file_path_in_cafs(cafs_dir.path(), &integrity, FileType::NonExec)
+        );
+        assert_eq!(fs::read(&file_path).unwrap(), b"hello world");
+        assert!(integrity.check(b"hello world").is_ok());
+    }
+
+    #[test]
+    fn add_files_of_directory() {
+        let cafs_dir = tempdir().unwrap();
+        let pkg_dir = tempdir().unwrap();
+        fs::create_dir_all(pkg_dir.path().join("lib")).unwrap();
+        fs::create_dir_all(pkg_dir.path().join("node_modules").join("dep")).unwrap();
+        fs::write(pkg_dir.path().join("package.json"), "{}").unwrap();
+        fs::write(pkg_dir.path().join("lib").join("index.js"), "").unwrap();
+        fs::write(
+            pkg_dir
+                .path()
+                .join("node_modules")
+                .join("dep")
+                .join("index.js"),
+            "",
+        )
+        .unwrap();
+
+        let files = add_files_from_dir(cafs_dir.path(), pkg_dir.path()).unwrap();
+        let mut names = files.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, vec!["lib/index.js", "package.json"]);
+        assert_eq!(files["package.json"].size, 2);
+    }
+}
@@ -0,0 +1,146 @@
+use constants::ENGINE_NAME;
+use dependency_path::create_base32_hash;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+pub struct DepStateNode {
+    pub dep_path: String,
+    /// alias -> dependency path of every dependency of the package
+    pub children: HashMap<String, String>,
+}
+
+pub type DepsGraph = HashMap<String, DepStateNode>;
+
+/// The state of a package is the (sorted) tree of the dependency paths
+/// it depends on. Cycles are cut at the package that was already visited.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct DepStateObj(BTreeMap<String, DepStateObj>);
+
+pub type DepsStateCache = HashMap<String, DepStateObj>;
+
+/// Side effects are only reusable on the same platform, architecture
+/// and major version of node, e.g. `linux-x86_64-node-v16`
+pub fn engine_name(node_version: &str) -> String {
+    let node_major = node_version.split('.').next().unwrap_or(node_version);
+    let node_major = if node_major.starts_with('v') {
+        node_major.to_string()
+    } else {
+        format!("v{}", node_major)
+    };
+
+    format!("{}-{}", ENGINE_NAME.concat(), node_major)
+}
+
+/// Returns the key under which the side effects of building the package
+/// at `dep_path` are stored
+pub fn calc_dep_state(
+    dep_path: &str,
+    deps_graph: &DepsGraph,
+    cache: &mut DepsStateCache,
+    node_version: &str,
+) -> String {
+    let dep_state_obj = calc_dep_state_obj(dep_path, deps_graph, cache, &HashSet::new());
+    let serialized = serde_json::to_string(&dep_state_obj).expect("dep state is serializable");
+
+    format!(
+        "{}-{}",
+        engine_name(node_version),
+        create_base32_hash(&serialized)
+    )
+}
+
+fn calc_dep_state_obj(
+    dep_path: &str,
+    deps_graph: &DepsGraph,
+    cache: &mut DepsStateCache,
+    parents: &HashSet<String>,
+) -> DepStateObj {
+    if let Some(state) = cache.get(dep_path) {
+        return state.clone();
+    }
+
+    let node = match deps_graph.get(dep_path) {
+        Some(node) => node,
+        None => return DepStateObj::default(),
+    };
+    let mut next_parents = parents.clone();
+    next_parents.insert(node.dep_path.clone());
+
+    let mut state = BTreeMap::new();
+    for child_dep_path in node.children.values() {
+        let child = match deps_graph.get(child_dep_path) {
+            Some(child) => child,
+            None => continue,
+        };
+
+        if parents.contains(&child.dep_path) {
+            state.insert(child.dep_path.clone(), DepStateObj::default());
+        } else {
+            state.insert(
+                child.dep_path.clone(),
+                calc_dep_state_obj(child_dep_path, deps_graph, cache, &next_parents),
+            );
+        }
+    }
+
+    let state = DepStateObj(state);
+    cache.insert(dep_path.to_string(), state.clone());
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn node(dep_path: &str, children: &[(&str, &str)]) -> (String, DepStateNode) {
+        (
+            dep_path.to_string(),
+            DepStateNode {
+                dep_path: dep_path.to_string(),
+                children: children
+                    .iter()
+                    .map(|(alias, dep_path)| (alias.to_string(), dep_path.to_string()))
+                    .collect(),
+            },
+        )
+    }
+
+    #[test]
+    fn engine_name_uses_node_major() {
+        assert_eq!(
+            engine_name("v16.13.0"),
+            format!("{}-v16", ENGINE_NAME.concat())
+        );
+        assert_eq!(
+            engine_name("14.0.0"),
+            format!("{}-v14", ENGINE_NAME.concat())
+        );
+    }
+
+    #[test]
+    fn dep_state_depends_on_the_dependency_tree() {
+        let graph_1 = DepsGraph::from_iter([
+            node("/foo/1.0.0", &[("bar", "/bar/1.0.0")]),
+            node("/bar/1.0.0", &[("foo", "/foo/1.0.0")]),
+        ]);
+        let graph_2 = DepsGraph::from_iter([
+            node("/foo/1.0.0", &[("bar", "/bar/2.0.0")]),
+            node("/bar/2.0.0", &[]),
+        ]);
+
+        let state_1 = calc_dep_state("/foo/1.0.0", &graph_1, &mut HashMap::new(), "v16.0.0");
+        let state_2 = calc_dep_state("/foo/1.0.0", &graph_2, &mut HashMap::new(), "v16.0.0");
+
+        assert!(state_1.starts_with(&engine_name("v16.0.0")));
+        assert_ne!(state_1, state_2);
+        assert_eq!(
+            state_1,
+            calc_dep_state("/foo/1.0.0", &graph_1, &mut HashMap::new(), "v16.0.0")
+        );
+        assert_ne!(
+            state_1,
+            calc_dep_state("/foo/1.0.0", &graph_1, &mut HashMap::new(), "v17.0.0")
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod package_is_installable {
+    use anyhow::Result;
+    use package_is_installable::check_package;
     use types::BaseManifest;
 
     #[derive(Default)]
@@ -13,12 +15,20 @@ pub mod package_is_installable {
         }
     }
 
+    /// Fails when the project doesn't support the platform or the version of node
+    /// and `engine-strict` is set, only warns otherwise
     pub fn package_is_installable(
-        _project_dir: &str,
-        _manifest: &BaseManifest,
-        _opts: PackageIsInstallableOpts,
-    ) {
-        // let pnpm_version = env!("CARGO_PKG_VERSION");
+        project_dir: &str,
+        manifest: &BaseManifest,
+        opts: PackageIsInstallableOpts,
+    ) -> Result<()> {
+        match check_package(project_dir, manifest, opts.node_version.as_deref()) {
+            Err(error) if !opts.engine_strict.unwrap_or(false) => {
+                eprintln!("WARN: {}", error);
+                Ok(())
+            }
+            result => result,
+        }
     }
 }
 
@@ -33,7 +43,7 @@ pub mod read_project_manifest {
     ) -> Result<ProjectManifest> {
         let manifest = read_project_manifest(project_dir)?;
         if let Some(manifest) = &manifest.manifest {
-            package_is_installable(project_dir, manifest, opts);
+            package_is_installable(project_dir, manifest, opts)?;
         }
 
         Ok(manifest)
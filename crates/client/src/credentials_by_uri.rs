@@ -1,12 +1,12 @@
 use crate::nerf_dart::*;
+use anyhow::{bail, Result};
 use std::process::Command;
 use std::{collections::HashMap, path::Path};
-use url::ParseError;
 
-#[derive(Default, Debug, PartialEq)]
+#[derive(Clone, Default, Debug, PartialEq)]
 pub struct Credentials {
-    auth_header_value: Option<String>,
-    always_auth: Option<bool>,
+    pub auth_header_value: Option<String>,
+    pub always_auth: Option<bool>,
 }
 
 impl Credentials {
@@ -26,32 +26,26 @@ pub fn get_credentials_by_uri(
     config: &HashMap<String, String>,
     url: &str,
     user_config: Option<&HashMap<String, String>>,
-) -> Result<Credentials, ParseError> {
+) -> Result<Credentials> {
     let nerfed = url.to_nerf_dart()?;
     let defnerf = config
         .get("registry")
         .expect("`registry` not found in `config`")
         .to_nerf_dart()?;
-    let credentials = get_scoped_credentials(
-        &nerfed,
-        format!("{}:", nerfed).as_str(),
-        config,
-        user_config,
-    );
+    let credentials = get_scoped_credentials(format!("{}:", nerfed).as_str(), config, user_config)?;
 
     if nerfed != defnerf {
         Ok(credentials)
     } else {
-        Ok(credentials.merge(get_scoped_credentials(&nerfed, "", config, user_config)))
+        Ok(credentials.merge(get_scoped_credentials("", config, user_config)?))
     }
 }
 
 fn get_scoped_credentials(
-    nerfed: &str,
     scope: &str,
     config: &HashMap<String, String>,
     user_config: Option<&HashMap<String, String>>,
-) -> Credentials {
+) -> Result<Credentials> {
     let mut credentials = Credentials::new();
 
     if let Some(val) = config.get(&format!("{}always-auth", scope)) {
@@ -59,11 +53,10 @@ fn get_scoped_credentials(
     }
 
     if let Some(user_config) = user_config {
-        if let Some(helper) = user_config.get(&format!("{}tokenHelpter", scope)) {
+        if let Some(helper) = user_config.get(&format!("{}tokenHelper", scope)) {
             let path = Path::new(helper);
-            if path.is_absolute() || path.exists() {
-                // TODO: change to recoverable errors
-                panic!("BAD_TOKEN_HELPER_PATH: {}tokenHelper must be an absolute path, without arguments", scope);
+            if !path.is_absolute() || !path.exists() {
+                bail!("BAD_TOKEN_HELPER_PATH: {}tokenHelper must be an absolute path, without arguments", scope);
             }
 
             // TODO: make windows compat
@@ -71,63 +64,54 @@ fn get_scoped_credentials(
             #[cfg(target_os = "windows")]
             todo!("Windows not supported");
 
-            let spawn_result = Command::new("sh").arg("-C").arg(helper).output().unwrap();
+            let spawn_result = Command::new("sh").arg("-c").arg(helper).output()?;
             if !matches!(spawn_result.status.code(), Some(0)) {
                 let exit_code = spawn_result
                     .status
                     .code()
                     .map(|code| code.to_string())
                     .unwrap_or_else(|| String::from("UNKNOWN"));
-                panic!("TOKEN_HELPER_ERROR_STATUS: Error running {} as a token helper, configured as {}tokenHelper. Exit code {}", helper, scope, exit_code);
+                bail!("TOKEN_HELPER_ERROR_STATUS: Error running {} as a token helper, configured as {}tokenHelper. Exit code {}", helper, scope, exit_code);
             }
 
             credentials.auth_header_value = Some(
-                std::str::from_utf8(&spawn_result.stdout)
-                    .unwrap()
+                String::from_utf8_lossy(&spawn_result.stdout)
                     .trim_end()
                     .to_string(),
             );
 
-            return credentials;
+            return Ok(credentials);
         }
     }
 
     if let Some(auth_token) = config.get(&format!("{}_authToken", scope)) {
         credentials.auth_header_value = Some(format!("Bearer {}", auth_token));
-        return credentials;
+        return Ok(credentials);
     }
 
     if let Some(auth) = config.get(&format!("{}_auth", scope)) {
         credentials.auth_header_value = Some(format!("Basic {}", auth));
-        return credentials;
+        return Ok(credentials);
     }
 
     let username = config.get(&format!("{}username", scope));
-    let password = config.get(&format!("{}_password", scope)).map(|password| {
-        if scope.is_empty() {
-            String::from(password)
-        } else {
-            String::from_utf8(base64::decode(password).expect("Can't decode password")).unwrap()
-        }
-    });
+    let password = match config.get(&format!("{}_password", scope)) {
+        Some(password) if scope.is_empty() => Some(String::from(password)),
+        Some(password) => match base64::decode(password).map(String::from_utf8) {
+            Ok(Ok(password)) => Some(password),
+            _ => bail!("BAD_PASSWORD: {}_password must be base64 encoded", scope),
+        },
+        None => None,
+    };
 
     if let (Some(username), Some(password)) = (username, password) {
-        credentials.auth_header_value = Some(base64::encode(format!("{}:{}", username, password)));
+        credentials.auth_header_value = Some(format!(
+            "Basic {}",
+            base64::encode(format!("{}:{}", username, password))
+        ));
     };
 
-    credentials
-}
-
-macro_rules! hash_map {
-    ( $( $key:expr => $value:expr ),+ ) => {{
-        let mut map = HashMap::new();
-
-        $(
-            map.insert($key, $value);
-        )+
-
-        map
-    }}
+    Ok(credentials)
 }
 
 #[cfg(test)]
@@ -138,18 +122,58 @@ mod tests {
 
     #[test]
     fn gets_credentials() {
-        let config = hash_map! {
-            "registry".into() => "https://registry.npmjs.com".into(),
-            "//registry.com/:_authToken".into() => "f23jj93f32dsaf==".into(),
-            "//registry.com/:always-auth".into() => "false".into()
-        };
+        let config = HashMap::from([
+            ("registry".into(), "https://registry.npmjs.com".into()),
+            (
+                "//registry.com/:_authToken".into(),
+                "f23jj93f32dsaf==".into(),
+            ),
+            ("//registry.com/:always-auth".into(), "false".into()),
+        ]);
 
         assert_eq!(
-            get_credentials_by_uri(&config, "https://registry.com", None),
-            Ok(Credentials {
+            get_credentials_by_uri(&config, "https://registry.com", None).unwrap(),
+            Credentials {
                 always_auth: Some(false),
                 auth_header_value: Some("Bearer f23jj93f32dsaf==".to_string())
-            })
+            }
+        );
+    }
+
+    #[test]
+    fn prefix_username_and_password_with_basic() {
+        let config = HashMap::from([
+            ("registry".into(), "https://registry.npmjs.com".into()),
+            ("//registry.com/:username".into(), "foo".into()),
+            ("//registry.com/:_password".into(), base64::encode("bar")),
+        ]);
+
+        assert_eq!(
+            get_credentials_by_uri(&config, "https://registry.com", None)
+                .unwrap()
+                .auth_header_value,
+            Some(format!("Basic {}", base64::encode("foo:bar")))
         );
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn use_token_helper() {
+        let config = HashMap::from([("registry".into(), "https://registry.com".into())]);
+        let user_config =
+            HashMap::from([("//registry.com/:tokenHelper".into(), "/bin/pwd".into())]);
+
+        assert!(
+            get_credentials_by_uri(&config, "https://registry.com", Some(&user_config))
+                .unwrap()
+                .auth_header_value
+                .is_some()
+        );
+
+        let relative_helper =
+            HashMap::from([("//registry.com/:tokenHelper".into(), "./helper.sh".into())]);
+        let err = get_credentials_by_uri(&config, "https://registry.com", Some(&relative_helper))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("BAD_TOKEN_HELPER_PATH: "));
+    }
 }
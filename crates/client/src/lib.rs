@@ -1,27 +1,157 @@
+use anyhow::Result;
 use credentials_by_uri::get_credentials_by_uri;
+pub use credentials_by_uri::Credentials;
+use fetch::{AgentOptions, FetchFromRegistry, FetchFromRegistryOptions, RetryTimeoutOptions};
+use nerf_dart::NerfDart;
+use resolvers::base::Resolution;
+use resolvers::default::Resolver;
+use resolvers::npm::{NpmResolver, ResolverFactoryOptions};
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tarball_fetcher::{fetch_tarball, FetchOptions, FetchResult};
+use url::Url;
 
 mod credentials_by_uri;
 mod nerf_dart;
 
+#[derive(Default)]
 pub struct ClientOptions {
+    pub auth_config: HashMap<String, String>,
+    pub retry: Option<RetryTimeoutOptions>,
+    /// Milliseconds to wait for a response, `fetch-timeout` in the `.npmrc`
+    pub timeout: Option<u64>,
+    /// Milliseconds to wait for a connection to be established
+    pub connect_timeout: Option<u64>,
+    pub user_agent: Option<String>,
+    pub user_config: Option<HashMap<String, String>>,
+    pub agent: AgentOptions,
+}
+
+pub struct Client {
+    pub fetch_from_registry: FetchFromRegistry,
     auth_config: HashMap<String, String>,
-    retry: Option<()>,
-    timeout: Option<i64>,
-    user_agent: Option<String>,
     user_config: Option<HashMap<String, String>>,
+    /// Keyed by the nerf-dart of the registry
+    credentials_cache: Mutex<HashMap<String, Credentials>>,
+}
+
+fn host_of(url: &str) -> Option<(String, Option<u16>)> {
+    let url = Url::parse(url).ok()?;
+    Some((url.host_str()?.to_string(), url.port_or_known_default()))
 }
 
-pub fn create_client(opts: ClientOptions) {}
+impl Client {
+    /// Credentials are read once per registry,
+    /// as reading them might spawn a token helper
+    pub fn get_credentials(&self, registry: &str) -> Result<Credentials> {
+        let nerfed = registry.to_nerf_dart()?;
+        if let Some(credentials) = self.credentials_cache.lock().unwrap().get(&nerfed) {
+            return Ok(credentials.clone());
+        }
 
-// pub fn create_resolver(opts: &ClientOptions) {
-//     let fetch_from_registry = create_fetch_from_registry(opts);
-//     // memoize function
-//     let get_credentials =
-//         Box::new(|registry: &str| get_credentials_by_uri(&opts.auth_config, registry, None));
+        let credentials =
+            get_credentials_by_uri(&self.auth_config, registry, self.user_config.as_ref())?;
+        self.credentials_cache
+            .lock()
+            .unwrap()
+            .insert(nerfed, credentials.clone());
+        Ok(credentials)
+    }
 
-//     create_resolve(fetch_from_registry, get_credentials, opts);
-// }
+    /// Looks up the credentials of the URL by its nerf-dart and the ones of its parent paths.
+    /// When there are none, the credentials of `registry` are used, but only if
+    /// the URL is on the host of the registry or `always-auth` is set for the registry.
+    pub fn get_auth_header_value(
+        &self,
+        url: &str,
+        registry: Option<&str>,
+    ) -> Result<Option<String>> {
+        let nerfed = url.to_nerf_dart()?;
+        let parts = nerfed.trim_end_matches('/').split('/').collect::<Vec<_>>();
+        // the first two parts are the empty strings before `//host`
+        for end in (3..=parts.len()).rev() {
+            let credentials =
+                self.get_credentials(&format!("https:{}/", parts[..end].join("/")))?;
+            if credentials.auth_header_value.is_some() {
+                return Ok(credentials.auth_header_value);
+            }
+        }
+
+        let registry = match registry {
+            Some(registry) => registry,
+            None => return Ok(None),
+        };
+        let credentials = self.get_credentials(registry)?;
+        let same_host = host_of(url) == host_of(registry);
+        Ok(credentials
+            .auth_header_value
+            .filter(|_| same_host || credentials.always_auth == Some(true)))
+    }
+
+    /// Fetches a tarball into the store, authenticated with the credentials
+    /// of its URL or of the registry it was resolved from
+    pub fn fetch_tarball<P: AsRef<Path>>(
+        &self,
+        cafs_dir: P,
+        resolution: &Resolution,
+        lockfile_dir: &str,
+    ) -> Result<FetchResult> {
+        let auth_header_value = match resolution {
+            Resolution::TarballResolution {
+                tarball, registry, ..
+            } if !tarball.starts_with("file:") => {
+                self.get_auth_header_value(tarball, registry.as_deref())?
+            }
+            _ => None,
+        };
+
+        fetch_tarball(
+            &self.fetch_from_registry,
+            cafs_dir,
+            resolution,
+            &FetchOptions {
+                lockfile_dir,
+                auth_header_value: auth_header_value.as_deref(),
+            },
+        )
+    }
+
+    /// The metadata requests are authenticated with the credentials of the registry
+    pub fn create_resolver(&self, opts: ResolverFactoryOptions) -> Resolver<'_> {
+        Resolver::new(NpmResolver::new(
+            &self.fetch_from_registry,
+            Box::new(move |registry| self.get_auth_header_value(registry, Some(registry))),
+            opts,
+        ))
+    }
+}
+
+pub fn create_client(opts: ClientOptions) -> Result<Client> {
+    let defaults = FetchFromRegistryOptions::default();
+    let fetch_from_registry = FetchFromRegistry::new(FetchFromRegistryOptions {
+        full_metadata: false,
+        user_agent: opts.user_agent,
+        agent: opts.agent,
+        retry: opts.retry.unwrap_or(defaults.retry),
+        connect_timeout: opts
+            .connect_timeout
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.connect_timeout),
+        timeout: opts
+            .timeout
+            .map(Duration::from_millis)
+            .unwrap_or(defaults.timeout),
+    })?;
+
+    Ok(Client {
+        fetch_from_registry,
+        auth_config: opts.auth_config,
+        user_config: opts.user_config,
+        credentials_cache: Mutex::new(HashMap::new()),
+    })
+}
 
 // pub fn create_fetchers(
 //     fetch_from_registry: FetchFromRegistry,
@@ -29,3 +159,77 @@ pub fn create_client(opts: ClientOptions) {}
 //     retry: Option<()>,
 // ) {
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn client(auth_config: &[(&str, &str)]) -> Client {
+        create_client(ClientOptions {
+            auth_config: auth_config
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn authenticate_requests_to_the_registry() {
+        let client = client(&[
+            ("registry", "https://registry.npmjs.org/"),
+            ("_authToken", "default"),
+            ("//npm.pkg.github.com/org/:_authToken", "github"),
+        ]);
+
+        assert_eq!(
+            client
+                .get_auth_header_value("https://registry.npmjs.org/foo", None)
+                .unwrap(),
+            Some("Bearer default".to_string())
+        );
+        assert_eq!(
+            client
+                .get_auth_header_value("https://npm.pkg.github.com/org/foo/-/foo-1.0.0.tgz", None)
+                .unwrap(),
+            Some("Bearer github".to_string())
+        );
+        assert_eq!(
+            client
+                .get_auth_header_value("https://example.com/foo.tgz", None)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn fall_back_to_the_registry_of_the_tarball() {
+        let registry = "https://registry.company.com/";
+        let tarball = "https://cdn.company.com/foo/-/foo-1.0.0.tgz";
+
+        let client_without_always_auth = client(&[
+            ("registry", "https://registry.npmjs.org/"),
+            ("//registry.company.com/:_authToken", "secret"),
+        ]);
+        assert_eq!(
+            client_without_always_auth
+                .get_auth_header_value(tarball, Some(registry))
+                .unwrap(),
+            None
+        );
+
+        let client_with_always_auth = client(&[
+            ("registry", "https://registry.npmjs.org/"),
+            ("//registry.company.com/:_authToken", "secret"),
+            ("//registry.company.com/:always-auth", "true"),
+        ]);
+        assert_eq!(
+            client_with_always_auth
+                .get_auth_header_value(tarball, Some(registry))
+                .unwrap(),
+            Some("Bearer secret".to_string())
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Replaces `${NAME}` with the value of the `NAME` environment variable.
+/// A placeholder escaped with a backslash, like `\${NAME}`, is kept as is.
+pub fn env_replace(text: &str, env: &HashMap<String, String>) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        let backslashes = rest[..start]
+            .chars()
+            .rev()
+            .take_while(|c| *c == '\\')
+            .count();
+        // every pair of backslashes is an escaped backslash
+        result.push_str(&rest[..start - backslashes]);
+        result.push_str(&"\\".repeat(backslashes / 2));
+
+        if backslashes % 2 == 1 {
+            result.push_str(&rest[start..=end]);
+        } else {
+            let name = &rest[start + 2..end];
+            match env.get(name) {
+                Some(value) => result.push_str(value),
+                None => bail!(
+                    "CONFIG_ENV_REPLACE: Failed to replace env in config: ${{{}}}",
+                    name
+                ),
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn replace_env_variables() {
+        let env = HashMap::from([("TOKEN".to_string(), "secret".to_string())]);
+
+        assert_eq!(
+            env_replace("//registry.npmjs.org/:_authToken=${TOKEN}", &env).unwrap(),
+            "//registry.npmjs.org/:_authToken=secret"
+        );
+        assert_eq!(env_replace("\\${TOKEN}", &env).unwrap(), "${TOKEN}");
+        assert_eq!(env_replace("\\\\${TOKEN}", &env).unwrap(), "\\secret");
+        assert_eq!(env_replace("${TOKEN", &env).unwrap(), "${TOKEN");
+        assert!(env_replace("${MISSING}", &env)
+            .unwrap_err()
+            .to_string()
+            .starts_with("CONFIG_ENV_REPLACE: "));
+    }
+}
@@ -0,0 +1,588 @@
+mod env_replace;
+mod npmrc;
+
+use anyhow::{bail, Result};
+use find_workspace_dir::{find_workspace_dir, WORKSPACE_DIR_ENV_VAR};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use types::{NodeLinker, Registries};
+
+pub use env_replace::env_replace;
+pub use npmrc::Npmrc;
+
+pub const NPMRC: &str = ".npmrc";
+pub const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+const DEFAULTS: &[(&str, &str)] = &[
+    ("registry", DEFAULT_REGISTRY),
+    ("hoist", "true"),
+    ("hoist-pattern", "*"),
+    (
+        "public-hoist-pattern",
+        "*types*,*eslint*,@prettier/plugin-*,*prettier-plugin-*",
+    ),
+    ("shamefully-hoist", "false"),
+    ("save-exact", "false"),
+    ("save-prefix", "^"),
+    ("modules-dir", "node_modules"),
+    ("node-linker", "isolated"),
+    ("strict-ssl", "true"),
+    ("engine-strict", "false"),
+    ("prefer-frozen-lockfile", "true"),
+    ("confirm-modules-purge", "true"),
+    ("side-effects-cache", "true"),
+    ("workspace-concurrency", "4"),
+];
+
+const ENV_PREFIXES: [&str; 2] = ["npm_config_", "pnpm_config_"];
+
+/// Where a setting was read from
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource {
+    Builtin,
+    Npmrc(PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Builtin => write!(f, "builtin"),
+            ConfigSource::Npmrc(path) => write!(f, "{}", path.display()),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+pub struct GetConfigOptions {
+    /// The directory of the project
+    pub dir: PathBuf,
+    pub workspace_dir: Option<PathBuf>,
+    /// Settings passed as command line flags, by their kebab-case names
+    pub cli_options: HashMap<String, String>,
+    pub env: HashMap<String, String>,
+    pub home_dir: Option<PathBuf>,
+}
+
+impl GetConfigOptions {
+    /// Options that read the environment and the home directory of the current process,
+    /// in the workspace that contains the project, if any
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        // the variables that are not valid unicode can't be settings, so they are skipped
+        let env = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect::<HashMap<_, _>>();
+        let workspace_dir =
+            find_workspace_dir(&dir, get_env(&env, WORKSPACE_DIR_ENV_VAR).map(Path::new))?;
+        Ok(Self {
+            dir,
+            workspace_dir,
+            cli_options: HashMap::new(),
+            env,
+            home_dir: dirs::home_dir(),
+        })
+    }
+}
+
+/// The `.npmrc` files that are read, from the lowest to the highest precedence
+#[derive(Debug, PartialEq)]
+pub struct NpmrcPaths {
+    /// `~/.npmrc`, unless overridden by `npm_config_userconfig`
+    pub global: Option<PathBuf>,
+    /// `$XDG_CONFIG_HOME/pnpm/rc`
+    pub user: Option<PathBuf>,
+    /// `None` when the project is the root of the workspace
+    pub workspace: Option<PathBuf>,
+    pub project: PathBuf,
+}
+
+fn get_env<'a>(env: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    env.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+        .filter(|value| !value.is_empty())
+}
+
+pub fn npmrc_paths(opts: &GetConfigOptions) -> NpmrcPaths {
+    let global = get_env(&opts.env, "npm_config_userconfig")
+        .map(PathBuf::from)
+        .or_else(|| opts.home_dir.as_ref().map(|home| home.join(NPMRC)));
+    let config_home = get_env(&opts.env, "XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| opts.home_dir.as_ref().map(|home| home.join(".config")));
+
+    NpmrcPaths {
+        global,
+        user: config_home.map(|config_home| config_home.join("pnpm").join("rc")),
+        workspace: opts
+            .workspace_dir
+            .as_ref()
+            .filter(|workspace_dir| *workspace_dir != &opts.dir)
+            .map(|workspace_dir| workspace_dir.join(NPMRC)),
+        project: opts.dir.join(NPMRC),
+    }
+}
+
+/// Env variables like `npm_config_save_exact` set `save-exact`.
+/// The first character is kept, so `npm_config__auth` sets `_auth`.
+fn env_key(name: &str, prefix: &str) -> Option<String> {
+    let key = name
+        .get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| name[prefix.len()..].to_lowercase())?;
+    let mut chars = key.chars();
+    let first = chars.next()?;
+    Some(format!("{}{}", first, chars.as_str().replace('_', "-")))
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub dir: PathBuf,
+    pub workspace_dir: Option<PathBuf>,
+    /// Every setting, including the auth ones like `//registry.npmjs.org/:_authToken`.
+    /// Arrays like `ca[]` are joined with commas under their name without brackets.
+    pub raw_config: HashMap<String, String>,
+    /// Settings from the `.npmrc` files of the workspace and of the project
+    pub raw_local_config: HashMap<String, String>,
+    /// Settings from the global `.npmrc`, the only ones allowed to set a `tokenHelper`
+    pub user_config: HashMap<String, String>,
+    pub sources: HashMap<String, ConfigSource>,
+
+    /// Built from `registry` and the `@scope:registry` settings
+    pub registries: Registries,
+    pub store_dir: Option<String>,
+    /// `cache-dir`, by default `$XDG_CACHE_HOME/pnpm` or `~/.cache/pnpm`
+    pub cache_dir: PathBuf,
+    pub modules_dir: String,
+    pub virtual_store_dir: PathBuf,
+    pub hoist: bool,
+    pub hoist_pattern: Vec<String>,
+    pub public_hoist_pattern: Vec<String>,
+    pub shamefully_hoist: bool,
+    pub node_linker: NodeLinker,
+    pub save_exact: bool,
+    pub save_prefix: String,
+    pub strict_ssl: bool,
+    pub engine_strict: bool,
+}
+
+impl Config {
+    /// Reads a boolean setting that has no field of its own, `false` when it is not set
+    pub fn get_bool(&self, key: &str) -> Result<bool> {
+        parse_bool(&self.raw_config, key)
+    }
+}
+
+struct Layer {
+    source: ConfigSource,
+    settings: Vec<(String, String)>,
+}
+
+fn read_npmrc_layer(path: &Path, env: &HashMap<String, String>) -> Result<Option<Layer>> {
+    let npmrc = match Npmrc::read(path)? {
+        Some(npmrc) => npmrc,
+        None => return Ok(None),
+    };
+
+    let mut settings: Vec<(String, String)> = vec![];
+    for (key, value) in npmrc.entries() {
+        let key = env_replace(key, env)?;
+        let value = env_replace(value, env)?;
+        match key.strip_suffix("[]") {
+            Some(key) => match settings.iter_mut().find(|(name, _)| name == key) {
+                Some((_, values)) => {
+                    values.push(',');
+                    values.push_str(&value);
+                }
+                None => settings.push((key.to_string(), value)),
+            },
+            None => settings.push((key, value)),
+        }
+    }
+
+    Ok(Some(Layer {
+        source: ConfigSource::Npmrc(path.to_path_buf()),
+        settings,
+    }))
+}
+
+fn parse_bool(raw_config: &HashMap<String, String>, key: &str) -> Result<bool> {
+    match raw_config.get(key).map(String::as_str) {
+        Some("true") | Some("") => Ok(true),
+        Some("false") | None => Ok(false),
+        Some(value) => bail!(
+            "CONFIG_INVALID_VALUE: {} should be true or false, got \"{}\"",
+            key,
+            value
+        ),
+    }
+}
+
+fn parse_list(raw_config: &HashMap<String, String>, key: &str) -> Vec<String> {
+    raw_config
+        .get(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn normalize_registry(registry: &str) -> String {
+    if registry.ends_with('/') {
+        registry.to_string()
+    } else {
+        format!("{}/", registry)
+    }
+}
+
+fn get_registries(raw_config: &HashMap<String, String>) -> Registries {
+    Registries {
+        default: normalize_registry(
+            raw_config
+                .get("registry")
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_REGISTRY),
+        ),
+        scoped: raw_config
+            .iter()
+            .filter_map(|(key, value)| {
+                let scope = key.strip_suffix(":registry")?;
+                scope
+                    .starts_with('@')
+                    .then(|| (scope.to_string(), normalize_registry(value)))
+            })
+            .collect(),
+    }
+}
+
+/// Reads the settings from the `.npmrc` files, the environment and the command line
+pub fn get_config(opts: GetConfigOptions) -> Result<Config> {
+    let paths = npmrc_paths(&opts);
+    let mut layers = vec![Layer {
+        source: ConfigSource::Builtin,
+        settings: DEFAULTS
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }];
+
+    let mut user_config = HashMap::new();
+    if let Some(global) = &paths.global {
+        if let Some(layer) = read_npmrc_layer(global, &opts.env)? {
+            user_config.extend(layer.settings.iter().cloned());
+            layers.push(layer);
+        }
+    }
+    if let Some(user) = &paths.user {
+        layers.extend(read_npmrc_layer(user, &opts.env)?);
+    }
+
+    let mut raw_local_config = HashMap::new();
+    for path in paths.workspace.iter().chain([&paths.project]) {
+        if let Some(layer) = read_npmrc_layer(path, &opts.env)? {
+            raw_local_config.extend(layer.settings.iter().cloned());
+            layers.push(layer);
+        }
+    }
+
+    for prefix in ENV_PREFIXES {
+        let mut env_vars = opts
+            .env
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .filter_map(|(name, value)| Some((env_key(name, prefix)?, name, value)))
+            .collect::<Vec<_>>();
+        // the env is unordered, so the same setting set twice has to be picked deterministically
+        env_vars.sort();
+        for (key, name, value) in env_vars {
+            layers.push(Layer {
+                source: ConfigSource::Env(name.clone()),
+                settings: vec![(key, value.clone())],
+            });
+        }
+    }
+
+    layers.push(Layer {
+        source: ConfigSource::Cli,
+        settings: opts
+            .cli_options
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+    });
+
+    let mut raw_config = HashMap::new();
+    let mut sources = HashMap::new();
+    for layer in layers {
+        for (key, value) in layer.settings {
+            sources.insert(key.clone(), layer.source.clone());
+            raw_config.insert(key, value);
+        }
+    }
+
+    let registries = get_registries(&raw_config);
+
+    let hoist = parse_bool(&raw_config, "hoist")?;
+    let shamefully_hoist = parse_bool(&raw_config, "shamefully-hoist")?;
+    let public_hoist_pattern = if shamefully_hoist
+        && sources.get("public-hoist-pattern") == Some(&ConfigSource::Builtin)
+    {
+        vec!["*".to_string()]
+    } else {
+        parse_list(&raw_config, "public-hoist-pattern")
+    };
+    let save_exact = parse_bool(&raw_config, "save-exact")?;
+    let modules_dir = raw_config
+        .get("modules-dir")
+        .cloned()
+        .unwrap_or_else(|| "node_modules".to_string());
+    let virtual_store_dir = opts.dir.join(
+        raw_config
+            .get("virtual-store-dir")
+            .cloned()
+            .unwrap_or_else(|| format!("{}/.pnpm", modules_dir)),
+    );
+
+    let cache_dir = match raw_config.get("cache-dir") {
+        Some(cache_dir) => opts.dir.join(cache_dir),
+        None => get_env(&opts.env, "XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| opts.home_dir.as_ref().map(|home| home.join(".cache")))
+            .unwrap_or_else(|| opts.dir.clone())
+            .join("pnpm"),
+    };
+
+    Ok(Config {
+        registries,
+        store_dir: raw_config.get("store-dir").cloned(),
+        cache_dir,
+        hoist,
+        hoist_pattern: if hoist {
+            parse_list(&raw_config, "hoist-pattern")
+        } else {
+            vec![]
+        },
+        public_hoist_pattern,
+        shamefully_hoist,
+        node_linker: match raw_config.get("node-linker").map(String::as_str) {
+            Some("isolated") | None => NodeLinker::Isolated,
+            Some("hoisted") => NodeLinker::Hoisted,
+            Some(value) => bail!(
+                "CONFIG_INVALID_VALUE: node-linker should be isolated or hoisted, got \"{}\"",
+                value
+            ),
+        },
+        save_exact,
+        save_prefix: if save_exact {
+            String::new()
+        } else {
+            raw_config.get("save-prefix").cloned().unwrap_or_default()
+        },
+        strict_ssl: parse_bool(&raw_config, "strict-ssl")?,
+        engine_strict: parse_bool(&raw_config, "engine-strict")?,
+        modules_dir,
+        virtual_store_dir,
+        dir: opts.dir,
+        workspace_dir: opts.workspace_dir,
+        raw_config,
+        raw_local_config,
+        user_config,
+        sources,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    struct Dirs {
+        home: TempDir,
+        workspace: TempDir,
+        project: PathBuf,
+    }
+
+    fn setup() -> Dirs {
+        let home = tempdir().unwrap();
+        let workspace = tempdir().unwrap();
+        let project = workspace.path().join("packages").join("foo");
+        fs::create_dir_all(&project).unwrap();
+        Dirs {
+            home,
+            workspace,
+            project,
+        }
+    }
+
+    fn options(dirs: &Dirs) -> GetConfigOptions {
+        GetConfigOptions {
+            dir: dirs.project.clone(),
+            workspace_dir: Some(dirs.workspace.path().to_path_buf()),
+            cli_options: HashMap::new(),
+            env: HashMap::new(),
+            home_dir: Some(dirs.home.path().to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn builtin_settings() {
+        let dirs = setup();
+        let config = get_config(options(&dirs)).unwrap();
+
+        assert_eq!(config.registries.default, DEFAULT_REGISTRY);
+        assert_eq!(config.hoist_pattern, vec!["*"]);
+        assert_eq!(config.save_prefix, "^");
+        assert!(!config.save_exact);
+        assert_eq!(config.store_dir, None);
+        assert_eq!(config.cache_dir, dirs.home.path().join(".cache/pnpm"));
+        assert_eq!(
+            config.virtual_store_dir,
+            dirs.project.join("node_modules/.pnpm")
+        );
+        assert_eq!(config.sources["registry"], ConfigSource::Builtin);
+    }
+
+    #[test]
+    fn order_of_precedence() {
+        let dirs = setup();
+        let global = dirs.home.path().join(NPMRC);
+        let user = dirs.home.path().join(".config/pnpm/rc");
+        let workspace = dirs.workspace.path().join(NPMRC);
+        let project = dirs.project.join(NPMRC);
+        fs::create_dir_all(user.parent().unwrap()).unwrap();
+        fs::write(
+            &global,
+            "a=global\nb=global\nc=global\nd=global\ne=global\nf=global",
+        )
+        .unwrap();
+        fs::write(&user, "b=user\nc=user\nd=user\ne=user\nf=user").unwrap();
+        fs::write(
+            &workspace,
+            "c=workspace\nd=workspace\ne=workspace\nf=workspace",
+        )
+        .unwrap();
+        fs::write(&project, "d=project\ne=project\nf=project").unwrap();
+
+        let mut opts = options(&dirs);
+        opts.env = HashMap::from([
+            ("npm_config_e".to_string(), "npm env".to_string()),
+            ("NPM_CONFIG_F".to_string(), "npm env".to_string()),
+            ("pnpm_config_f".to_string(), "pnpm env".to_string()),
+        ]);
+        opts.cli_options = HashMap::from([("g".to_string(), "cli".to_string())]);
+        let config = get_config(opts).unwrap();
+
+        for (key, value, source) in [
+            ("a", "global", ConfigSource::Npmrc(global)),
+            ("b", "user", ConfigSource::Npmrc(user)),
+            ("c", "workspace", ConfigSource::Npmrc(workspace)),
+            ("d", "project", ConfigSource::Npmrc(project)),
+            (
+                "e",
+                "npm env",
+                ConfigSource::Env("npm_config_e".to_string()),
+            ),
+            (
+                "f",
+                "pnpm env",
+                ConfigSource::Env("pnpm_config_f".to_string()),
+            ),
+            ("g", "cli", ConfigSource::Cli),
+        ] {
+            assert_eq!(config.raw_config[key], value);
+            assert_eq!(config.sources[key], source);
+        }
+        assert_eq!(config.raw_local_config["c"], "workspace");
+        assert_eq!(config.raw_local_config.get("b"), None);
+        assert_eq!(config.user_config["a"], "global");
+    }
+
+    #[test]
+    fn typed_settings() {
+        let dirs = setup();
+        fs::write(
+            dirs.project.join(NPMRC),
+            "registry=https://npm.company.com\n@company:registry=https://npm.company.com/private\nstore-dir=~/.pnpm-store\nsave-exact=true\nhoist-pattern[]=*eslint*\nhoist-pattern[]=*babel*\nshamefully-hoist=true\n",
+        )
+        .unwrap();
+        let config = get_config(options(&dirs)).unwrap();
+
+        assert_eq!(
+            config.registries,
+            Registries {
+                default: "https://npm.company.com/".to_string(),
+                scoped: HashMap::from([(
+                    "@company".to_string(),
+                    "https://npm.company.com/private/".to_string()
+                )]),
+            }
+        );
+        assert_eq!(config.store_dir.as_deref(), Some("~/.pnpm-store"));
+        assert!(config.save_exact);
+        assert_eq!(config.save_prefix, "");
+        assert_eq!(config.hoist_pattern, vec!["*eslint*", "*babel*"]);
+        assert_eq!(config.public_hoist_pattern, vec!["*"]);
+    }
+
+    #[test]
+    fn expand_env_variables_in_npmrc() {
+        let dirs = setup();
+        fs::write(
+            dirs.project.join(NPMRC),
+            "//${REGISTRY_HOST}/:_authToken=${NPM_TOKEN}\n",
+        )
+        .unwrap();
+        let mut opts = options(&dirs);
+        opts.env = HashMap::from([
+            ("REGISTRY_HOST".to_string(), "npm.company.com".to_string()),
+            ("NPM_TOKEN".to_string(), "secret".to_string()),
+        ]);
+
+        let config = get_config(opts).unwrap();
+
+        assert_eq!(config.raw_config["//npm.company.com/:_authToken"], "secret");
+    }
+
+    #[test]
+    fn reject_invalid_booleans() {
+        let dirs = setup();
+        let mut opts = options(&dirs);
+        opts.cli_options = HashMap::from([("save-exact".to_string(), "yes".to_string())]);
+
+        assert!(get_config(opts)
+            .unwrap_err()
+            .to_string()
+            .starts_with("CONFIG_INVALID_VALUE: "));
+    }
+
+    #[test]
+    fn env_variable_names() {
+        assert_eq!(
+            env_key("npm_config_save_exact", "npm_config_"),
+            Some("save-exact".to_string())
+        );
+        assert_eq!(
+            env_key("NPM_CONFIG__AUTH", "npm_config_"),
+            Some("_auth".to_string())
+        );
+        assert_eq!(env_key("npm_config_", "npm_config_"), None);
+        assert_eq!(env_key("PATH", "npm_config_"), None);
+        assert_eq!(env_key("npm_configé_foo", "npm_config_"), None);
+        assert_eq!(env_key("ÖÖ", "npm_config_"), None);
+        assert_eq!(
+            env_key("npm_config_ébc_d", "npm_config_"),
+            Some("ébc-d".to_string())
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Line {
+    Entry {
+        key: String,
+        value: String,
+        raw: String,
+    },
+    /// Comments, empty lines and anything else that is kept as is
+    Other(String),
+}
+
+/// The lines of an `.npmrc` file. Comments and the order of the
+/// settings are kept, so the file can be written back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Npmrc {
+    lines: Vec<Line>,
+}
+
+fn parse_value(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        if let Ok(value) = serde_json::from_str::<String>(value) {
+            return value;
+        }
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].to_string();
+    }
+    value.to_string()
+}
+
+fn parse_line(line: &str) -> Line {
+    let trimmed = line.trim();
+    if trimmed.is_empty()
+        || trimmed.starts_with(';')
+        || trimmed.starts_with('#')
+        || trimmed.starts_with('[')
+    {
+        return Line::Other(line.to_string());
+    }
+
+    let (key, value) = match trimmed.split_once('=') {
+        Some((key, value)) => (key.trim(), parse_value(value.trim())),
+        // a key without a value is a flag that is turned on
+        None => (trimmed, "true".to_string()),
+    };
+
+    Line::Entry {
+        key: key.to_string(),
+        value,
+        raw: line.to_string(),
+    }
+}
+
+/// Values that would be read back differently are quoted
+fn format_entry(key: &str, value: &str) -> String {
+    let needs_quotes =
+        value.is_empty() || value.trim() != value || value.contains([';', '#', '"', '\'']);
+    if needs_quotes {
+        format!("{}={}", key, serde_json::to_string(value).unwrap())
+    } else {
+        format!("{}={}", key, value)
+    }
+}
+
+impl Npmrc {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(parse_line).collect(),
+        }
+    }
+
+    /// Returns `None` when the file doesn't exist
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(Self::parse(&content))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(err).with_context(|| format!("NPMRC_READ: Could not read {}", path.display()))
+            }
+        }
+    }
+
+    /// The settings in the order they appear in the file
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Other(_) => None,
+        })
+    }
+
+    /// The last value of the key, as later lines override earlier ones
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value)
+            .last()
+    }
+
+    fn is_entry_of(line: &Line, key: &str) -> bool {
+        matches!(line, Line::Entry { key: entry_key, .. } if entry_key == key)
+    }
+
+    /// Replaces the value of the key where it was last set, dropping the lines that set it earlier.
+    /// Keys that are not set yet are appended.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let entry = Line::Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            raw: format_entry(key, value),
+        };
+        match self
+            .lines
+            .iter()
+            .rposition(|line| Self::is_entry_of(line, key))
+        {
+            Some(index) => {
+                self.lines[index] = entry;
+                let (earlier, rest) = self.lines.split_at(index);
+                self.lines = earlier
+                    .iter()
+                    .filter(|line| !Self::is_entry_of(line, key))
+                    .chain(rest)
+                    .cloned()
+                    .collect();
+            }
+            None => self.lines.push(entry),
+        }
+    }
+
+    /// Returns `false` when the key was not set
+    pub fn delete(&mut self, key: &str) -> bool {
+        let len = self.lines.len();
+        self.lines.retain(|line| !Self::is_entry_of(line, key));
+        self.lines.len() != len
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())
+            .with_context(|| format!("NPMRC_WRITE: Could not write {}", path.display()))
+    }
+}
+
+impl fmt::Display for Npmrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Entry { raw, .. } | Line::Other(raw) => writeln!(f, "{}", raw)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const NPMRC: &str = "; registry settings
+registry = https://registry.npmjs.org/
+# scoped
+@foo:registry=\"https://npm.foo.com/\"
+save-exact
+ca[]=first
+ca[]=second
+registry=https://registry.yarnpkg.com/
+";
+
+    #[test]
+    fn parse_npmrc() {
+        let npmrc = Npmrc::parse(NPMRC);
+
+        assert_eq!(
+            npmrc.entries().collect::<Vec<_>>(),
+            vec![
+                ("registry", "https://registry.npmjs.org/"),
+                ("@foo:registry", "https://npm.foo.com/"),
+                ("save-exact", "true"),
+                ("ca[]", "first"),
+                ("ca[]", "second"),
+                ("registry", "https://registry.yarnpkg.com/"),
+            ]
+        );
+        assert_eq!(npmrc.get("registry"), Some("https://registry.yarnpkg.com/"));
+        assert_eq!(npmrc.to_string(), NPMRC);
+    }
+
+    #[test]
+    fn edit_npmrc() {
+        let mut npmrc = Npmrc::parse(NPMRC);
+
+        npmrc.set("registry", "https://npm.company.com/");
+        npmrc.set("store-dir", "~/store # shared");
+        assert!(npmrc.delete("ca[]"));
+        assert!(!npmrc.delete("cafile"));
+
+        assert_eq!(
+            npmrc.to_string(),
+            "; registry settings
+# scoped
+@foo:registry=\"https://npm.foo.com/\"
+save-exact
+registry=https://npm.company.com/
+store-dir=\"~/store # shared\"
+"
+        );
+        assert_eq!(npmrc.get("store-dir"), Some("~/store # shared"));
+        assert_eq!(
+            Npmrc::parse(&npmrc.to_string()).get("store-dir"),
+            Some("~/store # shared")
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{bail, Result};
+use semver::Version;
+
+/// Dependency paths that don't start with `/` carry the registry host
+/// in front of them (e.g. `registry.npmjs.org/foo/1.0.0`)
+pub fn is_absolute(dependency_path: &str) -> bool {
+    !dependency_path.starts_with('/')
+}
+
+/// Converts a reference found in the lockfile (e.g. `1.0.0` for `foo`)
+/// into a dependency path relative to the registry (`/foo/1.0.0`).
+/// Linked dependencies don't have a dependency path.
+pub fn ref_to_relative(reference: &str, pkg_name: &str) -> Option<String> {
+    if reference.starts_with("link:") {
+        None
+    } else if reference.starts_with("file:") || reference.contains('/') {
+        Some(reference.to_string())
+    } else {
+        Some(format!("/{}/{}", pkg_name, reference))
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct DependencyPath {
+    pub host: Option<String>,
+    pub is_absolute: bool,
+    pub name: Option<String>,
+    pub peers_suffix: Option<String>,
+    pub version: Option<String>,
+}
+
+pub fn parse(dependency_path: &str) -> Result<DependencyPath> {
+    let is_absolute = is_absolute(dependency_path);
+    let mut parts = dependency_path.split('/');
+    if !is_absolute {
+        parts.next();
+    }
+    let host = if is_absolute {
+        parts.next().map(String::from)
+    } else {
+        None
+    };
+    let mut parts = parts.peekable();
+
+    if parts.peek().is_none() {
+        return Ok(DependencyPath {
+            host,
+            is_absolute,
+            ..Default::default()
+        });
+    }
+
+    let name = match parts.next() {
+        Some(scope) if scope.starts_with('@') => {
+            format!("{}/{}", scope, parts.next().unwrap_or_default())
+        }
+        Some(name) => name.to_string(),
+        None => unreachable!(),
+    };
+
+    if let Some(version) = parts.next() {
+        let (version, peers_suffix) = match version.find('_') {
+            Some(index) => (&version[..index], Some(version[index + 1..].to_string())),
+            None => (version, None),
+        };
+
+        if Version::parse(version).is_ok() {
+            return Ok(DependencyPath {
+                host,
+                is_absolute,
+                name: Some(name),
+                peers_suffix,
+                version: Some(version.to_string()),
+            });
+        }
+    }
+
+    if !is_absolute {
+        bail!("{} is an invalid relative dependency path", dependency_path);
+    }
+
+    Ok(DependencyPath {
+        host,
+        is_absolute,
+        ..Default::default()
+    })
+}
+
+/// Returns the name of the directory inside the virtual store
+/// (`node_modules/.pnpm`) in which the package is unpacked
+pub fn dep_path_to_filename(dep_path: &str) -> String {
+    let filename = dep_path_to_filename_unescaped(dep_path).replace('/', "+");
+
+    if filename.len() > 120
+        || (filename != filename.to_lowercase() && !filename.starts_with("file+"))
+    {
+        format!(
+            "{}_{}",
+            &filename[..floor_char_boundary(&filename, 50)],
+            create_base32_hash(&filename)
+        )
+    } else {
+        filename
+    }
+}
+
+fn dep_path_to_filename_unescaped(dep_path: &str) -> String {
+    if dep_path.starts_with("file:") {
+        return dep_path.replacen(':', "+", 1);
+    }
+
+    let dep_path = dep_path.strip_prefix('/').unwrap_or(dep_path);
+    match dep_path.rfind('/') {
+        Some(index) => format!("{}@{}", &dep_path[..index], &dep_path[index + 1..]),
+        None => dep_path.to_string(),
+    }
+}
+
+pub fn create_base32_hash(input: &str) -> String {
+    base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &md5::compute(input).0,
+    )
+    .to_lowercase()
+}
+
+// similar to the unstable `str::floor_char_boundary`
+fn floor_char_boundary(input: &str, index: usize) -> usize {
+    let mut index = index.min(input.len());
+    while !input.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_relative_dependency_path() {
+        assert_eq!(
+            parse("/foo/1.0.0").unwrap(),
+            DependencyPath {
+                is_absolute: false,
+                name: Some("foo".into()),
+                version: Some("1.0.0".into()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse("/@foo/bar/1.0.0_react@17.0.2").unwrap(),
+            DependencyPath {
+                is_absolute: false,
+                name: Some("@foo/bar".into()),
+                version: Some("1.0.0".into()),
+                peers_suffix: Some("react@17.0.2".into()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_absolute_dependency_path() {
+        assert_eq!(
+            parse("registry.npmjs.org/foo/1.0.0").unwrap(),
+            DependencyPath {
+                host: Some("registry.npmjs.org".into()),
+                is_absolute: true,
+                name: Some("foo".into()),
+                version: Some("1.0.0".into()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse("github.com/kevva/is-positive/97edff6").unwrap(),
+            DependencyPath {
+                host: Some("github.com".into()),
+                is_absolute: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_relative_dependency_path() {
+        assert_eq!(
+            parse("/foo/bar").unwrap_err().to_string(),
+            "/foo/bar is an invalid relative dependency path"
+        );
+    }
+
+    #[test]
+    fn convert_reference_to_relative_path() {
+        assert_eq!(ref_to_relative("1.0.0", "foo"), Some("/foo/1.0.0".into()));
+        assert_eq!(
+            ref_to_relative("/foo/1.0.0", "bar"),
+            Some("/foo/1.0.0".into())
+        );
+        assert_eq!(ref_to_relative("link:../foo", "foo"), None);
+    }
+
+    #[test]
+    fn convert_dependency_path_to_filename() {
+        assert_eq!(dep_path_to_filename("/foo/1.0.0"), "foo@1.0.0");
+        assert_eq!(dep_path_to_filename("/@foo/bar/1.0.0"), "@foo+bar@1.0.0");
+        assert_eq!(
+            dep_path_to_filename("github.com/something/foo/0000?v=1"),
+            "github.com+something+foo@0000?v=1"
+        );
+        assert_eq!(
+            dep_path_to_filename("file:./test/foo-1.0.0.tgz_foo@2.0.0"),
+            "file+.+test+foo-1.0.0.tgz_foo@2.0.0"
+        );
+        assert_eq!(
+            dep_path_to_filename("/foo/1.0.0_Foo@1.0.0"),
+            "foo@1.0.0_Foo@1.0.0_q4hnp2s5qc3htxtfymxmfjggjm"
+        );
+    }
+}
@@ -1,37 +1,449 @@
+mod retry;
+
+use anyhow::{bail, Context, Result};
 use reqwest::{
-    header::{HeaderMap, ACCEPT, AUTHORIZATION, USER_AGENT},
-    Client,
+    blocking::{Client, Response},
+    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT},
+    redirect::Policy,
+    Certificate, Identity, NoProxy, Proxy,
 };
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+pub use retry::RetryTimeoutOptions;
 
 const PNPM_USER_AGENT: &str = "pnpm"; // or maybe make it `${pkg.name}/${pkg.version} (+https://npm.im/${pkg.name})`
 
 const CORGI_DOC: &str = "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*";
 const JSON_DOC: &str = "application/json";
-const MAX_FOLLOWED_REDIRECTS: i32 = 20;
+const MAX_FOLLOWED_REDIRECTS: usize = 20;
+const PEM_CERTIFICATE_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_CERTIFICATE_END: &str = "-----END CERTIFICATE-----";
+
+/// Network settings of the `.npmrc`
+#[derive(Clone, Debug, Default)]
+pub struct AgentOptions {
+    /// PEM encoded certificates that are trusted on top of the system ones
+    pub ca: Vec<String>,
+    /// A file with one or more PEM encoded certificates
+    pub cafile: Option<PathBuf>,
+    /// PEM encoded client certificate, used together with `key`
+    pub cert: Option<String>,
+    /// PEM encoded (PKCS #8) private key of the client certificate
+    pub key: Option<String>,
+    pub strict_ssl: Option<bool>,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    /// Comma separated hosts that are requested without a proxy
+    pub no_proxy: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FetchFromRegistryOptions {
+    pub full_metadata: bool,
+    pub user_agent: Option<String>,
+    pub agent: AgentOptions,
+    pub retry: RetryTimeoutOptions,
+    pub connect_timeout: Duration,
+    /// How long to wait for the response and for every read of its body
+    pub timeout: Duration,
+}
+
+impl Default for FetchFromRegistryOptions {
+    fn default() -> Self {
+        Self {
+            full_metadata: false,
+            user_agent: None,
+            agent: AgentOptions::default(),
+            retry: RetryTimeoutOptions::default(),
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
 
 pub struct FetchFromRegistry {
     client: Client,
+    retry: RetryTimeoutOptions,
+}
+
+/// Splits a bundle of PEM encoded certificates, like the ones in a `cafile`
+fn split_pem_certificates(bundle: &str) -> Vec<String> {
+    bundle
+        .split_inclusive(PEM_CERTIFICATE_END)
+        .filter_map(|chunk| {
+            let start = chunk.find(PEM_CERTIFICATE_BEGIN)?;
+            chunk
+                .ends_with(PEM_CERTIFICATE_END)
+                .then(|| chunk[start..].to_string())
+        })
+        .collect()
+}
+
+fn create_proxy(proxy: reqwest::Result<Proxy>, url: &str, no_proxy: Option<&str>) -> Result<Proxy> {
+    let proxy =
+        proxy.with_context(|| format!("INVALID_PROXY: {} is not a valid proxy URL", url))?;
+    Ok(proxy.no_proxy(no_proxy.and_then(NoProxy::from_string)))
 }
 
 impl FetchFromRegistry {
-    pub fn new(full_meta_data: bool, auth: &str) -> Self {
+    pub fn new(opts: FetchFromRegistryOptions) -> Result<Self> {
         let mut headers = HeaderMap::new();
 
         headers.insert(
             ACCEPT,
-            (if full_meta_data { JSON_DOC } else { CORGI_DOC })
-                .parse()
-                .unwrap(),
+            HeaderValue::from_static(if opts.full_metadata {
+                JSON_DOC
+            } else {
+                CORGI_DOC
+            }),
+        );
+        let user_agent = opts.user_agent.as_deref().unwrap_or(PNPM_USER_AGENT);
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(user_agent)
+                .with_context(|| format!("INVALID_USER_AGENT: {}", user_agent))?,
         );
-        headers.insert(AUTHORIZATION, auth.parse().unwrap());
-        headers.insert(USER_AGENT, PNPM_USER_AGENT.parse().unwrap());
 
-        Self {
-            client: Client::builder().default_headers(headers).build().unwrap(),
+        let mut builder = Client::builder()
+            .default_headers(headers)
+            .redirect(Policy::limited(MAX_FOLLOWED_REDIRECTS))
+            .connect_timeout(opts.connect_timeout)
+            .timeout(opts.timeout)
+            .danger_accept_invalid_certs(!opts.agent.strict_ssl.unwrap_or(true));
+
+        let mut ca = opts.agent.ca.clone();
+        if let Some(cafile) = &opts.agent.cafile {
+            let bundle = fs::read_to_string(cafile)
+                .with_context(|| format!("BAD_CAFILE: Could not read {}", cafile.display()))?;
+            ca.extend(split_pem_certificates(&bundle));
+        }
+        for cert in ca {
+            let cert = Certificate::from_pem(cert.as_bytes())
+                .context("INVALID_CA: Could not parse a certificate of the `ca` setting")?;
+            builder = builder.add_root_certificate(cert);
         }
+
+        match (&opts.agent.cert, &opts.agent.key) {
+            (Some(cert), Some(key)) => {
+                let identity = Identity::from_pkcs8_pem(cert.as_bytes(), key.as_bytes())
+                    .context("INVALID_CLIENT_CERT: Could not use the `cert` and `key` settings")?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => bail!("INVALID_CLIENT_CERT: Both `cert` and `key` should be set"),
+        }
+
+        let no_proxy = opts.agent.no_proxy.as_deref();
+        if let Some(url) = &opts.agent.https_proxy {
+            builder = builder.proxy(create_proxy(Proxy::https(url), url, no_proxy)?);
+        }
+        if let Some(url) = &opts.agent.http_proxy {
+            builder = builder.proxy(create_proxy(Proxy::http(url), url, no_proxy)?);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            retry: opts.retry,
+        })
+    }
+
+    /// Sends a GET request and fails on responses that are not successful.
+    /// Server errors, rate limiting and dropped connections are retried.
+    /// The body of the response is not read, so it can be streamed.
+    pub fn fetch(&self, url: &str, auth_header_value: Option<&str>) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.get(url);
+            if let Some(auth_header_value) = auth_header_value {
+                request = request.header(AUTHORIZATION, auth_header_value);
+            }
+
+            let can_retry = attempt < self.retry.retries;
+            let delay = match request.send() {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if can_retry && retry::is_retriable_status(response.status()) => {
+                    retry::retry_after(&response)
+                        .map(|delay| delay.min(self.retry.max_timeout))
+                        .unwrap_or_else(|| self.retry.backoff(attempt))
+                }
+                Ok(response) => bail!(
+                    "FETCH_{}: GET {}: {}",
+                    response.status().as_u16(),
+                    url,
+                    response.status().canonical_reason().unwrap_or_default()
+                ),
+                Err(err) if can_retry && retry::is_retriable_error(&err) => {
+                    self.retry.backoff(attempt)
+                }
+                Err(err) => return Err(err).with_context(|| format!("GET {}", url)),
+            };
+
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_stub::{read_request, write_response, Reply, Request};
+    use native_tls::{Identity as ServerIdentity, TlsAcceptor};
+    use pretty_assertions::assert_eq;
+    use std::net::{TcpListener, TcpStream};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    fn fixture(name: &str) -> String {
+        fs::read_to_string(Path::new("fixtures").join(name)).unwrap()
+    }
+
+    /// Replies to the requests in order and records them.
+    /// The last reply is repeated once the list is exhausted.
+    fn serve(replies: Vec<Reply>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = Arc::clone(&requests);
+        let url = http_stub::serve(move |request| {
+            let mut recorded = recorded.lock().unwrap();
+            recorded.push(request.clone());
+            replies[(recorded.len() - 1).min(replies.len() - 1)].clone()
+        });
+        (url, requests)
     }
 
-    pub fn fetch(&self) {
-        // let headers = reqwest::header::HeaderMap
+    fn serve_tls() -> String {
+        let identity = ServerIdentity::from_pkcs8(
+            fixture("server.pem").as_bytes(),
+            fixture("server-key.pem").as_bytes(),
+        )
+        .unwrap();
+        let acceptor = TlsAcceptor::new(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Ok(mut stream) = acceptor.accept(stream) {
+                    read_request(&mut stream);
+                    write_response(&mut stream, 200, b"ok");
+                }
+            }
+        });
+        format!("https://localhost:{}", port)
+    }
+
+    fn fast_retries(retries: u32) -> FetchFromRegistryOptions {
+        FetchFromRegistryOptions {
+            retry: RetryTimeoutOptions {
+                retries,
+                factor: 2,
+                min_timeout: Duration::from_millis(10),
+                max_timeout: Duration::from_millis(100),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retry_server_errors_and_rate_limiting() {
+        let (url, requests) = serve(vec![
+            Reply::status(500),
+            Reply::status(429),
+            Reply::status(200),
+        ]);
+        let fetch_from_registry = FetchFromRegistry::new(fast_retries(2)).unwrap();
+
+        let response = fetch_from_registry.fetch(&url, None).unwrap();
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn give_up_after_the_last_retry() {
+        let (url, requests) = serve(vec![Reply::status(503)]);
+        let fetch_from_registry = FetchFromRegistry::new(fast_retries(1)).unwrap();
+
+        let err = fetch_from_registry.fetch(&url, None).unwrap_err();
+
+        assert!(err.to_string().starts_with("FETCH_503: "));
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn do_not_retry_client_errors() {
+        let (url, requests) = serve(vec![Reply::status(404)]);
+        let fetch_from_registry = FetchFromRegistry::new(fast_retries(2)).unwrap();
+
+        let err = fetch_from_registry.fetch(&url, None).unwrap_err();
+
+        assert!(err.to_string().starts_with("FETCH_404: "));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retry_reset_connections() {
+        let (url, requests) = serve(vec![Reply::Reset, Reply::status(200)]);
+        let fetch_from_registry = FetchFromRegistry::new(fast_retries(2)).unwrap();
+
+        let response = fetch_from_registry.fetch(&url, None).unwrap();
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn send_authorization_only_when_given() {
+        let (url, requests) = serve(vec![Reply::status(200)]);
+        let fetch_from_registry = FetchFromRegistry::new(fast_retries(0)).unwrap();
+
+        fetch_from_registry.fetch(&url, None).unwrap();
+        fetch_from_registry.fetch(&url, Some("Bearer foo")).unwrap();
+
+        let requests = requests.lock().unwrap();
+        let has_authorization =
+            |request: &Request| request.header("authorization") == Some("Bearer foo");
+        assert!(!has_authorization(&requests[0]));
+        assert!(has_authorization(&requests[1]));
+    }
+
+    #[test]
+    fn time_out_slow_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let _streams = listener.incoming().flatten().collect::<Vec<TcpStream>>();
+        });
+        let fetch_from_registry = FetchFromRegistry::new(FetchFromRegistryOptions {
+            timeout: Duration::from_millis(100),
+            ..fast_retries(0)
+        })
+        .unwrap();
+
+        let err = fetch_from_registry.fetch(&url, None).unwrap_err();
+
+        assert!(err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout));
+    }
+
+    #[test]
+    fn send_requests_through_proxy() {
+        let (proxy_url, requests) = serve(vec![Reply::status(200)]);
+        let fetch_from_registry = FetchFromRegistry::new(FetchFromRegistryOptions {
+            agent: AgentOptions {
+                http_proxy: Some(proxy_url),
+                ..Default::default()
+            },
+            ..fast_retries(0)
+        })
+        .unwrap();
+
+        fetch_from_registry
+            .fetch("http://registry.example/foo", None)
+            .unwrap();
+
+        assert_eq!(
+            requests.lock().unwrap()[0].request_line,
+            "GET http://registry.example/foo HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn bypass_proxy_for_no_proxy_hosts() {
+        let (proxy_url, proxy_requests) = serve(vec![Reply::status(200)]);
+        let (url, requests) = serve(vec![Reply::status(200)]);
+        let fetch_from_registry = FetchFromRegistry::new(FetchFromRegistryOptions {
+            agent: AgentOptions {
+                http_proxy: Some(proxy_url),
+                no_proxy: Some("127.0.0.1".to_string()),
+                ..Default::default()
+            },
+            ..fast_retries(0)
+        })
+        .unwrap();
+
+        fetch_from_registry.fetch(&url, None).unwrap();
+
+        assert_eq!(proxy_requests.lock().unwrap().len(), 0);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn trust_custom_certificate_authority() {
+        let url = serve_tls();
+
+        let untrusted = FetchFromRegistry::new(fast_retries(0)).unwrap();
+        assert!(untrusted.fetch(&url, None).is_err());
+
+        let with_ca = FetchFromRegistry::new(FetchFromRegistryOptions {
+            agent: AgentOptions {
+                ca: vec![fixture("ca.pem")],
+                ..Default::default()
+            },
+            ..fast_retries(0)
+        })
+        .unwrap();
+        assert!(with_ca.fetch(&url, None).is_ok());
+
+        let with_cafile = FetchFromRegistry::new(FetchFromRegistryOptions {
+            agent: AgentOptions {
+                cafile: Some(PathBuf::from("fixtures/ca.pem")),
+                ..Default::default()
+            },
+            ..fast_retries(0)
+        })
+        .unwrap();
+        assert!(with_cafile.fetch(&url, None).is_ok());
+    }
+
+    #[test]
+    fn skip_certificate_validation_without_strict_ssl() {
+        let url = serve_tls();
+        let fetch_from_registry = FetchFromRegistry::new(FetchFromRegistryOptions {
+            agent: AgentOptions {
+                strict_ssl: Some(false),
+                ..Default::default()
+            },
+            ..fast_retries(0)
+        })
+        .unwrap();
+
+        assert!(fetch_from_registry.fetch(&url, None).is_ok());
+    }
+
+    #[test]
+    fn load_client_certificate() {
+        let with_cert = |key: Option<String>| {
+            FetchFromRegistry::new(FetchFromRegistryOptions {
+                agent: AgentOptions {
+                    cert: Some(fixture("client.pem")),
+                    key,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+
+        assert!(with_cert(Some(fixture("client-key.pem"))).is_ok());
+        assert!(with_cert(None).is_err());
+    }
+
+    #[test]
+    fn split_certificate_bundle() {
+        let ca = fixture("ca.pem");
+        let bundle = format!("# test CA\n{}\n{}", ca, fixture("server.pem"));
+
+        let certs = split_pem_certificates(&bundle);
+
+        assert_eq!(
+            certs,
+            vec![
+                ca.trim().to_string(),
+                fixture("server.pem").trim().to_string()
+            ]
+        );
     }
 }
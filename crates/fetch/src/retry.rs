@@ -0,0 +1,104 @@
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// Same defaults as the `fetch-retries`, `fetch-retry-factor`,
+/// `fetch-retry-mintimeout` and `fetch-retry-maxtimeout` settings
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryTimeoutOptions {
+    pub retries: u32,
+    pub factor: u32,
+    pub min_timeout: Duration,
+    pub max_timeout: Duration,
+}
+
+impl Default for RetryTimeoutOptions {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            factor: 10,
+            min_timeout: Duration::from_secs(10),
+            max_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryTimeoutOptions {
+    /// The delay before retrying for the given attempt, starting at 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.factor.checked_pow(attempt).unwrap_or(u32::MAX);
+        self.min_timeout
+            .checked_mul(factor)
+            .unwrap_or(self.max_timeout)
+            .min(self.max_timeout)
+    }
+}
+
+pub(crate) fn is_retriable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Connection resets and refusals are usually temporary,
+/// so are timeouts
+pub(crate) fn is_retriable_error(err: &reqwest::Error) -> bool {
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io_err.kind(),
+                ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+            ) {
+                return true;
+            }
+        }
+        if let Some(hyper_err) = err.downcast_ref::<hyper::Error>() {
+            // the connection was closed before the response was received
+            if hyper_err.is_incomplete_message() {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+
+    false
+}
+
+/// Servers that rate limit the requests tell when to try again
+pub(crate) fn retry_after(response: &reqwest::blocking::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn exponential_backoff() {
+        let opts = RetryTimeoutOptions {
+            retries: 5,
+            factor: 10,
+            min_timeout: Duration::from_millis(10),
+            max_timeout: Duration::from_millis(5000),
+        };
+
+        assert_eq!(opts.backoff(0), Duration::from_millis(10));
+        assert_eq!(opts.backoff(1), Duration::from_millis(100));
+        assert_eq!(opts.backoff(2), Duration::from_millis(1000));
+        assert_eq!(opts.backoff(3), Duration::from_millis(5000));
+        assert_eq!(opts.backoff(40), Duration::from_millis(5000));
+    }
+}
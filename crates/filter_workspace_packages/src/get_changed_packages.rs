@@ -0,0 +1,185 @@
+use anyhow::{bail, Result};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Default)]
+pub struct ChangedPackagesOptions<'a> {
+    /// The changed files matching these patterns mark test changes,
+    /// that don't affect the dependents of their project
+    pub test_pattern: &'a [String],
+    /// The changed files matching these patterns are ignored
+    pub changed_files_ignore_pattern: &'a [String],
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ChangeType {
+    Source,
+    Test,
+}
+
+fn patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+    Ok(patterns
+        .iter()
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Like micromatch, `*` doesn't match `/` and dot files are matched
+fn matches_any(patterns: &[Pattern], file: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_with(file, options))
+}
+
+/// The files changed since the git ref, relative to the directory
+fn get_changed_files(since: &str, dir: &Path) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(["diff", "--name-only", "--relative", since, "--", "."])
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "FILTER_CHANGED: Filtering by changed packages failed. {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        // git quotes the paths with unusual characters
+        .map(|line| line.trim_matches('"').to_string())
+        .collect())
+}
+
+/// The directories of the projects that have files changed since the git ref,
+/// and the ones of the projects that only have changes in their tests.
+/// A file belongs to the deepest project that contains it.
+pub fn get_changed_packages<'a>(
+    project_dirs: &[&'a str],
+    since: &str,
+    workspace_dir: &Path,
+    opts: &ChangedPackagesOptions,
+) -> Result<(Vec<&'a str>, Vec<&'a str>)> {
+    let test_pattern = patterns(opts.test_pattern)?;
+    let ignore_pattern = patterns(opts.changed_files_ignore_pattern)?;
+
+    let mut changes = HashMap::<&str, ChangeType>::new();
+    for file in get_changed_files(since, workspace_dir)? {
+        if matches_any(&ignore_pattern, &file) {
+            continue;
+        }
+        let path = workspace_dir.join(&file);
+        let owner = project_dirs
+            .iter()
+            .filter(|dir| path.starts_with(dir))
+            .max_by_key(|dir| Path::new(dir).components().count());
+        if let Some(dir) = owner {
+            let change_type = match matches_any(&test_pattern, &file) {
+                true => ChangeType::Test,
+                false => ChangeType::Source,
+            };
+            let change = changes.entry(dir).or_insert(change_type);
+            if change_type == ChangeType::Source {
+                *change = ChangeType::Source;
+            }
+        }
+    }
+
+    let (mut changed, mut test_only) = (vec![], vec![]);
+    for dir in project_dirs {
+        match changes.get(dir) {
+            Some(ChangeType::Source) => changed.push(*dir),
+            Some(ChangeType::Test) => test_only.push(*dir),
+            None => {}
+        }
+    }
+    Ok((changed, test_only))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    fn write(dir: &Path, file: &str) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn changed_since_a_commit() {
+        let repo = tempdir().unwrap();
+        let root = repo.path();
+        for file in [
+            "packages/foo/index.js",
+            "packages/foo/test/index.js",
+            "packages/bar/index.js",
+            "packages/bar/test/index.js",
+            "packages/baz/index.js",
+        ] {
+            write(root, file);
+        }
+        git(root, &["init", "-q"]);
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "init"]);
+
+        write(root, "packages/foo/lib/new.js");
+        write(root, "packages/bar/test/new.js");
+        write(root, "packages/baz/README.md");
+        write(root, "README.md");
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "change"]);
+
+        let project_dirs = ["foo", "bar", "baz"].map(|name| {
+            root.join("packages")
+                .join(name)
+                .to_string_lossy()
+                .to_string()
+        });
+        let project_dirs = project_dirs.iter().map(String::as_str).collect::<Vec<_>>();
+        let (changed, test_only) = get_changed_packages(
+            &project_dirs,
+            "HEAD~1",
+            root,
+            &ChangedPackagesOptions {
+                test_pattern: &["**/test/**".to_string()],
+                changed_files_ignore_pattern: &["**/*.md".to_string()],
+            },
+        )
+        .unwrap();
+        assert_eq!(changed, [project_dirs[0]]);
+        assert_eq!(test_only, [project_dirs[1]]);
+
+        let (changed, test_only) =
+            get_changed_packages(&project_dirs, "HEAD~1", root, &Default::default()).unwrap();
+        assert_eq!(changed, project_dirs);
+        assert!(test_only.is_empty());
+
+        let error = get_changed_packages(&project_dirs, "no-such-ref", root, &Default::default())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("FILTER_CHANGED: Filtering by changed packages failed."));
+    }
+}
@@ -0,0 +1,503 @@
+mod get_changed_packages;
+mod parse_package_selector;
+
+use anyhow::{bail, Result};
+use glob::{MatchOptions, Pattern};
+use matcher::Matcher;
+use pkgs_graph::{create_pkgs_graph, CreatePkgsGraphOptions};
+use project::{Graph, Project, ProjectsGraph};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+pub use get_changed_packages::{get_changed_packages, ChangedPackagesOptions};
+pub use parse_package_selector::{parse_package_selector, PackageSelector};
+
+/// A `--filter` or `--filter-prod` selector
+pub struct WorkspaceFilter {
+    pub filter: String,
+    pub follow_prod_deps_only: bool,
+}
+
+pub struct FilterPackagesOptions<'a> {
+    /// The directory the path selectors are relative to
+    pub prefix: &'a Path,
+    pub workspace_dir: &'a Path,
+    /// The changes of the files matching these patterns don't select the dependents
+    pub test_pattern: &'a [String],
+    /// The changes of the files matching these patterns are ignored
+    pub changed_files_ignore_pattern: &'a [String],
+}
+
+pub struct FilterPackagesResult<'a> {
+    pub selected_projects_graph: ProjectsGraph<'a>,
+    /// The name patterns and directories that matched no project
+    pub unmatched_filters: Vec<String>,
+}
+
+/// Selects the projects of the workspace that match the filters.
+/// Every project is selected when there are only exclusions.
+pub fn filter_packages<'a>(
+    projects: &'a [Project],
+    filters: &[WorkspaceFilter],
+    opts: &FilterPackagesOptions,
+) -> Result<FilterPackagesResult<'a>> {
+    let selectors = filters
+        .iter()
+        .map(|filter| PackageSelector {
+            follow_prod_deps_only: filter.follow_prod_deps_only,
+            ..parse_package_selector(&filter.filter, opts.prefix)
+        })
+        .collect::<Vec<_>>();
+    filter_pkgs_by_selector_objects(projects, &selectors, opts)
+}
+
+pub fn filter_pkgs_by_selector_objects<'a>(
+    projects: &'a [Project],
+    selectors: &[PackageSelector],
+    opts: &FilterPackagesOptions,
+) -> Result<FilterPackagesResult<'a>> {
+    let (prod_selectors, all_selectors): (Vec<_>, Vec<_>) = selectors
+        .iter()
+        .partition(|selector| selector.follow_prod_deps_only);
+
+    let mut result = FilterPackagesResult {
+        selected_projects_graph: HashMap::new(),
+        unmatched_filters: vec![],
+    };
+    let mut add = |graph: ProjectsGraph<'a>, selectors: &[&PackageSelector]| -> Result<()> {
+        let (selected, unmatched_filters) = filter_graph(graph, selectors, opts)?;
+        result.selected_projects_graph.extend(selected);
+        result.unmatched_filters.extend(unmatched_filters);
+        Ok(())
+    };
+    if !all_selectors.is_empty() || prod_selectors.is_empty() {
+        add(
+            create_pkgs_graph(projects, &CreatePkgsGraphOptions::default()),
+            &all_selectors,
+        )?;
+    }
+    if !prod_selectors.is_empty() {
+        add(
+            create_pkgs_graph(
+                projects,
+                &CreatePkgsGraphOptions {
+                    ignore_dev_deps: true,
+                },
+            ),
+            &prod_selectors,
+        )?;
+    }
+    Ok(result)
+}
+
+/// The projects selected by the include selectors, or all of them when there are none,
+/// without the ones selected by the exclude selectors
+fn filter_graph<'a>(
+    graph: ProjectsGraph<'a>,
+    selectors: &[&PackageSelector],
+    opts: &FilterPackagesOptions,
+) -> Result<(ProjectsGraph<'a>, Vec<String>)> {
+    let (exclude_selectors, include_selectors): (Vec<_>, Vec<_>) = selectors
+        .iter()
+        .copied()
+        .partition(|selector| selector.exclude);
+
+    let mut unmatched_filters = vec![];
+    let included = if include_selectors.is_empty() {
+        graph.keys().map(String::as_str).collect()
+    } else {
+        select(&graph, &include_selectors, opts, &mut unmatched_filters)?
+    };
+    let excluded = select(&graph, &exclude_selectors, opts, &mut unmatched_filters)?;
+    let selected = included
+        .difference(&excluded)
+        .map(|dir| dir.to_string())
+        .collect::<HashSet<_>>();
+
+    let selected_projects_graph = graph
+        .into_iter()
+        .filter(|(dir, _)| selected.contains(dir))
+        .map(|(dir, node)| {
+            let dependencies = node
+                .dependencies
+                .into_iter()
+                .filter(|dependency| selected.contains(dependency))
+                .collect();
+            (
+                dir,
+                Graph {
+                    dependencies,
+                    package: node.package,
+                },
+            )
+        })
+        .collect();
+    Ok((selected_projects_graph, unmatched_filters))
+}
+
+/// The projects named like the pattern. A pattern without a scope matches
+/// a scoped project too, when it is the only one with that name.
+fn match_packages<'g>(graph: &'g ProjectsGraph, dirs: &[&'g str], pattern: &str) -> Vec<&'g str> {
+    let matcher = Matcher::new(&[pattern]);
+    let matches = dirs
+        .iter()
+        .copied()
+        .filter(|dir| {
+            graph[*dir]
+                .package
+                .manifest
+                .name
+                .as_deref()
+                .is_some_and(|name| matcher.is_match(name))
+        })
+        .collect::<Vec<_>>();
+    if matches.is_empty() && !pattern.starts_with('@') && !pattern.contains('/') {
+        let scoped_matches = match_packages(graph, dirs, &format!("@*/{}", pattern));
+        if scoped_matches.len() == 1 {
+            return scoped_matches;
+        }
+    }
+    matches
+}
+
+/// Adds the nodes and everything reachable from them
+fn pick_subgraph<'g>(
+    edges: &HashMap<&'g str, Vec<&'g str>>,
+    next_nodes: &[&'g str],
+    walked: &mut HashSet<&'g str>,
+    include_root: bool,
+) {
+    for node in next_nodes {
+        if walked.contains(node) {
+            continue;
+        }
+        if include_root {
+            walked.insert(node);
+        }
+        if let Some(next_nodes) = edges.get(node) {
+            pick_subgraph(edges, next_nodes, walked, true);
+        }
+    }
+}
+
+/// The projects picked by the selectors
+#[derive(Default)]
+struct Selection<'g> {
+    cherry_picked: HashSet<&'g str>,
+    walked_dependencies: HashSet<&'g str>,
+    walked_dependents: HashSet<&'g str>,
+    walked_dependents_dependencies: HashSet<&'g str>,
+}
+
+impl<'g> Selection<'g> {
+    fn select_entries(
+        &mut self,
+        dependencies: &HashMap<&'g str, Vec<&'g str>>,
+        dependents: &HashMap<&'g str, Vec<&'g str>>,
+        selector: &PackageSelector,
+        include_dependents: bool,
+        entry_packages: &[&'g str],
+    ) {
+        let include_root = !selector.exclude_self;
+        if selector.include_dependencies {
+            pick_subgraph(
+                dependencies,
+                entry_packages,
+                &mut self.walked_dependencies,
+                include_root,
+            );
+        }
+        if include_dependents {
+            pick_subgraph(
+                dependents,
+                entry_packages,
+                &mut self.walked_dependents,
+                include_root,
+            );
+        }
+        if selector.include_dependencies && include_dependents {
+            let walked = self.walked_dependents.iter().copied().collect::<Vec<_>>();
+            pick_subgraph(
+                dependencies,
+                &walked,
+                &mut self.walked_dependents_dependencies,
+                false,
+            );
+        }
+        if !selector.include_dependencies && !include_dependents {
+            self.cherry_picked.extend(entry_packages);
+        }
+    }
+
+    fn into_selected(self) -> HashSet<&'g str> {
+        self.cherry_picked
+            .into_iter()
+            .chain(self.walked_dependencies)
+            .chain(self.walked_dependents)
+            .chain(self.walked_dependents_dependencies)
+            .collect()
+    }
+}
+
+fn select<'g>(
+    graph: &'g ProjectsGraph,
+    selectors: &[&PackageSelector],
+    opts: &FilterPackagesOptions,
+    unmatched_filters: &mut Vec<String>,
+) -> Result<HashSet<&'g str>> {
+    let all_dirs = graph.keys().map(String::as_str).collect::<Vec<_>>();
+    let dependencies = graph
+        .iter()
+        .map(|(dir, node)| {
+            let dependencies = node.dependencies.iter().map(String::as_str).collect();
+            (dir.as_str(), dependencies)
+        })
+        .collect::<HashMap<_, Vec<_>>>();
+    let mut dependents = HashMap::<_, Vec<_>>::new();
+    for (dir, node) in graph {
+        for dependency in &node.dependencies {
+            dependents
+                .entry(dependency.as_str())
+                .or_default()
+                .push(dir.as_str());
+        }
+    }
+
+    let mut selection = Selection::default();
+    for selector in selectors {
+        let mut entry_packages = None;
+        if let Some(since) = &selector.diff {
+            let (changed, test_only) = get_changed_packages(
+                &all_dirs,
+                since,
+                selector.parent_dir.as_deref().unwrap_or(opts.workspace_dir),
+                &ChangedPackagesOptions {
+                    test_pattern: opts.test_pattern,
+                    changed_files_ignore_pattern: opts.changed_files_ignore_pattern,
+                },
+            )?;
+            // the changes of the tests don't affect the dependents
+            selection.select_entries(&dependencies, &dependents, selector, false, &test_only);
+            entry_packages = Some(changed);
+        }
+        if let Some(pattern) = &selector.name_pattern {
+            let dirs = entry_packages.as_deref().unwrap_or(&all_dirs);
+            entry_packages = Some(match_packages(graph, dirs, pattern));
+        }
+        if let Some(parent_dir) = &selector.parent_dir {
+            let dirs = entry_packages.as_deref().unwrap_or(&all_dirs);
+            entry_packages = Some(
+                dirs.iter()
+                    .copied()
+                    .filter(|dir| is_in_parent_dir(Path::new(dir), parent_dir))
+                    .collect(),
+            );
+        }
+        let entry_packages = match entry_packages {
+            Some(entry_packages) => entry_packages,
+            None => bail!(
+                "UNSUPPORTED_SELECTOR: Unsupported package selector: {:?}",
+                selector
+            ),
+        };
+
+        if entry_packages.is_empty() {
+            unmatched_filters.extend(selector.name_pattern.clone());
+            unmatched_filters.extend(
+                selector
+                    .parent_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string()),
+            );
+        }
+        selection.select_entries(
+            &dependencies,
+            &dependents,
+            selector,
+            selector.include_dependents,
+            &entry_packages,
+        );
+    }
+    Ok(selection.into_selected())
+}
+
+/// Whether the project is in the directory of the selector,
+/// which may also be a glob like `packages/**` or `packages/*`
+fn is_in_parent_dir(dir: &Path, parent_dir: &Path) -> bool {
+    let parent_dir = parent_dir.to_string_lossy();
+    if !parent_dir.contains(['*', '?', '[']) {
+        return dir.starts_with(parent_dir.as_ref());
+    }
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    Pattern::new(&parent_dir).is_ok_and(|pattern| pattern.matches_path_with(dir, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use read_project_manifest::WriterOptions;
+    use types::BaseManifest;
+
+    fn project(dir: &str, name: &str, deps: &[&str], dev_deps: &[&str]) -> Project {
+        let dependencies = |deps: &[&str]| {
+            Some(
+                deps.iter()
+                    .map(|dep| (dep.to_string(), "workspace:*".to_string()))
+                    .collect(),
+            )
+        };
+        Project {
+            dir: dir.to_string(),
+            manifest: BaseManifest {
+                name: Some(name.to_string()),
+                version: Some("1.0.0".to_string()),
+                dependencies: dependencies(deps),
+                dev_dependencies: dependencies(dev_deps),
+                ..Default::default()
+            },
+            writer_options: WriterOptions {
+                insert_final_newline: None,
+                manifest_path: format!("{}/package.json", dir),
+            },
+        }
+    }
+
+    fn selected(projects: &[Project], filters: &[&str], prod: bool) -> Vec<String> {
+        let filters = filters
+            .iter()
+            .map(|filter| WorkspaceFilter {
+                filter: filter.to_string(),
+                follow_prod_deps_only: prod,
+            })
+            .collect::<Vec<_>>();
+        let opts = FilterPackagesOptions {
+            prefix: Path::new("/ws"),
+            workspace_dir: Path::new("/ws"),
+            test_pattern: &[],
+            changed_files_ignore_pattern: &[],
+        };
+        let result = filter_packages(projects, &filters, &opts).unwrap();
+        let mut selected = result
+            .selected_projects_graph
+            .into_keys()
+            .collect::<Vec<_>>();
+        selected.sort();
+        selected
+    }
+
+    #[test]
+    fn select_by_name_path_and_relations() {
+        // app -> @scope/lib -> utils, and app has the dev dependency tools
+        let projects = [
+            project("/ws/apps/app", "app", &["@scope/lib"], &["tools"]),
+            project("/ws/libs/lib", "@scope/lib", &["utils"], &[]),
+            project("/ws/libs/utils", "utils", &[], &[]),
+            project("/ws/tools", "tools", &[], &[]),
+        ];
+
+        assert_eq!(selected(&projects, &["lib"], false), ["/ws/libs/lib"]);
+        assert_eq!(
+            selected(&projects, &["./libs"], false),
+            ["/ws/libs/lib", "/ws/libs/utils"]
+        );
+        assert_eq!(
+            selected(&projects, &["./libs/**"], false),
+            ["/ws/libs/lib", "/ws/libs/utils"]
+        );
+        assert_eq!(selected(&projects, &["{*/app}"], false), ["/ws/apps/app"]);
+        assert_eq!(
+            selected(&projects, &["{libs}", "!utils"], false),
+            ["/ws/libs/lib"]
+        );
+        assert_eq!(
+            selected(&projects, &["app..."], false),
+            [
+                "/ws/apps/app",
+                "/ws/libs/lib",
+                "/ws/libs/utils",
+                "/ws/tools"
+            ]
+        );
+        assert_eq!(
+            selected(&projects, &["app^..."], true),
+            ["/ws/libs/lib", "/ws/libs/utils"]
+        );
+        assert_eq!(
+            selected(&projects, &["...^utils"], false),
+            ["/ws/apps/app", "/ws/libs/lib"]
+        );
+        assert_eq!(
+            selected(&projects, &["!./libs"], false),
+            ["/ws/apps/app", "/ws/tools"]
+        );
+        assert_eq!(
+            selected(&projects, &["utils", "tools"], false),
+            ["/ws/libs/utils", "/ws/tools"]
+        );
+    }
+
+    #[test]
+    fn select_the_dependents_of_changed_projects() {
+        use std::fs;
+        use std::process::Command;
+
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(root)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        let write = |file: &str| {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        };
+        write("lib/index.js");
+        write("app/index.js");
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "init"]);
+
+        let dir = |name: &str| root.join(name).to_string_lossy().to_string();
+        let projects = [
+            project(&dir("app"), "app", &["lib"], &[]),
+            project(&dir("lib"), "lib", &[], &[]),
+        ];
+        let test_pattern = ["*/test/**".to_string()];
+        let selected = || {
+            let filters = [WorkspaceFilter {
+                filter: "...[HEAD]".to_string(),
+                follow_prod_deps_only: false,
+            }];
+            let opts = FilterPackagesOptions {
+                prefix: root,
+                workspace_dir: root,
+                test_pattern: &test_pattern,
+                changed_files_ignore_pattern: &[],
+            };
+            let result = filter_packages(&projects, &filters, &opts).unwrap();
+            let mut selected = result
+                .selected_projects_graph
+                .into_keys()
+                .collect::<Vec<_>>();
+            selected.sort();
+            selected
+        };
+
+        write("lib/test/index.js");
+        git(&["add", "."]);
+        assert_eq!(selected(), [dir("lib")]);
+
+        fs::write(root.join("lib/index.js"), "changed").unwrap();
+        assert_eq!(selected(), [dir("app"), dir("lib")]);
+    }
+}
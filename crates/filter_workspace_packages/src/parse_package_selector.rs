@@ -0,0 +1,141 @@
+use lazy_static::lazy_static;
+use normalize_path::normalize_path;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PackageSelector {
+    /// The projects changed since this git ref, like `origin/main`
+    pub diff: Option<String>,
+    pub exclude: bool,
+    /// Only select the dependencies or dependents of the matched projects
+    pub exclude_self: bool,
+    pub include_dependencies: bool,
+    pub include_dependents: bool,
+    pub name_pattern: Option<String>,
+    pub parent_dir: Option<PathBuf>,
+    /// Ignore the devDependencies when walking the dependencies and dependents
+    pub follow_prod_deps_only: bool,
+}
+
+/// `.`, `..` and the paths starting with them
+fn is_selector_by_location(raw_selector: &str) -> bool {
+    let rest = match raw_selector.strip_prefix("..") {
+        Some(rest) => rest,
+        None => match raw_selector.strip_prefix('.') {
+            Some(rest) => rest,
+            None => return false,
+        },
+    };
+    rest.is_empty() || rest.starts_with(['/', '\\'])
+}
+
+/// Parses a `--filter` selector, like `foo...`, `...^foo`, `./packages/**`,
+/// `{packages}[origin/main]` or `!foo`. Paths are relative to `prefix`.
+pub fn parse_package_selector(raw_selector: &str, prefix: &Path) -> PackageSelector {
+    lazy_static! {
+        static ref SELECTOR: Regex =
+            Regex::new(r"^([^.][^{}\[\]]*)?(\{[^}]+\})?(\[[^\]]+\])?$").unwrap();
+    }
+
+    let mut selector = PackageSelector::default();
+    let mut raw_selector = raw_selector;
+    if let Some(rest) = raw_selector.strip_prefix('!') {
+        selector.exclude = true;
+        raw_selector = rest;
+    }
+    if let Some(rest) = raw_selector.strip_suffix("...") {
+        selector.include_dependencies = true;
+        raw_selector = rest;
+        if let Some(rest) = raw_selector.strip_suffix('^') {
+            selector.exclude_self = true;
+            raw_selector = rest;
+        }
+    }
+    if let Some(rest) = raw_selector.strip_prefix("...") {
+        selector.include_dependents = true;
+        raw_selector = rest;
+        if let Some(rest) = raw_selector.strip_prefix('^') {
+            selector.exclude_self = true;
+            raw_selector = rest;
+        }
+    }
+
+    match SELECTOR.captures(raw_selector) {
+        Some(captures) => {
+            selector.name_pattern = captures.get(1).map(|name| name.as_str().to_string());
+            selector.parent_dir = captures.get(2).map(|dir| {
+                let dir = dir.as_str();
+                normalize_path(&prefix.join(&dir[1..dir.len() - 1]))
+            });
+            selector.diff = captures.get(3).map(|diff| {
+                let diff = diff.as_str();
+                diff[1..diff.len() - 1].to_string()
+            });
+            selector
+        }
+        None if is_selector_by_location(raw_selector) => PackageSelector {
+            exclude: selector.exclude,
+            parent_dir: Some(normalize_path(&prefix.join(raw_selector))),
+            ..Default::default()
+        },
+        None => PackageSelector {
+            name_pattern: Some(raw_selector.to_string()),
+            ..selector
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_selectors() {
+        let prefix = Path::new("/ws");
+        assert_eq!(
+            parse_package_selector("foo...", prefix),
+            PackageSelector {
+                name_pattern: Some("foo".to_string()),
+                include_dependencies: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_package_selector("!...^@scope/*", prefix),
+            PackageSelector {
+                name_pattern: Some("@scope/*".to_string()),
+                exclude: true,
+                include_dependents: true,
+                exclude_self: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_package_selector("./packages/../apps", prefix),
+            PackageSelector {
+                parent_dir: Some(PathBuf::from("/ws/apps")),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_package_selector("...{packages}[origin/main]", prefix),
+            PackageSelector {
+                parent_dir: Some(PathBuf::from("/ws/packages")),
+                diff: Some("origin/main".to_string()),
+                include_dependents: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_package_selector("[HEAD~2]^...", prefix),
+            PackageSelector {
+                diff: Some("HEAD~2".to_string()),
+                include_dependencies: true,
+                exclude_self: true,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{bail, Result};
+use constants::WORKSPACE_MANIFEST_FILENAME;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The env variable that sets the workspace directory instead of looking it up
+pub const WORKSPACE_DIR_ENV_VAR: &str = "NPM_CONFIG_WORKSPACE_DIR";
+
+/// The closest directory from `cwd` up that has a `pnpm-workspace.yaml`.
+/// `workspace_dir_override` is the value of `NPM_CONFIG_WORKSPACE_DIR`, used as is when set.
+pub fn find_workspace_dir(
+    cwd: &Path,
+    workspace_dir_override: Option<&Path>,
+) -> Result<Option<PathBuf>> {
+    if let Some(workspace_dir) = workspace_dir_override {
+        return Ok(Some(workspace_dir.to_path_buf()));
+    }
+
+    // The real path has the case of the file system, so a workspace found from `c:\code\project`
+    // has the same directory as its projects found from `C:\Code\Project`.
+    // It also resolves the symlinks, like the projects of the workspace are.
+    let cwd = fs::canonicalize(cwd).unwrap_or_else(|_| cwd.to_path_buf());
+    for dir in cwd.ancestors() {
+        if dir.join(WORKSPACE_MANIFEST_FILENAME).is_file() {
+            return Ok(Some(dir.to_path_buf()));
+        }
+        let misnamed_manifest = dir.join("pnpm-workspace.yml");
+        if misnamed_manifest.is_file() {
+            bail!(
+                "BAD_WORKSPACE_MANIFEST_NAME: The workspace manifest file should be named \"{}\". File found: {}",
+                WORKSPACE_MANIFEST_FILENAME,
+                misnamed_manifest.display()
+            );
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn find_the_closest_workspace_manifest() {
+        let root = tempdir().unwrap();
+        let root_dir = fs::canonicalize(root.path()).unwrap();
+        let project = root_dir.join("packages").join("foo");
+        fs::create_dir_all(&project).unwrap();
+        assert_eq!(find_workspace_dir(&project, None).unwrap(), None);
+
+        fs::write(root_dir.join(WORKSPACE_MANIFEST_FILENAME), "").unwrap();
+        assert_eq!(
+            find_workspace_dir(&project, None).unwrap(),
+            Some(root_dir.clone())
+        );
+        assert_eq!(
+            find_workspace_dir(&project, Some(Path::new("/elsewhere"))).unwrap(),
+            Some(PathBuf::from("/elsewhere"))
+        );
+
+        #[cfg(unix)]
+        {
+            let link = tempdir().unwrap();
+            let linked_project = link.path().join("foo");
+            std::os::unix::fs::symlink(&project, &linked_project).unwrap();
+            assert_eq!(
+                find_workspace_dir(&linked_project, None).unwrap(),
+                Some(root_dir.clone())
+            );
+        }
+
+        fs::write(project.join("pnpm-workspace.yml"), "").unwrap();
+        let error = find_workspace_dir(&project, None).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("BAD_WORKSPACE_MANIFEST_NAME: The workspace manifest file should be named \"pnpm-workspace.yaml\"."));
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use glob::{glob, Pattern};
 use lazy_static::lazy_static;
 use project::Project;
@@ -51,13 +51,16 @@ fn get_manifest_paths<P: AsRef<Path> + Sync>(
         })
         .collect::<Vec<_>>();
 
-    // I dont think that this part of code is required here
-    // So I'll probably remove it later down the line
     if include_root {
-        manifest_paths.extend(glob(&normalize_pattern("."))?.filter_map(Result::ok));
+        let root_manifest = root.as_ref().join("package.json");
+        if root_manifest.exists() {
+            manifest_paths.push(root_manifest);
+        }
     }
 
     manifest_paths.sort_by(|path_1, path_2| path_1.parent().partial_cmp(&path_2.parent()).unwrap());
+    // several patterns may match the same manifest
+    manifest_paths.dedup();
 
     Ok(manifest_paths)
 }
@@ -67,36 +70,46 @@ pub fn find_packages<P: AsRef<Path> + Sync>(
     opts: Option<FindPackagesOpts>,
 ) -> Result<Vec<Project>> {
     let opts = opts.unwrap_or_default();
-    let patterns = opts
+    let mut ignore = opts.ignore.unwrap_or_else(|| DEFAULT_IGNORE.clone());
+    // `!`-prefixed patterns exclude the directories they match
+    let (negated, patterns): (Vec<_>, Vec<_>) = opts
         .patterns
         .unwrap_or_else(|| vec!["**".to_string()])
+        .into_iter()
+        .partition(|pattern| pattern.starts_with('!'));
+    ignore.extend(
+        negated
+            .iter()
+            .map(|pattern| normalize_pattern(&pattern[1..])),
+    );
+    let patterns = patterns
         .par_iter()
         .map(|pattern| normalize_pattern(pattern))
         .collect::<Vec<_>>();
-    let manifest_paths = get_manifest_paths(
-        root,
-        &patterns,
-        opts.ignore.as_deref().unwrap_or(&DEFAULT_IGNORE),
-        opts.include_root.unwrap_or(false),
-    )?;
-
-    Ok(manifest_paths
+    let manifest_paths =
+        get_manifest_paths(root, &patterns, &ignore, opts.include_root.unwrap_or(false))?;
+
+    manifest_paths
         .iter()
-        .filter_map(
-            |manifest_path| match read_exact_project_manifest(&manifest_path) {
-                Ok(manifest) => Some(Project {
-                    dir: manifest_path
-                        .parent()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_string(),
-                    manifest: manifest.manifest.expect("manifest not found"),
-                    writer_options: manifest.writer_options,
-                }),
-                Err(_) => None,
-            },
-        )
-        .collect::<Vec<_>>())
+        .map(|manifest_path| {
+            let manifest = read_exact_project_manifest(manifest_path).map_err(|error| {
+                anyhow!(
+                    "BAD_PACKAGE_JSON: {}: {}",
+                    manifest_path.to_string_lossy(),
+                    error
+                )
+            })?;
+            Ok(Project {
+                dir: manifest_path
+                    .parent()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+                manifest: manifest.manifest.expect("manifest not found"),
+                writer_options: manifest.writer_options,
+            })
+        })
+        .collect()
 }
 
 fn normalize_pattern(pattern: &str) -> String {
@@ -192,4 +205,39 @@ mod tests {
             })
         })
     }
+
+    #[test]
+    fn exclude_packages_by_negated_patterns() {
+        let packages = find_packages(
+            Path::new("fixtures").join("many-pkgs"),
+            Some(FindPackagesOpts {
+                patterns: Some(vec![
+                    "**".to_string(),
+                    "!components/component-1".to_string(),
+                ]),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+
+        let names = packages
+            .iter()
+            .map(|package| package.manifest.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["component-2", "foo"]);
+    }
+
+    #[test]
+    fn report_the_path_of_a_bad_manifest() {
+        let root = tempfile::tempdir().unwrap();
+        let manifest_path = root.path().join("bad").join("package.json");
+        std::fs::create_dir(manifest_path.parent().unwrap()).unwrap();
+        std::fs::write(&manifest_path, "{ not json").unwrap();
+
+        let error = find_packages(root.path(), None).unwrap_err().to_string();
+        assert!(error.starts_with(&format!(
+            "BAD_PACKAGE_JSON: {}: ",
+            manifest_path.to_string_lossy()
+        )));
+    }
 }
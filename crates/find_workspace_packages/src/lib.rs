@@ -1,38 +1,73 @@
 use std::{collections::HashMap, path::Path};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use cli_utils::package_is_installable::{package_is_installable, PackageIsInstallableOpts};
 use find_packages::FindPackagesOpts;
+use package_is_installable::current_node_version;
 use project::Project;
 use serde::Deserialize;
 use types::BaseManifest;
 mod find_packages;
 
+#[derive(Default)]
 pub struct WorkspacePackagesOpts {
-    engine_strict: Option<bool>,
-    node_version: Option<String>,
-    patterns: Option<Vec<String>>,
+    pub engine_strict: Option<bool>,
+    pub node_version: Option<String>,
+    /// Overrides the `packages` of `pnpm-workspace.yaml`
+    pub patterns: Option<Vec<String>>,
 }
 
+/// Finds the projects of the workspace and checks that they can be installed
+/// on the current platform and version of node
 pub fn find_workspace_packages(
     workspace_root: &str,
     opts: WorkspacePackagesOpts,
 ) -> Result<Vec<Project>> {
-    let pkgs = find_workspace_packages_no_check(workspace_root, opts);
+    let engine_strict = opts.engine_strict;
+    // looked up once instead of for every project
+    let node_version = opts.node_version.clone().or_else(current_node_version);
+    let pkgs = find_workspace_packages_no_check(workspace_root, opts)?;
 
-    todo!("check if packages are installable");
+    for pkg in &pkgs {
+        package_is_installable(
+            &pkg.dir,
+            &pkg.manifest,
+            PackageIsInstallableOpts {
+                engine_strict,
+                node_version: node_version.clone(),
+            },
+        )?;
+    }
+    check_duplicate_names(&pkgs)?;
+
+    Ok(pkgs)
+}
 
-    pkgs
+fn check_duplicate_names(pkgs: &[Project]) -> Result<()> {
+    let mut dirs_by_name = HashMap::<&str, &str>::new();
+    for pkg in pkgs {
+        if let Some(name) = &pkg.manifest.name {
+            if let Some(dir) = dirs_by_name.insert(name, &pkg.dir) {
+                bail!(
+                    "DUPLICATE_PACKAGE_NAME: The name \"{}\" is used by the projects at {} and {}",
+                    name,
+                    dir,
+                    pkg.dir
+                );
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn find_workspace_packages_no_check(
     workspace_root: &str,
     opts: WorkspacePackagesOpts,
 ) -> Result<Vec<Project>> {
-    let patterns = opts.patterns.or_else(|| {
-        require_packages_manifest(workspace_root)
-            .unwrap_or_default()
-            .and_then(|manifest| manifest.packages)
-    });
+    let patterns = match opts.patterns {
+        Some(patterns) => Some(patterns),
+        None => require_packages_manifest(workspace_root)?.and_then(|manifest| manifest.packages),
+    };
     let mut packages = find_packages::find_packages(
         workspace_root,
         Some(FindPackagesOpts {
@@ -98,3 +133,82 @@ pub fn slice_of_workspace_packages_to_map<'a, 'r>(
 
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn write_manifest(root: &Path, dir: &str, manifest: &str) {
+        let dir = root.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), manifest).unwrap();
+    }
+
+    #[test]
+    fn use_the_patterns_of_the_workspace_manifest() {
+        let root = tempdir().unwrap();
+        write_manifest(root.path(), ".", r#"{"name":"root"}"#);
+        write_manifest(root.path(), "packages/foo", r#"{"name":"foo"}"#);
+        write_manifest(root.path(), "packages/bar", r#"{"name":"bar"}"#);
+        write_manifest(root.path(), "examples/baz", r#"{"name":"baz"}"#);
+        fs::write(
+            root.path().join("pnpm-workspace.yaml"),
+            "packages:\n  - packages/*\n  - '!packages/bar'\n",
+        )
+        .unwrap();
+
+        let packages = find_workspace_packages(
+            &root.path().to_string_lossy(),
+            WorkspacePackagesOpts::default(),
+        )
+        .unwrap();
+        let names = packages
+            .iter()
+            .map(|package| package.manifest.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["root", "foo"]);
+    }
+
+    #[test]
+    fn fail_on_duplicate_names() {
+        let root = tempdir().unwrap();
+        write_manifest(root.path(), "a", r#"{"name":"foo"}"#);
+        write_manifest(root.path(), "b", r#"{"name":"foo"}"#);
+
+        let error = find_workspace_packages(
+            &root.path().to_string_lossy(),
+            WorkspacePackagesOpts::default(),
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("DUPLICATE_PACKAGE_NAME: The name \"foo\" is used by the projects at"));
+    }
+
+    #[test]
+    fn fail_on_unsupported_engine_when_engine_strict() {
+        let root = tempdir().unwrap();
+        write_manifest(
+            root.path(),
+            "foo",
+            r#"{"name":"foo","engines":{"node":">=100"}}"#,
+        );
+        let opts = |engine_strict| WorkspacePackagesOpts {
+            engine_strict: Some(engine_strict),
+            node_version: Some("16.0.0".to_string()),
+            patterns: None,
+        };
+
+        let packages =
+            find_workspace_packages(&root.path().to_string_lossy(), opts(false)).unwrap();
+        assert_eq!(packages.len(), 1);
+
+        let error =
+            find_workspace_packages(&root.path().to_string_lossy(), opts(true)).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("UNSUPPORTED_ENGINE: Unsupported engine for"));
+    }
+}
@@ -0,0 +1,378 @@
+use anyhow::Result;
+use dependency_path::{dep_path_to_filename, ref_to_relative};
+use link_bins::link_bins;
+use lockfile_utils::name_ver_from_pkg_snapshot::name_ver_from_pkg_snapshot;
+use lockfile_utils::types::Lockfile;
+use matcher::Matcher;
+use normalize_path::normalize_path;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use symlink_dependency::symlink_dependency;
+
+pub use types::{HoistKind, HoistedDependencies};
+
+pub struct HoistOptions<'a> {
+    /// The packages that are installed, from the current lockfile
+    pub lockfile: &'a Lockfile,
+    pub private_hoist_pattern: &'a [String],
+    /// `node_modules/.pnpm/node_modules` by default
+    pub private_hoisted_modules_dir: &'a Path,
+    pub public_hoist_pattern: &'a [String],
+    /// The modules directory of the root project
+    pub public_hoisted_modules_dir: &'a Path,
+    pub virtual_store_dir: &'a Path,
+}
+
+/// No pattern hoists nothing, unlike an empty `Matcher` that matches everything
+fn hoist_matcher(patterns: &[String]) -> Option<Matcher> {
+    (!patterns.is_empty()).then(|| Matcher::new(patterns))
+}
+
+/// Picks the dependencies to hoist, going down the dependency graph breadth-first,
+/// so when several packages have the same alias, the shallowest one is hoisted.
+/// The direct dependencies of the root project are never hoisted, they are already there.
+pub fn get_hoisted_dependencies(
+    lockfile: &Lockfile,
+    private_hoist_pattern: &[String],
+    public_hoist_pattern: &[String],
+) -> HoistedDependencies {
+    let private_matcher = hoist_matcher(private_hoist_pattern);
+    let public_matcher = hoist_matcher(public_hoist_pattern);
+    let hoist_kind = |alias: &str| {
+        if public_matcher.as_ref().is_some_and(|m| m.is_match(alias)) {
+            Some(HoistKind::Public)
+        } else if private_matcher.as_ref().is_some_and(|m| m.is_match(alias)) {
+            Some(HoistKind::Private)
+        } else {
+            None
+        }
+    };
+    let mut hoisted = HoistedDependencies::new();
+    let packages = match &lockfile.packages {
+        Some(packages) => packages,
+        None => return hoisted,
+    };
+
+    let mut hoisted_aliases = HashSet::new();
+    if let Some(root) = lockfile.importers.get(".") {
+        for deps in [
+            &root.dependencies,
+            &root.dev_dependencies,
+            &root.optional_dependencies,
+        ] {
+            hoisted_aliases.extend(deps.iter().flatten().map(|(alias, _)| alias.to_lowercase()));
+        }
+    }
+
+    let mut importer_ids = lockfile.importers.keys().collect::<Vec<_>>();
+    importer_ids.sort();
+    let direct_deps = importer_ids
+        .into_iter()
+        .flat_map(|importer_id| {
+            let importer = &lockfile.importers[importer_id];
+            [
+                &importer.dependencies,
+                &importer.dev_dependencies,
+                &importer.optional_dependencies,
+            ]
+        })
+        .flatten()
+        .flat_map(|deps| deps.iter().collect::<BTreeMap<_, _>>())
+        .collect::<Vec<_>>();
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([direct_deps]);
+    while let Some(children) = queue.pop_front() {
+        for (alias, reference) in children {
+            let dep_path = match ref_to_relative(reference, alias) {
+                Some(dep_path) if packages.contains_key(&dep_path) => dep_path,
+                _ => continue,
+            };
+            if let Some(kind) = hoist_kind(alias) {
+                if hoisted_aliases.insert(alias.to_lowercase()) {
+                    hoisted
+                        .entry(dep_path.clone())
+                        .or_default()
+                        .insert(alias.clone(), kind);
+                }
+            }
+            if visited.insert(dep_path.clone()) {
+                let snapshot = &packages[&dep_path];
+                let grandchildren = snapshot
+                    .dependencies
+                    .iter()
+                    .chain(&snapshot.optional_dependencies)
+                    .flatten()
+                    .collect::<BTreeMap<_, _>>();
+                queue.push_back(grandchildren.into_iter().collect());
+            }
+        }
+    }
+    hoisted
+}
+
+/// The links directly in the modules directory and in its scope directories, by alias
+fn read_links(modules_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut links = vec![];
+    let entries = match fs::read_dir(modules_dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(links),
+        Err(error) => return Err(error.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            links.push((name, entry.path()));
+        } else if file_type.is_dir() && name.starts_with('@') {
+            for (scoped_name, path) in read_links(&entry.path())? {
+                links.push((format!("{}/{}", name, scoped_name), path));
+            }
+        }
+    }
+    Ok(links)
+}
+
+fn remove_link(path: &Path) -> Result<()> {
+    // links to directories are removed like directories on Windows
+    if fs::remove_file(path).is_err() {
+        fs::remove_dir(path)?;
+    }
+    Ok(())
+}
+
+/// Links the dependencies picked by `get_hoisted_dependencies` into the hoisted modules directories,
+/// and removes the links that were hoisted by a previous install and are not wanted anymore
+pub fn hoist(opts: &HoistOptions) -> Result<HoistedDependencies> {
+    let hoisted = get_hoisted_dependencies(
+        opts.lockfile,
+        opts.private_hoist_pattern,
+        opts.public_hoist_pattern,
+    );
+    let packages = opts.lockfile.packages.as_ref();
+
+    let mut wanted = HashSet::new();
+    for (dep_path, aliases) in &hoisted {
+        let snapshot = match packages.and_then(|packages| packages.get(dep_path)) {
+            Some(snapshot) => snapshot,
+            None => continue,
+        };
+        let name = name_ver_from_pkg_snapshot(dep_path, snapshot)?.name;
+        let target = opts
+            .virtual_store_dir
+            .join(dep_path_to_filename(dep_path))
+            .join("node_modules")
+            .join(name);
+        for (alias, kind) in aliases {
+            let modules_dir = match kind {
+                HoistKind::Private => opts.private_hoisted_modules_dir,
+                HoistKind::Public => opts.public_hoisted_modules_dir,
+            };
+            symlink_dependency(&target, modules_dir, alias)?;
+            wanted.insert(modules_dir.join(alias));
+        }
+    }
+
+    // everything linked in the private directory was hoisted, while the public directory
+    // also has the direct dependencies, which don't point into the virtual store
+    let virtual_store_dir = normalize_path(opts.virtual_store_dir);
+    for (_, path) in read_links(opts.private_hoisted_modules_dir)? {
+        if !wanted.contains(&path) {
+            remove_link(&path)?;
+        }
+    }
+    let root_deps = opts
+        .lockfile
+        .importers
+        .get(".")
+        .map(|root| {
+            [
+                &root.dependencies,
+                &root.dev_dependencies,
+                &root.optional_dependencies,
+            ]
+            .into_iter()
+            .flatten()
+            .flat_map(|deps| deps.keys().cloned())
+            .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+    for (alias, path) in read_links(opts.public_hoisted_modules_dir)? {
+        if wanted.contains(&path) || root_deps.contains(&alias) {
+            continue;
+        }
+        let target = match fs::read_link(&path) {
+            Ok(target) => normalize_path(&path.parent().unwrap_or(Path::new("")).join(target)),
+            Err(_) => continue,
+        };
+        if target.starts_with(&virtual_store_dir) {
+            remove_link(&path)?;
+        }
+    }
+
+    if opts.private_hoisted_modules_dir.exists() {
+        link_bins(
+            opts.private_hoisted_modules_dir,
+            opts.private_hoisted_modules_dir.join(".bin"),
+        )?;
+    }
+    Ok(hoisted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lockfile_utils::types::{LockfileResolution, PackageSnapshot, ProjectSnapshot};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn deps(deps: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        (!deps.is_empty()).then(|| {
+            deps.iter()
+                .map(|(alias, reference)| (alias.to_string(), reference.to_string()))
+                .collect()
+        })
+    }
+
+    fn snapshot(dependencies: &[(&str, &str)]) -> PackageSnapshot {
+        PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: "sha512-".to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            dependencies: deps(dependencies),
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        }
+    }
+
+    /// The project depends on `foo`, which depends on `bar@1.0.0` and `@types/qar`,
+    /// while `bar@1.0.0` depends on `bar@2.0.0` under the alias `bar2` and on `bar@2.0.0` as `qar`
+    fn lockfile() -> Lockfile {
+        let mut lockfile = Lockfile::new("5.3".to_string());
+        let mut importer = ProjectSnapshot::new();
+        importer.dependencies = deps(&[("foo", "1.0.0")]);
+        lockfile.importers.insert(".".to_string(), importer);
+        lockfile.packages = Some(HashMap::from([
+            (
+                "/foo/1.0.0".to_string(),
+                snapshot(&[("bar", "1.0.0"), ("@types/qar", "1.0.0")]),
+            ),
+            (
+                "/bar/1.0.0".to_string(),
+                snapshot(&[
+                    ("bar2", "/bar/2.0.0"),
+                    ("foo", "1.0.0"),
+                    ("qar", "/bar/2.0.0"),
+                ]),
+            ),
+            ("/bar/2.0.0".to_string(), snapshot(&[])),
+            ("/@types/qar/1.0.0".to_string(), snapshot(&[])),
+        ]));
+        lockfile
+    }
+
+    #[test]
+    fn hoist_the_shallowest_dependencies() {
+        let public = ["*types*".to_string()];
+        let hoisted = get_hoisted_dependencies(&lockfile(), &["*".to_string()], &public);
+        assert_eq!(
+            hoisted,
+            HoistedDependencies::from([
+                (
+                    "/bar/1.0.0".to_string(),
+                    BTreeMap::from([("bar".to_string(), HoistKind::Private)])
+                ),
+                (
+                    "/bar/2.0.0".to_string(),
+                    BTreeMap::from([
+                        ("bar2".to_string(), HoistKind::Private),
+                        ("qar".to_string(), HoistKind::Private)
+                    ])
+                ),
+                (
+                    "/@types/qar/1.0.0".to_string(),
+                    BTreeMap::from([("@types/qar".to_string(), HoistKind::Public)])
+                ),
+            ])
+        );
+
+        let hoisted = get_hoisted_dependencies(&lockfile(), &[], &public);
+        assert_eq!(hoisted.len(), 1);
+    }
+
+    #[test]
+    fn remove_the_links_that_are_not_hoisted_anymore() {
+        let root = tempdir().unwrap();
+        let modules_dir = root.path().join("node_modules");
+        let virtual_store_dir = modules_dir.join(".pnpm");
+        let private_dir = virtual_store_dir.join("node_modules");
+        let lockfile = lockfile();
+        for dep_path in lockfile.packages.as_ref().unwrap().keys() {
+            let name = dependency_path::parse(dep_path).unwrap().name.unwrap();
+            fs::create_dir_all(
+                virtual_store_dir
+                    .join(dep_path_to_filename(dep_path))
+                    .join("node_modules")
+                    .join(name),
+            )
+            .unwrap();
+        }
+        symlink_dependency(
+            virtual_store_dir.join("foo@1.0.0/node_modules/foo"),
+            &modules_dir,
+            "foo",
+        )
+        .unwrap();
+        let everything = ["*".to_string()];
+        let hoist_with = |private: &[String], public: &[String]| {
+            hoist(&HoistOptions {
+                lockfile: &lockfile,
+                private_hoist_pattern: private,
+                private_hoisted_modules_dir: &private_dir,
+                public_hoist_pattern: public,
+                public_hoisted_modules_dir: &modules_dir,
+                virtual_store_dir: &virtual_store_dir,
+            })
+            .unwrap()
+        };
+
+        hoist_with(&[], &everything);
+        assert!(modules_dir.join("bar").exists());
+        assert!(modules_dir.join("@types/qar").exists());
+        assert!(!private_dir.join("bar").exists());
+
+        hoist_with(&everything, &["*types*".to_string()]);
+        assert!(fs::symlink_metadata(modules_dir.join("bar")).is_err());
+        assert!(fs::symlink_metadata(modules_dir.join("qar")).is_err());
+        assert!(modules_dir.join("@types/qar").exists());
+        assert!(modules_dir.join("foo").exists());
+        assert_eq!(
+            fs::read_link(private_dir.join("bar")).unwrap(),
+            Path::new("../bar@1.0.0/node_modules/bar")
+        );
+
+        hoist_with(&[], &[]);
+        assert!(fs::symlink_metadata(private_dir.join("bar")).is_err());
+        assert!(fs::symlink_metadata(modules_dir.join("@types/qar")).is_err());
+        assert!(modules_dir.join("foo").exists());
+    }
+}
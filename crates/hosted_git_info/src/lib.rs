@@ -269,7 +269,11 @@ fn is_github_shorthand(url: &str) -> bool {
 
     let first_hash = index_of(url, "#");
     let first_slash = index_of(url, "/");
-    let second_slash = index_of(&url[first_slash as usize + 1..], "/");
+    // like `indexOf('/', firstSlash + 1)`, which also works when there is no slash at all
+    let second_slash = match url[(first_slash + 1) as usize..].find('/') {
+        Some(index) => first_slash + 1 + index as isize,
+        None => -1,
+    };
     let first_colon = index_of(url, ":");
     // let firstSpace = /\s/.exec(arg);
     let first_space = RE.find(url);
@@ -337,4 +341,12 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn specs_without_slashes_are_not_github_shorthands() {
+        assert!(!is_github_shorthand("workspace:^1.0.0"));
+        assert!(!is_github_shorthand("latest"));
+        assert!(is_github_shorthand("npm/hosted-git-info"));
+        assert!(!is_github_shorthand("npm/hosted-git-info/tree"));
+    }
 }
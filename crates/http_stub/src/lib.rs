@@ -0,0 +1,145 @@
+use socket2::Socket;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+/// The request line and the headers of a request, its body is not read
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// Like `GET /foo HTTP/1.1`
+    pub request_line: String,
+    pub headers: Vec<String>,
+}
+
+impl Request {
+    /// The path the request was sent to, or the whole URL when it was sent to a proxy
+    pub fn path(&self) -> &str {
+        self.request_line.split(' ').nth(1).unwrap_or_default()
+    }
+
+    /// The value of the header, whose name is compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find_map(|header| {
+            let (header_name, value) = header.split_once(':')?;
+            header_name.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Reply {
+    Response {
+        status: u16,
+        body: Vec<u8>,
+    },
+    /// The connection is reset without a response
+    Reset,
+}
+
+impl Reply {
+    /// A response with the status and a short body
+    pub fn status(status: u16) -> Self {
+        Reply::Response {
+            status,
+            body: b"ok".to_vec(),
+        }
+    }
+
+    /// A `200` response with the body
+    pub fn body(body: impl Into<Vec<u8>>) -> Self {
+        Reply::Response {
+            status: 200,
+            body: body.into(),
+        }
+    }
+}
+
+/// Reads a request up to the empty line that ends its headers
+pub fn read_request<S: Read>(stream: S) -> Request {
+    let mut reader = BufReader::new(stream);
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) <= 2 {
+            break;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    let mut lines = lines.into_iter();
+    Request {
+        request_line: lines.next().unwrap_or_default(),
+        headers: lines.collect(),
+    }
+}
+
+/// Writes a response that closes the connection
+pub fn write_response<S: Write>(mut stream: S, status: u16, body: &[u8]) {
+    write!(
+        stream,
+        "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(body).unwrap();
+}
+
+/// Replies to the requests one after the other with the replies of the handler,
+/// on a random port. Returns the URL of the server, like `http://127.0.0.1:1234/`.
+pub fn serve<F>(handler: F) -> String
+where
+    F: FnMut(&Request) -> Reply + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    serve_on(listener, handler);
+    url
+}
+
+/// Like `serve`, for servers whose replies depend on their own address
+pub fn serve_on<F>(listener: TcpListener, mut handler: F)
+where
+    F: FnMut(&Request) -> Reply + Send + 'static,
+{
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let request = read_request(&stream);
+            match handler(&request) {
+                Reply::Response { status, body } => write_response(&stream, status, &body),
+                Reply::Reset => {
+                    let socket = Socket::from(stream);
+                    socket.set_linger(Some(Duration::ZERO)).unwrap();
+                }
+            }
+        }
+    });
+}
+
+/// Serves the documents by their paths, like `/foo`, other paths are not found
+pub fn serve_documents<B: Into<Vec<u8>>>(documents: HashMap<String, B>) -> String {
+    let documents = documents
+        .into_iter()
+        .map(|(path, body)| (path, body.into()))
+        .collect::<HashMap<_, Vec<u8>>>();
+    serve(move |request| match documents.get(request.path()) {
+        Some(body) => Reply::body(body.clone()),
+        None => Reply::status(404),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_the_request_line_and_headers() {
+        let request = read_request(&b"GET /foo HTTP/1.1\r\nAuthorization: Bearer foo\r\n\r\n"[..]);
+
+        assert_eq!(request.request_line, "GET /foo HTTP/1.1");
+        assert_eq!(request.path(), "/foo");
+        assert_eq!(request.header("authorization"), Some("Bearer foo"));
+        assert_eq!(request.header("accept"), None);
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use types::BaseManifest;
+
+/// The scripts a dependency runs when it is installed
+pub const DEPENDENCY_BUILD_SCRIPTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+
+pub struct RunLifecycleHookOptions<'a> {
+    /// The directory of the package, the scripts run in it
+    pub pkg_root: &'a Path,
+    /// Directories prepended to `PATH`, after the `.bin` directories of the package
+    pub extra_bin_paths: &'a [PathBuf],
+    /// Appended to the script, like the `--watch` of `pnpm run test --watch`
+    pub args: &'a [String],
+}
+
+/// Whether installing the package runs any of its scripts
+pub fn requires_build(manifest: &BaseManifest) -> bool {
+    manifest.scripts.as_ref().is_some_and(|scripts| {
+        DEPENDENCY_BUILD_SCRIPTS
+            .iter()
+            .any(|stage| scripts.get(*stage).is_some_and(|script| !script.is_empty()))
+    })
+}
+
+/// The `.bin` directories of the package and of every directory above it,
+/// then the extra directories, in front of the current `PATH`
+pub fn path_env(pkg_root: &Path, extra_bin_paths: &[PathBuf]) -> Result<OsString> {
+    let mut paths = pkg_root
+        .ancestors()
+        .map(|dir| dir.join("node_modules").join(".bin"))
+        .collect::<Vec<_>>();
+    paths.extend(extra_bin_paths.iter().cloned());
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    Ok(std::env::join_paths(paths)?)
+}
+
+fn add_package_env(key: String, value: &Value, env: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => {
+            for (field, value) in fields.iter().filter(|(field, _)| !field.starts_with('_')) {
+                add_package_env(format!("{}_{}", key, field), value, env);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                add_package_env(format!("{}_{}", key, index), item, env);
+            }
+        }
+        Value::String(value) => env.push((key, value.clone())),
+        value => env.push((key, value.to_string())),
+    }
+}
+
+/// The fields of the manifest as `npm_package_*` variables,
+/// like `npm_package_name` or `npm_package_scripts_test`
+pub fn package_env(manifest: &BaseManifest) -> Vec<(String, String)> {
+    let mut env = vec![];
+    if let Ok(manifest) = serde_json::to_value(manifest) {
+        add_package_env("npm_package".to_string(), &manifest, &mut env);
+    }
+    env.into_iter()
+        .map(|(key, value)| {
+            let key = key.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_");
+            (key, value)
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn quote_arg(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(windows)]
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    }
+}
+
+/// A command that runs the script in the shell of the platform
+#[cfg(unix)]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    command
+}
+
+#[cfg(windows)]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/d").arg("/s").arg("/c").arg(script);
+    command
+}
+
+/// The script of a lifecycle stage, ready to run
+pub struct LifecycleHook {
+    command: Command,
+    /// Like `foo@1.0.0`
+    package: String,
+    stage: String,
+    script: String,
+}
+
+impl LifecycleHook {
+    /// Printed before the script runs, like npm does
+    pub fn banner(&self) -> String {
+        let dir = self.command.get_current_dir().unwrap_or(Path::new(""));
+        format!(
+            "> {} {} {}\n> {}",
+            self.package,
+            self.stage,
+            dir.display(),
+            self.script
+        )
+    }
+
+    pub fn run(mut self) -> Result<()> {
+        let status = self.command.status()?;
+        if !status.success() {
+            bail!(
+                "LIFECYCLE_ERROR: {} {}: `{}` exited with {}",
+                self.package,
+                self.stage,
+                self.script,
+                status
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The script of a lifecycle stage, if the package has one
+pub fn lifecycle_hook(
+    stage: &str,
+    manifest: &BaseManifest,
+    opts: &RunLifecycleHookOptions,
+) -> Result<Option<LifecycleHook>> {
+    let script = match manifest
+        .scripts
+        .as_ref()
+        .and_then(|scripts| scripts.get(stage))
+    {
+        Some(script) if !script.is_empty() => script,
+        _ => return Ok(None),
+    };
+    let name = manifest.name.as_deref().unwrap_or_default();
+    let version = manifest.version.as_deref().unwrap_or_default();
+    let command_line = opts.args.iter().fold(script.clone(), |command_line, arg| {
+        format!("{} {}", command_line, quote_arg(arg))
+    });
+
+    let mut command = shell_command(&command_line);
+    command
+        .current_dir(opts.pkg_root)
+        .envs(package_env(manifest))
+        .env("PATH", path_env(opts.pkg_root, opts.extra_bin_paths)?)
+        .env("npm_lifecycle_event", stage)
+        .env("npm_lifecycle_script", script)
+        .env("INIT_CWD", std::env::current_dir()?);
+
+    Ok(Some(LifecycleHook {
+        command,
+        package: format!("{}@{}", name, version),
+        stage: stage.to_string(),
+        script: command_line,
+    }))
+}
+
+/// Runs the script of a lifecycle stage, if the package has one.
+/// Returns whether a script was run.
+pub fn run_lifecycle_hook(
+    stage: &str,
+    manifest: &BaseManifest,
+    opts: &RunLifecycleHookOptions,
+) -> Result<bool> {
+    match lifecycle_hook(stage, manifest, opts)? {
+        Some(hook) => {
+            hook.run()?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    #[test]
+    fn run_script_of_stage() {
+        let dir = tempdir().unwrap();
+        let manifest = BaseManifest {
+            name: Some("foo".to_string()),
+            version: Some("1.0.0".to_string()),
+            scripts: Some(HashMap::from([
+                (
+                    "postinstall".to_string(),
+                    "echo $npm_package_name@$npm_package_version $npm_lifecycle_event > out"
+                        .to_string(),
+                ),
+                ("install".to_string(), "exit 1".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let opts = RunLifecycleHookOptions {
+            pkg_root: dir.path(),
+            extra_bin_paths: &[],
+            args: &[],
+        };
+
+        assert!(requires_build(&manifest));
+        assert!(!run_lifecycle_hook("preinstall", &manifest, &opts).unwrap());
+        assert!(run_lifecycle_hook("postinstall", &manifest, &opts).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out")).unwrap(),
+            "foo@1.0.0 postinstall\n"
+        );
+        assert!(run_lifecycle_hook("install", &manifest, &opts)
+            .unwrap_err()
+            .to_string()
+            .starts_with("LIFECYCLE_ERROR: foo@1.0.0 install"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pass_args_to_script() {
+        let dir = tempdir().unwrap();
+        let manifest = BaseManifest {
+            scripts: Some(HashMap::from([(
+                "test".to_string(),
+                "printf '%s|' > out".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        run_lifecycle_hook(
+            "test",
+            &manifest,
+            &RunLifecycleHookOptions {
+                pkg_root: dir.path(),
+                extra_bin_paths: &[],
+                args: &["--watch".to_string(), "it's $HOME".to_string()],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out")).unwrap(),
+            "--watch|it's $HOME|"
+        );
+    }
+
+    #[test]
+    fn flatten_manifest_to_env() {
+        let manifest: BaseManifest = serde_json::from_value(serde_json::json!({
+            "name": "foo",
+            "version": "1.0.0",
+            "scripts": { "pre-test": "lint" },
+            "config": { "port": 8080, "_private": "x" },
+            "files": ["lib", "bin"],
+        }))
+        .unwrap();
+
+        let mut env = package_env(&manifest);
+        env.sort();
+        assert_eq!(
+            env,
+            [
+                ("npm_package_config_port", "8080"),
+                ("npm_package_files_0", "lib"),
+                ("npm_package_files_1", "bin"),
+                ("npm_package_name", "foo"),
+                ("npm_package_scripts_pre_test", "lint"),
+                ("npm_package_version", "1.0.0"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+}
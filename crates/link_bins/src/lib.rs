@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use read_project_manifest::read_project_manifest;
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use types::{BaseManifest, PackageBin};
+
+#[derive(Debug, PartialEq)]
+pub struct Command {
+    pub name: String,
+    /// Relative to the directory of the package
+    pub path: String,
+}
+
+/// Names that would escape the bin directory are ignored
+fn is_safe_bin_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Scoped names are linked without their scope
+fn unscoped(name: &str) -> &str {
+    match name.strip_prefix('@').and_then(|name| name.split_once('/')) {
+        Some((_, name)) => name,
+        None => name,
+    }
+}
+
+/// The executables of a package, as declared in the `bin` field of its manifest.
+/// A single executable is named after the package, without its scope.
+pub fn get_bins(manifest: &BaseManifest) -> Vec<Command> {
+    let mut commands = match &manifest.bin {
+        Some(PackageBin::Single(path)) => manifest
+            .name
+            .as_deref()
+            .map(|name| {
+                vec![Command {
+                    name: unscoped(name).to_string(),
+                    path: path.clone(),
+                }]
+            })
+            .unwrap_or_default(),
+        Some(PackageBin::Map(bins)) => bins
+            .iter()
+            .map(|(name, path)| Command {
+                name: unscoped(name).to_string(),
+                path: path.clone(),
+            })
+            .collect(),
+        None => vec![],
+    };
+    commands.retain(|command| is_safe_bin_name(&command.name));
+    commands.sort_by(|command_1, command_2| command_1.name.cmp(&command_2.name));
+    commands
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    if permissions.mode() & 0o111 != 0o111 {
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+fn link_bin(command: &Command, pkg_dir: &Path, bins_dir: &Path) -> Result<()> {
+    let target = pkg_dir.join(&command.path);
+    if !target.exists() {
+        // packages sometimes declare executables they don't ship
+        return Ok(());
+    }
+    make_executable(&target)?;
+
+    let link = bins_dir.join(&command.name);
+    match fs::remove_file(&link) {
+        Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+    let relative_target = pathdiff::diff_paths(&target, bins_dir).unwrap_or(target);
+    symlink_file(&relative_target, &link)
+        .with_context(|| format!("LINK_BIN: Could not link {}", link.display()))?;
+    Ok(())
+}
+
+/// Links the executables of the packages into `bins_dir`.
+/// When several packages have an executable with the same name, the first one wins.
+pub fn link_bins_of_packages<P: AsRef<Path>>(
+    packages: &[(PathBuf, BaseManifest)],
+    bins_dir: P,
+) -> Result<()> {
+    let bins_dir = bins_dir.as_ref();
+    let mut linked = HashSet::new();
+
+    for (pkg_dir, manifest) in packages {
+        for command in get_bins(manifest) {
+            if !linked.insert(command.name.clone()) {
+                continue;
+            }
+            fs::create_dir_all(bins_dir)?;
+            link_bin(&command, pkg_dir, bins_dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the packages in a `node_modules` directory, including the scoped ones
+fn read_modules_dir(modules_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pkg_dirs = vec![];
+    let entries = match fs::read_dir(modules_dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(pkg_dirs),
+        Err(error) => return Err(error.into()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        if name.starts_with('@') {
+            for scoped in fs::read_dir(entry.path())? {
+                pkg_dirs.push(scoped?.path());
+            }
+        } else {
+            pkg_dirs.push(entry.path());
+        }
+    }
+
+    pkg_dirs.sort();
+    Ok(pkg_dirs)
+}
+
+/// Links the executables of every package in `modules_dir` into `bins_dir`
+pub fn link_bins<P: AsRef<Path>, B: AsRef<Path>>(modules_dir: P, bins_dir: B) -> Result<()> {
+    let packages = read_modules_dir(modules_dir.as_ref())?
+        .into_iter()
+        .filter_map(|pkg_dir| {
+            read_project_manifest(&pkg_dir)
+                .ok()?
+                .manifest
+                .map(|manifest| (pkg_dir, manifest))
+        })
+        .collect::<Vec<_>>();
+
+    link_bins_of_packages(&packages, bins_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn bins_of_manifest() {
+        let manifest = BaseManifest {
+            name: Some("@foo/bar".to_string()),
+            bin: Some(PackageBin::Single("cli.js".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_bins(&manifest),
+            vec![Command {
+                name: "bar".to_string(),
+                path: "cli.js".to_string(),
+            }]
+        );
+
+        let manifest = BaseManifest {
+            name: Some("foo".to_string()),
+            bin: Some(PackageBin::Map(HashMap::from([
+                ("foo".to_string(), "bin/foo.js".to_string()),
+                ("../evil".to_string(), "bin/evil.js".to_string()),
+            ]))),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_bins(&manifest),
+            vec![Command {
+                name: "foo".to_string(),
+                path: "bin/foo.js".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn link_bins_of_modules_dir() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let pkg_dir = modules_dir.join("@foo").join("bar");
+        fs::create_dir_all(&pkg_dir).unwrap();
+        fs::write(
+            pkg_dir.join("package.json"),
+            r#"{"name":"@foo/bar","version":"1.0.0","bin":"cli.js"}"#,
+        )
+        .unwrap();
+        fs::write(pkg_dir.join("cli.js"), "#!/usr/bin/env node\n").unwrap();
+
+        link_bins(&modules_dir, modules_dir.join(".bin")).unwrap();
+
+        let link = modules_dir.join(".bin").join("bar");
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            Path::new("..").join("@foo").join("bar").join("cli.js")
+        );
+        assert_eq!(fs::read_to_string(link).unwrap(), "#!/usr/bin/env node\n");
+    }
+}
@@ -1,9 +1,10 @@
 mod git_merge_file;
 mod merge_changes;
+pub mod name_ver_from_pkg_snapshot;
 pub mod read;
 pub mod satisfies_package_manifest;
 pub mod types;
-mod write;
+pub mod write;
 
 fn comver_to_semver(comver: &str) -> String {
     if !comver.contains('.') {
@@ -0,0 +1,31 @@
+use crate::types::PackageSnapshot;
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, PartialEq)]
+pub struct NameVer {
+    pub name: String,
+    pub version: String,
+}
+
+/// Packages from the npm registry don't have their name and version
+/// in the snapshot, so those are parsed from the dependency path
+pub fn name_ver_from_pkg_snapshot(
+    dep_path: &str,
+    pkg_snapshot: &PackageSnapshot,
+) -> Result<NameVer> {
+    if let (Some(name), Some(version)) = (&pkg_snapshot.name, &pkg_snapshot.version) {
+        return Ok(NameVer {
+            name: name.clone(),
+            version: version.clone(),
+        });
+    }
+
+    let parsed = dependency_path::parse(dep_path)?;
+    match (parsed.name, parsed.version) {
+        (Some(name), Some(version)) => Ok(NameVer { name, version }),
+        _ => Err(anyhow!(
+            "Cannot get the name and version of {} from the lockfile",
+            dep_path
+        )),
+    }
+}
@@ -1,10 +1,10 @@
 use crate::comver_to_semver;
 use crate::git_merge_file::autofix_merge_conflicts;
-use crate::types::{Lockfile, PackageSnapshot, ProjectSnapshot, ResolvedDependencies};
+use crate::types::{sorted, Lockfile, PackageSnapshot, ProjectSnapshot, ResolvedDependencies};
 use anyhow::{anyhow, bail, Result};
 use constants::WANTED_LOCKFILE;
 use semver::Version;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fmt::Display;
@@ -15,8 +15,8 @@ use types::{DependenciesMeta, DependencyField};
 
 #[derive(Default)]
 pub struct ReadLockfileOpts {
-    wanted_version: Option<i32>,
-    ignore_incompatible: bool,
+    pub wanted_version: Option<i32>,
+    pub ignore_incompatible: bool,
 }
 
 // TODO: change to async function
@@ -53,18 +53,78 @@ pub struct ReadResult {
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct LockfileFile {
+    #[serde(serialize_with = "serialize_lockfile_version")]
     pub lockfile_version: String,
-    #[serde(default)]
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "sorted::serialize"
+    )]
     pub importers: HashMap<String, ProjectSnapshot>,
-    pub packages: Option<HashMap<String, PackageSnapshot>>,
-    pub never_built_dependencies: Option<Rc<Vec<String>>>,
-    pub overrides: Option<HashMap<String, String>>,
-    pub package_extensions_checksum: Option<String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub specifiers: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub optional_dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dev_dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dependencies_meta: Option<DependenciesMeta>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub packages: Option<HashMap<String, PackageSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub never_built_dependencies: Option<Rc<Vec<String>>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub overrides: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_extensions_checksum: Option<String>,
+}
+
+/// Numeric versions like `5.3` are written without quotes
+fn serialize_lockfile_version<S: Serializer>(
+    version: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match version.parse::<f64>() {
+        Ok(number) => serializer.serialize_f64(number),
+        Err(_) => serializer.serialize_str(version),
+    }
+}
+
+impl From<LockfileFile> for Lockfile {
+    /// The importers of the lockfile, that is already normalized when read
+    fn from(lockfile: LockfileFile) -> Self {
+        Lockfile {
+            importers: lockfile.importers,
+            lockfile_version: lockfile.lockfile_version,
+            packages: lockfile.packages,
+            never_built_dependencies: lockfile.never_built_dependencies,
+            overrides: lockfile.overrides,
+            package_extensions_checksum: lockfile.package_extensions_checksum,
+        }
+    }
 }
 
 impl LockfileFile {
@@ -111,13 +171,13 @@ fn read<P: AsRef<Path> + Debug>(
     let lockfile_raw_content = match std::fs::read_to_string(&lockfile_path) {
         Ok(content) => String::from(content.strip_bom()),
         Err(error) => match error.kind() {
-            std::io::ErrorKind::NotFound => bail!(error.to_string()),
-            _ => {
+            std::io::ErrorKind::NotFound => {
                 return Ok(ReadResult {
                     lockfile: None,
                     had_conflicts: false,
                 })
             }
+            _ => bail!(error.to_string()),
         },
     };
     let mut had_conflicts = false;
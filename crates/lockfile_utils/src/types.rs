@@ -1,18 +1,50 @@
 // import { DependenciesMeta } from '@pnpm/types'
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
-use types::DependenciesMeta;
+use types::{DependenciesMeta, PeerDependenciesMeta};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The keys of maps are written in alphabetical order,
+/// so the lockfile doesn't change between runs
+pub(crate) mod sorted {
+    use super::*;
+
+    pub fn serialize<S: Serializer, V: Serialize>(
+        map: &HashMap<String, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+    }
+
+    pub fn serialize_option<S: Serializer, V: Serialize>(
+        map: &Option<HashMap<String, V>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        map.as_ref()
+            .map(|map| map.iter().collect::<BTreeMap<_, _>>())
+            .serialize(serializer)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Lockfile {
-    #[serde(default)]
+    #[serde(default, serialize_with = "sorted::serialize")]
     pub importers: HashMap<String, ProjectSnapshot>,
     pub lockfile_version: String,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub packages: Option<HashMap<String, PackageSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub never_built_dependencies: Option<Rc<Vec<String>>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub overrides: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub package_extensions_checksum: Option<String>,
 }
 
@@ -31,13 +63,30 @@ impl Lockfile {
 
 pub type ResolvedDependencies = HashMap<String, String>;
 
-#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectSnapshot {
+    #[serde(default, serialize_with = "sorted::serialize")]
     pub specifiers: ResolvedDependencies,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub optional_dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dev_dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
     pub dependencies_meta: Option<DependenciesMeta>,
 }
 
@@ -47,64 +96,105 @@ impl ProjectSnapshot {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// The variants are tried in order when reading the lockfile and unknown fields are ignored,
+/// so the variants with more required fields come first
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum LockfileResolution {
-    RegistryResolution {
-        integrity: String,
+    TarballResolution {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        integrity: Option<String>,
+        // needed in some cases to get the auth token
+        // sometimes the tarball URL is under a different path
+        // and the auth token is specified for the registry only
+        #[serde(skip_serializing_if = "Option::is_none")]
+        registry: Option<String>,
+        tarball: String,
     },
     GitRepositoryResolution {
-        r#type: String,
-        repo: String,
         commit: String,
+        repo: String,
+        r#type: String,
     },
     DirectoryResolution {
-        r#type: String,
         directory: String,
+        r#type: String,
     },
-    TarballResolution {
-        tarball: String,
-        integrity: Option<String>,
-        // needed in some cases to get the auth token
-        // sometimes the tarball URL is under a different path
-        // and the auth token is specified for the registry only
-        registry: Option<String>,
+    RegistryResolution {
+        integrity: String,
     },
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-struct SnapshotEngines {
-    node: String,
+impl LockfileResolution {
+    pub fn integrity(&self) -> Option<&str> {
+        match self {
+            Self::RegistryResolution { integrity } => Some(integrity),
+            Self::TarballResolution { integrity, .. } => integrity.as_deref(),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SnapshotEngines {
+    pub node: String,
+}
+
+/// The fields are in the order in which pnpm writes them
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageSnapshot {
-    id: Option<String>,
-    dev: Option<bool>,
-    optional: Option<bool>,
-    requires_build: Option<bool>,
-    prepare: Option<bool>,
-    has_bin: Option<bool>,
+    pub resolution: LockfileResolution,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     // name and version are only needed
     // for packages that are hosted not in the npm registry
-    name: Option<String>,
-    version: Option<String>,
-    resolution: LockfileResolution,
-    dependencies: Option<ResolvedDependencies>,
-    optional_dependencies: Option<ResolvedDependencies>,
-    peer_dependencies: Option<HashMap<String, String>>,
-    //   peerDependenciesMeta?: {
-    //     [name: string]: {
-    //       optional: true
-    //     }
-    //   }
-    transitive_peer_dependencies: Option<Vec<String>>,
-    bundled_dependencies: Option<Vec<String>>,
-    engines: Option<SnapshotEngines>,
-    os: Option<Vec<String>>,
-    cpu: Option<Vec<String>>,
-    deprecated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engines: Option<SnapshotEngines>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_bin: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prepare: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires_build: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundled_dependencies: Option<Vec<String>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub peer_dependencies: Option<HashMap<String, String>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub peer_dependencies_meta: Option<PeerDependenciesMeta>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub dependencies: Option<ResolvedDependencies>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "sorted::serialize_option"
+    )]
+    pub optional_dependencies: Option<ResolvedDependencies>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transitive_peer_dependencies: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optional: Option<bool>,
 }
 
 // pub struct Dependencies {
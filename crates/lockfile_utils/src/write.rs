@@ -1,28 +1,60 @@
 use crate::read::LockfileFile;
-use crate::types::Lockfile;
+use crate::types::{Lockfile, ProjectSnapshot, ResolvedDependencies};
 use anyhow::Result;
+use constants::WANTED_LOCKFILE;
 use rayon::prelude::*;
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use std::path::Path;
 use tempfile::NamedTempFile;
 
+/// The name of the lockfile inside the virtual store,
+/// that describes what is currently installed
+pub const CURRENT_LOCKFILE: &str = "lock.yaml";
+
+/// Writes `pnpm-lock.yaml`. A project that is not part of a workspace
+/// has its dependencies at the root of the file.
+pub fn write_wanted_lockfile<P: AsRef<Path>>(
+    pkg_path: P,
+    wanted_lockfile: &Lockfile,
+) -> Result<()> {
+    write_lockfile(WANTED_LOCKFILE, pkg_path.as_ref(), wanted_lockfile, false)
+}
+
+/// Writes `lock.yaml` into the virtual store
+pub fn write_current_lockfile<P: AsRef<Path>>(
+    virtual_store_dir: P,
+    current_lockfile: &Lockfile,
+) -> Result<()> {
+    write_lockfile(
+        CURRENT_LOCKFILE,
+        virtual_store_dir.as_ref(),
+        current_lockfile,
+        true,
+    )
+}
+
 fn write_lockfile(
     lockfile_filename: &str,
-    pkg_path: &str,
+    pkg_path: &Path,
     wanted_lockfile: &Lockfile,
-    force_shared_format: Option<bool>,
+    force_shared_format: bool,
 ) -> Result<()> {
-    let lockfile_path = Path::new(pkg_path).join(lockfile_filename);
+    let lockfile_path = pkg_path.join(lockfile_filename);
 
     if is_empty_lockfile(wanted_lockfile) {
-        std::fs::remove_dir_all(lockfile_path)?;
-    } else {
-        let yaml_doc = yaml_serialize(wanted_lockfile)?;
-        let mut file = NamedTempFile::new()?.persist(lockfile_path)?;
-        // file.write(yaml_doc.as_bytes())?;
-        write!(file, "{}", yaml_doc)?;
+        match std::fs::remove_file(lockfile_path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => return Ok(()),
+        }
     }
 
+    let yaml_doc = yaml_serialize(wanted_lockfile, force_shared_format)?;
+    std::fs::create_dir_all(pkg_path)?;
+    // the temp file is in the same directory, so it can be renamed into place
+    let mut file = NamedTempFile::new_in(pkg_path)?;
+    write!(file, "{}", yaml_doc)?;
+    file.persist(lockfile_path)?;
+
     Ok(())
 }
 
@@ -40,15 +72,169 @@ fn is_empty_lockfile(lockfile: &Lockfile) -> bool {
         })
 }
 
-fn yaml_serialize(lockfile: &Lockfile) -> serde_yaml::Result<String> {
-    let normalized_lockfile = normalize_lockfile(lockfile);
+fn yaml_serialize(lockfile: &Lockfile, force_shared_format: bool) -> serde_yaml::Result<String> {
+    let normalized_lockfile = normalize_lockfile(lockfile, force_shared_format);
     // the node version sorts the keys of the lockfile here
     // this isn't required with rust/serde since serde respects the order
     // of the keys in the struct definition
 
-    serde_yaml::to_string(&normalized_lockfile)
+    let yaml_doc = serde_yaml::to_string(&normalized_lockfile)?;
+    // serde_yaml starts documents with a `---` line that pnpm doesn't write
+    Ok(yaml_doc
+        .strip_prefix("---\n")
+        .map(String::from)
+        .unwrap_or(yaml_doc))
+}
+
+fn non_empty(dependencies: &Option<ResolvedDependencies>) -> Option<ResolvedDependencies> {
+    dependencies.clone().filter(|deps| !deps.is_empty())
+}
+
+fn normalize_project_snapshot(importer: &ProjectSnapshot) -> ProjectSnapshot {
+    ProjectSnapshot {
+        specifiers: importer.specifiers.clone(),
+        dependencies: non_empty(&importer.dependencies),
+        optional_dependencies: non_empty(&importer.optional_dependencies),
+        dev_dependencies: non_empty(&importer.dev_dependencies),
+        dependencies_meta: importer
+            .dependencies_meta
+            .clone()
+            .filter(|meta| !meta.is_empty()),
+    }
+}
+
+fn normalize_lockfile(lockfile: &Lockfile, force_shared_format: bool) -> LockfileFile {
+    let mut lockfile_file = LockfileFile {
+        lockfile_version: lockfile.lockfile_version.clone(),
+        packages: lockfile.packages.clone().filter(|pkgs| !pkgs.is_empty()),
+        never_built_dependencies: lockfile.never_built_dependencies.clone(),
+        overrides: lockfile.overrides.clone(),
+        package_extensions_checksum: lockfile.package_extensions_checksum.clone(),
+        ..Default::default()
+    };
+
+    match lockfile.importers.get(".") {
+        Some(importer) if lockfile.importers.len() == 1 && !force_shared_format => {
+            let importer = normalize_project_snapshot(importer);
+            lockfile_file.specifiers = Some(importer.specifiers);
+            lockfile_file.dependencies = importer.dependencies;
+            lockfile_file.optional_dependencies = importer.optional_dependencies;
+            lockfile_file.dev_dependencies = importer.dev_dependencies;
+            lockfile_file.dependencies_meta = importer.dependencies_meta;
+        }
+        _ => {
+            lockfile_file.importers = lockfile
+                .importers
+                .iter()
+                .map(|(id, importer)| (id.clone(), normalize_project_snapshot(importer)))
+                .collect();
+        }
+    }
+
+    lockfile_file
 }
 
-fn normalize_lockfile(lockfile: &Lockfile) -> LockfileFile {
-    todo!()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::{read_wanted_lockfile, ReadLockfileOpts};
+    use crate::types::{LockfileResolution, PackageSnapshot};
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn lockfile() -> Lockfile {
+        let snapshot = |integrity: &str| PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: integrity.to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            dependencies: None,
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: Some(false),
+            optional: None,
+        };
+
+        Lockfile {
+            importers: HashMap::from([(
+                ".".to_string(),
+                ProjectSnapshot {
+                    specifiers: HashMap::from([
+                        ("foo".to_string(), "^1.0.0".to_string()),
+                        ("bar".to_string(), "^2.0.0".to_string()),
+                    ]),
+                    dependencies: Some(HashMap::from([
+                        ("foo".to_string(), "1.0.0".to_string()),
+                        ("bar".to_string(), "2.0.0".to_string()),
+                    ])),
+                    dev_dependencies: Some(HashMap::new()),
+                    ..Default::default()
+                },
+            )]),
+            lockfile_version: "5.3".to_string(),
+            packages: Some(HashMap::from([
+                ("/foo/1.0.0".to_string(), snapshot("sha512-foo")),
+                ("/bar/2.0.0".to_string(), snapshot("sha512-bar")),
+            ])),
+            never_built_dependencies: None,
+            overrides: None,
+            package_extensions_checksum: None,
+        }
+    }
+
+    #[test]
+    fn write_sorted_lockfile() {
+        let dir = tempdir().unwrap();
+        write_wanted_lockfile(dir.path(), &lockfile()).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(WANTED_LOCKFILE)).unwrap(),
+            "lockfileVersion: 5.3
+specifiers:
+  bar: ^2.0.0
+  foo: ^1.0.0
+dependencies:
+  bar: 2.0.0
+  foo: 1.0.0
+packages:
+  /bar/2.0.0:
+    resolution:
+      integrity: sha512-bar
+    dev: false
+  /foo/1.0.0:
+    resolution:
+      integrity: sha512-foo
+    dev: false
+"
+        );
+
+        let read = read_wanted_lockfile(dir.path(), ReadLockfileOpts::default())
+            .unwrap()
+            .unwrap();
+        let mut expected = lockfile();
+        expected.importers.get_mut(".").unwrap().dev_dependencies = None;
+        assert_eq!(Lockfile::from(read), expected);
+    }
+
+    #[test]
+    fn remove_empty_lockfile() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(WANTED_LOCKFILE), "lockfileVersion: 5.3\n").unwrap();
+
+        write_wanted_lockfile(dir.path(), &Lockfile::new("5.3".to_string())).unwrap();
+
+        assert!(!dir.path().join(WANTED_LOCKFILE).exists());
+    }
 }
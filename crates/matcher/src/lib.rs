@@ -0,0 +1,62 @@
+use regex::Regex;
+
+/// Matches names against patterns like `@babel/*`.
+/// A pattern starting with `!` excludes the names it matches.
+pub struct Matcher {
+    included: Vec<Regex>,
+    excluded: Vec<Regex>,
+}
+
+fn pattern_to_regex(pattern: &str) -> Regex {
+    let regex = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    Regex::new(&format!("^{}$", regex)).unwrap()
+}
+
+impl Matcher {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        let mut included = vec![];
+        let mut excluded = vec![];
+        for pattern in patterns {
+            match pattern.as_ref().strip_prefix('!') {
+                Some(pattern) => excluded.push(pattern_to_regex(pattern)),
+                None => included.push(pattern_to_regex(pattern.as_ref())),
+            }
+        }
+        Self { included, excluded }
+    }
+
+    /// Only exclusions match everything they don't exclude
+    pub fn is_match(&self, input: &str) -> bool {
+        (self.included.is_empty() || self.included.iter().any(|regex| regex.is_match(input)))
+            && !self.excluded.iter().any(|regex| regex.is_match(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_patterns() {
+        let matcher = Matcher::new(&["@babel/*", "eslint"]);
+        assert!(matcher.is_match("@babel/core"));
+        assert!(matcher.is_match("eslint"));
+        assert!(!matcher.is_match("eslint-plugin-react"));
+        assert!(!matcher.is_match("@babel"));
+
+        let matcher = Matcher::new(&["*", "!@babel/*"]);
+        assert!(matcher.is_match("eslint"));
+        assert!(!matcher.is_match("@babel/core"));
+
+        let matcher = Matcher::new(&["!eslint"]);
+        assert!(matcher.is_match("eslint-plugin-react"));
+        assert!(!matcher.is_match("eslint"));
+
+        // regex characters are taken literally
+        assert!(!Matcher::new(&["a.c"]).is_match("abc"));
+    }
+}
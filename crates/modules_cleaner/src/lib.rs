@@ -0,0 +1,230 @@
+use anyhow::Result;
+use dependency_path::dep_path_to_filename;
+use link_bins::get_bins;
+use lockfile_utils::types::Lockfile;
+use read_project_manifest::read_project_manifest;
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use types::{HoistKind, HoistedDependencies};
+
+pub struct PruneOptions<'a> {
+    pub modules_dir: &'a Path,
+    pub virtual_store_dir: &'a Path,
+    /// The aliases of the direct dependencies that stay in `modules_dir`
+    pub wanted_direct_deps: &'a HashSet<String>,
+    /// The dependency paths of the packages that stay in the virtual store
+    pub wanted_packages: &'a HashSet<String>,
+    /// The aliases the packages were hoisted under, from `.modules.yaml`
+    pub hoisted_dependencies: &'a HoistedDependencies,
+    pub private_hoisted_modules_dir: &'a Path,
+    pub public_hoisted_modules_dir: &'a Path,
+}
+
+/// Removes a file, a directory or a link to a directory
+fn remove_path(path: &Path) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if fs::remove_file(path).is_err() {
+        // links to directories are removed like directories on Windows
+        fs::remove_dir(path)?;
+    }
+    Ok(())
+}
+
+/// Removes the executables that were linked from the package
+fn remove_bins(pkg_dir: &Path, bins_dir: &Path) -> Result<()> {
+    let manifest = match read_project_manifest(pkg_dir)?.manifest {
+        Some(manifest) => manifest,
+        None => return Ok(()),
+    };
+    for command in get_bins(&manifest) {
+        let bin = bins_dir.join(&command.name);
+        let linked_from_pkg =
+            fs::read_link(&bin).ok() == pathdiff::diff_paths(pkg_dir.join(&command.path), bins_dir);
+        if linked_from_pkg {
+            remove_path(&bin)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes a dependency from a modules directory, with its executables
+fn remove_dependency(modules_dir: &Path, alias: &str) -> Result<()> {
+    let pkg_dir = modules_dir.join(alias);
+    if pkg_dir.exists() {
+        remove_bins(&pkg_dir, &modules_dir.join(".bin"))?;
+    }
+    remove_path(&pkg_dir)?;
+    // the directory of a scope is removed with its last package
+    if alias.starts_with('@') {
+        if let Some(scope_dir) = pkg_dir.parent() {
+            fs::remove_dir(scope_dir).ok();
+        }
+    }
+    Ok(())
+}
+
+/// Removes the direct dependencies and the packages of the current lockfile
+/// that are not wanted anymore, with the executables of the removed direct dependencies
+/// and the links they were hoisted to.
+/// Returns the dependency paths of the removed packages.
+pub fn prune(current_lockfile: &Lockfile, opts: &PruneOptions) -> Result<Vec<String>> {
+    let mut removed_direct_deps = current_lockfile
+        .importers
+        .get(".")
+        .into_iter()
+        .flat_map(|importer| {
+            [
+                &importer.dependencies,
+                &importer.dev_dependencies,
+                &importer.optional_dependencies,
+            ]
+        })
+        .flatten()
+        .flat_map(|deps| deps.keys())
+        .filter(|alias| !opts.wanted_direct_deps.contains(*alias))
+        .collect::<Vec<_>>();
+    removed_direct_deps.sort();
+    removed_direct_deps.dedup();
+
+    for alias in removed_direct_deps {
+        remove_dependency(opts.modules_dir, alias)?;
+    }
+
+    let mut orphans = current_lockfile
+        .packages
+        .iter()
+        .flat_map(|packages| packages.keys())
+        .filter(|dep_path| !opts.wanted_packages.contains(*dep_path))
+        .cloned()
+        .collect::<Vec<_>>();
+    orphans.sort();
+    for dep_path in &orphans {
+        for (alias, kind) in opts
+            .hoisted_dependencies
+            .get(dep_path)
+            .into_iter()
+            .flatten()
+        {
+            let modules_dir = match kind {
+                HoistKind::Private => opts.private_hoisted_modules_dir,
+                HoistKind::Public => opts.public_hoisted_modules_dir,
+            };
+            remove_dependency(modules_dir, alias)?;
+        }
+        remove_path(&opts.virtual_store_dir.join(dep_path_to_filename(dep_path)))?;
+    }
+
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lockfile_utils::types::{LockfileResolution, PackageSnapshot, ProjectSnapshot};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn snapshot() -> PackageSnapshot {
+        PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: "sha512-".to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            dependencies: None,
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prune_removed_dependencies() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let virtual_store_dir = modules_dir.join(".pnpm");
+        for (dep_path, name) in [("foo@1.0.0", "foo"), ("@scope+bar@1.0.0", "@scope/bar")] {
+            let pkg_dir = virtual_store_dir
+                .join(dep_path)
+                .join("node_modules")
+                .join(name);
+            fs::create_dir_all(&pkg_dir).unwrap();
+            fs::write(
+                pkg_dir.join("package.json"),
+                format!("{{\"name\":\"{}\",\"bin\":\"cli.js\"}}", name),
+            )
+            .unwrap();
+            fs::write(pkg_dir.join("cli.js"), "").unwrap();
+            symlink_dependency::symlink_dependency(&pkg_dir, &modules_dir, name).unwrap();
+        }
+        link_bins::link_bins(&modules_dir, modules_dir.join(".bin")).unwrap();
+        let hoisted_modules_dir = virtual_store_dir.join("node_modules");
+        let qar_dir = virtual_store_dir.join("qar@1.0.0/node_modules/qar");
+        fs::create_dir_all(&qar_dir).unwrap();
+        symlink_dependency::symlink_dependency(&qar_dir, &hoisted_modules_dir, "qar").unwrap();
+
+        let mut current_lockfile = Lockfile::new("5.3".to_string());
+        current_lockfile.importers.insert(
+            ".".to_string(),
+            ProjectSnapshot {
+                dependencies: Some(HashMap::from([
+                    ("foo".to_string(), "1.0.0".to_string()),
+                    ("@scope/bar".to_string(), "1.0.0".to_string()),
+                ])),
+                ..Default::default()
+            },
+        );
+        current_lockfile.packages = Some(HashMap::from([
+            ("/foo/1.0.0".to_string(), snapshot()),
+            ("/@scope/bar/1.0.0".to_string(), snapshot()),
+            ("/qar/1.0.0".to_string(), snapshot()),
+        ]));
+
+        let removed = prune(
+            &current_lockfile,
+            &PruneOptions {
+                modules_dir: &modules_dir,
+                virtual_store_dir: &virtual_store_dir,
+                wanted_direct_deps: &HashSet::from(["foo".to_string()]),
+                wanted_packages: &HashSet::from(["/foo/1.0.0".to_string()]),
+                hoisted_dependencies: &HoistedDependencies::from([(
+                    "/qar/1.0.0".to_string(),
+                    [("qar".to_string(), HoistKind::Private)].into(),
+                )]),
+                private_hoisted_modules_dir: &hoisted_modules_dir,
+                public_hoisted_modules_dir: &modules_dir,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(removed, ["/@scope/bar/1.0.0", "/qar/1.0.0"]);
+        assert!(fs::symlink_metadata(hoisted_modules_dir.join("qar")).is_err());
+        assert!(!modules_dir.join("@scope").exists());
+        assert!(!modules_dir.join(".bin/bar").exists());
+        assert!(!virtual_store_dir.join("@scope+bar@1.0.0").exists());
+        assert!(modules_dir.join("foo").exists());
+        assert!(modules_dir.join(".bin/foo").exists());
+    }
+}
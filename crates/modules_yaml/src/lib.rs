@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use normalize_path::normalize_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use types::{HoistedDependencies, IncludedDependencies, NodeLinker};
+
+/// The file in the modules directory that records how it was installed
+pub const MODULES_FILENAME: &str = ".modules.yaml";
+
+/// The state of a modules directory, compared on the next install
+/// to know whether it can be updated in place.
+/// The fields are in alphabetical order, like pnpm writes them.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Modules {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hoist_pattern: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "HoistedDependencies::is_empty")]
+    pub hoisted_dependencies: HoistedDependencies,
+    pub included: IncludedDependencies,
+    pub layout_version: i32,
+    #[serde(default)]
+    pub node_linker: NodeLinker,
+    /// The dependency paths of the packages whose build scripts were not run
+    #[serde(default)]
+    pub pending_builds: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_hoist_pattern: Option<Vec<String>>,
+    /// The optional dependencies that are not installable on this system
+    #[serde(default)]
+    pub skipped: Vec<String>,
+    pub store_dir: PathBuf,
+    /// Written relative to the modules directory, so the project can be moved
+    pub virtual_store_dir: PathBuf,
+}
+
+/// Reads `.modules.yaml`, `None` when the modules directory was not installed by pnpm
+pub fn read_modules(modules_dir: &Path) -> Result<Option<Modules>> {
+    let path = modules_dir.join(MODULES_FILENAME);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let mut modules: Modules = serde_yaml::from_str(&content)
+        .with_context(|| format!("Could not parse {}", path.display()))?;
+    // without the `..` of the relative path, so it can be compared with the one of the config
+    modules.virtual_store_dir = normalize_path(&modules_dir.join(&modules.virtual_store_dir));
+    Ok(Some(modules))
+}
+
+/// Writes `.modules.yaml` into the modules directory
+pub fn write_modules(modules_dir: &Path, modules: &Modules) -> Result<()> {
+    let mut modules = modules.clone();
+    modules.skipped.sort();
+    modules.pending_builds.sort();
+    // the links point to absolute paths on Windows, so the virtual store is recorded the same way
+    if !cfg!(windows) {
+        if let Some(relative) = pathdiff::diff_paths(&modules.virtual_store_dir, modules_dir) {
+            modules.virtual_store_dir = relative;
+        }
+    }
+    fs::create_dir_all(modules_dir)?;
+    fs::write(
+        modules_dir.join(MODULES_FILENAME),
+        serde_yaml::to_string(&modules)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+    use types::HoistKind;
+
+    #[test]
+    fn write_and_read_modules() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let modules = Modules {
+            hoist_pattern: Some(vec!["*".to_string()]),
+            hoisted_dependencies: BTreeMap::from([(
+                "/bar/1.0.0".to_string(),
+                BTreeMap::from([("bar".to_string(), HoistKind::Private)]),
+            )]),
+            included: IncludedDependencies {
+                dependencies: true,
+                dev_dependencies: false,
+                optional_dependencies: true,
+            },
+            layout_version: 5,
+            node_linker: NodeLinker::Isolated,
+            pending_builds: vec!["/foo/1.0.0".to_string()],
+            public_hoist_pattern: None,
+            skipped: vec![],
+            store_dir: dir.path().join("store"),
+            virtual_store_dir: dir.path().join(".store"),
+        };
+        write_modules(&modules_dir, &modules).unwrap();
+
+        let content = fs::read_to_string(modules_dir.join(MODULES_FILENAME)).unwrap();
+        assert!(content.contains("virtualStoreDir: \"../.store\"\n"));
+        assert_eq!(read_modules(&modules_dir).unwrap(), Some(modules));
+        assert_eq!(read_modules(dir.path()).unwrap(), None);
+    }
+}
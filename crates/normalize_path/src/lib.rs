@@ -0,0 +1,31 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Removes the `.` and `..` of a path without touching the file system,
+/// so paths can be compared even when they don't exist or go through links
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_dots() {
+        assert_eq!(
+            normalize_path(Path::new("/workspace/./packages/foo/../bar")),
+            Path::new("/workspace/packages/bar")
+        );
+        assert_eq!(normalize_path(Path::new("foo/../..")), Path::new(""));
+    }
+}
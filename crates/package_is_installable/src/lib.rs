@@ -0,0 +1,150 @@
+use anyhow::{bail, Result};
+use semver_range::satisfies;
+use std::process::Command;
+use types::BaseManifest;
+
+/// The names used by node for the platform, in the `os` and `cpu` fields of manifests
+pub fn node_platform() -> (&'static str, &'static str) {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    };
+    let cpu = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    (os, cpu)
+}
+
+/// Entries starting with `!` exclude a platform, the others are the only allowed ones
+pub fn platform_matches(wanted: Option<&[String]>, current: &str) -> bool {
+    let wanted = match wanted {
+        Some(wanted) if !wanted.is_empty() => wanted,
+        _ => return true,
+    };
+    if wanted
+        .iter()
+        .any(|platform| platform.strip_prefix('!') == Some(current))
+    {
+        return false;
+    }
+    let mut allowed = wanted
+        .iter()
+        .filter(|platform| !platform.starts_with('!'))
+        .peekable();
+    allowed.peek().is_none() || allowed.any(|platform| platform == current || platform == "any")
+}
+
+/// The version of the node in the PATH, without its `v` prefix
+pub fn current_node_version() -> Option<String> {
+    let output = Command::new("node").arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout);
+    Some(version.trim().trim_start_matches('v').to_string())
+}
+
+/// Fails when the package doesn't support the platform or the version of node.
+/// The version of node is looked up when it is not given, and not checked when
+/// there is no node.
+pub fn check_package(
+    package_id: &str,
+    manifest: &BaseManifest,
+    node_version: Option<&str>,
+) -> Result<()> {
+    let (os, cpu) = node_platform();
+    let wanted_os = manifest.os.as_deref().map(Vec::as_slice);
+    let wanted_cpu = manifest.cpu.as_deref().map(Vec::as_slice);
+    if !platform_matches(wanted_os, os) || !platform_matches(wanted_cpu, cpu) {
+        bail!(
+            "UNSUPPORTED_PLATFORM: Unsupported platform for {}: wanted {{\"os\":{:?},\"cpu\":{:?}}} (current: {{\"os\":\"{}\",\"cpu\":\"{}\"}})",
+            package_id,
+            wanted_os.unwrap_or_default(),
+            wanted_cpu.unwrap_or_default(),
+            os,
+            cpu
+        );
+    }
+
+    // this is not pnpm, so `engines.pnpm` is not checked
+    let wanted_node = match manifest
+        .engines
+        .as_ref()
+        .and_then(|engines| engines.node.as_deref())
+    {
+        Some(wanted_node) => wanted_node,
+        None => return Ok(()),
+    };
+    let node_version = match node_version {
+        Some(node_version) => node_version.to_string(),
+        None => match current_node_version() {
+            Some(node_version) => node_version,
+            None => return Ok(()),
+        },
+    };
+    if !satisfies(&node_version, wanted_node) {
+        bail!(
+            "UNSUPPORTED_ENGINE: Unsupported engine for {}: wanted: {{\"node\":\"{}\"}} (current: {{\"node\":\"{}\"}})",
+            package_id,
+            wanted_node,
+            node_version
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use types::Engines;
+
+    #[test]
+    fn match_platform() {
+        let platforms = |platforms: &[&str]| {
+            platforms
+                .iter()
+                .map(|platform| platform.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(platform_matches(None, "linux"));
+        assert!(platform_matches(
+            Some(&platforms(&["linux", "darwin"])),
+            "linux"
+        ));
+        assert!(!platform_matches(Some(&platforms(&["darwin"])), "linux"));
+        assert!(platform_matches(Some(&platforms(&["!win32"])), "linux"));
+        assert!(!platform_matches(Some(&platforms(&["!linux"])), "linux"));
+    }
+
+    #[test]
+    fn check_engines_and_platform() {
+        let (os, _) = node_platform();
+        let mut manifest = BaseManifest {
+            engines: Some(Engines {
+                node: Some(">=14".to_string()),
+                ..Default::default()
+            }),
+            os: Some(Rc::new(vec![os.to_string()])),
+            ..Default::default()
+        };
+        assert!(check_package("foo", &manifest, Some("16.0.0")).is_ok());
+
+        let error = check_package("foo", &manifest, Some("12.0.0")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "UNSUPPORTED_ENGINE: Unsupported engine for foo: wanted: {\"node\":\">=14\"} (current: {\"node\":\"12.0.0\"})"
+        );
+
+        manifest.os = Some(Rc::new(vec![format!("!{}", os)]));
+        let error = check_package("foo", &manifest, Some("16.0.0")).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("UNSUPPORTED_PLATFORM: Unsupported platform for foo"));
+    }
+}
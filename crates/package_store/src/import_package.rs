@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use cafs::get_file_path_by_mode_in_cafs;
+use ssri::Integrity;
+use std::fs;
+use std::path::Path;
+use store_controller_types::{ImportPackageOptions, ImportPackageResult, PackageImportMethod};
+
+/// Imports the files of a package from the store into `to`.
+/// The files are first imported into a temporary directory next to `to`,
+/// so a package is never left half imported.
+pub fn import_package<P: AsRef<Path>, S: AsRef<Path>>(
+    cafs_dir: P,
+    to: S,
+    opts: ImportPackageOptions,
+) -> Result<ImportPackageResult> {
+    let to = to.as_ref();
+    let side_effects = opts.side_effects_cache_key.and_then(|key| {
+        opts.files_index
+            .side_effects
+            .as_ref()
+            .and_then(|side_effects| side_effects.get(key))
+    });
+    let is_built = side_effects.is_some();
+    let files = side_effects.unwrap_or(&opts.files_index.files);
+
+    if to.exists() && !opts.force {
+        return Ok(ImportPackageResult {
+            is_built,
+            imported: false,
+        });
+    }
+
+    let parent = to.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent)?;
+    let stage = temp_path::temp_path(parent);
+
+    let result = files
+        .iter()
+        .try_for_each(|(file_name, info)| -> Result<()> {
+            let integrity = info.integrity.parse::<Integrity>()?;
+            let src = get_file_path_by_mode_in_cafs(&cafs_dir, &integrity, info.mode);
+            let dest = stage.join(file_name);
+            if let Some(dest_dir) = dest.parent() {
+                fs::create_dir_all(dest_dir)?;
+            }
+
+            import_file(&src, &dest, opts.method).with_context(|| {
+                format!("Failed to import {} from {}", dest.display(), src.display())
+            })
+        });
+
+    if let Err(error) = result {
+        fs::remove_dir_all(&stage).ok();
+        return Err(error);
+    }
+
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    fs::rename(&stage, to)?;
+
+    Ok(ImportPackageResult {
+        is_built,
+        imported: true,
+    })
+}
+
+fn import_file(src: &Path, dest: &Path, method: PackageImportMethod) -> Result<()> {
+    match method {
+        PackageImportMethod::Hardlink => fs::hard_link(src, dest)?,
+        PackageImportMethod::Copy => {
+            fs::copy(src, dest)?;
+        }
+        PackageImportMethod::Auto => {
+            if fs::hard_link(src, dest).is_err() {
+                fs::copy(src, dest)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload;
+    use cafs::{
+        add_files_from_dir, read_package_files_index, write_package_files_index, PackageFilesIndex,
+    };
+    use pretty_assertions::assert_eq;
+    use store_controller_types::UploadOptions;
+    use tempfile::tempdir;
+
+    #[test]
+    fn import_side_effects_of_built_package() {
+        let cafs_dir = tempdir().unwrap();
+        let src_dir = tempdir().unwrap();
+        let project_dir = tempdir().unwrap();
+        fs::write(src_dir.path().join("package.json"), "{}").unwrap();
+        fs::write(src_dir.path().join("install.js"), "").unwrap();
+
+        let index_file = cafs_dir.path().join("index.json");
+        write_package_files_index(
+            &index_file,
+            &PackageFilesIndex {
+                files: add_files_from_dir(cafs_dir.path(), src_dir.path()).unwrap(),
+                side_effects: None,
+            },
+        )
+        .unwrap();
+
+        let pkg_dir = project_dir.path().join("foo");
+        let index = read_package_files_index(&index_file).unwrap();
+        assert_eq!(
+            import_package(
+                cafs_dir.path(),
+                &pkg_dir,
+                ImportPackageOptions {
+                    files_index: &index,
+                    side_effects_cache_key: Some("linux-x64-node-v16-abc"),
+                    force: false,
+                    method: PackageImportMethod::Copy,
+                },
+            )
+            .unwrap(),
+            ImportPackageResult {
+                is_built: false,
+                imported: true
+            }
+        );
+        assert!(pkg_dir.join("install.js").exists());
+
+        // the postinstall script produced a new file
+        fs::write(pkg_dir.join("binding.node"), "native").unwrap();
+        upload(
+            cafs_dir.path(),
+            &pkg_dir.to_string_lossy(),
+            &UploadOptions {
+                files_index_file: index_file.to_string_lossy().to_string(),
+                engine: "linux-x64-node-v16-abc".to_string(),
+            },
+        )
+        .unwrap();
+
+        let built_pkg_dir = project_dir.path().join("bar");
+        let index = read_package_files_index(&index_file).unwrap();
+        assert_eq!(
+            import_package(
+                cafs_dir.path(),
+                &built_pkg_dir,
+                ImportPackageOptions {
+                    files_index: &index,
+                    side_effects_cache_key: Some("linux-x64-node-v16-abc"),
+                    force: false,
+                    method: PackageImportMethod::Auto,
+                },
+            )
+            .unwrap(),
+            ImportPackageResult {
+                is_built: true,
+                imported: true
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(built_pkg_dir.join("binding.node")).unwrap(),
+            "native"
+        );
+
+        // a different engine doesn't get the built files
+        let other_pkg_dir = project_dir.path().join("baz");
+        let result = import_package(
+            cafs_dir.path(),
+            &other_pkg_dir,
+            ImportPackageOptions {
+                files_index: &index,
+                side_effects_cache_key: Some("darwin-arm64-node-v16-abc"),
+                force: false,
+                method: PackageImportMethod::Auto,
+            },
+        )
+        .unwrap();
+        assert!(!result.is_built);
+        assert!(!other_pkg_dir.join("binding.node").exists());
+    }
+}
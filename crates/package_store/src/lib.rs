@@ -0,0 +1,5 @@
+mod import_package;
+mod upload;
+
+pub use import_package::import_package;
+pub use upload::upload;
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use cafs::{add_files_from_dir, read_package_files_index, write_package_files_index};
+use std::path::Path;
+use store_controller_types::UploadOptions;
+
+/// Adds the files of a built package to the store and records them
+/// as the side effects of the package for the given engine
+pub fn upload<P: AsRef<Path> + Sync>(
+    cafs_dir: P,
+    built_pkg_location: &str,
+    opts: &UploadOptions,
+) -> Result<()> {
+    let files = add_files_from_dir(cafs_dir, built_pkg_location)?;
+
+    // the index is only read once the files are in the store, right before it is replaced,
+    // so the side effects that other builds saved in the meantime are kept.
+    // It is written to a temp file that is renamed over it, so readers never see half of it.
+    let mut index = read_package_files_index(&opts.files_index_file)?;
+    index
+        .side_effects
+        .get_or_insert_with(Default::default)
+        .insert(opts.engine.clone(), files);
+    write_package_files_index(&opts.files_index_file, &index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cafs::PackageFilesIndex;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn keep_the_side_effects_of_other_engines() {
+        let cafs_dir = tempdir().unwrap();
+        let pkg_dir = tempdir().unwrap();
+        let index_dir = tempdir().unwrap();
+        fs::write(pkg_dir.path().join("package.json"), "{}").unwrap();
+        let index_file = index_dir.path().join("index.json");
+        write_package_files_index(
+            &index_file,
+            &PackageFilesIndex {
+                files: add_files_from_dir(cafs_dir.path(), pkg_dir.path()).unwrap(),
+                side_effects: None,
+            },
+        )
+        .unwrap();
+
+        for engine in ["darwin-arm64-node-v16", "linux-x64-node-v16"] {
+            fs::write(pkg_dir.path().join("binding.node"), engine).unwrap();
+            upload(
+                cafs_dir.path(),
+                &pkg_dir.path().to_string_lossy(),
+                &UploadOptions {
+                    files_index_file: index_file.to_string_lossy().to_string(),
+                    engine: engine.to_string(),
+                },
+            )
+            .unwrap();
+        }
+
+        let side_effects = read_package_files_index(&index_file)
+            .unwrap()
+            .side_effects
+            .unwrap();
+        let mut engines = side_effects.keys().collect::<Vec<_>>();
+        engines.sort();
+        assert_eq!(engines, ["darwin-arm64-node-v16", "linux-x64-node-v16"]);
+        // only the index is left, the temp file it was written to was renamed
+        assert_eq!(fs::read_dir(index_dir.path()).unwrap().count(), 1);
+    }
+}
@@ -6,14 +6,18 @@ pub struct ParsedWantedDependency {
 }
 
 pub fn parse_wanted_dependency(raw_wanted_dependency: &str) -> ParsedWantedDependency {
-    let version_delimiter = &raw_wanted_dependency[1..].find('@'); // skip the @ that marks the scope
+    // skip the @ that marks the scope
+    let version_delimiter = raw_wanted_dependency
+        .get(1..)
+        .and_then(|rest| rest.find('@'))
+        .map(|index| index + 1);
 
     if let Some(version_delimiter) = version_delimiter {
-        let alias = &raw_wanted_dependency[0..*version_delimiter];
+        let alias = &raw_wanted_dependency[0..version_delimiter];
         if validate_npm_package_name(alias).valid_for_old_packages {
             return ParsedWantedDependency {
                 alias: Some(alias.to_string()),
-                pref: Some((&raw_wanted_dependency[version_delimiter + 1..]).to_string()),
+                pref: Some(raw_wanted_dependency[version_delimiter + 1..].to_string()),
             };
         }
 
@@ -35,3 +39,27 @@ pub fn parse_wanted_dependency(raw_wanted_dependency: &str) -> ParsedWantedDepen
         pref: Some(raw_wanted_dependency.to_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(raw: &str) -> (Option<String>, Option<String>) {
+        let ParsedWantedDependency { alias, pref } = parse_wanted_dependency(raw);
+        (alias, pref)
+    }
+
+    #[test]
+    fn parse_alias_and_pref() {
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(parse("foo"), (some("foo"), None));
+        assert_eq!(parse("foo@^1.0.0"), (some("foo"), some("^1.0.0")));
+        assert_eq!(parse("@foo/bar"), (some("@foo/bar"), None));
+        assert_eq!(parse("@foo/bar@latest"), (some("@foo/bar"), some("latest")));
+        assert_eq!(
+            parse("https://example.com/foo.tgz"),
+            (None, some("https://example.com/foo.tgz"))
+        );
+    }
+}
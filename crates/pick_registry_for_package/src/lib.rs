@@ -0,0 +1,76 @@
+use types::Registries;
+
+fn get_scope(package_name: &str, pref: Option<&str>) -> Option<String> {
+    // `npm:@foo/bar@1.0.0` installs `@foo/bar` under another name,
+    // so it comes from the registry of `@foo`
+    if let Some(aliased) = pref.and_then(|pref| pref.strip_prefix("npm:")) {
+        if aliased.starts_with('@') {
+            return aliased.split_once('/').map(|(scope, _)| scope.to_string());
+        }
+        return None;
+    }
+
+    if package_name.starts_with('@') {
+        return package_name
+            .split_once('/')
+            .map(|(scope, _)| scope.to_string());
+    }
+
+    None
+}
+
+pub fn pick_registry_for_package<'a>(
+    registries: &'a Registries,
+    package_name: &str,
+    pref: Option<&str>,
+) -> &'a str {
+    get_scope(package_name, pref)
+        .and_then(|scope| registries.scoped.get(&scope))
+        .unwrap_or(&registries.default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn registries() -> Registries {
+        Registries {
+            default: "https://registry.npmjs.org/".to_string(),
+            scoped: HashMap::from([("@ourco".to_string(), "https://npm.ourco.com/".to_string())]),
+        }
+    }
+
+    #[test]
+    fn pick_registry() {
+        let registries = registries();
+
+        assert_eq!(
+            pick_registry_for_package(&registries, "foo", None),
+            "https://registry.npmjs.org/"
+        );
+        assert_eq!(
+            pick_registry_for_package(&registries, "@ourco/foo", Some("^1.0.0")),
+            "https://npm.ourco.com/"
+        );
+        assert_eq!(
+            pick_registry_for_package(&registries, "@other/foo", None),
+            "https://registry.npmjs.org/"
+        );
+    }
+
+    #[test]
+    fn pick_registry_of_aliased_package() {
+        let registries = registries();
+
+        assert_eq!(
+            pick_registry_for_package(&registries, "foo", Some("npm:@ourco/foo@^1.0.0")),
+            "https://npm.ourco.com/"
+        );
+        assert_eq!(
+            pick_registry_for_package(&registries, "@ourco/foo", Some("npm:foo@^1.0.0")),
+            "https://registry.npmjs.org/"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use normalize_path::normalize_path;
+use project::{Graph, Project, ProjectsGraph};
+use semver_range::{parse_version, Range};
+use std::path::Path;
+
+/// The directory of the workspace project the dependency is resolved from, if any
+fn resolve_in_workspace<'p>(
+    projects: &'p [Project],
+    project_dir: &str,
+    alias: &str,
+    spec: &str,
+) -> Option<&'p str> {
+    if let Some(path) = spec
+        .strip_prefix("link:")
+        .or_else(|| spec.strip_prefix("file:"))
+    {
+        let dir = normalize_path(&Path::new(project_dir).join(path));
+        return projects
+            .iter()
+            .find(|project| normalize_path(Path::new(&project.dir)) == dir)
+            .map(|project| project.dir.as_str());
+    }
+
+    let spec = spec.strip_prefix("workspace:").unwrap_or(spec);
+    let range = match spec {
+        "*" | "^" | "~" | "" => None,
+        spec => Some(Range::parse(spec).ok()?),
+    };
+    projects
+        .iter()
+        .filter(|project| project.manifest.name.as_deref() == Some(alias))
+        .filter_map(|project| {
+            let version = project.manifest.version.as_deref().and_then(parse_version);
+            match (&range, version) {
+                (None, version) => Some((version, project)),
+                (Some(range), Some(version)) if range.satisfies(&version) => {
+                    Some((Some(version), project))
+                }
+                _ => None,
+            }
+        })
+        .max_by(|(version_1, _), (version_2, _)| version_1.cmp(version_2))
+        .map(|(_, project)| project.dir.as_str())
+}
+
+#[derive(Default)]
+pub struct CreatePkgsGraphOptions {
+    /// Leave out the edges of the devDependencies
+    pub ignore_dev_deps: bool,
+}
+
+/// The graph of the projects of a workspace, by their directories.
+/// A project depends on the workspace projects that satisfy its dependencies.
+pub fn create_pkgs_graph<'a>(
+    projects: &'a [Project],
+    opts: &CreatePkgsGraphOptions,
+) -> ProjectsGraph<'a> {
+    projects
+        .iter()
+        .map(|project| {
+            let manifest = &project.manifest;
+            let dev_dependencies = match opts.ignore_dev_deps {
+                true => &None,
+                false => &manifest.dev_dependencies,
+            };
+            let mut dependencies = [
+                &manifest.dependencies,
+                dev_dependencies,
+                &manifest.optional_dependencies,
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|(alias, spec)| {
+                resolve_in_workspace(projects, &project.dir, alias, spec).map(String::from)
+            })
+            .filter(|dir| *dir != project.dir)
+            .collect::<Vec<_>>();
+            dependencies.sort();
+            dependencies.dedup();
+
+            (
+                project.dir.clone(),
+                Graph {
+                    dependencies,
+                    package: project,
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use read_project_manifest::WriterOptions;
+    use std::collections::HashMap;
+    use types::BaseManifest;
+
+    fn project(dir: &str, name: &str, version: &str, deps: &[(&str, &str)]) -> Project {
+        Project {
+            dir: dir.to_string(),
+            manifest: BaseManifest {
+                name: Some(name.to_string()),
+                version: Some(version.to_string()),
+                dependencies: Some(
+                    deps.iter()
+                        .map(|(alias, spec)| (alias.to_string(), spec.to_string()))
+                        .collect::<HashMap<_, _>>(),
+                ),
+                ..Default::default()
+            },
+            writer_options: WriterOptions {
+                insert_final_newline: None,
+                manifest_path: format!("{}/package.json", dir),
+            },
+        }
+    }
+
+    #[test]
+    fn link_workspace_dependencies() {
+        let projects = [
+            project(
+                "/ws/app",
+                "app",
+                "1.0.0",
+                &[
+                    ("lib", "workspace:*"),
+                    ("utils", "^1.0.0"),
+                    ("old", "^2.0.0"),
+                    ("local", "link:../local"),
+                    ("react", "^17.0.0"),
+                ],
+            ),
+            project("/ws/lib", "lib", "1.0.0", &[("utils", "workspace:^1.0.0")]),
+            project("/ws/utils", "utils", "1.2.0", &[]),
+            project("/ws/old", "old", "1.0.0", &[]),
+            project("/ws/local", "local", "0.0.0", &[]),
+        ];
+
+        let graph = create_pkgs_graph(&projects, &Default::default());
+
+        assert_eq!(
+            graph["/ws/app"].dependencies,
+            ["/ws/lib", "/ws/local", "/ws/utils"]
+        );
+        assert_eq!(graph["/ws/lib"].dependencies, ["/ws/utils"]);
+        assert!(graph["/ws/utils"].dependencies.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use super::extend_install_options::InstallOptions;
+use super::fetch_package::{fetch_package, read_manifest_from_store};
+use super::install;
+use anyhow::{bail, Result};
+use client::Client;
+use lockfile_utils::types::Lockfile;
+use parse_wanted_dependency::{parse_wanted_dependency, ParsedWantedDependency};
+use resolvers::base::{ResolveOptions, ResolvedVia, WantedDependency};
+use resolvers::default::Resolver;
+use resolvers::npm::{parse_pref, RegistryPackageSpecType, ResolverFactoryOptions};
+use std::collections::HashMap;
+use store_connection_manager::StoreController;
+use types::{BaseManifest, DependencyField};
+
+pub struct AddDependenciesOptions {
+    /// When it is `None`, dependencies that are already in the manifest stay in their field
+    /// and new ones are saved to `dependencies`
+    pub target_dependency_field: Option<DependencyField>,
+    /// Save the dependencies to `peerDependencies` too
+    pub peer: bool,
+    /// Prepended to the resolved versions, like `^`. Empty to save the exact versions.
+    pub save_prefix: String,
+    /// The versions of the workspace packages by their names,
+    /// set when dependencies can only be added from the workspace
+    pub workspace_packages: Option<HashMap<String, String>>,
+    pub save_workspace_protocol: bool,
+}
+
+/// The alias and the specifier of a dependency, as they are saved to the manifest
+#[derive(Debug, PartialEq)]
+struct SavedDependency {
+    alias: String,
+    pref: String,
+}
+
+/// Whether the resolved version is saved instead of what the user asked for,
+/// which is the case when no version or a tag like `latest` was asked for
+fn pin_resolved_version(alias: &str, pref: Option<&str>) -> bool {
+    match pref {
+        None => true,
+        Some(pref) if pref.starts_with("npm:") => false,
+        Some(pref) => parse_pref(pref, Some(alias), "latest")
+            .is_some_and(|spec| spec.r#type == RegistryPackageSpecType::Tag),
+    }
+}
+
+fn workspace_dependency(
+    raw_wanted_dependency: &str,
+    workspace_packages: &HashMap<String, String>,
+    opts: &AddDependenciesOptions,
+) -> Result<SavedDependency> {
+    let alias = parse_wanted_dependency(raw_wanted_dependency)
+        .alias
+        .unwrap_or_else(|| raw_wanted_dependency.to_string());
+    let version = match workspace_packages.get(&alias) {
+        Some(version) => version,
+        None => bail!(
+            "WORKSPACE_PACKAGE_NOT_FOUND: \"{}\" not found in the workspace",
+            alias
+        ),
+    };
+    let protocol = if opts.save_workspace_protocol {
+        "workspace:"
+    } else {
+        ""
+    };
+
+    Ok(SavedDependency {
+        pref: format!("{}{}{}", protocol, opts.save_prefix, version),
+        alias,
+    })
+}
+
+fn resolve_wanted_dependency(
+    resolver: &Resolver,
+    client: &Client,
+    store_controller: &StoreController,
+    raw_wanted_dependency: &str,
+    opts: &AddDependenciesOptions,
+    install_opts: &InstallOptions,
+) -> Result<SavedDependency> {
+    let ParsedWantedDependency { alias, pref } = parse_wanted_dependency(raw_wanted_dependency);
+    let dir = install_opts.dir.to_string_lossy().to_string();
+    let result = resolver.resolve(
+        WantedDependency {
+            alias: alias.clone(),
+            pref: pref.clone(),
+            injected: None,
+        },
+        ResolveOptions {
+            always_try_workspace_packages: None,
+            default_tag: None,
+            project_dir: dir.clone(),
+            lockfile_dir: dir.clone(),
+            preferred_versions: &HashMap::new(),
+            prefer_workspace_packages: None,
+            registries: install_opts.registries.clone(),
+            workspace_packages: install_opts.workspace_packages.as_ref(),
+        },
+    )?;
+
+    let manifest = match result.manifest {
+        Some(manifest) => manifest,
+        None => {
+            // tarballs have to be downloaded to know what is in them
+            let cafs_dir = store_controller.cafs_dir();
+            let fetched = fetch_package(
+                client,
+                cafs_dir,
+                &result.resolution,
+                &dir,
+                install_opts.offline,
+            )?;
+            read_manifest_from_store(cafs_dir, &fetched.files_index)?
+        }
+    };
+    let alias = match alias.or(manifest.name) {
+        Some(alias) => alias,
+        None => bail!(
+            "MISSING_PACKAGE_NAME: Cannot add {} because its package.json has no name",
+            raw_wanted_dependency
+        ),
+    };
+
+    let pref = match manifest.version {
+        Some(version)
+            if result.resolved_via == ResolvedVia::NpmRegistry
+                && pin_resolved_version(&alias, pref.as_deref()) =>
+        {
+            format!("{}{}", opts.save_prefix, version)
+        }
+        _ => result.normalized_pref,
+    };
+
+    Ok(SavedDependency { alias, pref })
+}
+
+/// Saves the dependency to the target field and removes it from the other fields
+fn save_dependency(
+    manifest: &mut BaseManifest,
+    dependency: SavedDependency,
+    opts: &AddDependenciesOptions,
+) {
+    let SavedDependency { alias, pref } = dependency;
+    let current_field = DependencyField::iterator().find(|field| {
+        manifest
+            .dependencies_of(*field)
+            .is_some_and(|deps| deps.contains_key(&alias))
+    });
+    let target_field = opts
+        .target_dependency_field
+        .or(current_field)
+        .unwrap_or(DependencyField::Dependencies);
+
+    for field in DependencyField::iterator().filter(|field| *field != target_field) {
+        let deps = manifest.dependencies_of_mut(field);
+        if let Some(field_deps) = deps {
+            field_deps.remove(&alias);
+            if field_deps.is_empty() {
+                *deps = None;
+            }
+        }
+    }
+    if opts.peer {
+        manifest
+            .peer_dependencies
+            .get_or_insert_with(Default::default)
+            .insert(alias.clone(), pref.clone());
+    }
+    manifest
+        .dependencies_of_mut(target_field)
+        .get_or_insert_with(Default::default)
+        .insert(alias, pref);
+}
+
+/// Resolves the wanted dependencies, like `foo`, `foo@^1.0.0` or `foo@latest`,
+/// saves them to the manifest and installs the dependencies of the updated manifest.
+/// Returns the updated manifest, which is not written by this function.
+pub fn add_dependencies_to_package(
+    client: &Client,
+    store_controller: &StoreController,
+    manifest: &BaseManifest,
+    wanted_dependencies: &[&str],
+    opts: &AddDependenciesOptions,
+    install_opts: &InstallOptions,
+) -> Result<(BaseManifest, Lockfile)> {
+    let resolver = client.create_resolver(ResolverFactoryOptions {
+        cache_dir: install_opts.cache_dir.clone(),
+        offline: install_opts.offline,
+        prefer_offline: false,
+    });
+
+    let mut updated_manifest = manifest.clone();
+    for raw_wanted_dependency in wanted_dependencies {
+        let dependency = match &opts.workspace_packages {
+            Some(workspace_packages) => {
+                workspace_dependency(raw_wanted_dependency, workspace_packages, opts)?
+            }
+            None => resolve_wanted_dependency(
+                &resolver,
+                client,
+                store_controller,
+                raw_wanted_dependency,
+                opts,
+                install_opts,
+            )?,
+        };
+        save_dependency(&mut updated_manifest, dependency, opts);
+    }
+
+    let lockfile = install(client, store_controller, &updated_manifest, install_opts)?;
+    Ok((updated_manifest, lockfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn options(target_dependency_field: Option<DependencyField>) -> AddDependenciesOptions {
+        AddDependenciesOptions {
+            target_dependency_field,
+            peer: false,
+            save_prefix: "^".to_string(),
+            workspace_packages: None,
+            save_workspace_protocol: false,
+        }
+    }
+
+    fn saved(alias: &str, pref: &str) -> SavedDependency {
+        SavedDependency {
+            alias: alias.to_string(),
+            pref: pref.to_string(),
+        }
+    }
+
+    #[test]
+    fn pin_only_tags() {
+        assert!(pin_resolved_version("foo", None));
+        assert!(pin_resolved_version("foo", Some("latest")));
+        assert!(!pin_resolved_version("foo", Some("^1.0.0")));
+        assert!(!pin_resolved_version("foo", Some("1.0.0")));
+        assert!(!pin_resolved_version("foo", Some("npm:bar@latest")));
+    }
+
+    #[test]
+    fn move_dependency_to_target_field() {
+        let mut manifest = BaseManifest {
+            dev_dependencies: Some(HashMap::from([("foo".to_string(), "1.0.0".to_string())])),
+            ..Default::default()
+        };
+
+        save_dependency(&mut manifest, saved("foo", "^2.0.0"), &options(None));
+        assert_eq!(
+            manifest.dev_dependencies,
+            Some(HashMap::from([("foo".to_string(), "^2.0.0".to_string())]))
+        );
+
+        save_dependency(
+            &mut manifest,
+            saved("foo", "^2.0.0"),
+            &options(Some(DependencyField::OptionalDependencies)),
+        );
+        assert_eq!(manifest.dev_dependencies, None);
+        assert_eq!(
+            manifest.optional_dependencies,
+            Some(HashMap::from([("foo".to_string(), "^2.0.0".to_string())]))
+        );
+
+        save_dependency(&mut manifest, saved("bar", "^1.0.0"), &options(None));
+        assert_eq!(
+            manifest.dependencies,
+            Some(HashMap::from([("bar".to_string(), "^1.0.0".to_string())]))
+        );
+    }
+
+    #[test]
+    fn add_from_workspace() {
+        let workspace_packages = HashMap::from([("foo".to_string(), "1.0.0".to_string())]);
+        let opts = AddDependenciesOptions {
+            save_workspace_protocol: true,
+            ..options(None)
+        };
+
+        assert_eq!(
+            workspace_dependency("foo", &workspace_packages, &opts).unwrap(),
+            saved("foo", "workspace:^1.0.0")
+        );
+        assert_eq!(
+            workspace_dependency("bar", &workspace_packages, &opts)
+                .unwrap_err()
+                .to_string(),
+            "WORKSPACE_PACKAGE_NOT_FOUND: \"bar\" not found in the workspace"
+        );
+    }
+}
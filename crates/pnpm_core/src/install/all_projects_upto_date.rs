@@ -1,65 +1,119 @@
 use lockfile_utils::satisfies_package_manifest::satisfies_package_manifest;
-use lockfile_utils::types::{Lockfile, ProjectSnapshot};
+use lockfile_utils::types::{Lockfile, ProjectSnapshot, ResolvedDependencies};
+use normalize_path::normalize_path;
 use rayon::prelude::*;
+use read_project_manifest::read_project_manifest;
 use resolvers::base::WorkspacePackages;
 use std::collections::HashMap;
-use types::{BaseManifest, DependencyField, ProjectManifest};
+use std::path::{Path, PathBuf};
+use types::{BaseManifest, Dependencies, DependencyField, ProjectManifest};
 
-pub struct ProjectOptions {
-    id: String,
-    manifest: ProjectManifest,
-    modules_dir: String,
-    root_dir: String,
-    bins_dir: String,
+pub struct ProjectOptions<'a> {
+    /// The path of the project relative to the lockfile directory, `.` for the root project
+    pub id: &'a str,
+    pub manifest: &'a ProjectManifest,
+    pub root_dir: &'a Path,
 }
 
 pub struct Options<'a> {
-    link_workspace_packages: bool,
-    wanted_lockfile: Lockfile,
-    workspace_packages: WorkspacePackages<'a>,
+    pub link_workspace_packages: bool,
+    pub wanted_lockfile: &'a Lockfile,
+    pub workspace_packages: &'a WorkspacePackages,
 }
 
-const X: &str = std::env::consts::OS;
-const Y: &str = std::env::consts::ARCH;
-
-fn get_workspace_packages_by_directory<'a>(
-    workspace_packages: &'a WorkspacePackages<'a>,
-) -> HashMap<&'a str, &'a BaseManifest> {
-    let mut workspace_packages_by_directory = HashMap::new();
-
-    for (pkg_name, _) in workspace_packages {
-        for (_pkg_version, pkg) in workspace_packages.get(pkg_name).unwrap() {
-            workspace_packages_by_directory.insert(pkg.dir.as_str(), pkg.manifest);
-        }
-    }
-
-    workspace_packages_by_directory
+fn get_workspace_packages_by_directory(
+    workspace_packages: &WorkspacePackages,
+) -> HashMap<PathBuf, &BaseManifest> {
+    workspace_packages
+        .values()
+        .flat_map(|versions| versions.values())
+        .map(|pkg| (normalize_path(Path::new(&pkg.dir)), &pkg.manifest))
+        .collect()
 }
 
-pub fn all_projects_are_up_to_date<'a>(projects: &[ProjectOptions], opts: Options<'a>) -> bool {
-    let _manifestsByDir = get_workspace_packages_by_directory(&opts.workspace_packages);
+/// Whether the lockfile can be installed as is, without resolving anything
+pub fn all_projects_are_up_to_date(projects: &[ProjectOptions], opts: Options) -> bool {
+    let manifests_by_dir = get_workspace_packages_by_directory(opts.workspace_packages);
     projects.iter().all(|project| {
-        let importer = opts
-            .wanted_lockfile
-            .importers
-            .get(project.id.as_str())
-            .unwrap();
+        let importer = match opts.wanted_lockfile.importers.get(project.id) {
+            Some(importer) => importer,
+            None => return false,
+        };
 
-        has_local_tarball_deps_in_root(importer)
-            && satisfies_package_manifest(
-                &opts.wanted_lockfile,
-                &project.manifest,
-                project.id.as_str(),
+        // the content of a local tarball may change without its path changing
+        !has_local_tarball_deps_in_root(importer)
+            && satisfies_package_manifest(opts.wanted_lockfile, project.manifest, project.id)
+            && linked_packages_are_up_to_date(
+                project,
+                importer,
+                &manifests_by_dir,
+                opts.link_workspace_packages,
             )
-            && linked_packages_are_up_to_date()
     })
 }
 
-fn linked_packages_are_up_to_date() -> bool {
-    for dep_field in DependencyField::iterator() {
-        // let lockfile_deps =
+fn lockfile_deps(
+    importer: &ProjectSnapshot,
+    field: DependencyField,
+) -> Option<&ResolvedDependencies> {
+    match field {
+        DependencyField::Dependencies => importer.dependencies.as_ref(),
+        DependencyField::DevDependencies => importer.dev_dependencies.as_ref(),
+        DependencyField::OptionalDependencies => importer.optional_dependencies.as_ref(),
     }
-    todo!();
+}
+
+fn manifest_deps(manifest: &BaseManifest, field: DependencyField) -> Option<&Dependencies> {
+    match field {
+        DependencyField::Dependencies => manifest.dependencies.as_ref(),
+        DependencyField::DevDependencies => manifest.dev_dependencies.as_ref(),
+        DependencyField::OptionalDependencies => manifest.optional_dependencies.as_ref(),
+    }
+}
+
+/// Whether the packages that are linked from the workspace still satisfy
+/// the ranges in the manifest of the project
+fn linked_packages_are_up_to_date(
+    project: &ProjectOptions,
+    importer: &ProjectSnapshot,
+    manifests_by_dir: &HashMap<PathBuf, &BaseManifest>,
+    link_workspace_packages: bool,
+) -> bool {
+    DependencyField::iterator().all(|field| {
+        let specs = manifest_deps(&project.manifest.manifest, field);
+        lockfile_deps(importer, field)
+            .into_iter()
+            .flatten()
+            .all(|(alias, reference)| {
+                let linked_dir = match reference.strip_prefix("link:") {
+                    Some(path) => normalize_path(&project.root_dir.join(path)),
+                    None => return true,
+                };
+                let spec = match specs.and_then(|specs| specs.get(alias)) {
+                    Some(spec) => spec,
+                    None => return true,
+                };
+                if spec.starts_with("link:") || spec.starts_with("file:") {
+                    return true;
+                }
+                let range = match spec.strip_prefix("workspace:") {
+                    Some(range) => range,
+                    // the package would be resolved from the registry now
+                    None if !link_workspace_packages => return false,
+                    None => spec,
+                };
+                if matches!(range, "*" | "^" | "~") {
+                    return true;
+                }
+                let version = match manifests_by_dir.get(&linked_dir) {
+                    Some(manifest) => manifest.version.clone(),
+                    None => read_project_manifest(&linked_dir)
+                        .ok()
+                        .and_then(|manifest| manifest.manifest?.version),
+                };
+                version.is_some_and(|version| semver_range::satisfies(&version, range))
+            })
+    })
 }
 
 fn has_local_tarball_deps_in_root(importer: &ProjectSnapshot) -> bool {
@@ -79,7 +133,77 @@ fn has_local_tarball_deps_in_root(importer: &ProjectSnapshot) -> bool {
 }
 
 fn ref_is_local_tarball(ref_str: &str) -> bool {
-    ref_str.starts_with("file:") && ref_str.ends_with(".tgz")
-        || ref_str.ends_with(".tar.gz")
-        || ref_str.ends_with(".tar")
+    ref_str.starts_with("file:")
+        && (ref_str.ends_with(".tgz") || ref_str.ends_with(".tar.gz") || ref_str.ends_with(".tar"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resolvers::base::WorkspacePackage;
+    use std::collections::HashMap;
+
+    #[test]
+    fn linked_package_out_of_range() {
+        let mut importer = ProjectSnapshot::new();
+        importer.specifiers = HashMap::from([("foo".to_string(), "workspace:^1.0.0".to_string())]);
+        importer.dependencies = Some(HashMap::from([(
+            "foo".to_string(),
+            "link:../foo".to_string(),
+        )]));
+        let mut wanted_lockfile = Lockfile::new("5.3".to_string());
+        wanted_lockfile
+            .importers
+            .insert("project".to_string(), importer);
+        let manifest = ProjectManifest {
+            manifest: BaseManifest {
+                dependencies: Some(HashMap::from([(
+                    "foo".to_string(),
+                    "workspace:^1.0.0".to_string(),
+                )])),
+                ..Default::default()
+            },
+            pnpm: None,
+            private: None,
+            resolutions: None,
+        };
+        let projects = [ProjectOptions {
+            id: "project",
+            manifest: &manifest,
+            root_dir: Path::new("/workspace/project"),
+        }];
+
+        let up_to_date = |version: &str| {
+            let workspace_packages = HashMap::from([(
+                "foo".to_string(),
+                HashMap::from([(
+                    version.to_string(),
+                    WorkspacePackage {
+                        dir: "/workspace/foo".to_string(),
+                        manifest: BaseManifest {
+                            name: Some("foo".to_string()),
+                            version: Some(version.to_string()),
+                            ..Default::default()
+                        },
+                    },
+                )]),
+            )]);
+            all_projects_are_up_to_date(
+                &projects,
+                Options {
+                    link_workspace_packages: false,
+                    wanted_lockfile: &wanted_lockfile,
+                    workspace_packages: &workspace_packages,
+                },
+            )
+        };
+        assert!(up_to_date("1.2.0"));
+        assert!(!up_to_date("2.0.0"));
+    }
+
+    #[test]
+    fn local_tarballs() {
+        assert!(ref_is_local_tarball("file:foo.tgz"));
+        assert!(!ref_is_local_tarball("https://registry/foo.tgz"));
+    }
 }
@@ -1,3 +1,43 @@
-pub struct InstallOptions {}
+use resolvers::base::WorkspacePackages;
+use std::path::PathBuf;
+use types::{IncludedDependencies, NodeLinker, Registries};
+
+pub struct InstallOptions {
+    /// The directory of the project, where `pnpm-lock.yaml` is written
+    pub dir: PathBuf,
+    pub modules_dir: PathBuf,
+    pub virtual_store_dir: PathBuf,
+    /// The store the packages are imported from, recorded in `.modules.yaml`
+    pub store_dir: PathBuf,
+    /// The dependencies linked into the modules directory of the virtual store
+    pub hoist_pattern: Vec<String>,
+    /// The dependencies linked into the root modules directory
+    pub public_hoist_pattern: Vec<String>,
+    /// Whether hoist-pattern was set explicitly, instead of keeping the one of `.modules.yaml`
+    pub force_hoist_pattern: bool,
+    /// Whether public-hoist-pattern was set explicitly, instead of keeping the one of `.modules.yaml`
+    pub force_public_hoist_pattern: bool,
+    pub node_linker: NodeLinker,
+    /// The metadata of packages is cached in this directory
+    pub cache_dir: PathBuf,
+    pub registries: Registries,
+    /// The projects of the workspace the project is in, that `workspace:` dependencies link to
+    pub workspace_packages: Option<WorkspacePackages>,
+    /// The kinds of direct dependencies to install
+    pub include: IncludedDependencies,
+    /// Fail instead of updating a lockfile that doesn't satisfy the manifest
+    pub frozen_lockfile: bool,
+    /// Skip the resolution when the lockfile satisfies the manifest
+    pub prefer_frozen_lockfile: bool,
+    /// Only update the lockfile, without installing anything
+    pub lockfile_only: bool,
+    pub ignore_scripts: bool,
+    /// Keep the files of the packages after their build in the store,
+    /// so they are not built again on the next installs
+    pub side_effects_cache: bool,
+    pub offline: bool,
+    /// Ask before removing a modules directory that cannot be updated in place
+    pub confirm_modules_purge: bool,
+}
 
 pub fn extend_install_options(_options: InstallOptions) {}
@@ -0,0 +1,96 @@
+use anyhow::{bail, Context, Result};
+use cafs::{
+    get_file_path_by_mode_in_cafs, get_file_path_in_cafs, read_package_files_index,
+    write_package_files_index, FileType, PackageFilesIndex,
+};
+use client::Client;
+use resolvers::base::Resolution;
+use ssri::Integrity;
+use std::path::{Path, PathBuf};
+use types::BaseManifest;
+
+pub struct FetchedPackage {
+    pub integrity: String,
+    pub files_index: PackageFilesIndex,
+}
+
+/// Where the files index of the package is in the store,
+/// known once the integrity of its tarball is
+pub fn files_index_file(cafs_dir: &Path, resolution: &Resolution) -> Result<Option<PathBuf>> {
+    Ok(match resolution {
+        Resolution::TarballResolution {
+            integrity: Some(integrity),
+            ..
+        } => Some(get_file_path_in_cafs(
+            cafs_dir,
+            &integrity.parse::<Integrity>()?,
+            FileType::Index,
+        )),
+        _ => None,
+    })
+}
+
+/// Reads the files of a package from the store.
+/// Packages that are not in the store yet are downloaded into it.
+pub fn fetch_package(
+    client: &Client,
+    cafs_dir: &Path,
+    resolution: &Resolution,
+    lockfile_dir: &str,
+    offline: bool,
+) -> Result<FetchedPackage> {
+    let (tarball, integrity) = match resolution {
+        Resolution::TarballResolution {
+            tarball, integrity, ..
+        } => (tarball, integrity),
+        _ => bail!("FETCH_NOT_SUPPORTED: Only tarballs can be fetched into the store"),
+    };
+
+    if let (Some(integrity), Some(index_path)) =
+        (integrity, files_index_file(cafs_dir, resolution)?)
+    {
+        if index_path.exists() {
+            return Ok(FetchedPackage {
+                integrity: integrity.clone(),
+                files_index: read_package_files_index(index_path)?,
+            });
+        }
+    }
+
+    if offline && !tarball.starts_with("file:") {
+        bail!(
+            "NO_OFFLINE_TARBALL: A package is missing from the store but cannot download it in offline mode. The missing package may be downloaded from {}.",
+            tarball
+        );
+    }
+
+    let fetched = client.fetch_tarball(cafs_dir, resolution, lockfile_dir)?;
+    let files_index = PackageFilesIndex {
+        files: fetched.files_index,
+        side_effects: None,
+    };
+    write_package_files_index(
+        get_file_path_in_cafs(cafs_dir, &fetched.integrity, FileType::Index),
+        &files_index,
+    )?;
+
+    Ok(FetchedPackage {
+        integrity: fetched.integrity.to_string(),
+        files_index,
+    })
+}
+
+/// Reads the `package.json` of a package that is in the store
+pub fn read_manifest_from_store(
+    cafs_dir: &Path,
+    files_index: &PackageFilesIndex,
+) -> Result<BaseManifest> {
+    let info = match files_index.files.get("package.json") {
+        Some(info) => info,
+        None => bail!("BAD_PACKAGE_JSON: The package has no package.json"),
+    };
+    let path = get_file_path_by_mode_in_cafs(cafs_dir, &info.integrity.parse()?, info.mode);
+    let content =
+        std::fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+    Ok(serde_json::from_slice(&content)?)
+}
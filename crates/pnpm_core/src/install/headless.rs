@@ -0,0 +1,584 @@
+use super::fetch_package::{fetch_package, files_index_file};
+use super::link::{
+    hoisted_locations, link_direct_dependencies, link_virtual_store, HoistedLocation,
+    ImporterToLink, VirtualStorePackage,
+};
+use super::resolve_dependencies::registry_tarball_url;
+use anyhow::{anyhow, bail, Result};
+use calc_dep_state::{calc_dep_state, DepStateNode, DepsGraph, DepsStateCache};
+use client::Client;
+use dependency_path::ref_to_relative;
+use hoist::{hoist, HoistOptions};
+use lifecycle::{run_lifecycle_hook, RunLifecycleHookOptions, DEPENDENCY_BUILD_SCRIPTS};
+use link_bins::{link_bins, link_bins_of_packages};
+use lockfile_utils::name_ver_from_pkg_snapshot::name_ver_from_pkg_snapshot;
+use lockfile_utils::read::{read_current_lockfile, ReadLockfileOpts};
+use lockfile_utils::types::{Lockfile, LockfileResolution, PackageSnapshot};
+use lockfile_utils::write::write_current_lockfile;
+use modules_cleaner::{prune, PruneOptions};
+use modules_yaml::{write_modules, Modules};
+use package_is_installable::{current_node_version, node_platform, platform_matches};
+use pick_registry_for_package::pick_registry_for_package;
+use rayon::prelude::*;
+use read_project_manifest::read_project_manifest_only;
+use resolvers::base::Resolution;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use store_connection_manager::StoreController;
+use store_controller_types::{ImportPackageOptions, ImportPackageResult, UploadOptions};
+use types::{HoistedDependencies, IncludedDependencies, NodeLinker, Registries};
+
+pub struct HeadlessOptions<'a> {
+    pub lockfile_dir: &'a Path,
+    pub modules_dir: &'a Path,
+    pub virtual_store_dir: &'a Path,
+    pub store_dir: &'a Path,
+    /// The state of the previous install, from `.modules.yaml`
+    pub modules: Option<&'a Modules>,
+    pub hoist_pattern: &'a [String],
+    pub public_hoist_pattern: &'a [String],
+    pub node_linker: NodeLinker,
+    pub registries: &'a Registries,
+    pub include: IncludedDependencies,
+    pub ignore_scripts: bool,
+    pub side_effects_cache: bool,
+    pub offline: bool,
+}
+
+struct PackageToInstall {
+    resolution: Resolution,
+    optional: bool,
+    requires_build: bool,
+}
+
+fn is_installable(snapshot: &PackageSnapshot) -> bool {
+    let (os, cpu) = node_platform();
+    platform_matches(snapshot.os.as_deref(), os) && platform_matches(snapshot.cpu.as_deref(), cpu)
+}
+
+fn to_resolution(
+    dep_path: &str,
+    snapshot: &PackageSnapshot,
+    registries: &Registries,
+) -> Result<Resolution> {
+    Ok(match &snapshot.resolution {
+        LockfileResolution::RegistryResolution { integrity } => {
+            let name_ver = name_ver_from_pkg_snapshot(dep_path, snapshot)?;
+            let registry = pick_registry_for_package(registries, &name_ver.name, None);
+            Resolution::TarballResolution {
+                tarball: registry_tarball_url(registry, &name_ver.name, &name_ver.version),
+                integrity: Some(integrity.clone()),
+                registry: Some(registry.to_string()),
+            }
+        }
+        LockfileResolution::TarballResolution {
+            integrity,
+            registry,
+            tarball,
+        } => Resolution::TarballResolution {
+            tarball: tarball.clone(),
+            integrity: integrity.clone(),
+            registry: registry.clone(),
+        },
+        _ => bail!(
+            "RESOLUTION_NOT_SUPPORTED: {} cannot be installed from the lockfile",
+            dep_path
+        ),
+    })
+}
+
+fn children(
+    snapshot: &PackageSnapshot,
+    include_optional: bool,
+) -> impl Iterator<Item = (&String, &String)> {
+    let optional_dependencies = snapshot
+        .optional_dependencies
+        .iter()
+        .filter(move |_| include_optional);
+    snapshot
+        .dependencies
+        .iter()
+        .chain(optional_dependencies)
+        .flatten()
+}
+
+/// Installs the packages of the lockfile, without resolving anything.
+/// Only the packages that changed since the lockfile of the previous install are imported.
+/// The packages are imported into the virtual store and linked together with symlinks,
+/// or with `node-linker=hoisted`, into a `node_modules` tree without symlinks, like npm does.
+pub fn headless(
+    client: &Client,
+    store_controller: &StoreController,
+    lockfile: &Lockfile,
+    opts: &HeadlessOptions,
+) -> Result<()> {
+    let no_packages = HashMap::new();
+    let packages = lockfile.packages.as_ref().unwrap_or(&no_packages);
+    let include_optional = opts.include.optional_dependencies;
+    // other projects of the workspace have their modules directory at the same place as the root
+    let modules_dir_name = opts
+        .modules_dir
+        .strip_prefix(opts.lockfile_dir)
+        .unwrap_or_else(|_| Path::new("node_modules"));
+    let mut importer_ids = lockfile.importers.keys().collect::<Vec<_>>();
+    importer_ids.sort();
+    let importers = importer_ids
+        .into_iter()
+        .map(|importer_id| {
+            let importer = &lockfile.importers[importer_id];
+            let dir = match importer_id.as_str() {
+                "." => opts.lockfile_dir.to_path_buf(),
+                _ => opts.lockfile_dir.join(importer_id),
+            };
+            let direct_deps = [
+                (&importer.dependencies, opts.include.dependencies),
+                (&importer.dev_dependencies, opts.include.dev_dependencies),
+                (&importer.optional_dependencies, include_optional),
+            ]
+            .into_iter()
+            .filter(|(_, included)| *included)
+            .flat_map(|(deps, _)| deps.iter().flatten())
+            .collect::<Vec<_>>();
+            ImporterToLink {
+                id: importer_id,
+                modules_dir: match importer_id.as_str() {
+                    "." => opts.modules_dir.to_path_buf(),
+                    _ => dir.join(modules_dir_name),
+                },
+                dir,
+                direct_deps,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // the packages are sorted, so the build scripts run in the same order on every install
+    let mut to_install = BTreeMap::new();
+    let mut virtual_store = BTreeMap::new();
+    let mut skipped = BTreeSet::new();
+    let mut stack = importers
+        .iter()
+        .flat_map(|importer| &importer.direct_deps)
+        .filter_map(|(alias, reference)| ref_to_relative(reference, alias))
+        .collect::<Vec<_>>();
+    while let Some(dep_path) = stack.pop() {
+        if to_install.contains_key(&dep_path) {
+            continue;
+        }
+        let snapshot = packages.get(&dep_path).ok_or_else(|| {
+            anyhow!(
+                "LOCKFILE_MISSING_DEPENDENCY: Broken lockfile: no entry for '{}' in {}",
+                dep_path,
+                constants::WANTED_LOCKFILE
+            )
+        })?;
+        let optional = snapshot.optional == Some(true);
+        if optional && !is_installable(snapshot) {
+            skipped.insert(dep_path);
+            continue;
+        }
+
+        let name = name_ver_from_pkg_snapshot(&dep_path, snapshot)?.name;
+        let children = children(snapshot, include_optional)
+            .map(|(alias, reference)| (alias.clone(), reference.clone()))
+            .collect::<Vec<_>>();
+        stack.extend(
+            children
+                .iter()
+                .filter_map(|(alias, reference)| ref_to_relative(reference, alias)),
+        );
+        virtual_store.insert(
+            dep_path.clone(),
+            VirtualStorePackage::new(opts.virtual_store_dir, &dep_path, &name, children),
+        );
+        to_install.insert(
+            dep_path.clone(),
+            PackageToInstall {
+                resolution: to_resolution(&dep_path, snapshot, opts.registries)?,
+                optional,
+                requires_build: snapshot.requires_build == Some(true),
+            },
+        );
+    }
+
+    // the built files of a package are kept in the store under a key of its dependencies,
+    // the platform and the version of node, that all have to match to import them
+    let side_effects_cache_keys = match opts.side_effects_cache.then(current_node_version).flatten()
+    {
+        Some(node_version) => {
+            let deps_graph = virtual_store
+                .iter()
+                .map(|(dep_path, pkg)| {
+                    let children = pkg
+                        .children
+                        .iter()
+                        .filter_map(|(alias, reference)| {
+                            Some((alias.clone(), ref_to_relative(reference, alias)?))
+                        })
+                        .collect();
+                    let node = DepStateNode {
+                        dep_path: dep_path.clone(),
+                        children,
+                    };
+                    (dep_path.clone(), node)
+                })
+                .collect::<DepsGraph>();
+            let mut cache = DepsStateCache::new();
+            to_install
+                .iter()
+                .filter(|(_, pkg)| pkg.requires_build)
+                .map(|(dep_path, _)| {
+                    let key = calc_dep_state(dep_path, &deps_graph, &mut cache, &node_version);
+                    (dep_path.clone(), key)
+                })
+                .collect()
+        }
+        None => HashMap::new(),
+    };
+
+    // what was installed before and is not wanted anymore is removed first
+    let previous_lockfile = read_current_lockfile(
+        opts.virtual_store_dir,
+        ReadLockfileOpts {
+            wanted_version: None,
+            ignore_incompatible: true,
+        },
+    )?
+    .map(Lockfile::from);
+    if let Some(previous_lockfile) = &previous_lockfile {
+        prune(
+            previous_lockfile,
+            &PruneOptions {
+                modules_dir: opts.modules_dir,
+                virtual_store_dir: opts.virtual_store_dir,
+                wanted_direct_deps: &importers
+                    .iter()
+                    .find(|importer| importer.modules_dir == opts.modules_dir)
+                    .into_iter()
+                    .flat_map(|importer| &importer.direct_deps)
+                    .map(|(alias, _)| alias.to_string())
+                    .collect(),
+                wanted_packages: &to_install.keys().cloned().collect(),
+                hoisted_dependencies: opts
+                    .modules
+                    .map(|modules| &modules.hoisted_dependencies)
+                    .unwrap_or(&HoistedDependencies::new()),
+                private_hoisted_modules_dir: &opts.virtual_store_dir.join("node_modules"),
+                public_hoisted_modules_dir: opts.modules_dir,
+            },
+        )?;
+    }
+
+    let current_lockfile = filter_lockfile(lockfile, &to_install, opts.include);
+    let mut locations = match opts.node_linker {
+        NodeLinker::Isolated => virtual_store
+            .iter()
+            .map(|(dep_path, pkg)| HoistedLocation {
+                dir: pkg.dir.clone(),
+                dep_path: dep_path.clone(),
+                force: false,
+            })
+            .collect(),
+        NodeLinker::Hoisted => {
+            hoisted_locations(&current_lockfile, previous_lockfile.as_ref(), &importers)?
+        }
+    };
+    // the packages whose resolution changed since the previous install are imported again
+    let previous_packages = previous_lockfile
+        .as_ref()
+        .and_then(|previous_lockfile| previous_lockfile.packages.as_ref());
+    for location in &mut locations {
+        let previous = previous_packages.and_then(|packages| packages.get(&location.dep_path));
+        if previous
+            .is_some_and(|previous| previous.resolution != packages[&location.dep_path].resolution)
+        {
+            location.force = true;
+        }
+    }
+    let mut locations_by_dep_path = BTreeMap::<&str, Vec<&Path>>::new();
+    for location in &locations {
+        locations_by_dep_path
+            .entry(&location.dep_path)
+            .or_default()
+            .push(&location.dir);
+    }
+
+    // only what is not installed yet is read from the store, which includes
+    // the packages nested in a directory that is replaced
+    let mut replaced = HashSet::new();
+    let to_import = locations
+        .iter()
+        .filter(|location| {
+            let import = location.force
+                || !location.dir.exists()
+                || location.dir.ancestors().any(|dir| replaced.contains(dir));
+            if location.force {
+                replaced.insert(location.dir.as_path());
+            }
+            import
+        })
+        .collect::<Vec<_>>();
+
+    let lockfile_dir = opts.lockfile_dir.to_string_lossy().to_string();
+    let fetched = to_import
+        .iter()
+        .map(|location| location.dep_path.as_str())
+        .collect::<HashSet<_>>()
+        .into_par_iter()
+        .map(|dep_path| {
+            let fetched = fetch_package(
+                client,
+                store_controller.cafs_dir(),
+                &to_install[dep_path].resolution,
+                &lockfile_dir,
+                opts.offline,
+            )?;
+            Ok((dep_path, fetched.files_index))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    // a package is imported after the one it is nested in, that replaces its whole directory
+    let mut depths = BTreeMap::<usize, Vec<&HoistedLocation>>::new();
+    for location in to_import {
+        depths
+            .entry(location.dir.components().count())
+            .or_default()
+            .push(location);
+    }
+    let mut imported = HashMap::new();
+    for locations in depths.values() {
+        imported.par_extend(
+            locations
+                .par_iter()
+                .map(|location| {
+                    let result = store_controller.import_package(
+                        &location.dir,
+                        ImportPackageOptions {
+                            files_index: &fetched[location.dep_path.as_str()],
+                            side_effects_cache_key: side_effects_cache_keys
+                                .get(&location.dep_path)
+                                .map(String::as_str),
+                            force: location.force,
+                            method: Default::default(),
+                        },
+                    )?;
+                    Ok((location.dir.clone(), result))
+                })
+                .collect::<Result<Vec<_>>>()?,
+        );
+    }
+
+    let hoisted_dependencies = match opts.node_linker {
+        NodeLinker::Isolated => {
+            link_virtual_store(&virtual_store, opts.lockfile_dir)?;
+            // hoisted before the executables of the root are linked, which include the public ones
+            let hoisted_dependencies = hoist(&HoistOptions {
+                lockfile: &current_lockfile,
+                private_hoist_pattern: opts.hoist_pattern,
+                private_hoisted_modules_dir: &opts.virtual_store_dir.join("node_modules"),
+                public_hoist_pattern: opts.public_hoist_pattern,
+                public_hoisted_modules_dir: opts.modules_dir,
+                virtual_store_dir: opts.virtual_store_dir,
+            })?;
+            for importer in &importers {
+                link_direct_dependencies(importer, &virtual_store)?;
+            }
+            hoisted_dependencies
+        }
+        NodeLinker::Hoisted => {
+            for location in &locations {
+                let modules_dir = location.dir.join("node_modules");
+                if modules_dir.exists() {
+                    link_bins(&modules_dir, modules_dir.join(".bin"))?;
+                }
+            }
+            // only the linked dependencies are symlinked
+            for importer in &importers {
+                link_direct_dependencies(importer, &BTreeMap::new())?;
+            }
+            HoistedDependencies::new()
+        }
+    };
+
+    if !opts.ignore_scripts {
+        let mut built = HashSet::new();
+        let build = BuildContext {
+            store_controller,
+            side_effects_cache_keys: &side_effects_cache_keys,
+            to_install: &to_install,
+            virtual_store: &virtual_store,
+            locations: &locations_by_dep_path,
+            imported: &imported,
+            link_children_bins: opts.node_linker == NodeLinker::Isolated,
+        };
+        for dep_path in to_install.keys() {
+            build_dependency(dep_path, &build, &mut built)?;
+        }
+    }
+
+    // the builds that were skipped stay pending until they run
+    let mut pending_builds = opts
+        .modules
+        .into_iter()
+        .flat_map(|modules| &modules.pending_builds)
+        .filter(|dep_path| to_install.contains_key(*dep_path))
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    if opts.ignore_scripts {
+        pending_builds.extend(
+            to_install
+                .iter()
+                .filter(|(dep_path, pkg)| {
+                    pkg.requires_build
+                        && locations_by_dep_path[dep_path.as_str()]
+                            .iter()
+                            .any(|dir| needs_build(&imported, dir))
+                })
+                .map(|(dep_path, _)| dep_path.clone()),
+        );
+    }
+
+    write_current_lockfile(opts.virtual_store_dir, &current_lockfile)?;
+    write_modules(
+        opts.modules_dir,
+        &Modules {
+            hoist_pattern: (!opts.hoist_pattern.is_empty()).then(|| opts.hoist_pattern.to_vec()),
+            hoisted_dependencies,
+            included: opts.include,
+            layout_version: constants::LAYOUT_VERSION,
+            node_linker: opts.node_linker,
+            pending_builds: pending_builds.into_iter().collect(),
+            public_hoist_pattern: (!opts.public_hoist_pattern.is_empty())
+                .then(|| opts.public_hoist_pattern.to_vec()),
+            skipped: skipped.into_iter().collect(),
+            store_dir: opts.store_dir.to_path_buf(),
+            virtual_store_dir: opts.virtual_store_dir.to_path_buf(),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// The lockfile of what is installed, without the packages and the kinds of dependencies
+/// that were left out
+fn filter_lockfile(
+    lockfile: &Lockfile,
+    to_install: &BTreeMap<String, PackageToInstall>,
+    include: IncludedDependencies,
+) -> Lockfile {
+    let mut filtered = lockfile.clone();
+    if let Some(packages) = &mut filtered.packages {
+        packages.retain(|dep_path, _| to_install.contains_key(dep_path));
+    }
+    for importer in filtered.importers.values_mut() {
+        for (deps, included) in [
+            (&mut importer.dependencies, include.dependencies),
+            (&mut importer.dev_dependencies, include.dev_dependencies),
+            (
+                &mut importer.optional_dependencies,
+                include.optional_dependencies,
+            ),
+        ] {
+            if !included {
+                *deps = None;
+            }
+        }
+    }
+    filtered
+}
+
+/// Whether the package was just imported without the files of a previous build
+fn needs_build(imported: &HashMap<PathBuf, ImportPackageResult>, dir: &Path) -> bool {
+    imported
+        .get(dir)
+        .is_some_and(|result| result.imported && !result.is_built)
+}
+
+struct BuildContext<'a> {
+    store_controller: &'a StoreController,
+    /// By dependency path, for the packages that require a build
+    side_effects_cache_keys: &'a HashMap<String, String>,
+    to_install: &'a BTreeMap<String, PackageToInstall>,
+    virtual_store: &'a BTreeMap<String, VirtualStorePackage>,
+    /// The directories every package was imported to
+    locations: &'a BTreeMap<&'a str, Vec<&'a Path>>,
+    imported: &'a HashMap<PathBuf, ImportPackageResult>,
+    /// The executables of the dependencies are already linked in a hoisted tree
+    link_children_bins: bool,
+}
+
+/// Runs the build scripts of a package that was just imported,
+/// after the ones of its dependencies
+fn build_dependency(dep_path: &str, ctx: &BuildContext, built: &mut HashSet<String>) -> Result<()> {
+    if !built.insert(dep_path.to_string()) {
+        return Ok(());
+    }
+    let pkg = &ctx.to_install[dep_path];
+    let children = ctx.virtual_store[dep_path]
+        .children
+        .iter()
+        .filter_map(|(alias, reference)| ref_to_relative(reference, alias))
+        .filter(|child| ctx.to_install.contains_key(child))
+        .collect::<Vec<_>>();
+    for child in &children {
+        build_dependency(child, ctx, built)?;
+    }
+
+    if !pkg.requires_build {
+        return Ok(());
+    }
+    let dirs = ctx.locations.get(dep_path).into_iter().flatten();
+    for dir in dirs.filter(|dir| needs_build(ctx.imported, dir)) {
+        if ctx.link_children_bins {
+            let children_manifests = children
+                .iter()
+                .filter_map(|child| {
+                    let dir = &ctx.virtual_store[child].dir;
+                    Some((
+                        dir.clone(),
+                        read_project_manifest_only(&dir.to_string_lossy()).ok()?,
+                    ))
+                })
+                .collect::<Vec<_>>();
+            link_bins_of_packages(&children_manifests, dir.join("node_modules").join(".bin"))?;
+        }
+
+        let manifest = read_project_manifest_only(&dir.to_string_lossy())?;
+        let hook_opts = RunLifecycleHookOptions {
+            pkg_root: dir,
+            extra_bin_paths: &[],
+            args: &[],
+        };
+        for stage in DEPENDENCY_BUILD_SCRIPTS {
+            if let Err(error) = run_lifecycle_hook(stage, &manifest, &hook_opts) {
+                // a failed build of an optional dependency doesn't fail the install
+                if pkg.optional {
+                    eprintln!("WARN: {}", error);
+                    return Ok(());
+                }
+                return Err(error);
+            }
+        }
+
+        // the next installs import the package as it is after its build
+        if let Some(key) = ctx.side_effects_cache_keys.get(dep_path) {
+            let index_file = files_index_file(ctx.store_controller.cafs_dir(), &pkg.resolution)?;
+            let uploaded = match index_file {
+                Some(index_file) => ctx.store_controller.upload(
+                    &dir.to_string_lossy(),
+                    UploadOptions {
+                        files_index_file: index_file.to_string_lossy().to_string(),
+                        engine: key.clone(),
+                    },
+                ),
+                None => Ok(()),
+            };
+            if let Err(error) = uploaded {
+                eprintln!(
+                    "WARN: Could not save the side effects of {} in the store: {}",
+                    dep_path, error
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
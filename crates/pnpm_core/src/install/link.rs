@@ -0,0 +1,245 @@
+use anyhow::Result;
+use dependency_path::{dep_path_to_filename, ref_to_relative};
+use link_bins::link_bins;
+use lockfile_utils::types::Lockfile;
+use rayon::prelude::*;
+use real_hoist::HoistedNode;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use symlink_dependency::symlink_dependency;
+
+/// A package imported into the virtual store, `node_modules/.pnpm` by default
+pub struct VirtualStorePackage {
+    /// `<virtual-store-dir>/<escaped dependency path>/node_modules/<name>`
+    pub dir: PathBuf,
+    /// Where the dependencies of the package are linked, next to it
+    pub modules_dir: PathBuf,
+    /// The dependencies to link, as aliases with their references in the lockfile
+    pub children: Vec<(String, String)>,
+}
+
+impl VirtualStorePackage {
+    pub fn new(
+        virtual_store_dir: &Path,
+        dep_path: &str,
+        name: &str,
+        children: Vec<(String, String)>,
+    ) -> Self {
+        let modules_dir = virtual_store_dir
+            .join(dep_path_to_filename(dep_path))
+            .join("node_modules");
+        Self {
+            dir: modules_dir.join(name),
+            modules_dir,
+            children,
+        }
+    }
+}
+
+/// A project of the lockfile whose direct dependencies are linked into its modules directory
+pub struct ImporterToLink<'l> {
+    /// The path of the project relative to the lockfile directory, `.` for the root project
+    pub id: &'l str,
+    /// The directory of the project, that the `link:` references are relative to
+    pub dir: PathBuf,
+    pub modules_dir: PathBuf,
+    pub direct_deps: Vec<(&'l String, &'l String)>,
+}
+
+/// The location of a dependency, or `None` when it is not installed
+fn dependency_dir(
+    packages: &BTreeMap<String, VirtualStorePackage>,
+    base_dir: &Path,
+    alias: &str,
+    reference: &str,
+) -> Option<PathBuf> {
+    match reference.strip_prefix("link:") {
+        Some(path) => Some(base_dir.join(path)),
+        None => {
+            let dep_path = ref_to_relative(reference, alias)?;
+            packages.get(&dep_path).map(|pkg| pkg.dir.clone())
+        }
+    }
+}
+
+/// Links the dependencies of every package of the virtual store next to it,
+/// so a package finds them in the `node_modules` it is in
+pub fn link_virtual_store(
+    packages: &BTreeMap<String, VirtualStorePackage>,
+    lockfile_dir: &Path,
+) -> Result<()> {
+    packages.par_iter().try_for_each(|(_, pkg)| {
+        for (alias, reference) in &pkg.children {
+            if let Some(target) = dependency_dir(packages, lockfile_dir, alias, reference) {
+                symlink_dependency(target, &pkg.modules_dir, alias)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Links the direct dependencies of a project from the virtual store into its modules directory,
+/// under their aliases, with their executables
+pub fn link_direct_dependencies(
+    importer: &ImporterToLink,
+    packages: &BTreeMap<String, VirtualStorePackage>,
+) -> Result<()> {
+    for (alias, reference) in &importer.direct_deps {
+        if let Some(target) = dependency_dir(packages, &importer.dir, alias, reference) {
+            symlink_dependency(target, &importer.modules_dir, alias)?;
+        }
+    }
+    link_bins(&importer.modules_dir, importer.modules_dir.join(".bin"))
+}
+
+/// A directory of a `node_modules` tree made by `node-linker=hoisted`
+pub struct HoistedLocation {
+    pub dir: PathBuf,
+    pub dep_path: String,
+    /// Whatever is in the directory is replaced,
+    /// as it was not the same package after the previous install
+    pub force: bool,
+}
+
+/// The directories of the packages in the trees of the importers, parents before their children
+fn hoisted_tree(lockfile: &Lockfile, importers: &[ImporterToLink]) -> Vec<(PathBuf, String)> {
+    fn add(
+        nodes: &BTreeMap<String, HoistedNode>,
+        modules_dir: &Path,
+        locations: &mut Vec<(PathBuf, String)>,
+    ) {
+        for (alias, node) in nodes {
+            let dir = modules_dir.join(alias);
+            locations.push((dir.clone(), node.dep_path.clone()));
+            add(&node.dependencies, &dir.join("node_modules"), locations);
+        }
+    }
+    let mut locations = vec![];
+    for importer in importers {
+        let tree = real_hoist::hoist(lockfile, importer.id);
+        add(&tree, &importer.modules_dir, &mut locations);
+    }
+    locations
+}
+
+/// Where the packages go with `node-linker=hoisted`. The directories that had another package,
+/// or that are not in the new trees, are removed, as the tree of the previous install
+/// is computed from the lockfile of what was installed.
+pub fn hoisted_locations(
+    lockfile: &Lockfile,
+    previous_lockfile: Option<&Lockfile>,
+    importers: &[ImporterToLink],
+) -> Result<Vec<HoistedLocation>> {
+    let previous = previous_lockfile
+        .map(|previous_lockfile| hoisted_tree(previous_lockfile, importers))
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let locations = hoisted_tree(lockfile, importers)
+        .into_iter()
+        .map(|(dir, dep_path)| {
+            let is_dir = match fs::symlink_metadata(&dir) {
+                Ok(metadata) if metadata.is_dir() => true,
+                // a link left by the isolated layout
+                Ok(_) => {
+                    fs::remove_file(&dir)?;
+                    false
+                }
+                Err(error) if error.kind() == ErrorKind::NotFound => false,
+                Err(error) => return Err(error.into()),
+            };
+            Ok(HoistedLocation {
+                force: !is_dir || previous.get(&dir) != Some(&dep_path),
+                dir,
+                dep_path,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let wanted = locations
+        .iter()
+        .map(|location| &location.dir)
+        .collect::<HashSet<_>>();
+    for dir in previous.keys().filter(|dir| !wanted.contains(dir)) {
+        match fs::symlink_metadata(dir) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(dir)?,
+            Ok(_) => fs::remove_file(dir)?,
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn link_scoped_and_aliased_dependencies() {
+        let root = tempdir().unwrap();
+        let virtual_store_dir = root.path().join(".store");
+        let child = |alias: &str, reference: &str| (alias.to_string(), reference.to_string());
+        let packages = BTreeMap::from([
+            (
+                "/@scope/foo/1.0.0".to_string(),
+                VirtualStorePackage::new(
+                    &virtual_store_dir,
+                    "/@scope/foo/1.0.0",
+                    "@scope/foo",
+                    vec![child("qar", "/bar/1.0.0")],
+                ),
+            ),
+            (
+                "/bar/1.0.0".to_string(),
+                VirtualStorePackage::new(&virtual_store_dir, "/bar/1.0.0", "bar", vec![]),
+            ),
+        ]);
+        for pkg in packages.values() {
+            fs::create_dir_all(&pkg.dir).unwrap();
+        }
+        link_virtual_store(&packages, root.path()).unwrap();
+
+        let foo_dir = virtual_store_dir.join("@scope+foo@1.0.0/node_modules/@scope/foo");
+        assert_eq!(packages["/@scope/foo/1.0.0"].dir, foo_dir);
+        assert_eq!(
+            fs::read_link(virtual_store_dir.join("@scope+foo@1.0.0/node_modules/qar")).unwrap(),
+            Path::new("../../bar@1.0.0/node_modules/bar")
+        );
+
+        let (foo, bar, local) = (
+            "@scope/foo".to_string(),
+            "bar".to_string(),
+            "local".to_string(),
+        );
+        let (foo_ref, bar_ref, local_ref) = (
+            "1.0.0".to_string(),
+            "/bar/1.0.0".to_string(),
+            "link:../local".to_string(),
+        );
+        let importer = ImporterToLink {
+            id: ".",
+            dir: root.path().join("project"),
+            modules_dir: root.path().join("project/deps"),
+            direct_deps: vec![(&foo, &foo_ref), (&bar, &bar_ref), (&local, &local_ref)],
+        };
+        link_direct_dependencies(&importer, &packages).unwrap();
+        assert_eq!(
+            fs::read_link(importer.modules_dir.join("@scope/foo")).unwrap(),
+            Path::new("../../../.store/@scope+foo@1.0.0/node_modules/@scope/foo")
+        );
+        assert_eq!(
+            fs::read_link(importer.modules_dir.join("bar")).unwrap(),
+            Path::new("../../.store/bar@1.0.0/node_modules/bar")
+        );
+        assert_eq!(
+            fs::read_link(importer.modules_dir.join("local")).unwrap(),
+            Path::new("../../local")
+        );
+    }
+}
@@ -1,2 +1,221 @@
+pub mod add_dependencies;
 pub mod all_projects_upto_date;
 pub mod extend_install_options;
+pub mod fetch_package;
+pub mod headless;
+pub mod link;
+pub mod remove_dependencies;
+pub mod resolve_dependencies;
+pub mod resolve_peers;
+pub mod update_dependencies;
+pub mod validate_modules;
+
+use all_projects_upto_date::{all_projects_are_up_to_date, Options, ProjectOptions};
+use anyhow::{bail, Result};
+use client::Client;
+use constants::{LOCKFILE_VERSION, WANTED_LOCKFILE};
+use extend_install_options::InstallOptions;
+use headless::{headless, HeadlessOptions};
+use lifecycle::{run_lifecycle_hook, RunLifecycleHookOptions};
+use lockfile_utils::read::{read_wanted_lockfile, ReadLockfileOpts};
+use lockfile_utils::types::Lockfile;
+use lockfile_utils::write::write_wanted_lockfile;
+use resolve_dependencies::{
+    resolve_dependencies, Importer, ResolveDependenciesOptions, UpdateOptions,
+};
+use resolvers::npm::ResolverFactoryOptions;
+use std::collections::HashMap;
+use std::path::Path;
+use store_connection_manager::StoreController;
+use types::{BaseManifest, ProjectManifest};
+use validate_modules::validate_modules;
+
+/// Scripts of the project that run after its dependencies are installed
+const POST_INSTALL_SCRIPTS: [&str; 3] = ["install", "postinstall", "prepare"];
+
+/// Installs the dependencies of a project and writes its lockfile.
+/// The lockfile is only resolved again when it doesn't satisfy the manifest
+/// or when `prefer_frozen_lockfile` is off. Otherwise it is installed as is,
+/// without any request for the metadata of the packages.
+pub fn install(
+    client: &Client,
+    store_controller: &StoreController,
+    manifest: &BaseManifest,
+    opts: &InstallOptions,
+) -> Result<Lockfile> {
+    let wanted_lockfile = read_lockfile(&opts.dir)?;
+    install_project(
+        client,
+        store_controller,
+        manifest,
+        opts,
+        wanted_lockfile,
+        None,
+    )
+}
+
+/// The lockfile of the project, unless it was written by an incompatible version
+fn read_lockfile(dir: &Path) -> Result<Option<Lockfile>> {
+    Ok(read_wanted_lockfile(
+        dir,
+        ReadLockfileOpts {
+            wanted_version: Some(LOCKFILE_VERSION as i32),
+            ignore_incompatible: true,
+        },
+    )?
+    .map(Lockfile::from))
+}
+
+/// Like `install`, but from the wanted lockfile that is passed in,
+/// which is always resolved again when there are packages to update
+fn install_project(
+    client: &Client,
+    store_controller: &StoreController,
+    manifest: &BaseManifest,
+    opts: &InstallOptions,
+    wanted_lockfile: Option<Lockfile>,
+    update: Option<UpdateOptions>,
+) -> Result<Lockfile> {
+    let pnpm_manifest = manifest.pnpm()?;
+    let project_manifest = ProjectManifest {
+        manifest: manifest.clone(),
+        pnpm: pnpm_manifest.clone(),
+        private: None,
+        resolutions: None,
+    };
+    let no_workspace_packages = HashMap::new();
+    let workspace_packages = opts
+        .workspace_packages
+        .as_ref()
+        .unwrap_or(&no_workspace_packages);
+    let up_to_date = wanted_lockfile.as_ref().is_some_and(|wanted_lockfile| {
+        all_projects_are_up_to_date(
+            &[ProjectOptions {
+                id: ".",
+                manifest: &project_manifest,
+                root_dir: &opts.dir,
+            }],
+            Options {
+                link_workspace_packages: false,
+                wanted_lockfile,
+                workspace_packages,
+            },
+        )
+    });
+
+    if opts.frozen_lockfile && update.is_none() {
+        if wanted_lockfile.is_none() {
+            bail!(
+                "NO_LOCKFILE: Cannot install with \"frozen-lockfile\" because {} is absent",
+                WANTED_LOCKFILE
+            );
+        }
+        if !up_to_date {
+            bail!(
+                "OUTDATED_LOCKFILE: Cannot install with \"frozen-lockfile\" because {} is not up-to-date with package.json",
+                WANTED_LOCKFILE
+            );
+        }
+    }
+
+    let lockfile = match wanted_lockfile {
+        Some(lockfile)
+            if update.is_none()
+                && up_to_date
+                && (opts.frozen_lockfile || opts.prefer_frozen_lockfile) =>
+        {
+            lockfile
+        }
+        wanted_lockfile => {
+            let resolver = client.create_resolver(ResolverFactoryOptions {
+                cache_dir: opts.cache_dir.clone(),
+                offline: opts.offline,
+                prefer_offline: false,
+            });
+            let (lockfile, peer_dependency_issues) = resolve_dependencies(
+                &resolver,
+                client,
+                &[Importer { id: ".", manifest }],
+                &ResolveDependenciesOptions {
+                    lockfile_dir: &opts.dir,
+                    cafs_dir: store_controller.cafs_dir(),
+                    registries: &opts.registries,
+                    workspace_packages: opts.workspace_packages.as_ref(),
+                    preferred_lockfile: wanted_lockfile.as_ref(),
+                    update,
+                    peer_dependency_rules: pnpm_manifest
+                        .as_ref()
+                        .and_then(|pnpm| pnpm.peer_dependency_rules.as_ref()),
+                },
+            )?;
+            if !peer_dependency_issues.is_empty() {
+                eprint!(
+                    "WARN: Issues with peer dependencies found\n{}",
+                    peer_dependency_issues
+                );
+            }
+            write_wanted_lockfile(&opts.dir, &lockfile)?;
+            lockfile
+        }
+    };
+
+    if opts.lockfile_only {
+        return Ok(lockfile);
+    }
+
+    let modules = validate_modules(opts)?;
+    // the patterns of the previous install are kept, unless they were set explicitly
+    let hoist_pattern = match modules
+        .as_ref()
+        .and_then(|modules| modules.hoist_pattern.as_ref())
+    {
+        Some(hoist_pattern) if !opts.force_hoist_pattern => hoist_pattern,
+        _ => &opts.hoist_pattern,
+    };
+    let public_hoist_pattern = match modules
+        .as_ref()
+        .and_then(|modules| modules.public_hoist_pattern.as_ref())
+    {
+        Some(public_hoist_pattern) if !opts.force_public_hoist_pattern => public_hoist_pattern,
+        _ => &opts.public_hoist_pattern,
+    };
+
+    let extra_bin_paths = [opts.modules_dir.join(".bin")];
+    let hook_opts = RunLifecycleHookOptions {
+        pkg_root: &opts.dir,
+        extra_bin_paths: &extra_bin_paths,
+        args: &[],
+    };
+    if !opts.ignore_scripts {
+        run_lifecycle_hook("preinstall", manifest, &hook_opts)?;
+    }
+
+    headless(
+        client,
+        store_controller,
+        &lockfile,
+        &HeadlessOptions {
+            lockfile_dir: &opts.dir,
+            modules_dir: &opts.modules_dir,
+            virtual_store_dir: &opts.virtual_store_dir,
+            store_dir: &opts.store_dir,
+            modules: modules.as_ref(),
+            hoist_pattern,
+            public_hoist_pattern,
+            node_linker: opts.node_linker,
+            registries: &opts.registries,
+            include: opts.include,
+            ignore_scripts: opts.ignore_scripts,
+            side_effects_cache: opts.side_effects_cache,
+            offline: opts.offline,
+        },
+    )?;
+
+    if !opts.ignore_scripts {
+        for stage in POST_INSTALL_SCRIPTS {
+            run_lifecycle_hook(stage, manifest, &hook_opts)?;
+        }
+    }
+
+    Ok(lockfile)
+}
@@ -0,0 +1,152 @@
+use super::extend_install_options::InstallOptions;
+use super::{install_project, read_lockfile};
+use anyhow::{bail, Result};
+use client::Client;
+use lockfile_utils::types::Lockfile;
+use lockfile_utils::write::write_wanted_lockfile;
+use prune_lockfile::prune_lockfile;
+use std::collections::BTreeSet;
+use store_connection_manager::StoreController;
+use types::{BaseManifest, DependencyField};
+
+/// The fields the dependencies are removed from.
+/// Without a target field, they are removed from every field, `peerDependencies` included.
+fn fields_to_remove_from(
+    target_dependency_field: Option<DependencyField>,
+) -> impl Iterator<Item = DependencyField> {
+    DependencyField::iterator().filter(move |field| match target_dependency_field {
+        Some(target) => *field == target,
+        None => true,
+    })
+}
+
+/// Removes the dependencies from the manifest.
+/// Fails when one of them is not in the fields it is removed from.
+pub fn remove_deps(
+    manifest: &BaseManifest,
+    names: &[&str],
+    target_dependency_field: Option<DependencyField>,
+) -> Result<BaseManifest> {
+    let available = fields_to_remove_from(target_dependency_field)
+        .filter_map(|field| manifest.dependencies_of(field))
+        .flat_map(|deps| deps.keys().map(String::as_str))
+        .collect::<BTreeSet<_>>();
+    let missing = names
+        .iter()
+        .filter(|name| !available.contains(*name))
+        .map(|name| format!("'{}'", name))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        bail!(
+            "CANNOT_REMOVE_MISSING_DEPS: Cannot remove {}: no such {} found. Available dependencies: {}",
+            missing.join(", "),
+            if missing.len() == 1 {
+                "dependency"
+            } else {
+                "dependencies"
+            },
+            available.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    let mut updated_manifest = manifest.clone();
+    for field in fields_to_remove_from(target_dependency_field) {
+        let deps = updated_manifest.dependencies_of_mut(field);
+        if let Some(field_deps) = deps {
+            field_deps.retain(|alias, _| !names.contains(&alias.as_str()));
+            if field_deps.is_empty() {
+                *deps = None;
+            }
+        }
+    }
+    if target_dependency_field.is_none() {
+        let peers = &mut updated_manifest.peer_dependencies;
+        if let Some(peer_deps) = peers {
+            peer_deps.retain(|alias, _| !names.contains(&alias.as_str()));
+            if peer_deps.is_empty() {
+                *peers = None;
+            }
+        }
+    }
+    Ok(updated_manifest)
+}
+
+/// Removes the dependencies from the manifest and from the lockfile.
+/// The packages that are not used anymore are removed from `node_modules`.
+/// Returns the updated manifest, which is not written by this function.
+pub fn remove_dependencies_from_package(
+    client: &Client,
+    store_controller: &StoreController,
+    manifest: &BaseManifest,
+    names: &[&str],
+    target_dependency_field: Option<DependencyField>,
+    install_opts: &InstallOptions,
+) -> Result<(BaseManifest, Lockfile)> {
+    let updated_manifest = remove_deps(manifest, names, target_dependency_field)?;
+
+    // the pruned lockfile still satisfies the manifest, so nothing is resolved again.
+    // It is only written once it is installed, so a failed install leaves the lockfile as it was.
+    let wanted_lockfile = read_lockfile(&install_opts.dir)?
+        .map(|wanted_lockfile| prune_lockfile(&wanted_lockfile, &updated_manifest, "."));
+    let lockfile = install_project(
+        client,
+        store_controller,
+        &updated_manifest,
+        install_opts,
+        wanted_lockfile,
+        None,
+    )?;
+    write_wanted_lockfile(&install_opts.dir, &lockfile)?;
+    Ok((updated_manifest, lockfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn manifest() -> BaseManifest {
+        let deps = |deps: &[&str]| {
+            Some(
+                deps.iter()
+                    .map(|alias| (alias.to_string(), "^1.0.0".to_string()))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        BaseManifest {
+            dependencies: deps(&["foo", "bar"]),
+            dev_dependencies: deps(&["baz"]),
+            peer_dependencies: deps(&["baz"]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn remove_from_every_field() {
+        let manifest = remove_deps(&manifest(), &["foo", "baz"], None).unwrap();
+
+        assert_eq!(
+            manifest.dependencies,
+            Some(HashMap::from([("bar".to_string(), "^1.0.0".to_string())]))
+        );
+        assert_eq!(manifest.dev_dependencies, None);
+        assert_eq!(manifest.peer_dependencies, None);
+    }
+
+    #[test]
+    fn remove_missing_dependency() {
+        assert_eq!(
+            remove_deps(&manifest(), &["qar"], None)
+                .unwrap_err()
+                .to_string(),
+            "CANNOT_REMOVE_MISSING_DEPS: Cannot remove 'qar': no such dependency found. Available dependencies: bar, baz, foo"
+        );
+        assert_eq!(
+            remove_deps(&manifest(), &["foo", "qar"], Some(DependencyField::DevDependencies))
+                .unwrap_err()
+                .to_string(),
+            "CANNOT_REMOVE_MISSING_DEPS: Cannot remove 'foo', 'qar': no such dependencies found. Available dependencies: baz"
+        );
+    }
+}
@@ -0,0 +1,823 @@
+use super::fetch_package::{fetch_package, read_manifest_from_store};
+use super::resolve_peers::{resolve_peers, PeerDependencyIssues};
+use anyhow::{bail, Result};
+use client::Client;
+use constants::LOCKFILE_VERSION;
+use dependency_path::ref_to_relative;
+use lockfile_utils::name_ver_from_pkg_snapshot::name_ver_from_pkg_snapshot;
+use lockfile_utils::types::{
+    Lockfile, LockfileResolution, PackageSnapshot, ProjectSnapshot, ResolvedDependencies,
+    SnapshotEngines,
+};
+use matcher::Matcher;
+use pick_registry_for_package::pick_registry_for_package;
+use prune_lockfile::prune_shared_lockfile;
+use resolvers::base::{
+    PreferredVersions, Resolution, ResolveOptions, ResolvedVia, Version, WantedDependency,
+    WorkspacePackages,
+};
+use resolvers::default::Resolver;
+use resolvers::npm::encode_registry;
+use semver_range::{parse_version, Range};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+use types::{BaseManifest, Dependencies, DependencyField, PeerDependencyRules, Registries};
+
+/// Which packages are resolved again instead of keeping their locked versions
+pub struct UpdateOptions<'a> {
+    /// How deep the packages are updated, direct dependencies having a depth of 0
+    pub depth: usize,
+    /// Only the packages matching it are updated
+    pub matcher: &'a Matcher,
+}
+
+pub struct ResolveDependenciesOptions<'a> {
+    /// The directory of the project, where `pnpm-lock.yaml` is
+    pub lockfile_dir: &'a Path,
+    pub cafs_dir: &'a Path,
+    pub registries: &'a Registries,
+    pub workspace_packages: Option<&'a WorkspacePackages>,
+    /// The resolutions of this lockfile are kept as long as they satisfy the manifests
+    pub preferred_lockfile: Option<&'a Lockfile>,
+    pub update: Option<UpdateOptions<'a>>,
+    /// From the `pnpm.peerDependencyRules` of the root project
+    pub peer_dependency_rules: Option<&'a PeerDependencyRules>,
+}
+
+/// A project whose dependencies are resolved
+pub struct Importer<'a> {
+    /// The path of the project relative to the lockfile directory, `.` for the root project
+    pub id: &'a str,
+    pub manifest: &'a BaseManifest,
+}
+
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: String,
+    /// The depth of the shallowest place the package is in, direct dependencies having a depth of 0
+    pub depth: usize,
+    /// The package as it is written in the lockfile, with the references of its dependencies
+    pub snapshot: PackageSnapshot,
+}
+
+/// The resolved dependencies of the importers and of every package they depend on
+#[derive(Default)]
+pub struct DependenciesGraph {
+    /// The direct dependencies by importer id
+    pub importers: HashMap<String, ProjectSnapshot>,
+    /// The packages by dependency path
+    pub packages: HashMap<String, ResolvedPackage>,
+}
+
+impl DependenciesGraph {
+    /// The wanted lockfile of the graph, without the packages that nothing depends on
+    pub fn to_lockfile(&self) -> Lockfile {
+        let mut lockfile = Lockfile::new(LOCKFILE_VERSION.to_string());
+        lockfile.importers = self.importers.clone();
+        lockfile.packages = Some(
+            self.packages
+                .iter()
+                .map(|(dep_path, package)| (dep_path.clone(), package.snapshot.clone()))
+                .collect(),
+        );
+        prune_shared_lockfile(&lockfile)
+    }
+}
+
+/// Where the reference of a resolved dependency is written
+enum Parent<'i> {
+    Importer(&'i str, DependencyField),
+    Package(String),
+}
+
+enum Task<'i> {
+    Resolve {
+        parent: Parent<'i>,
+        alias: String,
+        pref: String,
+        optional: bool,
+        depth: usize,
+    },
+    /// A package of the preferred lockfile, already referenced by its dependent
+    CopyLocked { dep_path: String, depth: usize },
+}
+
+/// A package that was resolved for the first time, whose dependencies still have to be resolved
+struct NewPackage {
+    dep_path: String,
+    manifest: BaseManifest,
+}
+
+/// The dependencies of a manifest, sorted so the resolution is the same on every run.
+/// A dependency that is in several fields is only in the first of
+/// `optionalDependencies`, `dependencies` and `devDependencies`.
+fn sorted_deps<'m>(
+    deps: &'m Option<Dependencies>,
+    other_fields: &[&Option<Dependencies>],
+) -> BTreeMap<&'m str, &'m str> {
+    deps.iter()
+        .flatten()
+        .filter(|(alias, _)| {
+            !other_fields
+                .iter()
+                .any(|field| field.as_ref().is_some_and(|deps| deps.contains_key(*alias)))
+        })
+        .map(|(alias, pref)| (alias.as_str(), pref.as_str()))
+        .collect()
+}
+
+fn unscoped(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// The URL of a tarball in the registry, which the lockfile doesn't need to store
+pub fn registry_tarball_url(registry: &str, name: &str, version: &str) -> String {
+    format!("{}{}/-/{}-{}.tgz", registry, name, unscoped(name), version)
+}
+
+struct ResolutionContext<'a, 'b> {
+    resolver: &'b Resolver<'a>,
+    client: &'b Client,
+    opts: &'b ResolveDependenciesOptions<'b>,
+    graph: DependenciesGraph,
+    /// The dependency paths of the packages by the ids of their resolutions
+    dep_paths_by_id: HashMap<String, String>,
+    /// The versions resolved so far, which the next resolutions prefer
+    preferred_versions: PreferredVersions,
+}
+
+impl<'a, 'b> ResolutionContext<'a, 'b> {
+    fn resolve_options(&self, project_dir: &Path) -> ResolveOptions<'_> {
+        ResolveOptions {
+            always_try_workspace_packages: None,
+            default_tag: None,
+            project_dir: project_dir.to_string_lossy().to_string(),
+            lockfile_dir: self.opts.lockfile_dir.to_string_lossy().to_string(),
+            preferred_versions: &self.preferred_versions,
+            prefer_workspace_packages: None,
+            registries: self.opts.registries.clone(),
+            workspace_packages: self.opts.workspace_packages,
+        }
+    }
+
+    /// The reference to the locked package that satisfies the wanted dependency.
+    /// A direct dependency keeps its reference while its specifier doesn't change,
+    /// other dependencies take the newest locked version of the package that is in their range.
+    fn locked_reference(
+        &self,
+        alias: &str,
+        pref: &str,
+        importer_id: Option<&str>,
+    ) -> Option<String> {
+        let lockfile = self.opts.preferred_lockfile?;
+        let packages = lockfile.packages.as_ref()?;
+        let is_locked = |reference: &String| {
+            ref_to_relative(reference, alias)
+                .is_some_and(|dep_path| packages.contains_key(&dep_path))
+        };
+
+        if let Some(importer_id) = importer_id {
+            let importer = lockfile.importers.get(importer_id)?;
+            if importer.specifiers.get(alias).map(String::as_str) == Some(pref) {
+                let reference = [
+                    &importer.dependencies,
+                    &importer.dev_dependencies,
+                    &importer.optional_dependencies,
+                ]
+                .into_iter()
+                .flatten()
+                .find_map(|deps| deps.get(alias));
+                if let Some(reference) = reference.filter(|reference| is_locked(reference)) {
+                    return Some(reference.clone());
+                }
+            }
+        }
+
+        let range = Range::parse(pref).ok()?;
+        packages
+            .keys()
+            .filter_map(|dep_path| dependency_path::parse(dep_path).ok())
+            .filter(|dep_path| {
+                !dep_path.is_absolute
+                    && dep_path.peers_suffix.is_none()
+                    && dep_path.name.as_deref() == Some(alias)
+            })
+            .filter_map(|dep_path| {
+                let version = dep_path.version?;
+                Some((parse_version(&version)?, version))
+            })
+            .filter(|(version, _)| range.satisfies(version))
+            .max()
+            .map(|(_, version)| version)
+    }
+
+    /// Adds a package of the preferred lockfile to the graph as it is locked,
+    /// and returns the dependency paths of its dependencies
+    fn copy_locked_package(&mut self, dep_path: &str, depth: usize) -> Result<Vec<String>> {
+        if let Some(package) = self.graph.packages.get_mut(dep_path) {
+            package.depth = package.depth.min(depth);
+            return Ok(vec![]);
+        }
+        let snapshot = match self
+            .opts
+            .preferred_lockfile
+            .and_then(|lockfile| lockfile.packages.as_ref()?.get(dep_path))
+        {
+            Some(snapshot) => snapshot.clone(),
+            None => return Ok(vec![]),
+        };
+        let children = snapshot
+            .dependencies
+            .iter()
+            .chain(&snapshot.optional_dependencies)
+            .flatten()
+            .filter_map(|(alias, reference)| ref_to_relative(reference, alias))
+            .collect();
+        let name_ver = name_ver_from_pkg_snapshot(dep_path, &snapshot)?;
+        self.graph.packages.insert(
+            dep_path.to_string(),
+            ResolvedPackage {
+                name: name_ver.name,
+                version: name_ver.version,
+                depth,
+                snapshot,
+            },
+        );
+        Ok(children)
+    }
+
+    /// Returns the reference to the dependency, as written in the lockfile,
+    /// and the package when it is new to the graph.
+    /// Optional dependencies that cannot be resolved are skipped.
+    fn resolve_dependency(
+        &mut self,
+        alias: &str,
+        pref: &str,
+        optional: bool,
+        depth: usize,
+        importer_id: Option<&str>,
+    ) -> Result<Option<(String, Option<NewPackage>)>> {
+        let update = self.opts.update.as_ref();
+        let mut wanted_pref = pref.to_string();
+        let locked_reference = update
+            .is_none_or(|update| depth > update.depth || !update.matcher.is_match(alias))
+            .then(|| self.locked_reference(alias, pref, importer_id))
+            .flatten();
+        if let Some(reference) = &locked_reference {
+            let updated_below = update.is_some_and(|update| depth < update.depth);
+            let locked_version = ref_to_relative(reference, alias)
+                .and_then(|dep_path| dependency_path::parse(&dep_path).ok())
+                .filter(|dep_path| dep_path.name.as_deref() == Some(alias))
+                .and_then(|dep_path| dep_path.version);
+            match locked_version {
+                // the locked version is resolved again to find the dependencies to update below it
+                // without the peers suffix, which is added again once the peers are resolved
+                Some(version) if updated_below => wanted_pref = version,
+                _ => return Ok(locked_reference.map(|reference| (reference, None))),
+            }
+        }
+
+        let project_dir = self.opts.lockfile_dir.join(importer_id.unwrap_or("."));
+        let result = self.resolver.resolve(
+            WantedDependency {
+                alias: Some(alias.to_string()),
+                pref: Some(wanted_pref),
+                injected: None,
+            },
+            self.resolve_options(&project_dir),
+        );
+        let result = match result {
+            Ok(result) => result,
+            Err(error) if optional => {
+                eprintln!(
+                    "WARN: Skipping optional dependency {}@{}: {}",
+                    alias, pref, error
+                );
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+
+        let (tarball, integrity, tarball_registry) = match &result.resolution {
+            Resolution::DirectoryResolution { .. } => return Ok(Some((result.id, None))),
+            Resolution::GitRepositoryResolution { repo, .. } => bail!(
+                "GIT_NOT_SUPPORTED: Cannot install {} from the git repository {}",
+                alias,
+                repo
+            ),
+            Resolution::TarballResolution {
+                tarball,
+                integrity,
+                registry,
+            } => (tarball.clone(), integrity.clone(), registry.clone()),
+        };
+
+        let registry = pick_registry_for_package(self.opts.registries, alias, Some(pref));
+        let from_registry = result.resolved_via == ResolvedVia::NpmRegistry;
+        // the reference is known as soon as the name and the version are
+        let reference =
+            |dep_path: &str, manifest: &BaseManifest| match (&manifest.name, &manifest.version) {
+                (Some(name), Some(version)) if from_registry && name == alias => version.clone(),
+                _ => dep_path.to_string(),
+            };
+        let dep_path = match self.dep_paths_by_id.get(&result.id) {
+            Some(dep_path) => dep_path.clone(),
+            None => match encode_registry(registry) {
+                Some(host) if from_registry => result
+                    .id
+                    .strip_prefix(&host)
+                    .map(String::from)
+                    .unwrap_or_else(|| result.id.clone()),
+                _ => result.id.clone(),
+            },
+        };
+        // the package is resolved once, or was copied from the lockfile
+        if let Some(package) = self.graph.packages.get_mut(&dep_path) {
+            package.depth = package.depth.min(depth);
+            let reference = match &result.manifest {
+                Some(manifest) => reference(&dep_path, manifest),
+                None => dep_path.clone(),
+            };
+            return Ok(Some((reference, None)));
+        }
+
+        let (integrity, manifest) = match (integrity, result.manifest) {
+            (Some(integrity), Some(manifest)) => (integrity, manifest),
+            (_, manifest) => {
+                // the integrity of some tarballs is only known once they are downloaded
+                let fetched = fetch_package(
+                    self.client,
+                    self.opts.cafs_dir,
+                    &result.resolution,
+                    &self.opts.lockfile_dir.to_string_lossy(),
+                    false,
+                )?;
+                let manifest = match manifest {
+                    Some(manifest) => manifest,
+                    None => read_manifest_from_store(self.opts.cafs_dir, &fetched.files_index)?,
+                };
+                (fetched.integrity, manifest)
+            }
+        };
+        let name = manifest.name.clone().unwrap_or_else(|| alias.to_string());
+        let version = manifest.version.clone().unwrap_or_default();
+        if from_registry {
+            self.preferred_versions
+                .entry(name.clone())
+                .or_default()
+                .insert(version.clone(), Version::Version);
+        }
+
+        let resolution =
+            if from_registry && tarball == registry_tarball_url(registry, &name, &version) {
+                LockfileResolution::RegistryResolution { integrity }
+            } else {
+                LockfileResolution::TarballResolution {
+                    integrity: Some(integrity),
+                    registry: tarball_registry,
+                    tarball,
+                }
+            };
+        // the dependencies are added once they are resolved
+        let snapshot = PackageSnapshot {
+            resolution,
+            id: None,
+            // packages from the registry have them in their dependency path
+            name: (!from_registry).then(|| name.clone()),
+            version: (!from_registry).then(|| version.clone()),
+            engines: manifest
+                .engines
+                .as_ref()
+                .and_then(|engines| engines.node.clone())
+                .map(|node| SnapshotEngines { node }),
+            cpu: manifest.cpu.as_ref().map(|cpu| cpu.to_vec()),
+            os: manifest.os.as_ref().map(|os| os.to_vec()),
+            deprecated: None,
+            has_bin: manifest.bin.is_some().then_some(true),
+            prepare: None,
+            requires_build: lifecycle::requires_build(&manifest).then_some(true),
+            bundled_dependencies: None,
+            peer_dependencies: manifest
+                .peer_dependencies
+                .clone()
+                .filter(|peers| !peers.is_empty()),
+            peer_dependencies_meta: manifest
+                .peer_dependencies_meta
+                .clone()
+                .filter(|meta| !meta.is_empty()),
+            dependencies: None,
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        };
+        self.dep_paths_by_id.insert(result.id, dep_path.clone());
+        self.graph.packages.insert(
+            dep_path.clone(),
+            ResolvedPackage {
+                name,
+                version,
+                depth,
+                snapshot,
+            },
+        );
+
+        Ok(Some((
+            reference(&dep_path, &manifest),
+            Some(NewPackage { dep_path, manifest }),
+        )))
+    }
+
+    fn add_reference(&mut self, parent: &Parent, alias: &str, reference: String, optional: bool) {
+        let deps = match parent {
+            Parent::Importer(id, field) => {
+                let importer = self.graph.importers.get_mut(*id).unwrap();
+                match field {
+                    DependencyField::OptionalDependencies => &mut importer.optional_dependencies,
+                    DependencyField::Dependencies => &mut importer.dependencies,
+                    DependencyField::DevDependencies => &mut importer.dev_dependencies,
+                }
+            }
+            Parent::Package(dep_path) => {
+                let snapshot = &mut self.graph.packages.get_mut(dep_path).unwrap().snapshot;
+                match optional {
+                    true => &mut snapshot.optional_dependencies,
+                    false => &mut snapshot.dependencies,
+                }
+            }
+        };
+        deps.get_or_insert_with(ResolvedDependencies::new)
+            .insert(alias.to_string(), reference);
+    }
+}
+
+/// Resolves the dependencies of the importers and all their dependencies, breadth-first,
+/// so the versions picked for the shallower dependencies are preferred by the deeper ones.
+/// A package resolved several times is only once in the graph.
+pub fn resolve_dependency_tree(
+    resolver: &Resolver,
+    client: &Client,
+    importers: &[Importer],
+    opts: &ResolveDependenciesOptions,
+) -> Result<DependenciesGraph> {
+    let mut ctx = ResolutionContext {
+        resolver,
+        client,
+        opts,
+        graph: DependenciesGraph::default(),
+        dep_paths_by_id: HashMap::new(),
+        preferred_versions: PreferredVersions::new(),
+    };
+
+    let mut queue = VecDeque::new();
+    for importer in importers {
+        let manifest = importer.manifest;
+        let optional = &manifest.optional_dependencies;
+        let fields = [
+            (
+                DependencyField::OptionalDependencies,
+                sorted_deps(optional, &[]),
+            ),
+            (
+                DependencyField::Dependencies,
+                sorted_deps(&manifest.dependencies, &[optional]),
+            ),
+            (
+                DependencyField::DevDependencies,
+                sorted_deps(
+                    &manifest.dev_dependencies,
+                    &[optional, &manifest.dependencies],
+                ),
+            ),
+        ];
+        let mut snapshot = ProjectSnapshot::new();
+        for (field, deps) in fields {
+            for (alias, pref) in deps {
+                snapshot
+                    .specifiers
+                    .insert(alias.to_string(), pref.to_string());
+                queue.push_back(Task::Resolve {
+                    parent: Parent::Importer(importer.id, field),
+                    alias: alias.to_string(),
+                    pref: pref.to_string(),
+                    optional: field == DependencyField::OptionalDependencies,
+                    depth: 0,
+                });
+            }
+        }
+        snapshot.dependencies_meta = manifest.dependencies_meta.clone();
+        ctx.graph
+            .importers
+            .insert(importer.id.to_string(), snapshot);
+    }
+
+    while let Some(task) = queue.pop_front() {
+        match task {
+            Task::Resolve {
+                parent,
+                alias,
+                pref,
+                optional,
+                depth,
+            } => {
+                let importer_id = match &parent {
+                    Parent::Importer(id, _) => Some(*id),
+                    Parent::Package(_) => None,
+                };
+                let (reference, new_package) =
+                    match ctx.resolve_dependency(&alias, &pref, optional, depth, importer_id)? {
+                        Some(resolved) => resolved,
+                        None => continue,
+                    };
+                match new_package {
+                    Some(NewPackage { dep_path, manifest }) => {
+                        let children =
+                            sorted_deps(&manifest.dependencies, &[&manifest.optional_dependencies])
+                                .into_iter()
+                                .map(|(alias, pref)| (alias, pref, false))
+                                .chain(
+                                    sorted_deps(&manifest.optional_dependencies, &[])
+                                        .into_iter()
+                                        .map(|(alias, pref)| (alias, pref, true)),
+                                );
+                        for (child_alias, child_pref, child_optional) in children {
+                            queue.push_back(Task::Resolve {
+                                parent: Parent::Package(dep_path.clone()),
+                                alias: child_alias.to_string(),
+                                pref: child_pref.to_string(),
+                                optional: child_optional,
+                                depth: depth + 1,
+                            });
+                        }
+                    }
+                    None => {
+                        if let Some(dep_path) = ref_to_relative(&reference, &alias) {
+                            queue.push_back(Task::CopyLocked { dep_path, depth });
+                        }
+                    }
+                }
+                ctx.add_reference(&parent, &alias, reference, optional);
+            }
+            Task::CopyLocked { dep_path, depth } => {
+                for child in ctx.copy_locked_package(&dep_path, depth)? {
+                    queue.push_back(Task::CopyLocked {
+                        dep_path: child,
+                        depth: depth + 1,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(ctx.graph)
+}
+
+/// Resolves the dependencies of the importers, all their dependencies and their peers
+/// into a new wanted lockfile with an entry for every importer
+pub fn resolve_dependencies(
+    resolver: &Resolver,
+    client: &Client,
+    importers: &[Importer],
+    opts: &ResolveDependenciesOptions,
+) -> Result<(Lockfile, PeerDependencyIssues)> {
+    let graph = resolve_dependency_tree(resolver, client, importers, opts)?;
+    let (graph, peer_dependency_issues) = resolve_peers(&graph, opts.peer_dependency_rules);
+    Ok((graph.to_lockfile(), peer_dependency_issues))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::{create_client, ClientOptions};
+    use pretty_assertions::assert_eq;
+    use resolvers::npm::ResolverFactoryOptions;
+    use serde_json::{json, Value};
+    use tempfile::tempdir;
+
+    /// A registry with the packages, given as `name@version` and their dependencies
+    fn registry(packages: &[(&str, Value)]) -> String {
+        let mut documents = HashMap::<String, Value>::new();
+        for (id, dependencies) in packages {
+            let (name, version) = id.split_once('@').unwrap();
+            let metadata = documents.entry(format!("/{}", name)).or_insert_with(
+                || json!({ "name": name, "dist-tags": { "latest": version }, "versions": {} }),
+            );
+            metadata["versions"][version] = json!({
+                "name": name,
+                "version": version,
+                "dependencies": dependencies,
+                "dist": {
+                    "integrity": format!("sha512-{}", id),
+                    "tarball": format!("https://cdn.example.com/{0}/-/{0}-{1}.tgz", name, version),
+                },
+            });
+        }
+        http_stub::serve_documents(
+            documents
+                .into_iter()
+                .map(|(path, metadata)| (path, metadata.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Resolves the importers, given by their ids and their dependencies, in one graph
+    fn resolve_importers(
+        registry: &str,
+        importers: &[(&str, Value)],
+        preferred_lockfile: Option<&Lockfile>,
+    ) -> DependenciesGraph {
+        let dir = tempdir().unwrap();
+        let client = create_client(ClientOptions {
+            auth_config: HashMap::from([("registry".to_string(), registry.to_string())]),
+            ..Default::default()
+        })
+        .unwrap();
+        let resolver = client.create_resolver(ResolverFactoryOptions {
+            cache_dir: dir.path().join("cache"),
+            offline: false,
+            prefer_offline: false,
+        });
+        let manifests = importers
+            .iter()
+            .map(|(_, dependencies)| BaseManifest {
+                dependencies: Some(serde_json::from_value(dependencies.clone()).unwrap()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let importers = importers
+            .iter()
+            .zip(&manifests)
+            .map(|((id, _), manifest)| Importer { id, manifest })
+            .collect::<Vec<_>>();
+        resolve_dependency_tree(
+            &resolver,
+            &client,
+            &importers,
+            &ResolveDependenciesOptions {
+                lockfile_dir: dir.path(),
+                cafs_dir: &dir.path().join("store"),
+                registries: &Registries {
+                    default: registry.to_string(),
+                    scoped: HashMap::new(),
+                },
+                workspace_packages: None,
+                preferred_lockfile,
+                update: None,
+                peer_dependency_rules: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn resolve(
+        registry: &str,
+        dependencies: Value,
+        preferred_lockfile: Option<&Lockfile>,
+    ) -> DependenciesGraph {
+        resolve_importers(registry, &[(".", dependencies)], preferred_lockfile)
+    }
+
+    fn dependencies_of<'g>(
+        graph: &'g DependenciesGraph,
+        dep_path: &str,
+    ) -> &'g ResolvedDependencies {
+        graph.packages[dep_path]
+            .snapshot
+            .dependencies
+            .as_ref()
+            .unwrap()
+    }
+
+    fn sorted_dep_paths(graph: &DependenciesGraph) -> Vec<&str> {
+        let mut dep_paths = graph
+            .packages
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        dep_paths.sort();
+        dep_paths
+    }
+
+    #[test]
+    fn prefer_the_versions_of_shallower_dependencies() {
+        let registry = registry(&[
+            ("a@1.0.0", json!({ "z": "^1.0.0" })),
+            ("z@1.0.0", json!({})),
+            ("z@1.1.0", json!({})),
+        ]);
+
+        // `z` of `a` is resolved after the `z` of the project, though `a` comes first
+        let graph = resolve(&registry, json!({ "a": "^1.0.0", "z": "1.0.0" }), None);
+
+        assert_eq!(sorted_dep_paths(&graph), ["/a/1.0.0", "/z/1.0.0"]);
+        assert_eq!(dependencies_of(&graph, "/a/1.0.0")["z"], "1.0.0");
+        assert_eq!(graph.packages["/z/1.0.0"].depth, 0);
+    }
+
+    #[test]
+    fn resolve_a_package_once() {
+        let registry = registry(&[
+            ("a@1.0.0", json!({ "c": "^1.0.0" })),
+            ("b@1.0.0", json!({ "c": "1.0.0" })),
+            ("c@1.0.0", json!({})),
+        ]);
+
+        let graph = resolve(
+            &registry,
+            json!({ "a": "^1.0.0", "b": "^1.0.0", "c-alias": "npm:c@^1.0.0" }),
+            None,
+        );
+
+        // the alias of the project has the same resolution id as the dependencies of `a` and `b`
+        assert_eq!(
+            sorted_dep_paths(&graph),
+            ["/a/1.0.0", "/b/1.0.0", "/c/1.0.0"]
+        );
+        assert_eq!(
+            graph.importers["."].dependencies.as_ref().unwrap()["c-alias"],
+            "/c/1.0.0"
+        );
+        assert_eq!(dependencies_of(&graph, "/a/1.0.0")["c"], "1.0.0");
+        assert_eq!(dependencies_of(&graph, "/b/1.0.0")["c"], "1.0.0");
+        assert_eq!(graph.packages["/c/1.0.0"].depth, 0);
+    }
+
+    #[test]
+    fn resolve_every_importer_in_one_graph() {
+        let registry = registry(&[
+            ("a@1.0.0", json!({ "c": "^1.0.0" })),
+            ("b@1.0.0", json!({})),
+            ("c@1.0.0", json!({})),
+        ]);
+
+        let graph = resolve_importers(
+            &registry,
+            &[
+                (".", json!({ "a": "^1.0.0" })),
+                ("packages/foo", json!({ "b": "^1.0.0", "c": "^1.0.0" })),
+            ],
+            None,
+        );
+
+        assert_eq!(
+            sorted_dep_paths(&graph),
+            ["/a/1.0.0", "/b/1.0.0", "/c/1.0.0"]
+        );
+        let dependencies = |id: &str| graph.importers[id].dependencies.clone().unwrap();
+        assert_eq!(dependencies(".")["a"], "1.0.0");
+        assert_eq!(dependencies("packages/foo")["b"], "1.0.0");
+        // `c` is shared with the dependency of the other importer
+        assert_eq!(dependencies("packages/foo")["c"], "1.0.0");
+        assert_eq!(graph.packages["/c/1.0.0"].depth, 0);
+    }
+
+    #[test]
+    fn reuse_the_resolutions_of_the_lockfile() {
+        // `foo` and `bar` are not in the registry, so they can only come from the lockfile
+        let registry = registry(&[("qux@1.0.0", json!({ "bar": "^1.0.0" }))]);
+        let lockfile: Lockfile = serde_json::from_value(json!({
+            "lockfileVersion": "5.3",
+            "importers": {
+                ".": {
+                    "specifiers": { "foo": "^1.0.0" },
+                    "dependencies": { "foo": "1.0.0" },
+                },
+            },
+            "packages": {
+                "/foo/1.0.0": {
+                    "resolution": { "integrity": "sha512-foo" },
+                    "dependencies": { "bar": "1.0.0" },
+                },
+                "/bar/1.0.0": { "resolution": { "integrity": "sha512-bar" } },
+                "/bar/2.0.0": { "resolution": { "integrity": "sha512-bar2" } },
+            },
+        }))
+        .unwrap();
+
+        let graph = resolve(
+            &registry,
+            json!({ "foo": "^1.0.0", "qux": "^1.0.0" }),
+            Some(&lockfile),
+        );
+
+        // the new `qux` takes the newest locked `bar` in its range
+        assert_eq!(
+            sorted_dep_paths(&graph),
+            ["/bar/1.0.0", "/foo/1.0.0", "/qux/1.0.0"]
+        );
+        assert_eq!(dependencies_of(&graph, "/qux/1.0.0")["bar"], "1.0.0");
+        assert_eq!(
+            graph.packages["/foo/1.0.0"].snapshot,
+            lockfile.packages.as_ref().unwrap()["/foo/1.0.0"]
+        );
+        assert_eq!(graph.packages["/bar/1.0.0"].depth, 1);
+    }
+
+    #[test]
+    fn tarball_url_in_registry() {
+        assert_eq!(
+            registry_tarball_url("https://registry.npmjs.org/", "@foo/bar", "1.0.0"),
+            "https://registry.npmjs.org/@foo/bar/-/bar-1.0.0.tgz"
+        );
+    }
+}
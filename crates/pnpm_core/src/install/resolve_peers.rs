@@ -0,0 +1,736 @@
+use super::resolve_dependencies::{DependenciesGraph, ResolvedPackage};
+use dependency_path::ref_to_relative;
+use lockfile_utils::types::{ProjectSnapshot, ResolvedDependencies};
+use matcher::Matcher;
+use semver_range::satisfies;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use types::PeerDependencyRules;
+
+/// A peer dependency that is missing, or whose version doesn't satisfy the wanted range
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerDependencyIssue {
+    /// The packages from the importer down to the package that has the peer dependency,
+    /// as `name@version`
+    pub parents: Vec<String>,
+    pub peer_name: String,
+    pub wanted_range: String,
+    /// The version of the peer dependency found in the parents, when there is one
+    pub found_version: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PeerDependencyIssues {
+    pub missing: Vec<PeerDependencyIssue>,
+    pub bad: Vec<PeerDependencyIssue>,
+}
+
+impl PeerDependencyIssues {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.bad.is_empty()
+    }
+}
+
+impl fmt::Display for PeerDependencyIssues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.missing.is_empty() {
+            writeln!(f, "Missing peer dependencies:")?;
+            for issue in &self.missing {
+                writeln!(
+                    f,
+                    "  {}: {}@{}",
+                    issue.parents.join(" > "),
+                    issue.peer_name,
+                    issue.wanted_range
+                )?;
+            }
+        }
+        if !self.bad.is_empty() {
+            writeln!(f, "Peer dependencies with unmet versions:")?;
+            for issue in &self.bad {
+                writeln!(
+                    f,
+                    "  {}: {}@{} (found {})",
+                    issue.parents.join(" > "),
+                    issue.peer_name,
+                    issue.wanted_range,
+                    issue.found_version.as_deref().unwrap_or_default()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The one whose dependencies include a package that is visible to its descendants
+#[derive(Clone, Debug, PartialEq)]
+enum Owner {
+    Importer(String),
+    Node(usize),
+}
+
+/// A package that can satisfy the peer dependencies of the packages below it
+#[derive(Clone, Debug, PartialEq)]
+struct ParentPkg {
+    name: String,
+    /// Unknown for linked dependencies
+    version: Option<String>,
+    /// The package is the dependency `alias` of `owner`, whose dependency path
+    /// is only known once all the peers are resolved
+    owner: Owner,
+    alias: String,
+    /// The dependency path the package had before its peers were resolved,
+    /// or the reference to it when it is not in the graph
+    id: String,
+}
+
+type ParentPkgs = HashMap<String, ParentPkg>;
+
+/// The names of the peers a node was resolved with, and the ids of the packages found for them
+type PeerIds = Vec<(String, Option<String>)>;
+
+#[derive(Clone, Debug)]
+enum Child {
+    Node(usize),
+    /// A dependency that is not in the graph, like a linked directory
+    Reference(String),
+}
+
+/// A package with the peer dependencies it got from one place of the tree
+struct Node {
+    dep_path: String,
+    children: BTreeMap<String, Child>,
+    /// The aliases of the children that are optional dependencies
+    optional_children: Vec<String>,
+    /// The peer dependencies of the package, by name, with the packages that satisfy them
+    own_peers: BTreeMap<String, ParentPkg>,
+    /// The peer dependencies of the package and of its descendants that are not satisfied
+    /// inside the package. `None` for the missing ones.
+    external_peers: BTreeMap<String, Option<ParentPkg>>,
+}
+
+struct PeersResolver<'g> {
+    graph: &'g DependenciesGraph,
+    ignore_missing: Option<Matcher>,
+    allowed_versions: HashMap<String, String>,
+    nodes: Vec<Node>,
+    /// The nodes of a dependency path with the ids of the peers they were resolved with,
+    /// reused when the same peers are found again
+    cache: HashMap<String, Vec<(usize, PeerIds)>>,
+    /// The dependency paths being resolved, from the importer down, with their nodes
+    stack: Vec<(String, usize)>,
+    issues: PeerDependencyIssues,
+}
+
+/// The dependency path without the peers suffix of the lockfile it was copied from
+fn without_peers_suffix(dep_path: &str) -> &str {
+    match dependency_path::parse(dep_path)
+        .ok()
+        .and_then(|parsed| parsed.peers_suffix)
+    {
+        Some(suffix) => &dep_path[..dep_path.len() - suffix.len() - 1],
+        None => dep_path,
+    }
+}
+
+/// The reference to a package as written in the dependencies of the lockfile
+fn ref_for(dep_path: &str, alias: &str) -> String {
+    match dep_path
+        .strip_prefix('/')
+        .and_then(|rest| rest.strip_prefix(alias))
+        .and_then(|rest| rest.strip_prefix('/'))
+    {
+        Some(version) if !version.contains('/') => version.to_string(),
+        _ => dep_path.to_string(),
+    }
+}
+
+/// The suffix that tells apart the instances of a package with different peers,
+/// like `_react-dom@17.0.2+react@17.0.2`
+fn peers_suffix<'p, I: Iterator<Item = &'p ParentPkg>>(peers: I) -> String {
+    let mut peers = peers
+        .map(|peer| {
+            format!(
+                "{}@{}",
+                peer.name,
+                peer.version.as_deref().unwrap_or(&peer.id)
+            )
+            .replace('/', "+")
+        })
+        .collect::<Vec<_>>();
+    if peers.is_empty() {
+        return String::new();
+    }
+    peers.sort();
+    format!("_{}", peers.join("+"))
+}
+
+impl<'g> PeersResolver<'g> {
+    fn package(&self, dep_path: &str) -> &'g ResolvedPackage {
+        &self.graph.packages[dep_path]
+    }
+
+    /// The package that a reference points to, when it is in the graph
+    fn dep_path_of(&self, alias: &str, reference: &str) -> Option<String> {
+        ref_to_relative(reference, alias)
+            .filter(|dep_path| self.graph.packages.contains_key(dep_path))
+    }
+
+    /// The dependencies that are not in the graph, which are kept as they are.
+    /// The others are added once their peers are resolved.
+    fn unresolved_children(&self, deps: &ResolvedDependencies) -> BTreeMap<String, Child> {
+        deps.iter()
+            .filter(|(alias, reference)| self.dep_path_of(alias, reference).is_none())
+            .map(|(alias, reference)| (alias.clone(), Child::Reference(reference.clone())))
+            .collect()
+    }
+
+    fn parent_pkgs(
+        &self,
+        parents: &ParentPkgs,
+        owner: Owner,
+        deps: &ResolvedDependencies,
+    ) -> ParentPkgs {
+        let mut parent_pkgs = parents.clone();
+        for (alias, reference) in deps {
+            let parent_pkg = match self.dep_path_of(alias, reference) {
+                Some(dep_path) => ParentPkg {
+                    name: self.package(&dep_path).name.clone(),
+                    version: Some(self.package(&dep_path).version.clone()),
+                    owner: owner.clone(),
+                    alias: alias.clone(),
+                    id: dep_path,
+                },
+                None => ParentPkg {
+                    name: alias.clone(),
+                    version: None,
+                    owner: owner.clone(),
+                    alias: alias.clone(),
+                    id: reference.clone(),
+                },
+            };
+            parent_pkgs.insert(alias.clone(), parent_pkg);
+        }
+        parent_pkgs
+    }
+
+    fn issue_parents(&self) -> Vec<String> {
+        self.stack
+            .iter()
+            .map(|(dep_path, _)| {
+                let package = self.package(dep_path);
+                format!("{}@{}", package.name, package.version)
+            })
+            .collect()
+    }
+
+    fn add_issue(&mut self, missing: bool, issue: PeerDependencyIssue) {
+        let issues = match missing {
+            true => &mut self.issues.missing,
+            false => &mut self.issues.bad,
+        };
+        if !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    }
+
+    /// Resolves the peers of the package and of all the packages below it,
+    /// and returns the node of the package
+    fn resolve_node(&mut self, dep_path: &str, parents: &ParentPkgs) -> usize {
+        if let Some((_, node_id)) = self.stack.iter().find(|(path, _)| path == dep_path) {
+            return *node_id;
+        }
+        let cached = self.cache.get(dep_path).and_then(|entries| {
+            entries.iter().find(|(_, peers)| {
+                peers.iter().all(|(name, id)| {
+                    parents.get(name).map(|parent_pkg| &parent_pkg.id) == id.as_ref()
+                })
+            })
+        });
+        if let Some((node_id, _)) = cached {
+            return *node_id;
+        }
+
+        let package = self.package(dep_path);
+        let snapshot = &package.snapshot;
+        // the peers resolved in a lockfile are among the dependencies of the package
+        let had_peers = dependency_path::parse(dep_path)
+            .ok()
+            .is_some_and(|parsed| parsed.peers_suffix.is_some());
+        let peer_dependencies = snapshot.peer_dependencies.clone().unwrap_or_default();
+        let is_resolved_peer = |alias: &String| had_peers && peer_dependencies.contains_key(alias);
+        let deps: ResolvedDependencies = snapshot
+            .dependencies
+            .iter()
+            .chain(&snapshot.optional_dependencies)
+            .flatten()
+            .filter(|(alias, _)| !is_resolved_peer(alias))
+            .map(|(alias, reference)| (alias.clone(), reference.clone()))
+            .collect();
+        let optional_children = snapshot
+            .optional_dependencies
+            .iter()
+            .flatten()
+            .map(|(alias, _)| alias.clone())
+            .collect();
+
+        let node_id = self.nodes.len();
+        self.nodes.push(Node {
+            dep_path: dep_path.to_string(),
+            children: self.unresolved_children(&deps),
+            optional_children,
+            own_peers: BTreeMap::new(),
+            external_peers: BTreeMap::new(),
+        });
+        self.stack.push((dep_path.to_string(), node_id));
+
+        let mut external_peers = BTreeMap::new();
+        let child_parents = self.parent_pkgs(parents, Owner::Node(node_id), &deps);
+        for (alias, reference) in &deps {
+            let child_dep_path = match self.dep_path_of(alias, reference) {
+                Some(child_dep_path) => child_dep_path,
+                None => continue,
+            };
+            let child_id = self.resolve_node(&child_dep_path, &child_parents);
+            self.nodes[node_id]
+                .children
+                .insert(alias.clone(), Child::Node(child_id));
+            if child_id == node_id {
+                continue;
+            }
+            for (name, parent_pkg) in &self.nodes[child_id].external_peers {
+                let is_internal = parent_pkg.as_ref().is_some_and(|parent_pkg| {
+                    parent_pkg.owner == Owner::Node(node_id) || parent_pkg.id == dep_path
+                });
+                if !is_internal {
+                    external_peers.insert(name.clone(), parent_pkg.clone());
+                }
+            }
+        }
+
+        let mut own_peers = BTreeMap::new();
+        for (peer_name, wanted_range) in &peer_dependencies {
+            // a dependency with the name of the peer satisfies it
+            if !had_peers && deps.contains_key(peer_name) {
+                continue;
+            }
+            let optional = snapshot
+                .peer_dependencies_meta
+                .as_ref()
+                .and_then(|meta| meta.get(peer_name))
+                .and_then(|meta| meta.optional)
+                .unwrap_or(false);
+            match parents.get(peer_name) {
+                Some(parent_pkg) => {
+                    if let Some(version) = &parent_pkg.version {
+                        if !self.is_allowed_version(&package.name, peer_name, wanted_range, version)
+                        {
+                            let issue = PeerDependencyIssue {
+                                parents: self.issue_parents(),
+                                peer_name: peer_name.clone(),
+                                wanted_range: wanted_range.clone(),
+                                found_version: Some(version.clone()),
+                            };
+                            self.add_issue(false, issue);
+                        }
+                    }
+                    own_peers.insert(peer_name.clone(), parent_pkg.clone());
+                    external_peers.insert(peer_name.clone(), Some(parent_pkg.clone()));
+                }
+                None => {
+                    let ignored = self
+                        .ignore_missing
+                        .as_ref()
+                        .is_some_and(|matcher| matcher.is_match(peer_name));
+                    if !optional && !ignored {
+                        let issue = PeerDependencyIssue {
+                            parents: self.issue_parents(),
+                            peer_name: peer_name.clone(),
+                            wanted_range: wanted_range.clone(),
+                            found_version: None,
+                        };
+                        self.add_issue(true, issue);
+                    }
+                    external_peers.insert(peer_name.clone(), None);
+                }
+            }
+        }
+
+        self.stack.pop();
+        let peers = external_peers
+            .iter()
+            .map(|(name, parent_pkg)| {
+                (
+                    name.clone(),
+                    parent_pkg.as_ref().map(|parent_pkg| parent_pkg.id.clone()),
+                )
+            })
+            .collect();
+        self.cache
+            .entry(dep_path.to_string())
+            .or_default()
+            .push((node_id, peers));
+        let node = &mut self.nodes[node_id];
+        node.own_peers = own_peers;
+        node.external_peers = external_peers;
+        node_id
+    }
+
+    /// A version that doesn't satisfy the range of the peer is still accepted
+    /// when it satisfies the range allowed by the `peerDependencyRules`
+    fn is_allowed_version(
+        &self,
+        pkg_name: &str,
+        peer_name: &str,
+        wanted_range: &str,
+        version: &str,
+    ) -> bool {
+        satisfies(version, wanted_range)
+            || [format!("{}>{}", pkg_name, peer_name), peer_name.to_string()]
+                .iter()
+                .filter_map(|key| self.allowed_versions.get(key))
+                .any(|allowed_range| satisfies(version, allowed_range))
+    }
+}
+
+/// Resolves the peer dependencies of every package of the graph from the packages above it.
+/// A package gets a suffix with the peers it was resolved with, so it is in the returned graph
+/// once for every set of peers it needs. The peers of the packages below a package that are
+/// not satisfied inside it are its `transitivePeerDependencies`.
+pub fn resolve_peers(
+    graph: &DependenciesGraph,
+    rules: Option<&PeerDependencyRules>,
+) -> (DependenciesGraph, PeerDependencyIssues) {
+    let mut resolver = PeersResolver {
+        graph,
+        ignore_missing: rules
+            .and_then(|rules| rules.ignore_missing.as_ref())
+            .filter(|patterns| !patterns.is_empty())
+            .map(|patterns| Matcher::new(patterns)),
+        allowed_versions: rules
+            .and_then(|rules| rules.allowed_versions.clone())
+            .unwrap_or_default(),
+        nodes: vec![],
+        cache: HashMap::new(),
+        stack: vec![],
+        issues: PeerDependencyIssues::default(),
+    };
+
+    let mut importer_ids = graph.importers.keys().collect::<Vec<_>>();
+    importer_ids.sort();
+    let mut importer_children = HashMap::new();
+    for importer_id in importer_ids {
+        let importer = &graph.importers[importer_id];
+        let deps: ResolvedDependencies = [
+            &importer.dependencies,
+            &importer.dev_dependencies,
+            &importer.optional_dependencies,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|(alias, reference)| (alias.clone(), reference.clone()))
+        .collect();
+        let parents = resolver.parent_pkgs(
+            &ParentPkgs::new(),
+            Owner::Importer(importer_id.clone()),
+            &deps,
+        );
+        let mut children = resolver.unresolved_children(&deps);
+        let mut aliases = deps.keys().collect::<Vec<_>>();
+        aliases.sort();
+        for alias in aliases {
+            if let Some(dep_path) = resolver.dep_path_of(alias, &deps[alias]) {
+                let node_id = resolver.resolve_node(&dep_path, &parents);
+                children.insert(alias.clone(), Child::Node(node_id));
+            }
+        }
+        importer_children.insert(importer_id.clone(), children);
+    }
+
+    let nodes = &resolver.nodes;
+    let dep_paths = nodes
+        .iter()
+        .map(|node| {
+            format!(
+                "{}{}",
+                without_peers_suffix(&node.dep_path),
+                peers_suffix(node.external_peers.values().flatten())
+            )
+        })
+        .collect::<Vec<_>>();
+    let reference_to = |child: &Child, alias: &str| match child {
+        Child::Node(node_id) => ref_for(&dep_paths[*node_id], alias),
+        Child::Reference(reference) => reference.clone(),
+    };
+    let reference_to_parent = |parent_pkg: &ParentPkg| {
+        let child = match &parent_pkg.owner {
+            Owner::Importer(importer_id) => &importer_children[importer_id][&parent_pkg.alias],
+            Owner::Node(node_id) => &nodes[*node_id].children[&parent_pkg.alias],
+        };
+        reference_to(child, &parent_pkg.alias)
+    };
+
+    let mut resolved = DependenciesGraph::default();
+    for (node_id, node) in nodes.iter().enumerate() {
+        if resolved.packages.contains_key(&dep_paths[node_id]) {
+            continue;
+        }
+        let package = &graph.packages[&node.dep_path];
+        let mut snapshot = package.snapshot.clone();
+        let mut dependencies = ResolvedDependencies::new();
+        let mut optional_dependencies = ResolvedDependencies::new();
+        for (alias, child) in &node.children {
+            let deps = match node.optional_children.contains(alias) {
+                true => &mut optional_dependencies,
+                false => &mut dependencies,
+            };
+            deps.insert(alias.clone(), reference_to(child, alias));
+        }
+        for (peer_name, parent_pkg) in &node.own_peers {
+            dependencies.insert(peer_name.clone(), reference_to_parent(parent_pkg));
+        }
+        let transitive_peer_dependencies = node
+            .external_peers
+            .keys()
+            .filter(|name| {
+                !snapshot
+                    .peer_dependencies
+                    .as_ref()
+                    .is_some_and(|peers| peers.contains_key(*name))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        snapshot.dependencies = Some(dependencies).filter(|deps| !deps.is_empty());
+        snapshot.optional_dependencies =
+            Some(optional_dependencies).filter(|deps| !deps.is_empty());
+        snapshot.transitive_peer_dependencies =
+            Some(transitive_peer_dependencies).filter(|peers| !peers.is_empty());
+        resolved.packages.insert(
+            dep_paths[node_id].clone(),
+            ResolvedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                depth: package.depth,
+                snapshot,
+            },
+        );
+    }
+
+    for (importer_id, importer) in &graph.importers {
+        let children = &importer_children[importer_id];
+        let rewrite = |deps: &Option<ResolvedDependencies>| {
+            deps.as_ref().map(|deps| {
+                deps.keys()
+                    .map(|alias| (alias.clone(), reference_to(&children[alias], alias)))
+                    .collect()
+            })
+        };
+        resolved.importers.insert(
+            importer_id.clone(),
+            ProjectSnapshot {
+                dependencies: rewrite(&importer.dependencies),
+                dev_dependencies: rewrite(&importer.dev_dependencies),
+                optional_dependencies: rewrite(&importer.optional_dependencies),
+                ..importer.clone()
+            },
+        );
+    }
+
+    (resolved, resolver.issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lockfile_utils::types::{LockfileResolution, PackageSnapshot};
+    use pretty_assertions::assert_eq;
+    use std::rc::Rc;
+
+    fn deps(deps: &[(&str, &str)]) -> Option<ResolvedDependencies> {
+        (!deps.is_empty()).then(|| {
+            deps.iter()
+                .map(|(alias, reference)| (alias.to_string(), reference.to_string()))
+                .collect()
+        })
+    }
+
+    fn package(
+        dep_path: &str,
+        dependencies: &[(&str, &str)],
+        peer_dependencies: &[(&str, &str)],
+    ) -> (String, ResolvedPackage) {
+        let parsed = dependency_path::parse(dep_path).unwrap();
+        let snapshot = PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: "sha512-".to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: deps(peer_dependencies),
+            peer_dependencies_meta: None,
+            dependencies: deps(dependencies),
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        };
+        let package = ResolvedPackage {
+            name: parsed.name.unwrap(),
+            version: parsed.version.unwrap(),
+            depth: 0,
+            snapshot,
+        };
+        (dep_path.to_string(), package)
+    }
+
+    fn graph(
+        importer: &[(&str, &str)],
+        packages: Vec<(String, ResolvedPackage)>,
+    ) -> DependenciesGraph {
+        let mut project = ProjectSnapshot::new();
+        project.dependencies = deps(importer);
+        DependenciesGraph {
+            importers: HashMap::from([(".".to_string(), project)]),
+            packages: packages.into_iter().collect(),
+        }
+    }
+
+    fn dependencies_of<'g>(
+        graph: &'g DependenciesGraph,
+        dep_path: &str,
+    ) -> &'g ResolvedDependencies {
+        graph.packages[dep_path]
+            .snapshot
+            .dependencies
+            .as_ref()
+            .unwrap()
+    }
+
+    /// `foo` is installed twice, with the `react` of the project and with the `react` of `bar`
+    fn graph_with_two_reacts() -> DependenciesGraph {
+        graph(
+            &[("bar", "1.0.0"), ("foo", "1.0.0"), ("react", "17.0.0")],
+            vec![
+                package("/bar/1.0.0", &[("foo", "1.0.0"), ("react", "16.0.0")], &[]),
+                package("/foo/1.0.0", &[], &[("react", "^17.0.0")]),
+                package("/react/16.0.0", &[], &[]),
+                package("/react/17.0.0", &[], &[]),
+            ],
+        )
+    }
+
+    #[test]
+    fn suffix_packages_with_their_peers() {
+        let (resolved, issues) = resolve_peers(&graph_with_two_reacts(), None);
+
+        let mut dep_paths = resolved.packages.keys().cloned().collect::<Vec<_>>();
+        dep_paths.sort();
+        assert_eq!(
+            dep_paths,
+            [
+                "/bar/1.0.0",
+                "/foo/1.0.0_react@16.0.0",
+                "/foo/1.0.0_react@17.0.0",
+                "/react/16.0.0",
+                "/react/17.0.0",
+            ]
+        );
+        assert_eq!(
+            resolved.importers["."].dependencies,
+            deps(&[
+                ("bar", "1.0.0"),
+                ("foo", "1.0.0_react@17.0.0"),
+                ("react", "17.0.0")
+            ])
+        );
+        assert_eq!(
+            dependencies_of(&resolved, "/bar/1.0.0"),
+            &deps(&[("foo", "1.0.0_react@16.0.0"), ("react", "16.0.0")]).unwrap()
+        );
+        assert_eq!(
+            dependencies_of(&resolved, "/foo/1.0.0_react@17.0.0"),
+            &deps(&[("react", "17.0.0")]).unwrap()
+        );
+        assert_eq!(
+            issues.bad,
+            [PeerDependencyIssue {
+                parents: vec!["bar@1.0.0".to_string(), "foo@1.0.0".to_string()],
+                peer_name: "react".to_string(),
+                wanted_range: "^17.0.0".to_string(),
+                found_version: Some("16.0.0".to_string()),
+            }]
+        );
+        assert!(issues.missing.is_empty());
+
+        // the lockfile written from the resolved graph gives the same graph
+        let (again, _) = resolve_peers(&resolved, None);
+        let mut again_dep_paths = again.packages.keys().cloned().collect::<Vec<_>>();
+        again_dep_paths.sort();
+        assert_eq!(again_dep_paths, dep_paths);
+        assert_eq!(
+            dependencies_of(&again, "/foo/1.0.0_react@16.0.0"),
+            &deps(&[("react", "16.0.0")]).unwrap()
+        );
+    }
+
+    #[test]
+    fn transitive_and_missing_peers() {
+        let graph = graph(
+            &[("qar", "1.0.0"), ("react", "17.0.0")],
+            vec![
+                package("/qar/1.0.0", &[("foo", "1.0.0")], &[]),
+                package(
+                    "/foo/1.0.0",
+                    &[],
+                    &[("react", "^17.0.0"), ("vue", "^3.0.0")],
+                ),
+                package("/react/17.0.0", &[], &[]),
+            ],
+        );
+        let (resolved, issues) = resolve_peers(&graph, None);
+
+        let qar = &resolved.packages["/qar/1.0.0_react@17.0.0"].snapshot;
+        assert_eq!(
+            qar.transitive_peer_dependencies,
+            Some(vec!["react".to_string(), "vue".to_string()])
+        );
+        assert_eq!(qar.dependencies, deps(&[("foo", "1.0.0_react@17.0.0")]));
+        assert_eq!(
+            issues.missing,
+            [PeerDependencyIssue {
+                parents: vec!["qar@1.0.0".to_string(), "foo@1.0.0".to_string()],
+                peer_name: "vue".to_string(),
+                wanted_range: "^3.0.0".to_string(),
+                found_version: None,
+            }]
+        );
+        assert!(issues.bad.is_empty());
+    }
+
+    #[test]
+    fn peer_dependency_rules() {
+        let rules = PeerDependencyRules {
+            ignore_missing: Some(Rc::new(vec!["vu*".to_string()])),
+            allowed_versions: Some(HashMap::from([("foo>react".to_string(), "16".to_string())])),
+        };
+        let (_, issues) = resolve_peers(&graph_with_two_reacts(), Some(&rules));
+        assert!(issues.is_empty());
+
+        let graph = graph(
+            &[("foo", "1.0.0")],
+            vec![package("/foo/1.0.0", &[], &[("vue", "^3.0.0")])],
+        );
+        let (_, issues) = resolve_peers(&graph, Some(&rules));
+        assert!(issues.is_empty());
+    }
+}
@@ -0,0 +1,349 @@
+use super::extend_install_options::InstallOptions;
+use super::resolve_dependencies::UpdateOptions;
+use super::{install_project, read_lockfile};
+use anyhow::{bail, Result};
+use client::Client;
+use dependency_path::ref_to_relative;
+use lockfile_utils::types::{Lockfile, ProjectSnapshot};
+use matcher::Matcher;
+use parse_wanted_dependency::{parse_wanted_dependency, ParsedWantedDependency};
+use resolvers::base::{ResolveOptions, ResolvedVia, WantedDependency};
+use resolvers::default::Resolver;
+use resolvers::npm::ResolverFactoryOptions;
+use semver_range::{parse_version, satisfies, Range};
+use std::collections::HashMap;
+use store_connection_manager::StoreController;
+use types::{BaseManifest, DependencyField, IncludedDependencies};
+
+pub struct UpdateDependenciesOptions<'a> {
+    /// Names or patterns like `@babel/*` of the packages to update, all of them when empty.
+    /// A version, like in `foo@2`, also changes the specifier of the dependency.
+    pub patterns: &'a [&'a str],
+    /// How deep the packages are updated, direct dependencies having a depth of 0
+    pub depth: usize,
+    /// Update the direct dependencies to their latest versions, even outside of their ranges
+    pub latest: bool,
+    /// Prepended to the latest versions when the style of the range is not recognized
+    pub save_prefix: String,
+}
+
+/// A direct dependency that has a newer version to update to
+#[derive(Debug, PartialEq)]
+pub struct OutdatedDependency {
+    pub alias: String,
+    pub field: DependencyField,
+    /// The locked version, if the dependency is locked
+    pub current: Option<String>,
+    pub wanted: String,
+}
+
+/// Splits the patterns into the ones that match the names of the packages to update
+/// and the new specifiers of dependencies, like `foo@2`
+fn split_patterns(patterns: &[&str]) -> (Vec<String>, Vec<(String, String)>) {
+    let mut names = vec![];
+    let mut specs = vec![];
+    for pattern in patterns {
+        match parse_wanted_dependency(pattern) {
+            ParsedWantedDependency {
+                alias: Some(alias),
+                pref: Some(pref),
+            } if !pattern.starts_with('!') => {
+                names.push(alias.clone());
+                specs.push((alias, pref));
+            }
+            _ => names.push(pattern.to_string()),
+        }
+    }
+    (names, specs)
+}
+
+pub(crate) fn is_included(include: IncludedDependencies, field: DependencyField) -> bool {
+    match field {
+        DependencyField::OptionalDependencies => include.optional_dependencies,
+        DependencyField::Dependencies => include.dependencies,
+        DependencyField::DevDependencies => include.dev_dependencies,
+    }
+}
+
+/// The direct dependencies of the included fields that match,
+/// sorted by field and by alias
+fn selected_dependencies<'m>(
+    manifest: &'m BaseManifest,
+    matcher: &Matcher,
+    include: IncludedDependencies,
+) -> Vec<(DependencyField, &'m String, &'m String)> {
+    let mut selected = vec![];
+    for field in DependencyField::iterator().filter(|field| is_included(include, *field)) {
+        let mut deps = manifest
+            .dependencies_of(field)
+            .into_iter()
+            .flatten()
+            .filter(|(alias, _)| matcher.is_match(alias))
+            .map(|(alias, pref)| (field, alias, pref))
+            .collect::<Vec<_>>();
+        deps.sort_by_key(|(_, alias, _)| *alias);
+        selected.extend(deps);
+    }
+    selected
+}
+
+/// Splits `npm:bar@^1.0.0` into `npm:bar@` and `^1.0.0`.
+/// Returns `None` when the specifier is not a range of a package from the registry.
+fn split_registry_range(pref: &str) -> Option<(&str, &str)> {
+    let range_start = match pref.strip_prefix("npm:") {
+        Some(aliased) => "npm:".len() + aliased.get(1..)?.find('@')? + 2,
+        None => 0,
+    };
+    let range = &pref[range_start..];
+    Range::parse(range).ok()?;
+    Some((&pref[..range_start], range))
+}
+
+/// `^` or `~` for caret and tilde ranges, an empty prefix for exact versions
+fn range_prefix(range: &str) -> Option<&str> {
+    let (prefix, version) = match range.strip_prefix(['^', '~']) {
+        Some(version) => (&range[..1], version),
+        None => ("", range),
+    };
+    parse_version(version).map(|_| prefix)
+}
+
+/// The specifier of the latest version, in the style of the current specifier.
+/// Returns `None` when the specifier stays the same.
+fn latest_pref(pref: &str, latest: &str, save_prefix: &str) -> Option<String> {
+    let (alias_prefix, range) = split_registry_range(pref)?;
+    let prefix = match range_prefix(range) {
+        Some(prefix) => prefix,
+        // ranges like `*` or `1.x` are kept while the latest version satisfies them
+        None if satisfies(latest, range) => return None,
+        None => save_prefix,
+    };
+    let latest_pref = format!("{}{}{}", alias_prefix, prefix, latest);
+    (latest_pref != pref).then_some(latest_pref)
+}
+
+/// The version the dependency resolves to, if it is from the registry
+fn resolve_version(
+    resolver: &Resolver,
+    alias: &str,
+    pref: &str,
+    install_opts: &InstallOptions,
+) -> Result<Option<String>> {
+    let dir = install_opts.dir.to_string_lossy().to_string();
+    let result = resolver.resolve(
+        WantedDependency {
+            alias: Some(alias.to_string()),
+            pref: Some(pref.to_string()),
+            injected: None,
+        },
+        ResolveOptions {
+            always_try_workspace_packages: None,
+            default_tag: None,
+            project_dir: dir.clone(),
+            lockfile_dir: dir,
+            preferred_versions: &HashMap::new(),
+            prefer_workspace_packages: None,
+            registries: install_opts.registries.clone(),
+            workspace_packages: install_opts.workspace_packages.as_ref(),
+        },
+    )?;
+    if result.resolved_via != ResolvedVia::NpmRegistry {
+        return Ok(None);
+    }
+    Ok(result.manifest.and_then(|manifest| manifest.version))
+}
+
+/// The specifier that resolves to the latest version of the dependency
+fn latest_tag(pref: &str) -> Option<String> {
+    split_registry_range(pref).map(|(alias_prefix, _)| format!("{}latest", alias_prefix))
+}
+
+fn create_resolver<'c>(client: &'c Client, install_opts: &InstallOptions) -> Resolver<'c> {
+    client.create_resolver(ResolverFactoryOptions {
+        cache_dir: install_opts.cache_dir.clone(),
+        offline: install_opts.offline,
+        prefer_offline: false,
+    })
+}
+
+fn locked_version(
+    importer: &ProjectSnapshot,
+    field: DependencyField,
+    alias: &str,
+) -> Option<String> {
+    let deps = match field {
+        DependencyField::OptionalDependencies => &importer.optional_dependencies,
+        DependencyField::Dependencies => &importer.dependencies,
+        DependencyField::DevDependencies => &importer.dev_dependencies,
+    };
+    let dep_path = ref_to_relative(deps.as_ref()?.get(alias)?, alias)?;
+    dependency_path::parse(&dep_path).ok()?.version
+}
+
+/// The direct dependencies that would get a new version from an update
+pub fn outdated_dependencies(
+    client: &Client,
+    manifest: &BaseManifest,
+    opts: &UpdateDependenciesOptions,
+    install_opts: &InstallOptions,
+) -> Result<Vec<OutdatedDependency>> {
+    let resolver = create_resolver(client, install_opts);
+    let wanted_lockfile = read_lockfile(&install_opts.dir)?;
+    let importer = wanted_lockfile
+        .as_ref()
+        .and_then(|lockfile| lockfile.importers.get("."));
+    let (names, _) = split_patterns(opts.patterns);
+
+    let mut outdated = vec![];
+    for (field, alias, pref) in
+        selected_dependencies(manifest, &Matcher::new(&names), install_opts.include)
+    {
+        let wanted_pref = match latest_tag(pref) {
+            Some(latest_tag) if opts.latest => latest_tag,
+            Some(_) => pref.clone(),
+            None => continue,
+        };
+        let wanted = match resolve_version(&resolver, alias, &wanted_pref, install_opts)? {
+            Some(wanted) => wanted,
+            None => continue,
+        };
+        let current = importer.and_then(|importer| locked_version(importer, field, alias));
+        if current.as_ref() != Some(&wanted) {
+            outdated.push(OutdatedDependency {
+                alias: alias.clone(),
+                field,
+                current,
+                wanted,
+            });
+        }
+    }
+    Ok(outdated)
+}
+
+/// Resolves the matching packages again, up to the wanted depth,
+/// while the other packages keep their locked versions.
+/// Returns the updated manifest, which is not written by this function.
+pub fn update_dependencies_of_package(
+    client: &Client,
+    store_controller: &StoreController,
+    manifest: &BaseManifest,
+    opts: &UpdateDependenciesOptions,
+    install_opts: &InstallOptions,
+) -> Result<(BaseManifest, Lockfile)> {
+    let (names, specs) = split_patterns(opts.patterns);
+    let matcher = Matcher::new(&names);
+    let selected = selected_dependencies(manifest, &matcher, install_opts.include);
+    if !opts.patterns.is_empty() && selected.is_empty() {
+        bail!(
+            "NO_PACKAGE_IN_DEPENDENCIES: None of the specified packages were found in the dependencies"
+        );
+    }
+
+    let mut updated_manifest = manifest.clone();
+    for (alias, pref) in &specs {
+        let field = match selected.iter().find(|(_, selected, _)| *selected == alias) {
+            Some((field, _, _)) => *field,
+            None => bail!(
+                "NO_PACKAGE_IN_DEPENDENCIES: {} was not found in the dependencies",
+                alias
+            ),
+        };
+        updated_manifest
+            .dependencies_of_mut(field)
+            .get_or_insert_with(Default::default)
+            .insert(alias.clone(), pref.clone());
+    }
+
+    if opts.latest {
+        let resolver = create_resolver(client, install_opts);
+        for (field, alias, pref) in &selected {
+            if specs.iter().any(|(spec_alias, _)| spec_alias == *alias) {
+                continue;
+            }
+            let latest = match latest_tag(pref) {
+                Some(latest_tag) => resolve_version(&resolver, alias, &latest_tag, install_opts)?,
+                None => None,
+            };
+            if let Some(latest_pref) =
+                latest.and_then(|latest| latest_pref(pref, &latest, &opts.save_prefix))
+            {
+                updated_manifest
+                    .dependencies_of_mut(*field)
+                    .get_or_insert_with(Default::default)
+                    .insert(alias.to_string(), latest_pref);
+            }
+        }
+    }
+
+    let lockfile = install_project(
+        client,
+        store_controller,
+        &updated_manifest,
+        install_opts,
+        read_lockfile(&install_opts.dir)?,
+        Some(UpdateOptions {
+            depth: opts.depth,
+            matcher: &matcher,
+        }),
+    )?;
+    Ok((updated_manifest, lockfile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn split_names_and_specs() {
+        assert_eq!(
+            split_patterns(&["@babel/*", "foo@2", "@scope/bar@^1.0.0", "!qar", "baz"]),
+            (
+                vec![
+                    "@babel/*".to_string(),
+                    "foo".to_string(),
+                    "@scope/bar".to_string(),
+                    "!qar".to_string(),
+                    "baz".to_string(),
+                ],
+                vec![
+                    ("foo".to_string(), "2".to_string()),
+                    ("@scope/bar".to_string(), "^1.0.0".to_string()),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn keep_the_style_of_the_range() {
+        assert_eq!(
+            latest_pref("^1.0.0", "2.1.0", "^"),
+            Some("^2.1.0".to_string())
+        );
+        assert_eq!(
+            latest_pref("~1.0.0", "2.1.0", "^"),
+            Some("~2.1.0".to_string())
+        );
+        assert_eq!(
+            latest_pref("1.0.0", "2.1.0", "^"),
+            Some("2.1.0".to_string())
+        );
+        assert_eq!(
+            latest_pref("npm:bar@^1.0.0", "2.1.0", "^"),
+            Some("npm:bar@^2.1.0".to_string())
+        );
+        assert_eq!(
+            latest_pref("npm:@scope/bar@1.0.0", "2.1.0", "^"),
+            Some("npm:@scope/bar@2.1.0".to_string())
+        );
+        assert_eq!(
+            latest_pref(">=1.0.0 <2", "2.1.0", "~"),
+            Some("~2.1.0".to_string())
+        );
+        assert_eq!(latest_pref("*", "2.1.0", "^"), None);
+        assert_eq!(latest_pref("^2.1.0", "2.1.0", "^"), None);
+        assert_eq!(latest_pref("next", "2.1.0", "^"), None);
+        assert_eq!(latest_pref("workspace:^1.0.0", "2.1.0", "^"), None);
+        assert_eq!(latest_pref("file:../foo", "2.1.0", "^"), None);
+    }
+}
@@ -0,0 +1,223 @@
+use super::extend_install_options::InstallOptions;
+use super::update_dependencies::is_included;
+use anyhow::{bail, Result};
+use constants::LAYOUT_VERSION;
+use dialoguer::Confirm;
+use modules_yaml::{read_modules, Modules};
+use std::fs;
+use std::io::{stdin, ErrorKind, IsTerminal};
+use std::path::Path;
+use types::{DependencyField, IncludedDependencies};
+
+/// The pattern as it is recorded in `.modules.yaml`
+fn recorded_pattern(pattern: &[String]) -> Option<Vec<String>> {
+    (!pattern.is_empty()).then(|| pattern.to_vec())
+}
+
+fn included_fields(include: IncludedDependencies) -> String {
+    DependencyField::iterator()
+        .filter(|field| is_included(include, *field))
+        .map(|field| field.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Why the modules directory cannot be updated in place, if it cannot
+fn incompatibility(modules: &Modules, opts: &InstallOptions) -> Option<String> {
+    if modules.layout_version != LAYOUT_VERSION {
+        return Some(format!(
+            "The modules directory was created with layout version {}, the current layout version is {}.",
+            modules.layout_version, LAYOUT_VERSION
+        ));
+    }
+    if modules.store_dir != opts.store_dir {
+        return Some(format!(
+            "Unexpected store location. The dependencies at \"{}\" are currently linked from the store at \"{}\". pnpm now wants to use the store at \"{}\" to link dependencies.",
+            opts.modules_dir.display(),
+            modules.store_dir.display(),
+            opts.store_dir.display()
+        ));
+    }
+    if modules.virtual_store_dir != opts.virtual_store_dir {
+        return Some(format!(
+            "Unexpected virtual store location. The dependencies at \"{}\" are currently symlinked from the virtual store at \"{}\". pnpm now wants to use the virtual store at \"{}\" to link dependencies.",
+            opts.modules_dir.display(),
+            modules.virtual_store_dir.display(),
+            opts.virtual_store_dir.display()
+        ));
+    }
+    // the patterns of the previous install are used when they are not set explicitly
+    if opts.force_public_hoist_pattern
+        && modules.public_hoist_pattern != recorded_pattern(&opts.public_hoist_pattern)
+    {
+        return Some(
+            "This modules directory was created using a different public-hoist-pattern value."
+                .to_string(),
+        );
+    }
+    if opts.force_hoist_pattern && modules.hoist_pattern != recorded_pattern(&opts.hoist_pattern) {
+        return Some(
+            "This modules directory was created using a different hoist-pattern value.".to_string(),
+        );
+    }
+    if modules.node_linker != opts.node_linker {
+        return Some(format!(
+            "The modules directory was created with node-linker={}, the current node-linker is {}.",
+            modules.node_linker.as_str(),
+            opts.node_linker.as_str()
+        ));
+    }
+    if modules.included != opts.include {
+        return Some(format!(
+            "The modules directory at \"{}\" was installed with {}. The current install wants {}.",
+            opts.modules_dir.display(),
+            included_fields(modules.included),
+            included_fields(opts.include)
+        ));
+    }
+    None
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the state of the previous install from `.modules.yaml`. A modules directory
+/// that was installed with another layout, store, node-linker, hoist patterns
+/// or dependency fields is removed, once the user confirms it when
+/// `confirm-modules-purge` is on.
+pub fn validate_modules(opts: &InstallOptions) -> Result<Option<Modules>> {
+    let modules = match read_modules(&opts.modules_dir)? {
+        Some(modules) => modules,
+        None => return Ok(None),
+    };
+    let reason = match incompatibility(&modules, opts) {
+        Some(reason) => reason,
+        None => return Ok(Some(modules)),
+    };
+
+    eprintln!("WARN: {}", reason);
+    if opts.confirm_modules_purge {
+        if !stdin().is_terminal() {
+            bail!(
+                "ABORTED_REMOVE_MODULES_DIR_NO_TTY: Aborted removal of modules directory due to no TTY\n\nSet confirm-modules-purge to false to remove it without asking."
+            );
+        }
+        let confirmed = Confirm::new()
+            .with_prompt(format!(
+                "The modules directory at \"{}\" will be removed and reinstalled from scratch. Proceed?",
+                opts.modules_dir.display()
+            ))
+            .default(true)
+            .interact()?;
+        if !confirmed {
+            bail!("ABORTED_REMOVE_MODULES_DIR: Aborted removal of modules directory");
+        }
+    }
+
+    println!("Recreating {}", opts.modules_dir.display());
+    remove_dir(&opts.modules_dir)?;
+    // a virtual store outside of the modules directory goes with it
+    remove_dir(&modules.virtual_store_dir)?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+    use types::{NodeLinker, Registries};
+
+    fn options() -> InstallOptions {
+        InstallOptions {
+            dir: PathBuf::from("/project"),
+            modules_dir: PathBuf::from("/project/node_modules"),
+            virtual_store_dir: PathBuf::from("/project/node_modules/.pnpm"),
+            store_dir: PathBuf::from("/store/v3"),
+            hoist_pattern: vec!["*".to_string()],
+            public_hoist_pattern: vec!["*eslint*".to_string()],
+            force_hoist_pattern: false,
+            force_public_hoist_pattern: false,
+            node_linker: NodeLinker::Isolated,
+            cache_dir: PathBuf::from("/cache"),
+            registries: Registries {
+                default: "https://registry.npmjs.org/".to_string(),
+                scoped: Default::default(),
+            },
+            workspace_packages: None,
+            include: IncludedDependencies {
+                dependencies: true,
+                dev_dependencies: true,
+                optional_dependencies: true,
+            },
+            frozen_lockfile: false,
+            prefer_frozen_lockfile: true,
+            lockfile_only: false,
+            ignore_scripts: false,
+            side_effects_cache: true,
+            offline: false,
+            confirm_modules_purge: true,
+        }
+    }
+
+    /// The state that is written by an install with the options
+    fn modules(opts: &InstallOptions) -> Modules {
+        Modules {
+            hoist_pattern: recorded_pattern(&opts.hoist_pattern),
+            hoisted_dependencies: Default::default(),
+            included: opts.include,
+            layout_version: LAYOUT_VERSION,
+            node_linker: opts.node_linker,
+            pending_builds: vec![],
+            public_hoist_pattern: recorded_pattern(&opts.public_hoist_pattern),
+            skipped: vec![],
+            store_dir: opts.store_dir.clone(),
+            virtual_store_dir: opts.virtual_store_dir.clone(),
+        }
+    }
+
+    #[test]
+    fn hoist_patterns_that_are_set_explicitly() {
+        let mut opts = options();
+        let modules = modules(&opts);
+        assert_eq!(incompatibility(&modules, &opts), None);
+
+        opts.public_hoist_pattern = vec![];
+        opts.hoist_pattern = vec![];
+        assert_eq!(incompatibility(&modules, &opts), None);
+
+        opts.force_hoist_pattern = true;
+        assert_eq!(
+            incompatibility(&modules, &opts).unwrap(),
+            "This modules directory was created using a different hoist-pattern value."
+        );
+        opts.force_public_hoist_pattern = true;
+        assert_eq!(
+            incompatibility(&modules, &opts).unwrap(),
+            "This modules directory was created using a different public-hoist-pattern value."
+        );
+    }
+
+    #[test]
+    fn other_node_linker_or_dependency_fields() {
+        let mut opts = options();
+        let modules = modules(&opts);
+
+        opts.node_linker = NodeLinker::Hoisted;
+        assert_eq!(
+            incompatibility(&modules, &opts).unwrap(),
+            "The modules directory was created with node-linker=isolated, the current node-linker is hoisted."
+        );
+
+        opts.node_linker = NodeLinker::Isolated;
+        opts.include.dev_dependencies = false;
+        assert_eq!(
+            incompatibility(&modules, &opts).unwrap(),
+            "The modules directory at \"/project/node_modules\" was installed with optionalDependencies, dependencies, devDependencies. The current install wants optionalDependencies, dependencies."
+        );
+    }
+}
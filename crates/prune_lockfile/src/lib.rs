@@ -0,0 +1,240 @@
+use dependency_path::ref_to_relative;
+use lockfile_utils::types::{Lockfile, PackageSnapshot, ProjectSnapshot, ResolvedDependencies};
+use std::collections::{HashMap, HashSet};
+use types::{BaseManifest, DependencyField};
+
+/// Every package reachable from the roots
+fn walk<'l>(
+    packages: &'l HashMap<String, PackageSnapshot>,
+    roots: impl Iterator<Item = (&'l String, &'l String)>,
+    include_optional: bool,
+) -> HashSet<String> {
+    let mut reached = HashSet::new();
+    let mut stack = roots
+        .filter_map(|(alias, reference)| ref_to_relative(reference, alias))
+        .collect::<Vec<_>>();
+
+    while let Some(dep_path) = stack.pop() {
+        if !reached.insert(dep_path.clone()) {
+            continue;
+        }
+        if let Some(snapshot) = packages.get(&dep_path) {
+            let optional_dependencies = snapshot
+                .optional_dependencies
+                .iter()
+                .filter(|_| include_optional);
+            stack.extend(
+                snapshot
+                    .dependencies
+                    .iter()
+                    .chain(optional_dependencies)
+                    .flatten()
+                    .filter_map(|(alias, reference)| ref_to_relative(reference, alias)),
+            );
+        }
+    }
+
+    reached
+}
+
+fn resolved_deps(
+    importers: &HashMap<String, ProjectSnapshot>,
+    field: fn(&ProjectSnapshot) -> &Option<ResolvedDependencies>,
+) -> impl Iterator<Item = (&String, &String)> + Clone {
+    importers
+        .values()
+        .flat_map(move |importer| field(importer).iter().flatten())
+}
+
+/// Packages only used by dev dependencies are marked with `dev: true`,
+/// the ones only used in production with `dev: false`.
+/// Packages that are only reached through optional dependencies are marked as optional.
+fn set_dev_and_optional_flags(
+    importers: &HashMap<String, ProjectSnapshot>,
+    packages: &mut HashMap<String, PackageSnapshot>,
+) {
+    let dependencies = resolved_deps(importers, |importer| &importer.dependencies);
+    let optional_dependencies =
+        resolved_deps(importers, |importer| &importer.optional_dependencies);
+    let dev_dependencies = resolved_deps(importers, |importer| &importer.dev_dependencies);
+
+    let prod = walk(
+        packages,
+        dependencies.clone().chain(optional_dependencies),
+        true,
+    );
+    let dev = walk(packages, dev_dependencies.clone(), true);
+    let required = walk(packages, dependencies.chain(dev_dependencies), false);
+
+    for (dep_path, snapshot) in packages.iter_mut() {
+        snapshot.dev = match (prod.contains(dep_path), dev.contains(dep_path)) {
+            (true, true) => None,
+            (false, true) => Some(true),
+            _ => Some(false),
+        };
+        snapshot.optional = (!required.contains(dep_path)).then_some(true);
+    }
+}
+
+/// Removes the packages that no importer depends on
+/// and updates the `dev` and `optional` flags of the others
+pub fn prune_shared_lockfile(lockfile: &Lockfile) -> Lockfile {
+    let mut lockfile = lockfile.clone();
+    if let Some(packages) = &mut lockfile.packages {
+        let roots = lockfile.importers.values().flat_map(|importer| {
+            [
+                &importer.dependencies,
+                &importer.dev_dependencies,
+                &importer.optional_dependencies,
+            ]
+            .into_iter()
+            .flatten()
+            .flatten()
+        });
+        let reached = walk(packages, roots, true);
+        packages.retain(|dep_path, _| reached.contains(dep_path));
+        set_dev_and_optional_flags(&lockfile.importers, packages);
+    }
+    lockfile
+}
+
+/// Removes the dependencies of an importer that are not in its manifest anymore,
+/// together with the packages that are not used after that
+pub fn prune_lockfile(lockfile: &Lockfile, manifest: &BaseManifest, importer_id: &str) -> Lockfile {
+    let mut lockfile = lockfile.clone();
+    if let Some(importer) = lockfile.importers.get_mut(importer_id) {
+        let in_manifest = |field: DependencyField, alias: &String| {
+            manifest
+                .dependencies_of(field)
+                .is_some_and(|deps| deps.contains_key(alias))
+        };
+        for (deps, field) in [
+            (&mut importer.dependencies, DependencyField::Dependencies),
+            (
+                &mut importer.dev_dependencies,
+                DependencyField::DevDependencies,
+            ),
+            (
+                &mut importer.optional_dependencies,
+                DependencyField::OptionalDependencies,
+            ),
+        ] {
+            if let Some(resolved) = deps {
+                resolved.retain(|alias, _| in_manifest(field, alias));
+                if resolved.is_empty() {
+                    *deps = None;
+                }
+            }
+        }
+        importer
+            .specifiers
+            .retain(|alias, _| DependencyField::iterator().any(|field| in_manifest(field, alias)));
+    }
+    prune_shared_lockfile(&lockfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lockfile_utils::types::LockfileResolution;
+    use pretty_assertions::assert_eq;
+
+    fn snapshot(
+        dependencies: &[(&str, &str)],
+        optional_dependencies: &[(&str, &str)],
+    ) -> PackageSnapshot {
+        let deps = |deps: &[(&str, &str)]| {
+            (!deps.is_empty()).then(|| {
+                deps.iter()
+                    .map(|(alias, reference)| (alias.to_string(), reference.to_string()))
+                    .collect()
+            })
+        };
+        PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: "sha512-".to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            dependencies: deps(dependencies),
+            optional_dependencies: deps(optional_dependencies),
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        let mut lockfile = Lockfile::new("5.3".to_string());
+        lockfile.importers.insert(
+            ".".to_string(),
+            ProjectSnapshot {
+                specifiers: HashMap::from([
+                    ("foo".to_string(), "^1.0.0".to_string()),
+                    ("bar".to_string(), "^1.0.0".to_string()),
+                ]),
+                dependencies: Some(HashMap::from([("foo".to_string(), "1.0.0".to_string())])),
+                dev_dependencies: Some(HashMap::from([("bar".to_string(), "1.0.0".to_string())])),
+                ..Default::default()
+            },
+        );
+        lockfile.packages = Some(HashMap::from([
+            (
+                "/foo/1.0.0".to_string(),
+                snapshot(&[("shared", "1.0.0")], &[("fsevents", "2.0.0")]),
+            ),
+            (
+                "/bar/1.0.0".to_string(),
+                snapshot(&[("shared", "1.0.0")], &[]),
+            ),
+            ("/shared/1.0.0".to_string(), snapshot(&[], &[])),
+            ("/fsevents/2.0.0".to_string(), snapshot(&[], &[])),
+        ]));
+        lockfile
+    }
+
+    #[test]
+    fn dev_and_optional_flags() {
+        let lockfile = prune_shared_lockfile(&lockfile());
+
+        let packages = lockfile.packages.unwrap();
+        let flags = |dep_path: &str| (packages[dep_path].dev, packages[dep_path].optional);
+        assert_eq!(flags("/foo/1.0.0"), (Some(false), None));
+        assert_eq!(flags("/bar/1.0.0"), (Some(true), None));
+        assert_eq!(flags("/shared/1.0.0"), (None, None));
+        assert_eq!(flags("/fsevents/2.0.0"), (Some(false), Some(true)));
+    }
+
+    #[test]
+    fn prune_removed_dependency() {
+        let manifest = BaseManifest {
+            dev_dependencies: Some(HashMap::from([("bar".to_string(), "^1.0.0".to_string())])),
+            ..Default::default()
+        };
+
+        let lockfile = prune_lockfile(&lockfile(), &manifest, ".");
+
+        let importer = &lockfile.importers["."];
+        assert_eq!(
+            importer.specifiers,
+            HashMap::from([("bar".to_string(), "^1.0.0".to_string())])
+        );
+        assert_eq!(importer.dependencies, None);
+        let packages = lockfile.packages.unwrap();
+        let mut dep_paths = packages.keys().collect::<Vec<_>>();
+        dep_paths.sort();
+        assert_eq!(dep_paths, ["/bar/1.0.0", "/shared/1.0.0"]);
+        assert_eq!(packages["/shared/1.0.0"].dev, Some(true));
+    }
+}
@@ -1,8 +1,10 @@
 use anyhow::Result;
 use read_file::{read_json_file, ParsedFile};
+use serde_json::{Map, Value};
 use std::fs;
 use std::io::Write;
 use std::path::Path;
+use strip_bom::StripBom;
 use tempfile::NamedTempFile;
 use types::BaseManifest;
 
@@ -38,32 +40,59 @@ pub fn write_project_manifest(
         }
 
         fs::write(file_path, serde_yaml::to_string(manifest)?)?;
+        return Ok(());
     }
 
-    fs::create_dir_all(
-        path.parent()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_else(|| ".".to_string()),
-    )?;
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
 
     let trailing_new_line = if opts.insert_final_newline.unwrap_or(true) {
-        ""
-    } else {
         "\n"
+    } else {
+        ""
     };
 
     let json = if ext == "json5" {
         json5::to_string(&manifest)?
     } else {
+        let manifest = match serde_json::to_value(manifest)? {
+            Value::Object(fields) => Value::Object(keep_field_order(path, fields)),
+            manifest => manifest,
+        };
         serde_json::to_string_pretty(&manifest)?
     };
 
-    let mut file = NamedTempFile::new()?.persist(file_path)?;
+    // the temporary file is in the same directory, so it can be renamed to the manifest
+    let mut file = NamedTempFile::new_in(dir)?;
     write!(file, "{}{}", json, trailing_new_line)?;
+    file.persist(file_path)?;
 
     Ok(())
 }
 
+/// The fields of the manifest in the order of the manifest that is overwritten,
+/// with the fields that were added at the end
+fn keep_field_order(path: &Path, mut fields: Map<String, Value>) -> Map<String, Value> {
+    let original = fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(text.strip_bom()).ok());
+    let original = match original {
+        Some(Value::Object(original)) => original,
+        _ => return fields,
+    };
+    let mut ordered = Map::new();
+    for key in original.keys() {
+        if let Some(value) = fields.remove(key) {
+            ordered.insert(key.clone(), value);
+        }
+    }
+    ordered.extend(fields);
+    ordered
+}
+
 impl ProjectManifest {
     pub fn write_project_manifest(
         &self,
@@ -104,7 +133,7 @@ pub fn read_project_manifest<P: AsRef<Path>>(project_dir: P) -> Result<ProjectMa
             },
         }),
         Err(error) => match error.downcast_ref::<std::io::Error>() {
-            Some(file_error) => match dbg!(file_error.kind()) {
+            Some(file_error) => match file_error.kind() {
                 std::io::ErrorKind::NotFound => Ok(ProjectManifest {
                     file_name: "package.json".to_string(),
                     manifest: None,
@@ -237,12 +266,38 @@ mod tests {
         let raw_manifest = fs::read_to_string("package.json").unwrap();
         assert_eq!(
             raw_manifest,
-            "{\n  \"name\": \"foo\",\n  \"dependencies\": {\n    \"bar\": \"1.0.0\"\n  }\n}"
+            "{\n  \"name\": \"foo\",\n  \"dependencies\": {\n    \"bar\": \"1.0.0\"\n  }\n}\n"
         );
 
         assert!(std::env::set_current_dir(original_dir).is_ok())
     }
 
+    #[test]
+    fn keep_unknown_fields_in_their_order() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("package.json"),
+            "{\"name\":\"foo\",\"private\":true,\"license\":\"MIT\",\"dependencies\":{\"qar\":\"1.0.0\"}}",
+        )
+        .unwrap();
+        let manifest = read_project_manifest(temp_dir.path()).unwrap();
+
+        let mut updated_manifest = manifest.manifest.clone().unwrap();
+        updated_manifest
+            .dependencies
+            .as_mut()
+            .unwrap()
+            .insert("bar".into(), "^1.0.0".into());
+        manifest
+            .write_project_manifest(&updated_manifest, false)
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("package.json")).unwrap(),
+            "{\n  \"name\": \"foo\",\n  \"private\": true,\n  \"license\": \"MIT\",\n  \"dependencies\": {\n    \"bar\": \"^1.0.0\",\n    \"qar\": \"1.0.0\"\n  }\n}"
+        );
+    }
+
     #[test]
     fn fail_on_invalid_json() {
         let _shared = RESOURCE.lock().unwrap();
@@ -0,0 +1,250 @@
+use dependency_path::ref_to_relative;
+use lockfile_utils::types::{Lockfile, PackageSnapshot, ResolvedDependencies};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+/// A package placed in the `node_modules` tree, with the packages nested in its own `node_modules`
+#[derive(Debug, PartialEq)]
+pub struct HoistedNode {
+    pub dep_path: String,
+    pub dependencies: BTreeMap<String, HoistedNode>,
+}
+
+/// A directory of the tree being built. The root is the modules directory of the importer.
+struct Slot {
+    dep_path: String,
+    parent: Option<usize>,
+    children: BTreeMap<String, usize>,
+    /// The aliases that the packages below resolve from higher up,
+    /// so a different package with that alias cannot be placed here
+    blocked: HashSet<String>,
+}
+
+/// The dependencies of a package that are in the lockfile, sorted by alias
+fn requirements<'l>(
+    deps: impl Iterator<Item = &'l ResolvedDependencies>,
+    packages: &HashMap<String, PackageSnapshot>,
+) -> Vec<(String, String)> {
+    deps.flatten()
+        .filter_map(|(alias, reference)| {
+            let dep_path = ref_to_relative(reference, alias)?;
+            packages
+                .contains_key(&dep_path)
+                .then(|| (alias.clone(), dep_path))
+        })
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect()
+}
+
+/// Lays out the packages of the lockfile that an importer depends on as a flat tree, like npm does.
+/// Every package goes as high as it can, breadth-first, so the shallowest packages win the top
+/// of the tree, and a package that conflicts with another one of the same alias is nested
+/// under the package that depends on it. Linked dependencies are not in the tree.
+pub fn hoist(lockfile: &Lockfile, importer_id: &str) -> BTreeMap<String, HoistedNode> {
+    let no_packages = HashMap::new();
+    let packages = lockfile.packages.as_ref().unwrap_or(&no_packages);
+    let importer = match lockfile.importers.get(importer_id) {
+        Some(importer) => importer,
+        None => return BTreeMap::new(),
+    };
+
+    let mut slots = vec![Slot {
+        dep_path: String::new(),
+        parent: None,
+        children: BTreeMap::new(),
+        blocked: HashSet::new(),
+    }];
+    let direct_deps = requirements(
+        [
+            &importer.dependencies,
+            &importer.dev_dependencies,
+            &importer.optional_dependencies,
+        ]
+        .into_iter()
+        .flatten(),
+        packages,
+    );
+    let mut queue = VecDeque::from([(0, direct_deps)]);
+    while let Some((slot_id, deps)) = queue.pop_front() {
+        for (alias, dep_path) in deps {
+            // the highest slot the package can go to, unless it is already visible from here
+            let mut target = None;
+            let mut found = None;
+            let mut current = Some(slot_id);
+            while let Some(id) = current {
+                let slot = &slots[id];
+                match slot.children.get(&alias) {
+                    Some(child) if slots[*child].dep_path == dep_path => {
+                        found = Some(id);
+                        break;
+                    }
+                    Some(_) => break,
+                    // the package can still be found higher up
+                    None if slot.blocked.contains(&alias) => {}
+                    None => target = Some(id),
+                }
+                current = slot.parent;
+            }
+            let stop = match (found, target) {
+                (Some(found), _) => found,
+                (None, Some(target)) => {
+                    let child_id = slots.len();
+                    slots.push(Slot {
+                        dep_path: dep_path.clone(),
+                        parent: Some(target),
+                        children: BTreeMap::new(),
+                        blocked: HashSet::new(),
+                    });
+                    slots[target].children.insert(alias.clone(), child_id);
+                    let snapshot = &packages[&dep_path];
+                    let deps = requirements(
+                        [&snapshot.dependencies, &snapshot.optional_dependencies]
+                            .into_iter()
+                            .flatten(),
+                        packages,
+                    );
+                    queue.push_back((child_id, deps));
+                    target
+                }
+                // the same alias is taken by another package in the directory of the dependent
+                (None, None) => continue,
+            };
+            // another package placed between the dependent and the one it resolves would hide it
+            let mut current = Some(slot_id);
+            while let Some(id) = current.filter(|id| *id != stop) {
+                slots[id].blocked.insert(alias.clone());
+                current = slots[id].parent;
+            }
+        }
+    }
+
+    fn to_nodes(slots: &[Slot], slot_id: usize) -> BTreeMap<String, HoistedNode> {
+        slots[slot_id]
+            .children
+            .iter()
+            .map(|(alias, child_id)| {
+                let node = HoistedNode {
+                    dep_path: slots[*child_id].dep_path.clone(),
+                    dependencies: to_nodes(slots, *child_id),
+                };
+                (alias.clone(), node)
+            })
+            .collect()
+    }
+    to_nodes(&slots, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lockfile_utils::types::{LockfileResolution, ProjectSnapshot};
+    use pretty_assertions::assert_eq;
+
+    fn deps(deps: &[(&str, &str)]) -> Option<ResolvedDependencies> {
+        (!deps.is_empty()).then(|| {
+            deps.iter()
+                .map(|(alias, reference)| (alias.to_string(), reference.to_string()))
+                .collect()
+        })
+    }
+
+    fn snapshot(dependencies: &[(&str, &str)]) -> PackageSnapshot {
+        PackageSnapshot {
+            resolution: LockfileResolution::RegistryResolution {
+                integrity: "sha512-".to_string(),
+            },
+            id: None,
+            name: None,
+            version: None,
+            engines: None,
+            cpu: None,
+            os: None,
+            deprecated: None,
+            has_bin: None,
+            prepare: None,
+            requires_build: None,
+            bundled_dependencies: None,
+            peer_dependencies: None,
+            peer_dependencies_meta: None,
+            dependencies: deps(dependencies),
+            optional_dependencies: None,
+            transitive_peer_dependencies: None,
+            dev: None,
+            optional: None,
+        }
+    }
+
+    fn node(dep_path: &str, dependencies: Vec<(&str, HoistedNode)>) -> HoistedNode {
+        HoistedNode {
+            dep_path: dep_path.to_string(),
+            dependencies: dependencies
+                .into_iter()
+                .map(|(alias, node)| (alias.to_string(), node))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn nest_the_conflicting_versions() {
+        let mut lockfile = Lockfile::new("5.3".to_string());
+        let mut importer = ProjectSnapshot::new();
+        importer.dependencies = deps(&[("bar", "1.0.0"), ("foo", "1.0.0"), ("qar", "1.0.0")]);
+        lockfile.importers.insert(".".to_string(), importer);
+        lockfile.packages = Some(HashMap::from([
+            ("/bar/1.0.0".to_string(), snapshot(&[])),
+            ("/bar/2.0.0".to_string(), snapshot(&[("baz", "1.0.0")])),
+            ("/baz/1.0.0".to_string(), snapshot(&[("foo", "1.0.0")])),
+            (
+                "/foo/1.0.0".to_string(),
+                snapshot(&[("bar", "2.0.0"), ("baz", "1.0.0"), ("link", "link:../link")]),
+            ),
+            ("/qar/1.0.0".to_string(), snapshot(&[("bar", "2.0.0")])),
+        ]));
+
+        let expected = BTreeMap::from([
+            ("bar".to_string(), node("/bar/1.0.0", vec![])),
+            ("baz".to_string(), node("/baz/1.0.0", vec![])),
+            (
+                "foo".to_string(),
+                node("/foo/1.0.0", vec![("bar", node("/bar/2.0.0", vec![]))]),
+            ),
+            (
+                "qar".to_string(),
+                node("/qar/1.0.0", vec![("bar", node("/bar/2.0.0", vec![]))]),
+            ),
+        ]);
+        assert_eq!(hoist(&lockfile, "."), expected);
+        assert!(hoist(&lockfile, "packages/foo").is_empty());
+    }
+
+    #[test]
+    fn dont_hide_the_dependencies_resolved_from_above() {
+        // foo resolves bar@1.0.0 from the root, so bar@2.0.0 that zoo@2.0.0 needs cannot go into foo
+        let mut lockfile = Lockfile::new("5.3".to_string());
+        let mut importer = ProjectSnapshot::new();
+        importer.dependencies = deps(&[("bar", "1.0.0"), ("foo", "1.0.0"), ("zoo", "1.0.0")]);
+        lockfile.importers.insert(".".to_string(), importer);
+        lockfile.packages = Some(HashMap::from([
+            ("/bar/1.0.0".to_string(), snapshot(&[])),
+            ("/bar/2.0.0".to_string(), snapshot(&[])),
+            (
+                "/foo/1.0.0".to_string(),
+                snapshot(&[("bar", "1.0.0"), ("zoo", "2.0.0")]),
+            ),
+            ("/zoo/1.0.0".to_string(), snapshot(&[])),
+            ("/zoo/2.0.0".to_string(), snapshot(&[("bar", "2.0.0")])),
+        ]));
+
+        let tree = hoist(&lockfile, ".");
+        assert_eq!(
+            tree["foo"],
+            node(
+                "/foo/1.0.0",
+                vec![(
+                    "zoo",
+                    node("/zoo/2.0.0", vec![("bar", node("/bar/2.0.0", vec![]))])
+                )]
+            )
+        );
+    }
+}
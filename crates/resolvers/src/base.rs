@@ -1,9 +1,9 @@
 use std::collections::HashMap;
-use types::BaseManifest;
+use types::{BaseManifest, Registries};
 
-pub struct WorkspacePackage<'a> {
+pub struct WorkspacePackage {
     pub dir: String,
-    pub manifest: &'a BaseManifest,
+    pub manifest: BaseManifest,
 }
 
 pub struct WantedDependency {
@@ -13,26 +13,32 @@ pub struct WantedDependency {
 }
 
 pub struct ResolveOptions<'a> {
-    always_try_workspace_packages: Option<bool>,
-    default_tag: Option<String>,
+    pub always_try_workspace_packages: Option<bool>,
+    pub default_tag: Option<String>,
     pub project_dir: String,
     pub lockfile_dir: String,
-    preferred_versions: PreferredVersions,
-    prefer_workspace_packages: Option<bool>,
-    registry: String,
-    workspace_packages: Option<WorkspacePackages<'a>>,
+    /// The versions that are picked over the newer ones when they satisfy the wanted range
+    pub preferred_versions: &'a PreferredVersions,
+    pub prefer_workspace_packages: Option<bool>,
+    pub registries: Registries,
+    /// The projects of the workspace, that `workspace:` specifiers are resolved from
+    pub workspace_packages: Option<&'a WorkspacePackages>,
 }
 
+/// The version selectors by package name
 pub type PreferredVersions = HashMap<String, VersionSelectors>;
 pub type VersionSelectors = HashMap<String, Version>;
 
+/// The kind of a version selector
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Version {
     Version,
     Range,
     Tag,
 }
 
-pub type WorkspacePackages<'a> = HashMap<String, HashMap<String, WorkspacePackage<'a>>>;
+/// The projects of the workspace by name and version
+pub type WorkspacePackages = HashMap<String, HashMap<String, WorkspacePackage>>;
 
 /// This type looks almost exactly the same as `LockfileResolution`
 /// inside @pnpm/lockfile-utils/types
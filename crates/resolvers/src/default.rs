@@ -1,22 +1,18 @@
 use crate::base::{ResolveOptions, ResolveResult, WantedDependency};
 use crate::git;
 use crate::local::{self, ResolveLocalOpts, WantedLocalDependency};
-// use crate::npm::NpmResolver;
+use crate::npm::{resolve_from_workspace, NpmResolver};
 use crate::tarball;
 use anyhow::{bail, Result};
+use pick_registry_for_package::pick_registry_for_package;
 
-struct Resolver {
-    // npm_resolver: NpmResolver,
+pub struct Resolver<'a> {
+    pub npm_resolver: NpmResolver<'a>,
 }
 
-impl Resolver {
-    pub fn new(// registry: &Registry, credentials: &Credentials, pnpm_options: ResolverFactoryOpts
-    ) -> Self {
-        // let resolve_from_npm = NpmResolver::new(registry, credentials, &pnpm_options);
-
-        Resolver {
-            // npm_resolver: resolve_from_npm,
-        }
+impl<'a> Resolver<'a> {
+    pub fn new(npm_resolver: NpmResolver<'a>) -> Self {
+        Resolver { npm_resolver }
     }
 
     pub fn resolve(
@@ -24,6 +20,29 @@ impl Resolver {
         wanted_dependency: WantedDependency,
         opts: ResolveOptions,
     ) -> Result<ResolveResult> {
+        let registry = pick_registry_for_package(
+            &opts.registries,
+            wanted_dependency.alias.as_deref().unwrap_or_default(),
+            wanted_dependency.pref.as_deref(),
+        );
+        if let Some(resolution) = resolve_from_workspace(
+            wanted_dependency.pref.as_deref().unwrap_or_default(),
+            wanted_dependency.alias.as_deref(),
+            &opts.project_dir,
+            opts.workspace_packages,
+        )? {
+            return Ok(resolution);
+        }
+        if let Some(resolution) = self.npm_resolver.resolve(
+            wanted_dependency.pref.as_deref().unwrap_or_default(),
+            wanted_dependency.alias.as_deref(),
+            opts.default_tag.as_deref().unwrap_or("latest"),
+            registry,
+            opts.preferred_versions,
+        )? {
+            return Ok(resolution);
+        }
+
         let resolution = wanted_dependency.pref.as_ref().and_then(|pref| {
             tarball::resolve_tarball(pref)
                 .or_else(|| git::resolve_from_git(pref))
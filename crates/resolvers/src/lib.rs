@@ -2,4 +2,5 @@ pub mod base;
 pub mod default;
 pub mod git;
 pub mod local;
+pub mod npm;
 pub mod tarball;
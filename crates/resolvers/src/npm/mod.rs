@@ -1,21 +1,391 @@
+mod parse_pref;
+mod pick_package;
+mod resolve_from_workspace;
+
+use crate::base::{PreferredVersions, Resolution, ResolveResult, ResolvedVia};
+use anyhow::{bail, Context, Result};
+use fetch::FetchFromRegistry;
+pub use parse_pref::{parse_pref, RegistryPackageSpec, RegistryPackageSpecType};
+pub use pick_package::{pick_package, PackageDist, PackageInRegistry, PackageMeta};
+pub use resolve_from_workspace::resolve_from_workspace;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use types::BaseManifest;
+use url::Url;
+
 pub struct ResolverFactoryOptions {
-    cache_dir: String,
-    full_metadata: Option<bool>,
-    offline: Option<bool>,
-    prefer_offline: Option<bool>,
-    retry: Option<RetryTimeoutOptions>,
-    timeout: Option<i64>,
+    /// The metadata of the packages is cached under `<cache_dir>/metadata`
+    pub cache_dir: PathBuf,
+    /// Only the cached metadata is used
+    pub offline: bool,
+    /// The cached metadata is used when there is any, even if it is stale
+    pub prefer_offline: bool,
+}
+
+/// Returns the authorization header to send to the given registry
+pub type GetAuthHeaderValue<'a> = Box<dyn Fn(&str) -> Result<Option<String>> + Send + Sync + 'a>;
+
+pub struct NpmResolver<'a> {
+    fetch_from_registry: &'a FetchFromRegistry,
+    get_auth_header_value: GetAuthHeaderValue<'a>,
+    opts: ResolverFactoryOptions,
+    /// Every package is requested only once per run
+    meta_cache: Mutex<HashMap<String, Arc<PackageMeta>>>,
+}
+
+/// The host of the registry, as it is used in the IDs of packages,
+/// e.g. `registry.npmjs.org` or `localhost+4873`
+pub fn encode_registry(registry: &str) -> Option<String> {
+    let url = Url::parse(registry).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}+{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn same_host(url: &str, registry: &str) -> bool {
+    let host = |url: &str| {
+        Url::parse(url)
+            .ok()
+            .and_then(|url| Some((url.host_str()?.to_string(), url.port_or_known_default())))
+    };
+    host(url) == host(registry)
+}
+
+/// Old packages only have the sha1 checksum of their tarballs, in hex
+fn integrity_from_shasum(shasum: &str) -> Option<String> {
+    let bytes = (0..shasum.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(shasum.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("sha1-{}", base64::encode(bytes)))
+}
+
+impl<'a> NpmResolver<'a> {
+    pub fn new(
+        fetch_from_registry: &'a FetchFromRegistry,
+        get_auth_header_value: GetAuthHeaderValue<'a>,
+        opts: ResolverFactoryOptions,
+    ) -> Self {
+        Self {
+            fetch_from_registry,
+            get_auth_header_value,
+            opts,
+            meta_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cache_path(&self, name: &str, registry: &str) -> PathBuf {
+        self.opts
+            .cache_dir
+            .join("metadata")
+            .join(encode_registry(registry).unwrap_or_default())
+            .join(format!("{}.json", name))
+    }
+
+    fn read_cached_meta(&self, name: &str, registry: &str) -> Option<PackageMeta> {
+        let content = fs::read(self.cache_path(name, registry)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    fn fetch_meta_from_registry(&self, name: &str, registry: &str) -> Result<PackageMeta> {
+        // the slash of scoped packages is escaped, like in `@foo%2fbar`
+        let url = format!("{}{}", registry, name.replace('/', "%2f"));
+        let auth_header_value = (self.get_auth_header_value)(registry)?;
+        let mut content = vec![];
+        self.fetch_from_registry
+            .fetch(&url, auth_header_value.as_deref())?
+            .read_to_end(&mut content)
+            .with_context(|| format!("FETCH_META: Could not read the response of {}", url))?;
+        let meta = serde_json::from_slice(&content).with_context(|| {
+            format!(
+                "BAD_PACKAGE_META: The registry returned invalid JSON for {}",
+                url
+            )
+        })?;
+
+        // failing to cache the metadata doesn't fail the resolution
+        let cache_path = self.cache_path(name, registry);
+        if let Some(parent) = cache_path.parent() {
+            let _ = fs::create_dir_all(parent).and_then(|_| fs::write(&cache_path, &content));
+        }
+
+        Ok(meta)
+    }
+
+    pub fn fetch_meta(&self, name: &str, registry: &str) -> Result<Arc<PackageMeta>> {
+        let key = format!("{}{}", registry, name);
+        if let Some(meta) = self.meta_cache.lock().unwrap().get(&key) {
+            return Ok(meta.clone());
+        }
+
+        let cached_meta = if self.opts.offline || self.opts.prefer_offline {
+            self.read_cached_meta(name, registry)
+        } else {
+            None
+        };
+        let meta = match cached_meta {
+            Some(meta) => meta,
+            None if self.opts.offline => bail!(
+                "NO_OFFLINE_META: Failed to resolve {} in package mirror {}",
+                name,
+                self.cache_path(name, registry).display()
+            ),
+            None => self.fetch_meta_from_registry(name, registry)?,
+        };
+
+        let meta = Arc::new(meta);
+        self.meta_cache.lock().unwrap().insert(key, meta.clone());
+        Ok(meta)
+    }
+
+    /// Returns `None` when the pref is not for a package from the registry
+    pub fn resolve(
+        &self,
+        pref: &str,
+        alias: Option<&str>,
+        default_tag: &str,
+        registry: &str,
+        preferred_versions: &PreferredVersions,
+    ) -> Result<Option<ResolveResult>> {
+        let spec = match parse_pref(pref, alias, default_tag) {
+            Some(spec) => spec,
+            None => return Ok(None),
+        };
+
+        let meta = self.fetch_meta(&spec.name, registry)?;
+        let (version, package) =
+            match pick_package(&meta, &spec, preferred_versions.get(&spec.name)) {
+                Some(picked) => picked,
+                None => bail!(
+                    "NO_MATCHING_VERSION: No matching version found for {}@{}",
+                    spec.name,
+                    spec.fetch_spec
+                ),
+            };
+
+        let manifest: BaseManifest = serde_json::from_value(package.manifest.clone())
+            .with_context(|| {
+                format!(
+                    "BAD_PACKAGE_MANIFEST: Invalid manifest of {}@{}",
+                    spec.name, version
+                )
+            })?;
+        let integrity = package
+            .dist
+            .integrity
+            .clone()
+            .or_else(|| integrity_from_shasum(package.dist.shasum.as_deref()?));
+        // the credentials of the registry may be needed to download the tarball
+        let tarball_registry =
+            (!same_host(&package.dist.tarball, registry)).then(|| registry.to_string());
+        let normalized_pref = match spec.r#type {
+            RegistryPackageSpecType::Tag => format!("^{}", version),
+            _ => spec.normalized_pref,
+        };
+
+        Ok(Some(ResolveResult {
+            id: format!(
+                "{}/{}/{}",
+                encode_registry(registry).unwrap_or_default(),
+                spec.name,
+                version
+            ),
+            latest: meta.dist_tags.get("latest").cloned(),
+            manifest: Some(manifest),
+            normalized_pref,
+            resolution: Resolution::TarballResolution {
+                tarball: package.dist.tarball.clone(),
+                integrity,
+                registry: tarball_registry,
+            },
+            resolved_via: ResolvedVia::NpmRegistry,
+        }))
+    }
 }
 
-pub struct NpmResolver;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_stub::serve_documents;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    fn resolver<'a>(
+        fetch_from_registry: &'a FetchFromRegistry,
+        cache_dir: PathBuf,
+        offline: bool,
+    ) -> NpmResolver<'a> {
+        NpmResolver::new(
+            fetch_from_registry,
+            Box::new(|_| Ok(None)),
+            ResolverFactoryOptions {
+                cache_dir,
+                offline,
+                prefer_offline: false,
+            },
+        )
+    }
+
+    #[test]
+    fn resolve_from_registry() {
+        let registry = serve_documents(HashMap::from([(
+            "/foo".to_string(),
+            serde_json::json!({
+                "name": "foo",
+                "dist-tags": { "latest": "1.0.0" },
+                "versions": {
+                    "1.0.0": {
+                        "name": "foo",
+                        "version": "1.0.0",
+                        "repository": { "type": "git", "url": "https://github.com/foo/foo" },
+                        "dist": {
+                            "shasum": "0a4b4c",
+                            "tarball": "https://cdn.example.com/foo/-/foo-1.0.0.tgz",
+                        },
+                    },
+                },
+            })
+            .to_string(),
+        )]));
+        let fetch_from_registry = FetchFromRegistry::new(Default::default()).unwrap();
+        let cache_dir = tempdir().unwrap();
+
+        let result = resolver(&fetch_from_registry, cache_dir.path().to_path_buf(), false)
+            .resolve("", Some("foo"), "latest", &registry, &HashMap::new())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            result.id,
+            format!("{}/foo/1.0.0", encode_registry(&registry).unwrap())
+        );
+        assert_eq!(result.normalized_pref, "^1.0.0");
+        assert_eq!(result.latest, Some("1.0.0".to_string()));
+        assert_eq!(
+            result.resolution,
+            Resolution::TarballResolution {
+                tarball: "https://cdn.example.com/foo/-/foo-1.0.0.tgz".to_string(),
+                integrity: Some("sha1-CktM".to_string()),
+                registry: Some(registry.clone()),
+            }
+        );
+
+        // the metadata is cached for offline installs
+        let offline_result = resolver(&fetch_from_registry, cache_dir.path().to_path_buf(), true)
+            .resolve("1.0.0", Some("foo"), "latest", &registry, &HashMap::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(offline_result.id, result.id);
+
+        let err = resolver(&fetch_from_registry, cache_dir.path().to_path_buf(), true)
+            .resolve("1.0.0", Some("bar"), "latest", &registry, &HashMap::new())
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("NO_OFFLINE_META"));
+
+        let err = resolver(&fetch_from_registry, cache_dir.path().to_path_buf(), false)
+            .resolve("^2.0.0", Some("foo"), "latest", &registry, &HashMap::new())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "NO_MATCHING_VERSION: No matching version found for foo@^2.0.0"
+        );
+    }
+
+    #[test]
+    fn resolve_from_the_registry_of_the_scope() {
+        let package = |name: &str, tarball: &str| {
+            serde_json::json!({
+                "name": name,
+                "dist-tags": { "latest": "1.0.0" },
+                "versions": {
+                    "1.0.0": {
+                        "name": name,
+                        "version": "1.0.0",
+                        "dist": { "integrity": "sha512-AAAA", "tarball": tarball },
+                    },
+                },
+            })
+            .to_string()
+        };
+        let default_registry = serve_documents(HashMap::from([(
+            "/bar".to_string(),
+            package("bar", "https://registry.npmjs.org/bar/-/bar-1.0.0.tgz"),
+        )]));
+        let scoped_registry = serve_documents(HashMap::from([(
+            "/@foo%2fbar".to_string(),
+            package("@foo/bar", "https://cdn.foo.com/@foo/bar/-/bar-1.0.0.tgz"),
+        )]));
+        let fetch_from_registry = FetchFromRegistry::new(Default::default()).unwrap();
+        let cache_dir = tempdir().unwrap();
+        let resolver = crate::default::Resolver::new(resolver(
+            &fetch_from_registry,
+            cache_dir.path().to_path_buf(),
+            false,
+        ));
+        let resolve = |alias: &str, pref: &str| {
+            resolver
+                .resolve(
+                    crate::base::WantedDependency {
+                        injected: None,
+                        pref: Some(pref.to_string()),
+                        alias: Some(alias.to_string()),
+                    },
+                    crate::base::ResolveOptions {
+                        always_try_workspace_packages: None,
+                        default_tag: None,
+                        project_dir: ".".to_string(),
+                        lockfile_dir: ".".to_string(),
+                        preferred_versions: &HashMap::new(),
+                        prefer_workspace_packages: None,
+                        registries: types::Registries {
+                            default: default_registry.clone(),
+                            scoped: HashMap::from([("@foo".to_string(), scoped_registry.clone())]),
+                        },
+                        workspace_packages: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        let result = resolve("@foo/bar", "^1.0.0");
+        assert_eq!(
+            result.id,
+            format!(
+                "{}/@foo/bar/1.0.0",
+                encode_registry(&scoped_registry).unwrap()
+            )
+        );
+        assert_eq!(
+            result.resolution,
+            Resolution::TarballResolution {
+                tarball: "https://cdn.foo.com/@foo/bar/-/bar-1.0.0.tgz".to_string(),
+                integrity: Some("sha512-AAAA".to_string()),
+                registry: Some(scoped_registry.clone()),
+            }
+        );
 
-impl NpmResolver {
-    pub fn new<F>(registry: Registry, get_credentials: F, opts: ResolverFactoryOptions) -> Self
-    where
-        F: Fn() -> Credentials,
-    {
-        // figure a way to memoize this
+        // the alias is installed from the registry of the aliased package
+        let result = resolve("bar", "npm:@foo/bar@1.0.0");
+        assert_eq!(result.normalized_pref, "npm:@foo/bar@1.0.0");
+        assert_eq!(
+            result.id,
+            format!(
+                "{}/@foo/bar/1.0.0",
+                encode_registry(&scoped_registry).unwrap()
+            )
+        );
 
-        Self
+        let result = resolve("bar", "1");
+        assert_eq!(
+            result.id,
+            format!("{}/bar/1.0.0", encode_registry(&default_registry).unwrap())
+        );
     }
 }
@@ -0,0 +1,128 @@
+use semver_range::{parse_version, Range};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegistryPackageSpecType {
+    Tag,
+    Version,
+    Range,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegistryPackageSpec {
+    pub name: String,
+    pub fetch_spec: String,
+    pub r#type: RegistryPackageSpecType,
+    pub normalized_pref: String,
+}
+
+/// Tags are anything that is left unchanged by `encodeURIComponent`
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '!' | '~' | '*' | '\'' | '(' | ')')
+        })
+}
+
+fn parse_spec(name: &str, fetch_spec: &str) -> Option<RegistryPackageSpec> {
+    let fetch_spec = fetch_spec.trim();
+    let r#type = if parse_version(fetch_spec).is_some() {
+        RegistryPackageSpecType::Version
+    } else if Range::parse(fetch_spec).is_ok() {
+        RegistryPackageSpecType::Range
+    } else if is_valid_tag(fetch_spec) {
+        RegistryPackageSpecType::Tag
+    } else {
+        return None;
+    };
+
+    Some(RegistryPackageSpec {
+        name: name.to_string(),
+        fetch_spec: fetch_spec.to_string(),
+        r#type,
+        normalized_pref: fetch_spec.to_string(),
+    })
+}
+
+/// Splits `foo@1.0.0` or `@foo/bar@1.0.0` into the name and the spec.
+/// The `@` of the scope is skipped when looking for the separator.
+fn split_name_and_spec(aliased: &str) -> (&str, Option<&str>) {
+    let start = usize::from(aliased.starts_with('@'));
+    match aliased[start..].find('@') {
+        Some(index) => (
+            &aliased[..start + index],
+            Some(&aliased[start + index + 1..]),
+        ),
+        None => (aliased, None),
+    }
+}
+
+/// Returns `None` when the pref isn't a version, range or tag
+/// of a package from the registry
+pub fn parse_pref(
+    pref: &str,
+    alias: Option<&str>,
+    default_tag: &str,
+) -> Option<RegistryPackageSpec> {
+    if let Some(aliased) = pref.strip_prefix("npm:") {
+        let (name, fetch_spec) = split_name_and_spec(aliased);
+        let mut spec = parse_spec(name, fetch_spec.unwrap_or(default_tag))?;
+        spec.normalized_pref = format!("npm:{}@{}", spec.name, spec.normalized_pref);
+        return Some(spec);
+    }
+
+    let pref = if pref.is_empty() { default_tag } else { pref };
+    parse_spec(alias?, pref)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_registry_prefs() {
+        let spec = |name: &str, fetch_spec: &str, r#type, normalized_pref: &str| {
+            Some(RegistryPackageSpec {
+                name: name.to_string(),
+                fetch_spec: fetch_spec.to_string(),
+                r#type,
+                normalized_pref: normalized_pref.to_string(),
+            })
+        };
+
+        assert_eq!(
+            parse_pref("1.0.0", Some("foo"), "latest"),
+            spec("foo", "1.0.0", RegistryPackageSpecType::Version, "1.0.0")
+        );
+        assert_eq!(
+            parse_pref("^1.0.0 || 2", Some("foo"), "latest"),
+            spec(
+                "foo",
+                "^1.0.0 || 2",
+                RegistryPackageSpecType::Range,
+                "^1.0.0 || 2"
+            )
+        );
+        assert_eq!(
+            parse_pref("", Some("foo"), "latest"),
+            spec("foo", "latest", RegistryPackageSpecType::Tag, "latest")
+        );
+        assert_eq!(
+            parse_pref("npm:@bar/qar@~2.1.0", Some("foo"), "latest"),
+            spec(
+                "@bar/qar",
+                "~2.1.0",
+                RegistryPackageSpecType::Range,
+                "npm:@bar/qar@~2.1.0"
+            )
+        );
+        assert_eq!(
+            parse_pref("npm:qar", None, "next"),
+            spec("qar", "next", RegistryPackageSpecType::Tag, "npm:qar@next")
+        );
+        assert_eq!(parse_pref("file:../foo", Some("foo"), "latest"), None);
+        assert_eq!(parse_pref("github:foo/bar", Some("foo"), "latest"), None);
+        assert_eq!(parse_pref("1.0.0", None, "latest"), None);
+    }
+}
@@ -1,37 +1,211 @@
+use super::parse_pref::{RegistryPackageSpec, RegistryPackageSpecType};
+use crate::base::{Version as SelectorType, VersionSelectors};
+use semver::Version;
+use semver_range::{parse_version, Range};
+use serde::Deserialize;
 use std::collections::HashMap;
 
+/// The document the registry returns for a package
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct PackageMeta {
-    // TODO: serde rename to 'dist-tag'
-    dist_tag: HashMap<String, String>,
-    versions: HashMap<String, PackageInRegistry>,
-    cached_at: Option<i64>,
+    pub name: String,
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub versions: HashMap<String, PackageInRegistry>,
 }
 
-// maybe convert to a trait
-struct PackageMetaCache;
-
-impl PackageMetaCache {
-    pub fn get(&self, key: &str) -> Option<PackageMeta> {}
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PackageInRegistry {
+    pub dist: PackageDist,
+    /// The rest of the manifest of the version
+    #[serde(flatten)]
+    pub manifest: serde_json::Value,
+}
 
-    pub fn set(&self, key: &str) {}
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PackageDist {
+    pub integrity: Option<String>,
+    pub shasum: Option<String>,
+    pub tarball: String,
 }
 
-pub struct PackageInRegistry {
-    manifest: PackageManifest,
-    dist: PackageDist,
+/// The newest version in the range that is also selected by a preferred version or range,
+/// so a package already in the dependency graph is picked over a newer one
+fn pick_preferred_version<'a>(
+    meta: &'a PackageMeta,
+    range: &Range,
+    preferred: &VersionSelectors,
+) -> Option<&'a str> {
+    let preferred_ranges = preferred
+        .iter()
+        .filter(|(_, selector_type)| **selector_type == SelectorType::Range)
+        .filter_map(|(selector, _)| Range::parse(selector).ok())
+        .collect::<Vec<_>>();
+    meta.versions
+        .keys()
+        .filter_map(|key| Some((parse_version(key)?, key.as_str())))
+        .filter(|(version, key)| {
+            range.satisfies(version)
+                && (preferred.get(*key) == Some(&SelectorType::Version)
+                    || preferred_ranges
+                        .iter()
+                        .any(|preferred_range| preferred_range.satisfies(version)))
+        })
+        .max_by(|(version_1, _), (version_2, _)| version_1.cmp(version_2))
+        .map(|(_, key)| key)
 }
 
-pub struct PackageDist {
-    integrity: Option<String>,
-    shasum: String,
-    tarball: String,
+fn pick_version_by_range<'a>(meta: &'a PackageMeta, range: &Range) -> Option<&'a str> {
+    // the latest tag wins when it satisfies the range,
+    // even if there are newer versions
+    if let Some(latest) = meta.dist_tags.get("latest") {
+        if meta.versions.contains_key(latest)
+            && parse_version(latest).is_some_and(|version| range.satisfies(&version))
+        {
+            return Some(latest);
+        }
+    }
+
+    let versions = meta
+        .versions
+        .keys()
+        .filter_map(|key| Some((parse_version(key)?, key.as_str())))
+        .collect::<Vec<(Version, &str)>>();
+    let max = semver_range::max_satisfying(versions.iter().map(|(version, _)| version), range)?;
+    versions
+        .iter()
+        .find(|(version, _)| version == max)
+        .map(|(_, key)| *key)
 }
 
-pub struct PickPackageOptions {
-    auth_header_value: Option<String>,
-    preferred_version_selectors: Option<VersionSelectors>,
-    registry: String,
-    dry_run: bool,
+/// Picks the version of the package that matches the spec.
+/// A range prefers the versions selected by `preferred`.
+pub fn pick_package<'a>(
+    meta: &'a PackageMeta,
+    spec: &RegistryPackageSpec,
+    preferred: Option<&VersionSelectors>,
+) -> Option<(&'a str, &'a PackageInRegistry)> {
+    let version = match spec.r#type {
+        RegistryPackageSpecType::Tag => meta.dist_tags.get(&spec.fetch_spec)?.as_str(),
+        RegistryPackageSpecType::Version => {
+            let wanted = parse_version(&spec.fetch_spec)?;
+            meta.versions
+                .keys()
+                .find(|key| parse_version(key).as_ref() == Some(&wanted))?
+                .as_str()
+        }
+        RegistryPackageSpecType::Range => {
+            let range = Range::parse(&spec.fetch_spec).ok()?;
+            preferred
+                .and_then(|preferred| pick_preferred_version(meta, &range, preferred))
+                .or_else(|| pick_version_by_range(meta, &range))?
+        }
+    };
+
+    meta.versions
+        .get_key_value(version)
+        .map(|(version, package)| (version.as_str(), package))
 }
 
-pub fn pick_package() {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn meta() -> PackageMeta {
+        let version = |version: &str| {
+            (
+                version.to_string(),
+                json!({
+                    "name": "foo",
+                    "version": version,
+                    "dist": { "tarball": format!("https://registry.npmjs.org/foo/-/foo-{}.tgz", version) },
+                }),
+            )
+        };
+        let versions = ["1.0.0", "1.1.0", "1.2.0", "2.0.0-beta.1"]
+            .into_iter()
+            .map(version)
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::from_value(json!({
+            "name": "foo",
+            "dist-tags": { "latest": "1.1.0", "next": "2.0.0-beta.1" },
+            "versions": versions,
+        }))
+        .unwrap()
+    }
+
+    fn spec(fetch_spec: &str, r#type: RegistryPackageSpecType) -> RegistryPackageSpec {
+        RegistryPackageSpec {
+            name: "foo".to_string(),
+            fetch_spec: fetch_spec.to_string(),
+            r#type,
+            normalized_pref: fetch_spec.to_string(),
+        }
+    }
+
+    #[test]
+    fn pick_matching_version() {
+        let meta = meta();
+        let picked = |fetch_spec, r#type| {
+            pick_package(&meta, &spec(fetch_spec, r#type), None).map(|(version, _)| version)
+        };
+
+        assert_eq!(
+            picked("next", RegistryPackageSpecType::Tag),
+            Some("2.0.0-beta.1")
+        );
+        assert_eq!(
+            picked("1.0.0", RegistryPackageSpecType::Version),
+            Some("1.0.0")
+        );
+        // prefers the latest tag
+        assert_eq!(
+            picked("^1.0.0", RegistryPackageSpecType::Range),
+            Some("1.1.0")
+        );
+        assert_eq!(
+            picked(">1.1.0", RegistryPackageSpecType::Range),
+            Some("1.2.0")
+        );
+        assert_eq!(picked("^3.0.0", RegistryPackageSpecType::Range), None);
+        assert_eq!(picked("beta", RegistryPackageSpecType::Tag), None);
+    }
+
+    #[test]
+    fn pick_preferred_version() {
+        let meta = meta();
+        let picked = |fetch_spec, preferred: &[(&str, SelectorType)]| {
+            let preferred = preferred
+                .iter()
+                .map(|(selector, selector_type)| (selector.to_string(), *selector_type))
+                .collect::<VersionSelectors>();
+            pick_package(
+                &meta,
+                &spec(fetch_spec, RegistryPackageSpecType::Range),
+                Some(&preferred),
+            )
+            .map(|(version, _)| version)
+        };
+
+        assert_eq!(
+            picked("^1.0.0", &[("1.0.0", SelectorType::Version)]),
+            Some("1.0.0")
+        );
+        assert_eq!(
+            picked("^1.0.0", &[("~1.2.0", SelectorType::Range)]),
+            Some("1.2.0")
+        );
+        // a preferred version out of the range is not picked
+        assert_eq!(
+            picked(">=1.1.0", &[("1.0.0", SelectorType::Version)]),
+            Some("1.1.0")
+        );
+        assert_eq!(
+            picked("^1.0.0", &[("latest", SelectorType::Tag)]),
+            Some("1.1.0")
+        );
+    }
+}
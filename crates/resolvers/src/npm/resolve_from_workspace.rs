@@ -0,0 +1,171 @@
+use crate::base::{Resolution, ResolveResult, ResolvedVia, WorkspacePackages};
+use anyhow::{bail, Result};
+use semver_range::{parse_version, Range};
+use std::path::Path;
+
+/// The name and the range of a `workspace:` specifier, like `workspace:^1.0.0`
+/// or `workspace:foo@*`. `^` and `~` alone stand for any version.
+fn parse_workspace_pref<'p>(pref: &'p str, alias: Option<&'p str>) -> Option<(&'p str, &'p str)> {
+    let spec = pref.strip_prefix("workspace:")?;
+    // `workspace:../foo` is a path, which the local resolver handles
+    if spec.starts_with(['.', '/', '_']) {
+        return None;
+    }
+    let (name, range) = match spec.get(1..).and_then(|rest| rest.find('@')) {
+        Some(at) => (&spec[..=at], &spec[at + 2..]),
+        None => (alias?, spec),
+    };
+    let range = match range {
+        "^" | "~" | "" => "*",
+        range => range,
+    };
+    Some((name, range))
+}
+
+/// Resolves a `workspace:` specifier to the newest project of the workspace that satisfies it,
+/// linked from the directory of the project that depends on it.
+/// Returns `None` when the pref is not for a project of the workspace.
+pub fn resolve_from_workspace(
+    pref: &str,
+    alias: Option<&str>,
+    project_dir: &str,
+    workspace_packages: Option<&WorkspacePackages>,
+) -> Result<Option<ResolveResult>> {
+    let (name, range) = match parse_workspace_pref(pref, alias) {
+        Some(spec) => spec,
+        None => return Ok(None),
+    };
+    let workspace_packages = match workspace_packages {
+        Some(workspace_packages) => workspace_packages,
+        None => bail!(
+            "WORKSPACE_PKG_NOT_FOUND: Cannot resolve {}@{} outside of a workspace",
+            name,
+            pref
+        ),
+    };
+    let versions = match workspace_packages.get(name) {
+        Some(versions) => versions,
+        None => bail!(
+            "WORKSPACE_PKG_NOT_FOUND: In {}: \"{}@{}\" is in the dependencies but no package named \"{}\" is present in the workspace",
+            project_dir,
+            name,
+            pref,
+            name
+        ),
+    };
+    let range = Range::parse(range)?;
+    let package = versions
+        .iter()
+        .filter_map(|(version, package)| Some((parse_version(version)?, package)))
+        .filter(|(version, _)| range.satisfies(version))
+        .max_by(|(version_1, _), (version_2, _)| version_1.cmp(version_2))
+        .map(|(_, package)| package);
+    let package = match package {
+        Some(package) => package,
+        None => bail!(
+            "NO_MATCHING_VERSION_INSIDE_WORKSPACE: In {}: No matching version found for {}@{} inside the workspace",
+            project_dir,
+            name,
+            pref
+        ),
+    };
+
+    let relative_dir = pathdiff::diff_paths(&package.dir, project_dir)
+        .unwrap_or_else(|| Path::new(&package.dir).to_path_buf());
+    Ok(Some(ResolveResult {
+        id: format!("link:{}", relative_dir.to_string_lossy().replace('\\', "/")),
+        latest: None,
+        manifest: Some(package.manifest.clone()),
+        normalized_pref: pref.to_string(),
+        resolution: Resolution::DirectoryResolution {
+            r#type: "directory".to_string(),
+            directory: package.dir.clone(),
+        },
+        resolved_via: ResolvedVia::LocalFilesystem,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::WorkspacePackage;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use types::BaseManifest;
+
+    fn workspace_packages(versions: &[&str]) -> WorkspacePackages {
+        let versions = versions
+            .iter()
+            .map(|version| {
+                let package = WorkspacePackage {
+                    dir: format!("/workspace/foo-{}", version),
+                    manifest: BaseManifest {
+                        name: Some("foo".to_string()),
+                        version: Some(version.to_string()),
+                        ..Default::default()
+                    },
+                };
+                (version.to_string(), package)
+            })
+            .collect();
+        HashMap::from([("foo".to_string(), versions)])
+    }
+
+    #[test]
+    fn parse_workspace_prefs() {
+        assert_eq!(
+            parse_workspace_pref("workspace:^1.0.0", Some("foo")),
+            Some(("foo", "^1.0.0"))
+        );
+        assert_eq!(
+            parse_workspace_pref("workspace:^", Some("foo")),
+            Some(("foo", "*"))
+        );
+        assert_eq!(
+            parse_workspace_pref("workspace:@foo/bar@~", Some("bar")),
+            Some(("@foo/bar", "*"))
+        );
+        assert_eq!(parse_workspace_pref("workspace:../foo", Some("foo")), None);
+        assert_eq!(parse_workspace_pref("^1.0.0", Some("foo")), None);
+    }
+
+    #[test]
+    fn link_the_newest_project_in_range() {
+        let workspace_packages = workspace_packages(&["1.0.0", "1.1.0", "2.0.0"]);
+        let resolve = |pref: &str| {
+            resolve_from_workspace(
+                pref,
+                Some("foo"),
+                "/workspace/project",
+                Some(&workspace_packages),
+            )
+        };
+
+        let result = resolve("workspace:^1.0.0").unwrap().unwrap();
+        assert_eq!(result.id, "link:../foo-1.1.0");
+        assert_eq!(result.normalized_pref, "workspace:^1.0.0");
+        assert_eq!(result.resolved_via, ResolvedVia::LocalFilesystem);
+        assert_eq!(
+            result.resolution,
+            Resolution::DirectoryResolution {
+                r#type: "directory".to_string(),
+                directory: "/workspace/foo-1.1.0".to_string(),
+            }
+        );
+        assert_eq!(
+            resolve("workspace:*").unwrap().unwrap().id,
+            "link:../foo-2.0.0"
+        );
+
+        assert!(resolve("workspace:^3.0.0")
+            .unwrap_err()
+            .to_string()
+            .starts_with("NO_MATCHING_VERSION_INSIDE_WORKSPACE"));
+        assert!(
+            resolve_from_workspace("workspace:*", Some("bar"), "/workspace/project", None)
+                .unwrap_err()
+                .to_string()
+                .starts_with("WORKSPACE_PKG_NOT_FOUND")
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use semver::{Version, VersionReq};
+use std::str::FromStr;
+
+/// A range in the syntax of the `semver` package of npm,
+/// like `^1.0.0 || >=2.1.0 <3`, `1.2 - 1.4` or `1.x`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range {
+    alternatives: Vec<VersionReq>,
+}
+
+const OPERATORS: [&str; 7] = [">=", "<=", ">", "<", "=", "~", "^"];
+
+/// Versions are allowed to start with `v` or `=`, like `v1.0.0`
+pub fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim();
+    let version = version.strip_prefix('=').unwrap_or(version).trim_start();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    Version::parse(version).ok()
+}
+
+fn is_wildcard(part: &str) -> bool {
+    matches!(part, "*" | "x" | "X")
+}
+
+/// The exclusive upper bound of a partial version, `1.2` becomes `<1.3.0-0`
+fn hyphen_upper_bound(version: &str) -> String {
+    let parts = version
+        .split('.')
+        .take_while(|part| !is_wildcard(part))
+        .collect::<Vec<_>>();
+    match parts.as_slice() {
+        [] => String::new(),
+        [major] => format!("<{}.0.0-0", major.parse::<u64>().unwrap_or(0) + 1),
+        [major, minor] => format!("<{}.{}.0-0", major, minor.parse::<u64>().unwrap_or(0) + 1),
+        _ => format!("<={}", version),
+    }
+}
+
+/// Converts one of the `||` alternatives to the comma separated syntax of `VersionReq`
+fn to_version_req(set: &str) -> Result<VersionReq> {
+    let tokens = set.split_whitespace().collect::<Vec<_>>();
+    let mut comparators = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens.get(i + 1) == Some(&"-") && i + 2 < tokens.len() {
+            comparators.push(format!(">={}", tokens[i].trim_start_matches('v')));
+            comparators.push(hyphen_upper_bound(tokens[i + 2].trim_start_matches('v')));
+            i += 3;
+            continue;
+        }
+
+        let mut token = tokens[i].to_string();
+        // operators might be separated from their versions, like `>= 1.0.0`
+        if OPERATORS.contains(&token.as_str()) && i + 1 < tokens.len() {
+            token.push_str(tokens[i + 1]);
+            i += 1;
+        }
+        let (operator, version) = match OPERATORS.iter().find(|op| token.starts_with(*op)) {
+            Some(op) => (
+                op.to_string(),
+                token[op.len()..].trim_start_matches('v').to_string(),
+            ),
+            // a bare version means exactly that version in npm, but a caret range in `VersionReq`
+            None => {
+                let version = token.trim_start_matches('v');
+                let first = version.split('.').next().unwrap_or_default();
+                if is_wildcard(first) || version.contains(['x', 'X', '*']) {
+                    (String::new(), version.to_string())
+                } else {
+                    ("=".to_string(), version.to_string())
+                }
+            }
+        };
+        comparators.push(format!("{}{}", operator, version));
+        i += 1;
+    }
+
+    let comparators = comparators
+        .into_iter()
+        .filter(|comparator| !comparator.is_empty())
+        .collect::<Vec<_>>();
+    if comparators.is_empty() {
+        return Ok(VersionReq::STAR);
+    }
+    Ok(VersionReq::parse(&comparators.join(", "))?)
+}
+
+impl Range {
+    pub fn parse(range: &str) -> Result<Self> {
+        let alternatives = range
+            .split("||")
+            .map(|set| to_version_req(set.trim()))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("INVALID_RANGE: {} is not a valid semver range", range))?;
+        Ok(Self { alternatives })
+    }
+
+    pub fn satisfies(&self, version: &Version) -> bool {
+        self.alternatives.iter().any(|req| req.matches(version))
+    }
+}
+
+impl FromStr for Range {
+    type Err = anyhow::Error;
+
+    fn from_str(range: &str) -> Result<Self> {
+        Self::parse(range)
+    }
+}
+
+/// `false` for invalid versions or ranges
+pub fn satisfies(version: &str, range: &str) -> bool {
+    match (parse_version(version), Range::parse(range)) {
+        (Some(version), Ok(range)) => range.satisfies(&version),
+        _ => false,
+    }
+}
+
+pub fn max_satisfying<'a, I>(versions: I, range: &Range) -> Option<&'a Version>
+where
+    I: IntoIterator<Item = &'a Version>,
+{
+    versions
+        .into_iter()
+        .filter(|version| range.satisfies(version))
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn npm_range_syntax() {
+        for (range, version, expected) in [
+            ("1.2.3", "1.2.3", true),
+            ("1.2.3", "1.2.4", false),
+            ("v1.2.3", "1.2.3", true),
+            ("^1.2.3", "1.9.0", true),
+            ("^1.2.3", "2.0.0", false),
+            ("~1.2.3", "1.2.9", true),
+            ("~1.2.3", "1.3.0", false),
+            ("1.x", "1.5.0", true),
+            ("1.2.x", "1.3.0", false),
+            ("*", "3.0.0", true),
+            ("", "3.0.0", true),
+            (">=1.0.0 <2.0.0", "1.5.0", true),
+            (">=1.0.0 <2.0.0", "2.0.0", false),
+            (">= 1.0.0", "1.0.0", true),
+            ("^1.0.0 || ^3.0.0", "3.1.0", true),
+            ("^1.0.0 || ^3.0.0", "2.1.0", false),
+            ("1.2 - 1.4", "1.4.9", true),
+            ("1.2 - 1.4", "1.5.0", false),
+            ("1.2.3 - 2.3.4", "2.3.4", true),
+            ("1.2.3 - 2.3.4", "1.2.2", false),
+            ("^1.0.0", "1.1.0-beta.1", false),
+            ("^1.1.0-beta.0", "1.1.0-beta.1", true),
+        ] {
+            assert_eq!(
+                satisfies(version, range),
+                expected,
+                "{} satisfies {}",
+                version,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_range() {
+        assert!(Range::parse("latest").is_err());
+        assert!(!satisfies("1.0.0", "latest"));
+    }
+
+    #[test]
+    fn max_satisfying_version() {
+        let versions = ["1.0.0", "1.2.0", "2.0.0"]
+            .iter()
+            .map(|version| Version::parse(version).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            max_satisfying(&versions, &Range::parse("^1.0.0").unwrap()),
+            Some(&versions[1])
+        );
+        assert_eq!(
+            max_satisfying(&versions, &Range::parse("^3.0.0").unwrap()),
+            None
+        );
+    }
+}
@@ -1,10 +1,15 @@
-use crate::StoreEntry;
+use crate::{StoreController, StoreEntry};
 
 pub struct CreateNewStoreControllerOptions {}
 
 pub fn create_new_store_controller(
-    opts: CreateNewStoreControllerOptions,
+    _opts: CreateNewStoreControllerOptions,
     store_dir: &str,
 ) -> StoreEntry {
-    todo!("implement https://github.dev/pnpm/pnpm/blob/master/packages/store-connection-manager/src/createNewStoreController.ts");
+    // TODO: pass the resolver and fetchers options once they are implemented
+    // https://github.dev/pnpm/pnpm/blob/master/packages/store-connection-manager/src/createNewStoreController.ts
+    StoreEntry {
+        ctrl: StoreController::new(store_dir),
+        dir: store_dir.to_string(),
+    }
 }
@@ -11,9 +11,12 @@ use serde::Deserialize;
 use server_connection_info_dir::server_connection_info_dir;
 use std::collections::HashMap;
 use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use std::{fs, thread};
+use store_controller_types::{ImportPackageOptions, ImportPackageResult, UploadOptions};
 use store_path::store_path;
+use types::Registries;
 
 fn run_server_in_background(store_dir: &str) {
     thread::spawn(|| {});
@@ -34,16 +37,16 @@ struct ServerInfo {
 }
 
 pub struct CreateStoreControllerOptions {
-    dir: String,
-    store_dir: Option<String>,
-    workspace_dir: Option<String>,
-    use_running_store_server: Option<bool>,
-    use_store_server: Option<bool>,
+    pub dir: String,
+    pub store_dir: Option<String>,
+    pub workspace_dir: Option<String>,
+    pub use_running_store_server: Option<bool>,
+    pub use_store_server: Option<bool>,
 }
 
 pub struct StoreEntry {
-    ctrl: StoreController,
-    dir: String,
+    pub ctrl: StoreController,
+    pub dir: String,
 }
 
 impl StoreEntry {}
@@ -51,7 +54,7 @@ impl StoreEntry {}
 pub struct StoreController {
     request_package: RequestPackage,
     fetch_package: (),
-    import_package: (),
+    cafs_dir: PathBuf,
 }
 
 pub struct CreateStoreControllerOpts {
@@ -124,11 +127,36 @@ impl StoreController {
         todo!()
     }
 
+    pub fn new<P: AsRef<Path>>(store_dir: P) -> Self {
+        Self {
+            request_package: RequestPackage {},
+            fetch_package: (),
+            cafs_dir: cafs::cafs_dir(store_dir),
+        }
+    }
+
+    /// The directory in which the files of the packages are stored by their content
+    pub fn cafs_dir(&self) -> &Path {
+        &self.cafs_dir
+    }
+
     pub fn close() {}
 
     pub fn prune() {}
 
-    pub fn upload(built_pkg_location: &str, opts: UploadOptions) {}
+    /// Saves the files of a package after its build scripts ran,
+    /// so that the next install on the same engine can skip the build
+    pub fn upload(&self, built_pkg_location: &str, opts: UploadOptions) -> Result<()> {
+        package_store::upload(&self.cafs_dir, built_pkg_location, &opts)
+    }
+
+    pub fn import_package<P: AsRef<Path>>(
+        &self,
+        to: P,
+        opts: ImportPackageOptions,
+    ) -> Result<ImportPackageResult> {
+        package_store::import_package(&self.cafs_dir, to, opts)
+    }
 }
 
 fn fetch(url: &str, body: HashMap<&str, &str>) -> Result<HashMap<String, String>> {
@@ -209,11 +237,6 @@ fn try_load_server_json(
     }
 }
 
-pub struct UploadOptions {
-    files_index_file: String,
-    engine: String,
-}
-
 pub struct RequestPackage {}
 
 impl RequestPackage {
@@ -234,11 +257,11 @@ pub struct RequestPackageOptions<'a> {
     lockfile_dir: String,
     preferred_versions: PreferredVersions,
     prefer_workspace_packages: Option<bool>,
-    registry: String,
+    registries: Registries,
     side_effects_cache: Option<bool>,
     skip_fetch: Option<bool>,
     update: Option<bool>,
-    workspace_packages: Option<WorkspacePackages<'a>>,
+    workspace_packages: Option<&'a WorkspacePackages>,
 }
 
 struct Package {
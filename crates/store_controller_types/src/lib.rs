@@ -1 +1,37 @@
+use cafs::PackageFilesIndex;
 
+pub struct UploadOptions {
+    /// The index file of the package that was built
+    pub files_index_file: String,
+    /// The side effects cache key of the build,
+    /// usually computed by `calc_dep_state`
+    pub engine: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum PackageImportMethod {
+    /// Hard link the files and fall back to copying them
+    /// when the store is on a different file system
+    #[default]
+    Auto,
+    Hardlink,
+    Copy,
+}
+
+pub struct ImportPackageOptions<'a> {
+    pub files_index: &'a PackageFilesIndex,
+    /// When set and the store has side effects for this key,
+    /// the built files are imported instead of the original ones
+    pub side_effects_cache_key: Option<&'a str>,
+    pub force: bool,
+    pub method: PackageImportMethod,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportPackageResult {
+    /// The package was imported with its side effects,
+    /// so there is no need to run its build scripts
+    pub is_built: bool,
+    /// `false` when the package was already in place
+    pub imported: bool,
+}
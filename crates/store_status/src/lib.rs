@@ -0,0 +1,197 @@
+use anyhow::{bail, Result};
+use cafs::{
+    cafs_dir, check_pkg_files, get_file_path_in_cafs, read_package_files_index, FileType, FilesDiff,
+};
+use dependency_path::dep_path_to_filename;
+use lockfile_utils::name_ver_from_pkg_snapshot::{name_ver_from_pkg_snapshot, NameVer};
+use lockfile_utils::read::{read_current_lockfile, ReadLockfileOpts};
+use rayon::prelude::*;
+use ssri::Integrity;
+use std::path::{Path, PathBuf};
+use store_path::store_path;
+
+pub struct StoreStatusOptions {
+    /// The directory that contains `node_modules`
+    pub dir: String,
+    pub store_dir: Option<String>,
+    pub virtual_store_dir: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ModifiedPackage {
+    pub dep_path: String,
+    pub name: String,
+    pub version: String,
+    pub pkg_dir: PathBuf,
+    pub diff: FilesDiff,
+}
+
+/// Finds the packages in `node_modules/.pnpm` which content
+/// differs from the content recorded in the store
+pub fn store_status(opts: StoreStatusOptions) -> Result<Vec<ModifiedPackage>> {
+    let virtual_store_dir = opts
+        .virtual_store_dir
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&opts.dir).join("node_modules").join(".pnpm"));
+    let store_dir = store_path(&opts.dir, opts.store_dir.as_deref())?;
+    let cafs_dir = cafs_dir(&store_dir);
+
+    let packages = match read_current_lockfile(&virtual_store_dir, ReadLockfileOpts::default())? {
+        Some(lockfile) => lockfile.packages.unwrap_or_default(),
+        None => return Ok(vec![]),
+    };
+
+    let mut modified = packages
+        .par_iter()
+        .filter_map(|(dep_path, pkg_snapshot)| {
+            // packages that are not from the store (git repos, local directories)
+            // have no integrity and nothing to compare against
+            let integrity = pkg_snapshot.resolution.integrity()?;
+            Some((dep_path, pkg_snapshot, integrity))
+        })
+        .map(
+            |(dep_path, pkg_snapshot, integrity)| -> Result<Option<ModifiedPackage>> {
+                let NameVer { name, version } = name_ver_from_pkg_snapshot(dep_path, pkg_snapshot)?;
+                let pkg_dir = virtual_store_dir
+                    .join(dep_path_to_filename(dep_path))
+                    .join("node_modules")
+                    .join(&name);
+
+                // skipped optional dependencies are in the lockfile but not on the disk
+                if !pkg_dir.exists() {
+                    return Ok(None);
+                }
+
+                let index_path = get_file_path_in_cafs(
+                    &cafs_dir,
+                    &integrity.parse::<Integrity>()?,
+                    FileType::Index,
+                );
+                if !index_path.exists() {
+                    bail!(
+                        "MISSING_PACKAGE_INDEX: {} is not in the store at {}",
+                        dep_path,
+                        store_dir.display()
+                    );
+                }
+                let index = read_package_files_index(&index_path)?;
+                let diff = check_pkg_files(&pkg_dir, &index.files)?;
+
+                Ok((!diff.is_empty()).then(|| ModifiedPackage {
+                    dep_path: dep_path.clone(),
+                    name,
+                    version,
+                    pkg_dir,
+                    diff,
+                }))
+            },
+        )
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>>>()?;
+
+    modified.sort_by(|pkg_1, pkg_2| pkg_1.dep_path.cmp(&pkg_2.dep_path));
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cafs::{add_files_from_dir, write_package_files_index, PackageFilesIndex};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const INTEGRITY: &str = "sha512-UHd2zKRT/w70KKzFlj4qcT81A1Q0H7NM9uKxLzIZ/VZqJXzt5Hnnp2PYPb5Ezq/hAamoYKIn5g7fuv69kP258w==";
+
+    fn install_package(project_dir: &Path, store_dir: &Path, dep_path: &str, name: &str) {
+        let pkg_dir = project_dir
+            .join("node_modules")
+            .join(".pnpm")
+            .join(dep_path_to_filename(dep_path))
+            .join("node_modules")
+            .join(name);
+        fs::create_dir_all(&pkg_dir).unwrap();
+        fs::write(
+            pkg_dir.join("package.json"),
+            format!("{{\"name\":\"{}\"}}", name),
+        )
+        .unwrap();
+        fs::write(pkg_dir.join("index.js"), "module.exports = 1").unwrap();
+
+        let files = add_files_from_dir(cafs_dir(store_dir), &pkg_dir).unwrap();
+        write_package_files_index(
+            get_file_path_in_cafs(
+                cafs_dir(store_dir),
+                &INTEGRITY.parse::<Integrity>().unwrap(),
+                FileType::Index,
+            ),
+            &PackageFilesIndex {
+                files,
+                side_effects: None,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn report_modified_packages() {
+        let project_dir = tempdir().unwrap();
+        let store_dir = tempdir().unwrap();
+        let store_dir_v3 = store_dir.path().join("v3");
+        install_package(project_dir.path(), &store_dir_v3, "/foo/1.0.0", "foo");
+        fs::write(
+            project_dir.path().join("node_modules/.pnpm/lock.yaml"),
+            format!(
+                "lockfileVersion: 5.3\npackages:\n  /foo/1.0.0:\n    resolution: {{integrity: {}}}\n  /bar/1.0.0:\n    resolution: {{integrity: {}}}\n    optional: true\n",
+                INTEGRITY, INTEGRITY
+            ),
+        )
+        .unwrap();
+
+        let opts = || StoreStatusOptions {
+            dir: project_dir.path().to_string_lossy().to_string(),
+            store_dir: Some(store_dir_v3.to_string_lossy().to_string()),
+            virtual_store_dir: None,
+        };
+        assert_eq!(store_status(opts()).unwrap(), vec![]);
+
+        let pkg_dir = project_dir
+            .path()
+            .join("node_modules/.pnpm/foo@1.0.0/node_modules/foo");
+        fs::write(pkg_dir.join("index.js"), "module.exports = 2").unwrap();
+        fs::write(pkg_dir.join("patch.js"), "").unwrap();
+        fs::remove_file(pkg_dir.join("package.json")).unwrap();
+
+        assert_eq!(
+            store_status(opts()).unwrap(),
+            vec![ModifiedPackage {
+                dep_path: "/foo/1.0.0".to_string(),
+                name: "foo".to_string(),
+                version: "1.0.0".to_string(),
+                pkg_dir,
+                diff: FilesDiff {
+                    added: vec!["patch.js".to_string()],
+                    modified: vec!["index.js".to_string()],
+                    removed: vec!["package.json".to_string()],
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn no_current_lockfile() {
+        let project_dir = tempdir().unwrap();
+        let store_dir = tempdir().unwrap();
+
+        assert_eq!(
+            store_status(StoreStatusOptions {
+                dir: project_dir.path().to_string_lossy().to_string(),
+                store_dir: Some(store_dir.path().to_string_lossy().to_string()),
+                virtual_store_dir: None,
+            })
+            .unwrap(),
+            vec![]
+        );
+    }
+}
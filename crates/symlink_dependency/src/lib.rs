@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+pub struct SymlinkResult {
+    /// `true` when the link already pointed to the target
+    pub reused: bool,
+    /// Set when something else was at the location of the link and was removed
+    pub warn: Option<String>,
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+// the link points to the absolute path of the target directory on Windows
+#[cfg(windows)]
+fn create_symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    let target = path.parent().unwrap_or_else(|| Path::new(".")).join(target);
+    std::os::windows::fs::symlink_dir(target, path)
+}
+
+/// Creates a relative symlink at `path` that points to the `target` directory.
+/// An existing link to another directory is replaced.
+pub fn symlink_dir<T: AsRef<Path>, P: AsRef<Path>>(target: T, path: P) -> Result<SymlinkResult> {
+    let path = path.as_ref();
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let target = pathdiff::diff_paths(target.as_ref(), parent)
+        .unwrap_or_else(|| target.as_ref().to_path_buf());
+
+    let mut warn = None;
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            if fs::read_link(path)? == target {
+                return Ok(SymlinkResult { reused: true, warn });
+            }
+            remove_link(path)?;
+        }
+        Ok(metadata) => {
+            warn = Some(format!(
+                "Symlink wanted name was occupied by {}, replaced it",
+                if metadata.is_dir() {
+                    "directory"
+                } else {
+                    "file"
+                }
+            ));
+            if metadata.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    fs::create_dir_all(parent)?;
+    create_symlink(&target, path).with_context(|| {
+        format!(
+            "SYMLINK_FAILED: Could not link {} to {}",
+            path.display(),
+            target.display()
+        )
+    })?;
+
+    Ok(SymlinkResult {
+        reused: false,
+        warn,
+    })
+}
+
+#[cfg(unix)]
+fn remove_link(path: &Path) -> std::io::Result<()> {
+    fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn remove_link(path: &Path) -> std::io::Result<()> {
+    fs::remove_dir(path)
+}
+
+/// Links a dependency into a `node_modules` directory under the name it is imported as
+pub fn symlink_dependency<T: AsRef<Path>, M: AsRef<Path>>(
+    dependency_location: T,
+    dest_modules_dir: M,
+    import_as: &str,
+) -> Result<SymlinkResult> {
+    let link: PathBuf = dest_modules_dir.as_ref().join(import_as);
+    symlink_dir(dependency_location, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn relink_dependency() {
+        let dir = tempdir().unwrap();
+        let modules_dir = dir.path().join("node_modules");
+        let foo_1 = dir.path().join("store").join("foo@1.0.0");
+        let foo_2 = dir.path().join("store").join("foo@2.0.0");
+        fs::create_dir_all(&foo_1).unwrap();
+        fs::create_dir_all(&foo_2).unwrap();
+
+        let result = symlink_dependency(&foo_1, &modules_dir, "@scope/foo").unwrap();
+        assert!(!result.reused);
+        assert_eq!(
+            fs::read_link(modules_dir.join("@scope/foo")).unwrap(),
+            Path::new("../../store/foo@1.0.0")
+        );
+
+        let result = symlink_dependency(&foo_1, &modules_dir, "@scope/foo").unwrap();
+        assert!(result.reused);
+
+        symlink_dependency(&foo_2, &modules_dir, "@scope/foo").unwrap();
+        assert_eq!(
+            fs::read_link(modules_dir.join("@scope/foo")).unwrap(),
+            Path::new("../../store/foo@2.0.0")
+        );
+    }
+
+    #[test]
+    fn replace_directory() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("target");
+        let link = dir.path().join("node_modules").join("foo");
+        fs::create_dir_all(&target).unwrap();
+        fs::create_dir_all(&link).unwrap();
+
+        let result = symlink_dir(&target, &link).unwrap();
+
+        assert!(result.warn.is_some());
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{bail, Result};
+use cafs::{add_files_from_tarball, PackageFiles};
+use fetch::FetchFromRegistry;
+use resolvers::base::Resolution;
+use ssri::{Algorithm, Integrity, IntegrityOpts};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Returned when the downloaded tarball doesn't match the integrity
+/// from the resolution. Can be recovered with `anyhow::Error::downcast_ref`.
+#[derive(Debug, PartialEq)]
+pub struct TarballIntegrityError {
+    pub url: String,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for TarballIntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TARBALL_INTEGRITY: Got unexpected checksum for \"{}\". Wanted \"{}\". Got \"{}\".",
+            self.url, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for TarballIntegrityError {}
+
+pub struct FetchOptions<'a> {
+    /// `file:` tarballs are relative to the directory of the lockfile
+    pub lockfile_dir: &'a str,
+    /// Sent with requests of remote tarballs
+    pub auth_header_value: Option<&'a str>,
+}
+
+#[derive(Debug)]
+pub struct FetchResult {
+    pub files_index: PackageFiles,
+    /// The integrity of the tarball. Computed when the resolution had none.
+    pub integrity: Integrity,
+}
+
+/// Hashes everything that is read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: IntegrityOpts,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.input(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn add_files_from_verified_tarball<P: AsRef<Path>, R: Read>(
+    cafs_dir: P,
+    reader: R,
+    url: &str,
+    integrity: Option<&str>,
+) -> Result<FetchResult> {
+    let expected = integrity.map(str::parse::<Integrity>).transpose()?;
+    let mut reader = HashingReader {
+        inner: reader,
+        hasher: IntegrityOpts::new().algorithm(
+            expected
+                .as_ref()
+                .map(Integrity::pick_algorithm)
+                .unwrap_or(Algorithm::Sha512),
+        ),
+    };
+
+    let files_index = add_files_from_tarball(cafs_dir, &mut reader)?;
+    let found = reader.hasher.result();
+
+    if let Some(expected) = expected {
+        if expected.matches(&found).is_none() {
+            return Err(TarballIntegrityError {
+                url: url.to_string(),
+                expected: expected.to_string(),
+                found: found.to_string(),
+            }
+            .into());
+        }
+    }
+
+    Ok(FetchResult {
+        files_index,
+        integrity: found,
+    })
+}
+
+/// Streams a tarball from a registry or any other URL into the store
+pub fn fetch_remote_tarball<P: AsRef<Path>>(
+    fetch_from_registry: &FetchFromRegistry,
+    cafs_dir: P,
+    url: &str,
+    integrity: Option<&str>,
+    auth_header_value: Option<&str>,
+) -> Result<FetchResult> {
+    let response = fetch_from_registry.fetch(url, auth_header_value)?;
+    add_files_from_verified_tarball(cafs_dir, response, url, integrity)
+}
+
+/// Unpacks a `file:` tarball into the store
+pub fn fetch_local_tarball<P: AsRef<Path>>(
+    cafs_dir: P,
+    tarball: &str,
+    integrity: Option<&str>,
+    opts: &FetchOptions,
+) -> Result<FetchResult> {
+    let path = Path::new(opts.lockfile_dir).join(tarball.trim_start_matches("file:"));
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) => bail!(
+            "TARBALL_NOT_FOUND: Could not read {}: {}",
+            path.display(),
+            err
+        ),
+    };
+    add_files_from_verified_tarball(cafs_dir, file, tarball, integrity)
+}
+
+pub fn fetch_tarball<P: AsRef<Path>>(
+    fetch_from_registry: &FetchFromRegistry,
+    cafs_dir: P,
+    resolution: &Resolution,
+    opts: &FetchOptions,
+) -> Result<FetchResult> {
+    match resolution {
+        Resolution::TarballResolution {
+            tarball, integrity, ..
+        } => {
+            if tarball.starts_with("file:") {
+                fetch_local_tarball(cafs_dir, tarball, integrity.as_deref(), opts)
+            } else {
+                fetch_remote_tarball(
+                    fetch_from_registry,
+                    cafs_dir,
+                    tarball,
+                    integrity.as_deref(),
+                    opts.auth_header_value,
+                )
+            }
+        }
+        _ => bail!("The tarball fetcher can only fetch tarball resolutions"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use http_stub::Reply;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tar::{Builder, Header};
+    use tempfile::tempdir;
+
+    fn create_tarball() -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        let content = b"{\"name\":\"foo\",\"version\":\"1.0.0\"}";
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "package/package.json", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Serves the same body to every request
+    fn serve(body: Vec<u8>) -> String {
+        let registry = http_stub::serve(move |_| Reply::body(body.clone()));
+        format!("{}foo/-/foo-1.0.0.tgz", registry)
+    }
+
+    #[test]
+    fn fetch_remote() {
+        let tarball = create_tarball();
+        let integrity = Integrity::from(&tarball).to_string();
+        let url = serve(tarball);
+        let cafs_dir = tempdir().unwrap();
+
+        let result = fetch_tarball(
+            &FetchFromRegistry::new(Default::default()).unwrap(),
+            cafs_dir.path(),
+            &Resolution::TarballResolution {
+                tarball: url,
+                integrity: Some(integrity.clone()),
+                registry: None,
+            },
+            &FetchOptions {
+                lockfile_dir: "",
+                auth_header_value: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(result.integrity.to_string(), integrity);
+        assert_eq!(result.files_index["package.json"].size, 32);
+    }
+
+    #[test]
+    fn fail_on_integrity_mismatch() {
+        let url = serve(create_tarball());
+        let cafs_dir = tempdir().unwrap();
+        let wrong_integrity = Integrity::from(b"foo").to_string();
+
+        let err = fetch_remote_tarball(
+            &FetchFromRegistry::new(Default::default()).unwrap(),
+            cafs_dir.path(),
+            &url,
+            Some(&wrong_integrity),
+            None,
+        )
+        .unwrap_err();
+
+        let err = err.downcast_ref::<TarballIntegrityError>().unwrap();
+        assert_eq!(err.url, url);
+        assert_eq!(err.expected, wrong_integrity);
+        assert!(err.to_string().starts_with("TARBALL_INTEGRITY: "));
+    }
+
+    #[test]
+    fn fetch_local() {
+        let lockfile_dir = tempdir().unwrap();
+        let tarball = create_tarball();
+        fs::write(lockfile_dir.path().join("foo-1.0.0.tgz"), &tarball).unwrap();
+        let cafs_dir = tempdir().unwrap();
+
+        let result = fetch_local_tarball(
+            cafs_dir.path(),
+            "file:foo-1.0.0.tgz",
+            None,
+            &FetchOptions {
+                lockfile_dir: &lockfile_dir.path().to_string_lossy(),
+                auth_header_value: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(result.integrity, cafs::file_integrity(&tarball));
+        assert!(result.files_index.contains_key("package.json"));
+    }
+}
@@ -1,13 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
 mod package {
-    use serde::{Deserialize, Serialize};
-    use std::{collections::HashMap, rc::Rc};
+    use serde::{Deserialize, Serialize, Serializer};
+    use std::collections::{BTreeMap, HashMap};
+    use std::rc::Rc;
 
-    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+    /// Dependencies are written in alphabetical order, like npm does
+    fn sorted<S: Serializer>(
+        deps: &Option<Dependencies>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        deps.as_ref()
+            .map(|deps| deps.iter().collect::<BTreeMap<_, _>>())
+            .serialize(serializer)
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
     #[serde(rename_all = "camelCase")]
-    pub enum Engines {
-        Node(String),
-        Npm(String),
-        Pnpm(String),
+    pub struct Engines {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub node: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub npm: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub pnpm: Option<String>,
+    }
+
+    /// Either a URL or an object with the type of the repository and its URL
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+    #[serde(untagged)]
+    pub enum Repository {
+        Url(String),
+        Object {
+            r#type: Option<String>,
+            url: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            directory: Option<String>,
+        },
     }
 
     #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Default)]
@@ -22,13 +52,13 @@ mod package {
         //   directories: Option<{,
         //     bin: Option<String>,
         //   }>
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted")]
         pub dev_dependencies: Option<Dependencies>,
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted")]
         pub dependencies: Option<Dependencies>,
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted")]
         pub optional_dependencies: Option<Dependencies>,
-        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(skip_serializing_if = "Option::is_none", serialize_with = "sorted")]
         pub peer_dependencies: Option<Dependencies>,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub peer_dependencies_meta: Option<PeerDependenciesMeta>,
@@ -41,8 +71,9 @@ mod package {
         #[serde(skip_serializing_if = "Option::is_none")]
         pub homepage: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
-        pub repository: Option<String>,
-        //   scripts: Option<PackageScripts>,
+        pub repository: Option<Repository>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub scripts: Option<PackageScripts>,
         //   config: Option<object>,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub engines: Option<Engines>,
@@ -62,9 +93,25 @@ mod package {
         pub publish_config: Option<PublishConfig>,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub readme: Option<String>,
-        //   bin: Option<PackageBin>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub bin: Option<PackageBin>,
+        /// The fields that are not used by pnpm, kept so they are not lost
+        /// when the manifest is written back
+        #[serde(flatten)]
+        pub other_fields: BTreeMap<String, serde_json::Value>,
+    }
+
+    /// Either the path of a single executable named after the package,
+    /// or the paths of the executables by their names
+    #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+    #[serde(untagged)]
+    pub enum PackageBin {
+        Single(String),
+        Map(HashMap<String, String>),
     }
 
+    pub type PackageScripts = HashMap<String, String>;
+
     #[derive(Deserialize, Serialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct DependencyManifest {
@@ -83,7 +130,7 @@ mod package {
         bundle_dependencies: Option<Rc<Vec<String>>>,
         bundled_dependencies: Option<Rc<Vec<String>>>,
         homepage: Option<String>,
-        repository: Option<String>,
+        repository: Option<Repository>,
         //   scripts: Option<PackageScripts>,
         //   config: Option<object>,
         engines: Engines,
@@ -103,11 +150,19 @@ mod package {
         manifest: BaseManifest,
     }
 
+    /// How the issues with peer dependencies are reported, from `pnpm.peerDependencyRules`
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
     pub struct PeerDependencyRules {
-        ignore_missing: Option<Rc<Vec<String>>>,
-        allowed_versions: Option<HashMap<String, String>>,
+        /// Patterns of the peer dependencies that are not reported when they are missing
+        pub ignore_missing: Option<Rc<Vec<String>>>,
+        /// Ranges of versions that are accepted for the peer dependencies, by peer name,
+        /// or by `parent>peer` for the peer dependency of a single package
+        pub allowed_versions: Option<HashMap<String, String>>,
     }
 
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
     pub struct PackageExtension {
         pub dependencies: Option<Dependencies>,
         pub optional_dependencies: Option<Dependencies>,
@@ -115,11 +170,14 @@ mod package {
         pub peer_dependencies_meta: Option<PeerDependenciesMeta>,
     }
 
+    /// The `pnpm` field of the manifest of a project
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "camelCase")]
     pub struct PnpmManifest {
-        never_built_dependencies: Option<Rc<Vec<String>>>,
-        overrides: Option<HashMap<String, String>>,
-        package_extensions: Option<HashMap<String, PackageExtension>>,
-        peer_dependency_rules: Option<PeerDependencyRules>,
+        pub never_built_dependencies: Option<Rc<Vec<String>>>,
+        pub overrides: Option<HashMap<String, String>>,
+        pub package_extensions: Option<HashMap<String, PackageExtension>>,
+        pub peer_dependency_rules: Option<PeerDependencyRules>,
     }
 
     pub struct ProjectManifest {
@@ -139,14 +197,17 @@ mod package {
     #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct DependencyMeta {
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub injected: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub node: Option<String>,
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct PeerDependencyMeta {
-        optional: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub optional: Option<bool>,
     }
 
     pub type DependenciesMeta = HashMap<String, DependencyMeta>;
@@ -156,13 +217,58 @@ mod package {
 
 pub use package::*;
 
-#[derive(Clone, Copy, Debug)]
+/// The registry of packages without a scope is under `default`,
+/// the registries of scopes are under their names, like `@foo`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Registries {
+    pub default: String,
+    #[serde(flatten)]
+    pub scoped: HashMap<String, String>,
+}
+
+/// How the packages are laid out in `node_modules`, the `node-linker` setting
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeLinker {
+    /// Every package is in the virtual store, with its dependencies linked next to it
+    #[default]
+    Isolated,
+    /// A flat tree of directories, like npm creates
+    Hoisted,
+}
+
+impl NodeLinker {
+    /// The value of the `node-linker` setting
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeLinker::Isolated => "isolated",
+            NodeLinker::Hoisted => "hoisted",
+        }
+    }
+}
+
+/// Where a dependency is hoisted to
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HoistKind {
+    /// Into the modules directory of the virtual store, only visible to the packages in it
+    Private,
+    /// Into the root modules directory, next to the direct dependencies of the project
+    Public,
+}
+
+/// The aliases each package is hoisted under, by dependency path
+pub type HoistedDependencies = BTreeMap<String, BTreeMap<String, HoistKind>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DependencyField {
     OptionalDependencies,
     Dependencies,
     DevDependencies,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub struct IncludedDependencies {
     pub optional_dependencies: bool,
     pub dependencies: bool,
@@ -179,4 +285,45 @@ impl DependencyField {
         .iter()
         .copied()
     }
+
+    /// The name of the field in `package.json`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyField::OptionalDependencies => "optionalDependencies",
+            DependencyField::Dependencies => "dependencies",
+            DependencyField::DevDependencies => "devDependencies",
+        }
+    }
+}
+
+impl BaseManifest {
+    /// The settings of the `pnpm` field, only read from the manifests of projects
+    pub fn pnpm(&self) -> anyhow::Result<Option<PnpmManifest>> {
+        self.other_fields
+            .get("pnpm")
+            .map(|pnpm| serde_json::from_value(pnpm.clone()))
+            .transpose()
+            .map_err(|error| {
+                anyhow::anyhow!(
+                    "BAD_PNPM_FIELD: Invalid \"pnpm\" field in package.json: {}",
+                    error
+                )
+            })
+    }
+
+    pub fn dependencies_of(&self, field: DependencyField) -> Option<&Dependencies> {
+        match field {
+            DependencyField::OptionalDependencies => self.optional_dependencies.as_ref(),
+            DependencyField::Dependencies => self.dependencies.as_ref(),
+            DependencyField::DevDependencies => self.dev_dependencies.as_ref(),
+        }
+    }
+
+    pub fn dependencies_of_mut(&mut self, field: DependencyField) -> &mut Option<Dependencies> {
+        match field {
+            DependencyField::OptionalDependencies => &mut self.optional_dependencies,
+            DependencyField::Dependencies => &mut self.dependencies,
+            DependencyField::DevDependencies => &mut self.dev_dependencies,
+        }
+    }
 }
@@ -1,9 +1,131 @@
+use crate::install_deps::{install_deps, InstallDepsOpts, RawLocalConfig};
 use crate::Command;
+use anyhow::{bail, Result};
 use clap::Parser;
+use config::{get_config, GetConfigOptions};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use types::DependencyField;
 
 #[derive(Parser, Debug)]
-pub struct Add {}
+pub struct Add {
+    /// The packages to add, like `foo`, `foo@^1.0.0`, `foo@latest` or a tarball URL
+    #[clap(required = true)]
+    packages: Vec<String>,
+    /// Save the packages to devDependencies
+    #[clap(long = "save-dev", short = 'D')]
+    save_dev: bool,
+    /// Save the packages to optionalDependencies
+    #[clap(long = "save-optional", short = 'O', conflicts_with = "save-dev")]
+    save_optional: bool,
+    /// Save the packages to dependencies. The default for new dependencies.
+    #[clap(
+        long = "save-prod",
+        short = 'P',
+        conflicts_with_all = &["save-dev", "save-optional"]
+    )]
+    save_prod: bool,
+    /// Save the packages to peerDependencies and install them as devDependencies
+    #[clap(long, conflicts_with_all = &["save-optional", "save-prod"])]
+    save_peer: bool,
+    /// Save the exact versions instead of ranges
+    #[clap(long = "save-exact", short = 'E')]
+    save_exact: bool,
+    /// The prefix of the saved ranges, like `^` or `~`
+    #[clap(long, conflicts_with = "save-exact")]
+    save_prefix: Option<String>,
+    /// Only add packages that are found in the workspace
+    #[clap(long)]
+    workspace: bool,
+    /// Add the packages to the root of the workspace
+    #[clap(long = "workspace-root", short = 'w')]
+    workspace_root: bool,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    dir: Option<String>,
+}
+
+impl Add {
+    fn cli_options(&self) -> HashMap<String, String> {
+        let mut cli_options = HashMap::new();
+        if self.save_exact {
+            cli_options.insert("save-exact".to_string(), "true".to_string());
+        }
+        if let Some(save_prefix) = &self.save_prefix {
+            cli_options.insert("save-prefix".to_string(), save_prefix.clone());
+        }
+        cli_options
+    }
+
+    fn target_dependency_field(&self) -> Option<DependencyField> {
+        if self.save_dev || self.save_peer {
+            Some(DependencyField::DevDependencies)
+        } else if self.save_optional {
+            Some(DependencyField::OptionalDependencies)
+        } else if self.save_prod {
+            Some(DependencyField::Dependencies)
+        } else {
+            None
+        }
+    }
+}
 
 impl Command for Add {
-    fn exec(&self) {}
+    fn exec(&self) -> Result<()> {
+        let dir = self
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap());
+        let mut config_opts = GetConfigOptions::new(&dir)?;
+        config_opts.cli_options = self.cli_options();
+        let config = get_config(config_opts)?;
+
+        let dir = if self.workspace_root {
+            match &config.workspace_dir {
+                Some(workspace_dir) => workspace_dir.clone(),
+                None => {
+                    bail!("NOT_IN_WORKSPACE: --workspace-root may only be used inside a workspace")
+                }
+            }
+        } else {
+            if config.workspace_dir.as_ref() == Some(&dir)
+                && !config.get_bool("ignore-workspace-root-check")?
+            {
+                bail!(
+                    "ADDING_TO_ROOT: Running this command will add the dependency to the workspace root, \
+                    which might not be what you want - if you really meant it, \
+                    make it explicit by running this command again with the -w flag (or --workspace-root). \
+                    If you don't want to see this warning anymore, \
+                    you may set the ignore-workspace-root-check setting to true."
+                );
+            }
+            dir
+        };
+
+        let packages = self.packages.iter().map(String::as_str).collect::<Vec<_>>();
+        install_deps(
+            InstallDepsOpts {
+                dir: dir.to_string_lossy().to_string(),
+                update: None,
+                workspace: Some(self.workspace),
+                latest: None,
+                update_depth: None,
+                save_workspace_protocol: None,
+                link_workspace_packages: config.get_bool("link-workspace-packages")?,
+                workspace_dir: config
+                    .workspace_dir
+                    .as_ref()
+                    .map(|dir| dir.to_string_lossy().to_string()),
+                raw_local_config: RawLocalConfig::from_config(&config),
+                include_direct: None,
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+                target_dependency_field: self.target_dependency_field(),
+                save_peer: self.save_peer,
+                config: &config,
+            },
+            &packages,
+        )
+    }
 }
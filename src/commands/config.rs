@@ -0,0 +1,162 @@
+use crate::Command;
+use anyhow::{bail, Result};
+use clap::{ArgEnum, Parser, Subcommand};
+use config::{get_config, npmrc_paths, GetConfigOptions, Npmrc};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Config {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+    /// Read or write only the `.npmrc` of this location.
+    /// By default `set` and `delete` change the global `.npmrc`.
+    #[clap(long, arg_enum, global = true)]
+    location: Option<Location>,
+    /// Print the settings as JSON
+    #[clap(long, global = true)]
+    json: bool,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C', global = true)]
+    dir: Option<String>,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+enum Location {
+    Project,
+    Global,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Prints every setting and the file it comes from
+    List,
+    /// Prints the value of a setting
+    Get { key: String },
+    /// Sets a setting in an `.npmrc` file
+    Set { key: String, value: String },
+    /// Removes a setting from an `.npmrc` file
+    Delete { key: String },
+}
+
+/// Secrets are not printed when listing all the settings
+fn is_protected(key: &str) -> bool {
+    ["_authToken", "_auth", "_password"]
+        .iter()
+        .any(|suffix| key.ends_with(suffix))
+}
+
+impl Config {
+    fn opts(&self) -> Result<GetConfigOptions> {
+        let dir = self
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap());
+        GetConfigOptions::new(dir)
+    }
+
+    fn npmrc_path(&self, location: Location) -> Result<PathBuf> {
+        let paths = npmrc_paths(&self.opts()?);
+        match location {
+            Location::Project => Ok(paths.project),
+            Location::Global => match paths.global {
+                Some(path) => Ok(path),
+                None => bail!("NO_GLOBAL_NPMRC: Could not find the home directory"),
+            },
+        }
+    }
+
+    fn read_npmrc(&self, location: Location) -> Result<(PathBuf, Npmrc)> {
+        let path = self.npmrc_path(location)?;
+        let npmrc = Npmrc::read(&path)?.unwrap_or_default();
+        Ok((path, npmrc))
+    }
+
+    fn list(&self) -> Result<()> {
+        // the settings of a single file have no other source than the file
+        let settings = match self.location {
+            Some(location) => {
+                let (path, npmrc) = self.read_npmrc(location)?;
+                npmrc
+                    .entries()
+                    .map(|(key, value)| {
+                        (
+                            key.to_string(),
+                            (value.to_string(), path.display().to_string()),
+                        )
+                    })
+                    .collect::<BTreeMap<_, _>>()
+            }
+            None => {
+                let config = get_config(self.opts()?)?;
+                config
+                    .raw_config
+                    .iter()
+                    .map(|(key, value)| {
+                        let source = config.sources[key].to_string();
+                        (key.clone(), (value.clone(), source))
+                    })
+                    .collect()
+            }
+        };
+        let settings = settings.into_iter().map(|(key, (value, source))| {
+            let value = if is_protected(&key) {
+                "(protected)".to_string()
+            } else {
+                value
+            };
+            (key, value, source)
+        });
+
+        if self.json {
+            let json = settings
+                .map(|(key, value, _)| (key, value))
+                .collect::<BTreeMap<_, _>>();
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            return Ok(());
+        }
+
+        for (key, value, source) in settings {
+            println!("{}={} ; {}", key, value, source);
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<()> {
+        let value = match self.location {
+            Some(location) => self.read_npmrc(location)?.1.get(key).map(String::from),
+            None => get_config(self.opts()?)?.raw_config.remove(key),
+        };
+
+        match (value, self.json) {
+            (value, true) => println!("{}", serde_json::to_string(&value)?),
+            (Some(value), false) => println!("{}", value),
+            (None, false) => println!("undefined"),
+        }
+        Ok(())
+    }
+}
+
+impl Command for Config {
+    fn exec(&self) -> Result<()> {
+        match &self.command {
+            ConfigCommand::List => self.list(),
+            ConfigCommand::Get { key } => self.get(key),
+            ConfigCommand::Set { key, value } => {
+                let (path, mut npmrc) =
+                    self.read_npmrc(self.location.unwrap_or(Location::Global))?;
+                npmrc.set(key, value);
+                npmrc.write(path)
+            }
+            ConfigCommand::Delete { key } => {
+                let (path, mut npmrc) =
+                    self.read_npmrc(self.location.unwrap_or(Location::Global))?;
+                if npmrc.delete(key) {
+                    npmrc.write(path)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::exec::run_command;
+use crate::install_deps::{install_deps, InstallDepsOpts, RawLocalConfig};
+use crate::Command;
+use anyhow::{bail, Result};
+use clap::{AppSettings, Parser};
+use config::{get_config, GetConfigOptions};
+use lifecycle::path_env;
+use link_bins::get_bins;
+use parse_wanted_dependency::parse_wanted_dependency;
+use read_project_manifest::read_project_manifest_only;
+use std::fs;
+use std::path::Path;
+
+#[derive(Parser, Debug)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct Dlx {
+    /// The packages to install before running the command.
+    /// The command is the package to install when none is given.
+    #[clap(long, multiple_occurrences = true)]
+    package: Vec<String>,
+    /// The command to run, with its arguments
+    #[clap(required = true)]
+    command: Vec<String>,
+}
+
+/// The executable of the package that runs when the command is a package:
+/// its only executable or the one named after it
+fn default_bin(modules_dir: &Path, alias: &str) -> Result<String> {
+    let manifest = read_project_manifest_only(&modules_dir.join(alias).to_string_lossy())?;
+    let bins = get_bins(&manifest);
+    if let [bin] = bins.as_slice() {
+        return Ok(bin.name.clone());
+    }
+    let name = alias.rsplit('/').next().unwrap_or(alias);
+    match bins.iter().find(|bin| bin.name == name) {
+        Some(bin) => Ok(bin.name.clone()),
+        None => bail!(
+            "DLX_NO_BIN: Could not determine the executable to run. {} has the executables: {}",
+            alias,
+            bins.iter()
+                .map(|bin| bin.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+impl Command for Dlx {
+    fn exec(&self) -> Result<()> {
+        let cwd = std::env::current_dir()?;
+        let mut config = get_config(GetConfigOptions::new(&cwd)?)?;
+        let packages = if self.package.is_empty() {
+            vec![self.command[0].clone()]
+        } else {
+            self.package.clone()
+        };
+
+        let prefix = tempfile::tempdir()?;
+        fs::write(prefix.path().join("package.json"), "{}")?;
+        // the settings of the project are used, but nothing is installed in it
+        config.virtual_store_dir = prefix.path().join(&config.modules_dir).join(".pnpm");
+        install_deps(
+            InstallDepsOpts {
+                dir: prefix.path().to_string_lossy().to_string(),
+                update: None,
+                workspace: None,
+                latest: None,
+                update_depth: None,
+                save_workspace_protocol: None,
+                link_workspace_packages: false,
+                workspace_dir: None,
+                raw_local_config: RawLocalConfig::from_config(&config),
+                include_direct: None,
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+                target_dependency_field: None,
+                save_peer: false,
+                config: &config,
+            },
+            &packages.iter().map(String::as_str).collect::<Vec<_>>(),
+        )?;
+
+        let (bin, args) = if self.package.is_empty() {
+            let alias = parse_wanted_dependency(&self.command[0])
+                .alias
+                .unwrap_or_else(|| self.command[0].clone());
+            let bin = default_bin(&prefix.path().join(&config.modules_dir), &alias)?;
+            (bin, &self.command[1..])
+        } else {
+            (self.command[0].clone(), &self.command[1..])
+        };
+
+        let mut command = std::process::Command::new(&bin);
+        command
+            .args(args)
+            .current_dir(&cwd)
+            .env("PATH", path_env(prefix.path(), &[])?);
+        run_command(command, &self.command.join(" "))
+    }
+}
@@ -0,0 +1,89 @@
+use super::run::RunTarget;
+use crate::recursive::{recursive, Job};
+use crate::Command;
+use anyhow::{bail, Context, Result};
+use clap::{AppSettings, Parser};
+use config::{get_config, GetConfigOptions};
+use lifecycle::{package_env, path_env, shell_command};
+use read_project_manifest::read_project_manifest_only;
+use std::path::Path;
+use types::BaseManifest;
+
+#[derive(Parser, Debug)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct Exec {
+    /// Run the command in a shell, so it may use pipes and variables
+    #[clap(long, short = 'c')]
+    shell_mode: bool,
+    #[clap(flatten)]
+    target: RunTarget,
+    /// The command to run, with its arguments
+    #[clap(required = true)]
+    command: Vec<String>,
+}
+
+/// A command that runs in a project, with the executables of its dependencies in the PATH
+fn exec_command(
+    command: &[String],
+    shell_mode: bool,
+    dir: &Path,
+    manifest: Option<&BaseManifest>,
+) -> Result<std::process::Command> {
+    let mut exec = if shell_mode {
+        shell_command(&command.join(" "))
+    } else {
+        let mut exec = std::process::Command::new(&command[0]);
+        exec.args(&command[1..]);
+        exec
+    };
+    exec.current_dir(dir).env("PATH", path_env(dir, &[])?);
+    if let Some(manifest) = manifest {
+        exec.envs(package_env(manifest));
+        if let Some(name) = &manifest.name {
+            exec.env("PNPM_PACKAGE_NAME", name);
+        }
+    }
+    Ok(exec)
+}
+
+/// Runs the command and fails when it can't be spawned or exits with an error
+pub fn run_command(mut command: std::process::Command, command_line: &str) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("EXEC_ERROR: Could not run `{}`", command_line))?;
+    if !status.success() {
+        bail!("EXEC_ERROR: `{}` exited with {}", command_line, status);
+    }
+    Ok(())
+}
+
+impl Command for Exec {
+    fn exec(&self) -> Result<()> {
+        let dir = self.target.dir();
+        let command_line = self.command.join(" ");
+        if !self.target.is_recursive() {
+            let manifest = read_project_manifest_only(&dir.to_string_lossy()).ok();
+            let command = exec_command(&self.command, self.shell_mode, &dir, manifest.as_ref())?;
+            return run_command(command, &command_line);
+        }
+
+        let config = get_config(GetConfigOptions::new(&dir)?)?;
+        let projects = self.target.workspace_projects(&config)?;
+        recursive(
+            &self.target.recursive_options(&config, &projects)?,
+            |project| {
+                let command = exec_command(
+                    &self.command,
+                    self.shell_mode,
+                    Path::new(&project.dir),
+                    Some(&project.manifest),
+                )?;
+                let command_line = command_line.clone();
+                Ok(Some(
+                    Box::new(move || run_command(command, &command_line)) as Job
+                ))
+            },
+        )?;
+        Ok(())
+    }
+}
@@ -1,9 +1,108 @@
+use crate::install_deps::{install_deps, InstallDepsOpts, RawLocalConfig};
 use crate::Command;
+use anyhow::Result;
 use clap::Parser;
+use config::{get_config, GetConfigOptions};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use types::IncludedDependencies;
 
 #[derive(Parser, Debug)]
-pub struct Install {}
+pub struct Install {
+    /// Don't generate a lockfile and fail if an update is needed
+    #[clap(long)]
+    frozen_lockfile: bool,
+    /// Skip the resolution when the lockfile satisfies package.json. On by default.
+    #[clap(long)]
+    prefer_frozen_lockfile: bool,
+    /// Resolve the dependencies again even if the lockfile satisfies package.json
+    #[clap(long, conflicts_with = "prefer-frozen-lockfile")]
+    no_prefer_frozen_lockfile: bool,
+    /// Only update pnpm-lock.yaml, without linking anything into node_modules
+    #[clap(long)]
+    lockfile_only: bool,
+    /// Use only the packages and metadata that are already available offline
+    #[clap(long)]
+    offline: bool,
+    /// Only install the dependencies and optionalDependencies
+    #[clap(long, short = 'P', visible_alias = "production")]
+    prod: bool,
+    /// Only install the devDependencies
+    #[clap(long, short = 'D', conflicts_with = "prod")]
+    dev: bool,
+    /// Don't install the optionalDependencies
+    #[clap(long)]
+    no_optional: bool,
+    /// Don't run the scripts of the project and of its dependencies
+    #[clap(long)]
+    ignore_scripts: bool,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    dir: Option<String>,
+}
+
+impl Install {
+    /// The flags that are settings too, so they can be set in the `.npmrc` as well
+    fn cli_options(&self) -> HashMap<String, String> {
+        let mut cli_options = HashMap::new();
+        let mut set = |key: &str, enabled: bool| {
+            if enabled {
+                cli_options.insert(key.to_string(), "true".to_string());
+            }
+        };
+        set("frozen-lockfile", self.frozen_lockfile);
+        set("prefer-frozen-lockfile", self.prefer_frozen_lockfile);
+        set("lockfile-only", self.lockfile_only);
+        set("offline", self.offline);
+        set("ignore-scripts", self.ignore_scripts);
+        if self.no_prefer_frozen_lockfile {
+            cli_options.insert("prefer-frozen-lockfile".to_string(), "false".to_string());
+        }
+        cli_options
+    }
+
+    fn include(&self) -> IncludedDependencies {
+        IncludedDependencies {
+            dependencies: !self.dev,
+            dev_dependencies: !self.prod,
+            optional_dependencies: !self.dev && !self.no_optional,
+        }
+    }
+}
 
 impl Command for Install {
-    fn exec(&self) {}
+    fn exec(&self) -> Result<()> {
+        let dir = self
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap());
+        let mut config_opts = GetConfigOptions::new(&dir)?;
+        config_opts.cli_options = self.cli_options();
+        let config = get_config(config_opts)?;
+
+        install_deps(
+            InstallDepsOpts {
+                dir: dir.to_string_lossy().to_string(),
+                update: None,
+                workspace: None,
+                latest: None,
+                update_depth: None,
+                save_workspace_protocol: None,
+                link_workspace_packages: false,
+                workspace_dir: config
+                    .workspace_dir
+                    .as_ref()
+                    .map(|dir| dir.to_string_lossy().to_string()),
+                raw_local_config: RawLocalConfig::from_config(&config),
+                include_direct: Some(self.include()),
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+                target_dependency_field: None,
+                save_peer: false,
+                config: &config,
+            },
+            &[],
+        )
+    }
 }
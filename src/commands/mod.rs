@@ -1,2 +1,9 @@
 pub mod add;
+pub mod config;
+pub mod dlx;
+pub mod exec;
 pub mod install;
+pub mod remove;
+pub mod run;
+pub mod store;
+pub mod update;
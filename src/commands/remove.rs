@@ -0,0 +1,154 @@
+use crate::install_deps::{create_install_context, InstallContext};
+use crate::Command;
+use anyhow::{bail, Result};
+use clap::Parser;
+use cli_utils::{
+    package_is_installable::PackageIsInstallableOpts,
+    read_project_manifest::try_read_project_manifest,
+};
+use config::{get_config, GetConfigOptions};
+use find_workspace_packages::{find_workspace_packages, WorkspacePackagesOpts};
+use pnpm_core::install::remove_dependencies::remove_dependencies_from_package;
+use std::path::{Path, PathBuf};
+use types::{DependencyField, IncludedDependencies};
+
+#[derive(Parser, Debug)]
+pub struct Remove {
+    /// The names of the dependencies to remove
+    #[clap(required = true)]
+    packages: Vec<String>,
+    /// Only remove the dependencies from devDependencies
+    #[clap(long = "save-dev", short = 'D')]
+    save_dev: bool,
+    /// Only remove the dependencies from dependencies
+    #[clap(long = "save-prod", short = 'P', conflicts_with = "save-dev")]
+    save_prod: bool,
+    /// Only remove the dependencies from optionalDependencies
+    #[clap(
+        long = "save-optional",
+        short = 'O',
+        conflicts_with_all = &["save-dev", "save-prod"]
+    )]
+    save_optional: bool,
+    /// Remove the dependencies from every project of the workspace that has them
+    #[clap(long, short = 'r')]
+    recursive: bool,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    dir: Option<String>,
+}
+
+impl Remove {
+    fn target_dependency_field(&self) -> Option<DependencyField> {
+        if self.save_dev {
+            Some(DependencyField::DevDependencies)
+        } else if self.save_prod {
+            Some(DependencyField::Dependencies)
+        } else if self.save_optional {
+            Some(DependencyField::OptionalDependencies)
+        } else {
+            None
+        }
+    }
+
+    fn remove_from_project(&self, dir: &Path, names: &[&str]) -> Result<()> {
+        let config = get_config(GetConfigOptions::new(dir)?)?;
+        let dir = dir.to_string_lossy().to_string();
+        let project_manifest = try_read_project_manifest(
+            &dir,
+            PackageIsInstallableOpts {
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+            },
+        )?;
+        let manifest = match &project_manifest.manifest {
+            Some(manifest) => manifest,
+            None => bail!("NO_IMPORTER_MANIFEST: No package.json found in {}", dir),
+        };
+
+        let InstallContext {
+            store,
+            client,
+            install_opts,
+        } = create_install_context(
+            &config,
+            &dir,
+            config
+                .workspace_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().to_string()),
+            IncludedDependencies {
+                dependencies: true,
+                dev_dependencies: true,
+                optional_dependencies: true,
+            },
+        )?;
+        let (updated_manifest, _) = remove_dependencies_from_package(
+            &client,
+            &store.ctrl,
+            manifest,
+            names,
+            self.target_dependency_field(),
+            &install_opts,
+        )?;
+        project_manifest.write_project_manifest(&updated_manifest, false)?;
+        Ok(())
+    }
+}
+
+impl Command for Remove {
+    fn exec(&self) -> Result<()> {
+        let dir = self
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap());
+        let names = self.packages.iter().map(String::as_str).collect::<Vec<_>>();
+
+        if !self.recursive {
+            return self.remove_from_project(&dir, &names);
+        }
+
+        let config = get_config(GetConfigOptions::new(&dir)?)?;
+        let workspace_dir = config.workspace_dir.unwrap_or(dir);
+        let mut projects = find_workspace_packages(
+            &workspace_dir.to_string_lossy(),
+            WorkspacePackagesOpts {
+                engine_strict: Some(config.engine_strict),
+                ..Default::default()
+            },
+        )?;
+        projects.sort_by(|project_1, project_2| project_1.dir.cmp(&project_2.dir));
+
+        // each project only removes the dependencies it has
+        let mut removed_any = false;
+        for project in &projects {
+            let project_names = names
+                .iter()
+                .copied()
+                .filter(|name| {
+                    DependencyField::iterator()
+                        .filter(|field| {
+                            self.target_dependency_field()
+                                .is_none_or(|target| target == *field)
+                        })
+                        .filter_map(|field| project.manifest.dependencies_of(field))
+                        .any(|deps| deps.contains_key(*name))
+                })
+                .collect::<Vec<_>>();
+            if !project_names.is_empty() {
+                self.remove_from_project(Path::new(&project.dir), &project_names)?;
+                removed_any = true;
+            }
+        }
+
+        if !removed_any {
+            bail!(
+                "CANNOT_REMOVE_MISSING_DEPS: None of the projects in {} has {}",
+                workspace_dir.display(),
+                names.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
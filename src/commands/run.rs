@@ -0,0 +1,391 @@
+use crate::install_deps::parse_number;
+use crate::recursive::{recursive, Job, RecursiveOptions};
+use crate::Command;
+use anyhow::{bail, Result};
+use clap::{AppSettings, Parser};
+use config::{get_config, Config, GetConfigOptions};
+use filter_workspace_packages::{
+    filter_packages, FilterPackagesOptions, FilterPackagesResult, WorkspaceFilter,
+};
+use find_workspace_packages::{find_workspace_packages, WorkspacePackagesOpts};
+use lifecycle::{lifecycle_hook, LifecycleHook, RunLifecycleHookOptions};
+use project::Project;
+use read_project_manifest::read_project_manifest_only;
+use std::path::{Path, PathBuf};
+use types::BaseManifest;
+
+/// Where a command runs: in one project or in the projects of the workspace
+#[derive(Parser, Debug, Clone, Default)]
+pub struct RunTarget {
+    /// Run in every project of the workspace
+    #[clap(long, short = 'r')]
+    pub recursive: bool,
+    /// Only run in the selected projects of the workspace, like `@babel/*`, `foo...`,
+    /// `...^foo`, `./packages/**`, `{packages}[origin/main]` or `!foo`
+    #[clap(long, multiple_occurrences = true)]
+    pub filter: Vec<String>,
+    /// Like --filter, but only follows the dependencies and optionalDependencies
+    #[clap(long, multiple_occurrences = true)]
+    pub filter_prod: Vec<String>,
+    /// The changed files matching these patterns don't select the dependents of
+    /// their project with `...[<since>]`, like `**/test/**`
+    #[clap(long, multiple_occurrences = true)]
+    pub test_pattern: Vec<String>,
+    /// The changed files matching these patterns are ignored by `[<since>]`
+    #[clap(long, multiple_occurrences = true)]
+    pub changed_files_ignore_pattern: Vec<String>,
+    /// Run in every project at once, ignoring the order of their dependencies
+    #[clap(long)]
+    pub parallel: bool,
+    /// How many projects run at the same time. 4 by default.
+    #[clap(long)]
+    pub workspace_concurrency: Option<usize>,
+    /// Stop after the first project that fails. On by default.
+    #[clap(long, overrides_with = "no-bail")]
+    pub bail: bool,
+    /// Run in every project, even after some of them failed
+    #[clap(long, overrides_with = "bail")]
+    pub no_bail: bool,
+    /// Don't run the projects after the projects they depend on
+    #[clap(long)]
+    pub no_sort: bool,
+    /// Run the projects before the projects they depend on
+    #[clap(long)]
+    pub reverse: bool,
+    /// Run in the root project of the workspace too, which a plain `-r` leaves out
+    #[clap(long)]
+    pub include_workspace_root: bool,
+    /// Run in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    pub dir: Option<String>,
+}
+
+impl RunTarget {
+    pub fn dir(&self) -> PathBuf {
+        self.dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap())
+    }
+
+    pub fn is_recursive(&self) -> bool {
+        self.recursive || !self.filter.is_empty() || !self.filter_prod.is_empty()
+    }
+
+    pub fn workspace_dir(&self, config: &Config) -> PathBuf {
+        config.workspace_dir.clone().unwrap_or_else(|| self.dir())
+    }
+
+    pub fn workspace_projects(&self, config: &Config) -> Result<Vec<Project>> {
+        find_workspace_packages(
+            &self.workspace_dir(config).to_string_lossy(),
+            WorkspacePackagesOpts {
+                engine_strict: Some(config.engine_strict),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn filters(&self) -> Vec<WorkspaceFilter> {
+        let filters = self.filter.iter().map(|filter| (filter, false));
+        let prod_filters = self.filter_prod.iter().map(|filter| (filter, true));
+        filters
+            .chain(prod_filters)
+            .map(|(filter, follow_prod_deps_only)| WorkspaceFilter {
+                filter: filter.clone(),
+                follow_prod_deps_only,
+            })
+            .collect()
+    }
+
+    /// The patterns of the flag, or else of the setting
+    fn patterns(flag: &[String], config: &Config, key: &str) -> Vec<String> {
+        if !flag.is_empty() {
+            return flag.to_vec();
+        }
+        config
+            .raw_config
+            .get(key)
+            .map(|patterns| patterns.split(',').map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// How to run in the projects that are selected by the filters
+    pub fn recursive_options<'a>(
+        &self,
+        config: &Config,
+        projects: &'a [Project],
+    ) -> Result<RecursiveOptions<'a>> {
+        let workspace_dir = self.workspace_dir(config);
+        let filters = self.filters();
+        let FilterPackagesResult {
+            mut selected_projects_graph,
+            unmatched_filters,
+        } = filter_packages(
+            projects,
+            &filters,
+            &FilterPackagesOptions {
+                prefix: &self.dir(),
+                workspace_dir: &workspace_dir,
+                test_pattern: &Self::patterns(&self.test_pattern, config, "test-pattern"),
+                changed_files_ignore_pattern: &Self::patterns(
+                    &self.changed_files_ignore_pattern,
+                    config,
+                    "changed-files-ignore-pattern",
+                ),
+            },
+        )?;
+        // the scripts of the root usually run the recursive command themselves,
+        // like `"build": "pnpm -r build"`, so the root only runs when it is filtered in
+        if filters.is_empty()
+            && !(self.include_workspace_root || config.get_bool("include-workspace-root")?)
+        {
+            selected_projects_graph.retain(|dir, _| Path::new(dir) != workspace_dir);
+        }
+        if selected_projects_graph.is_empty() {
+            println!(
+                "No projects matched the filters in \"{}\"",
+                workspace_dir.display()
+            );
+        } else if !unmatched_filters.is_empty() {
+            println!(
+                "WARN: No projects matched the filters: {}",
+                unmatched_filters.join(", ")
+            );
+        }
+
+        Ok(RecursiveOptions {
+            selected_projects_graph,
+            sort: !self.no_sort && !self.parallel,
+            reverse: self.reverse,
+            workspace_concurrency: if self.parallel {
+                usize::MAX
+            } else {
+                match self.workspace_concurrency {
+                    Some(concurrency) => concurrency,
+                    None => parse_number(config, "workspace-concurrency")?.unwrap_or(4),
+                }
+            },
+            bail: self.bail(),
+        })
+    }
+
+    /// `--bail` and `--no-bail` override each other, the last one wins
+    fn bail(&self) -> bool {
+        self.bail || !self.no_bail
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct Run {
+    /// The script to run. The available scripts are listed when it is omitted.
+    script: Option<String>,
+    /// Passed to the script
+    args: Vec<String>,
+    /// Don't fail when the script is missing
+    #[clap(long)]
+    if_present: bool,
+    #[clap(flatten)]
+    target: RunTarget,
+}
+
+/// The scripts of a project that run for the script, with its `pre` and `post` scripts
+fn script_hooks(
+    script: &str,
+    args: &[String],
+    manifest: &BaseManifest,
+    dir: &Path,
+    enable_pre_post_scripts: bool,
+) -> Result<Vec<LifecycleHook>> {
+    let opts = |args| RunLifecycleHookOptions {
+        pkg_root: dir,
+        extra_bin_paths: &[],
+        args,
+    };
+    let mut hooks = vec![];
+    if enable_pre_post_scripts {
+        hooks.extend(lifecycle_hook(
+            &format!("pre{}", script),
+            manifest,
+            &opts(&[]),
+        )?);
+    }
+    hooks.extend(lifecycle_hook(script, manifest, &opts(args))?);
+    if enable_pre_post_scripts {
+        hooks.extend(lifecycle_hook(
+            &format!("post{}", script),
+            manifest,
+            &opts(&[]),
+        )?);
+    }
+    Ok(hooks)
+}
+
+fn run_hooks(hooks: Vec<LifecycleHook>) -> Result<()> {
+    for hook in hooks {
+        println!("\n{}\n", hook.banner());
+        hook.run()?;
+    }
+    Ok(())
+}
+
+fn has_script(manifest: &BaseManifest, script: &str) -> bool {
+    manifest
+        .scripts
+        .as_ref()
+        .is_some_and(|scripts| scripts.contains_key(script))
+}
+
+/// `start` runs `node server.js` when there is no start script
+fn with_default_start_script(manifest: &BaseManifest, dir: &Path) -> Result<BaseManifest> {
+    let mut manifest = manifest.clone();
+    if !has_script(&manifest, "start") {
+        if !dir.join("server.js").exists() {
+            bail!("NO_SCRIPT_OR_SERVER: Missing script start or file server.js");
+        }
+        manifest
+            .scripts
+            .get_or_insert_with(Default::default)
+            .insert("start".to_string(), "node server.js".to_string());
+    }
+    Ok(manifest)
+}
+
+fn print_scripts(manifest: &BaseManifest) {
+    let mut scripts = manifest.scripts.iter().flatten().collect::<Vec<_>>();
+    if scripts.is_empty() {
+        println!("There are no scripts specified.");
+        return;
+    }
+    scripts.sort();
+    println!("Commands available via \"pnpm run\":");
+    for (name, script) in scripts {
+        println!("  {}\n    {}", name, script);
+    }
+}
+
+impl Run {
+    pub fn new(script: &str, args: Vec<String>, target: RunTarget) -> Self {
+        Self {
+            script: Some(script.to_string()),
+            args,
+            if_present: false,
+            target,
+        }
+    }
+
+    /// The script of the project in the current directory that is run as `pnpm <script>`
+    pub fn implicit(args: &[String]) -> Option<Self> {
+        let cwd = std::env::current_dir().ok()?;
+        let manifest = read_project_manifest_only(&cwd.to_string_lossy()).ok()?;
+        if !has_script(&manifest, &args[0]) {
+            return None;
+        }
+        Some(Self::new(
+            &args[0],
+            args[1..].to_vec(),
+            RunTarget::default(),
+        ))
+    }
+
+    fn run_in_workspace(&self, script: &str, config: &Config) -> Result<()> {
+        let enable_pre_post_scripts = config.get_bool("enable-pre-post-scripts")?;
+        let projects = self.target.workspace_projects(config)?;
+        let summary = recursive(
+            &self.target.recursive_options(config, &projects)?,
+            |project| {
+                if !has_script(&project.manifest, script) {
+                    return Ok(None);
+                }
+                let hooks = script_hooks(
+                    script,
+                    &self.args,
+                    &project.manifest,
+                    Path::new(&project.dir),
+                    enable_pre_post_scripts,
+                )?;
+                Ok(Some(Box::new(move || run_hooks(hooks)) as Job))
+            },
+        )?;
+
+        let nothing_ran = summary.skipped() == summary.results.len();
+        if nothing_ran && !summary.results.is_empty() && !self.if_present {
+            bail!(
+                "RECURSIVE_RUN_NO_SCRIPT: None of the selected packages has a \"{}\" script",
+                script
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Command for Run {
+    fn exec(&self) -> Result<()> {
+        let dir = self.target.dir();
+        let config = get_config(GetConfigOptions::new(&dir)?)?;
+        let script = match &self.script {
+            Some(script) => script,
+            None if self.target.is_recursive() => {
+                bail!("SCRIPT_NAME_IS_REQUIRED: You must specify the script you want to run")
+            }
+            None => {
+                print_scripts(&read_project_manifest_only(&dir.to_string_lossy())?);
+                return Ok(());
+            }
+        };
+        if self.target.is_recursive() {
+            return self.run_in_workspace(script, &config);
+        }
+
+        let mut manifest = read_project_manifest_only(&dir.to_string_lossy())?;
+        if script == "start" {
+            manifest = with_default_start_script(&manifest, &dir)?;
+        }
+        if !has_script(&manifest, script) {
+            if self.if_present {
+                return Ok(());
+            }
+            bail!("NO_SCRIPT: Missing script: {}", script);
+        }
+        run_hooks(script_hooks(
+            script,
+            &self.args,
+            &manifest,
+            &dir,
+            config.get_bool("enable-pre-post-scripts")?,
+        )?)
+    }
+}
+
+/// Runs the `test` script
+#[derive(Parser, Debug)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct Test {
+    /// Passed to the script
+    args: Vec<String>,
+    #[clap(flatten)]
+    target: RunTarget,
+}
+
+impl Command for Test {
+    fn exec(&self) -> Result<()> {
+        Run::new("test", self.args.clone(), self.target.clone()).exec()
+    }
+}
+
+/// Runs the `start` script, or `node server.js` when there is none
+#[derive(Parser, Debug)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+pub struct Start {
+    /// Passed to the script
+    args: Vec<String>,
+    #[clap(flatten)]
+    target: RunTarget,
+}
+
+impl Command for Start {
+    fn exec(&self) -> Result<()> {
+        Run::new("start", self.args.clone(), self.target.clone()).exec()
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Command;
+use anyhow::{bail, Result};
+use cafs::{verify_store, VerifyStoreOptions};
+use clap::{Parser, Subcommand};
+use store_path::store_path;
+use store_status::{store_status, StoreStatusOptions};
+
+#[derive(Parser, Debug)]
+pub struct Store {
+    #[clap(subcommand)]
+    command: StoreCommand,
+    /// The directory in which the store is located
+    #[clap(long)]
+    store_dir: Option<String>,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    dir: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum StoreCommand {
+    /// Checks for modified packages in the store
+    Status {
+        /// The directory with links to the store (`node_modules/.pnpm` by default)
+        #[clap(long)]
+        virtual_store_dir: Option<String>,
+    },
+    /// Checks every file in the store against its digest
+    Verify {
+        /// Move the corrupted files out of the store
+        #[clap(long)]
+        quarantine: bool,
+    },
+}
+
+impl Store {
+    fn dir(&self) -> String {
+        self.dir.clone().unwrap_or_else(|| {
+            std::env::current_dir()
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        })
+    }
+}
+
+impl Command for Store {
+    fn exec(&self) -> Result<()> {
+        match &self.command {
+            StoreCommand::Status { virtual_store_dir } => {
+                let modified = store_status(StoreStatusOptions {
+                    dir: self.dir(),
+                    store_dir: self.store_dir.clone(),
+                    virtual_store_dir: virtual_store_dir.clone(),
+                })?;
+
+                if modified.is_empty() {
+                    println!("Packages in the store are untouched");
+                    return Ok(());
+                }
+
+                for pkg in &modified {
+                    println!("{} ({})", pkg.dep_path, pkg.pkg_dir.display());
+                    for file in &pkg.diff.modified {
+                        println!("  modified: {}", file);
+                    }
+                    for file in &pkg.diff.added {
+                        println!("  added:    {}", file);
+                    }
+                    for file in &pkg.diff.removed {
+                        println!("  removed:  {}", file);
+                    }
+                }
+
+                bail!("MODIFIED_DEPENDENCY: Packages in the store have been mutated")
+            }
+            StoreCommand::Verify { quarantine } => {
+                let store_dir = store_path(&self.dir(), self.store_dir.as_deref())?;
+                let corrupted = verify_store(
+                    &store_dir,
+                    VerifyStoreOptions {
+                        quarantine: *quarantine,
+                    },
+                )?;
+
+                if corrupted.is_empty() {
+                    println!("All files in {} are valid", store_dir.display());
+                    return Ok(());
+                }
+
+                for file in &corrupted {
+                    match &file.quarantined_to {
+                        Some(target) => {
+                            println!("{} moved to {}", file.path.display(), target.display())
+                        }
+                        None => println!("{}", file.path.display()),
+                    }
+                }
+
+                bail!(
+                    "CORRUPTED_STORE: {} files in the store don't match their digest",
+                    corrupted.len()
+                )
+            }
+        }
+    }
+}
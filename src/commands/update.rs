@@ -0,0 +1,194 @@
+use crate::install_deps::{
+    create_install_context, install_deps, InstallContext, InstallDepsOpts, RawLocalConfig,
+};
+use crate::Command;
+use anyhow::{bail, Result};
+use clap::Parser;
+use cli_utils::{
+    package_is_installable::PackageIsInstallableOpts,
+    read_project_manifest::try_read_project_manifest,
+};
+use config::{get_config, Config, GetConfigOptions};
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+use pnpm_core::install::update_dependencies::{
+    outdated_dependencies, OutdatedDependency, UpdateDependenciesOptions,
+};
+use std::path::{Path, PathBuf};
+use types::{DependencyField, IncludedDependencies};
+
+/// `Infinity` or a number
+fn parse_depth(depth: &str) -> Result<usize, String> {
+    if depth == "Infinity" {
+        return Ok(usize::MAX);
+    }
+    depth
+        .parse()
+        .map_err(|_| format!("{} is not a number or Infinity", depth))
+}
+
+#[derive(Parser, Debug)]
+pub struct Update {
+    /// The packages to update, like `foo`, `@babel/*` or `foo@2`. All of them when none is given.
+    packages: Vec<String>,
+    /// Update to the latest versions, even if they don't satisfy the ranges in package.json
+    #[clap(long, short = 'L')]
+    latest: bool,
+    /// Choose the dependencies to update from the list of the outdated ones
+    #[clap(long, short = 'i')]
+    interactive: bool,
+    /// How deep the dependencies are updated, 0 for the direct dependencies only.
+    /// Infinity by default.
+    #[clap(long, parse(try_from_str = parse_depth))]
+    depth: Option<usize>,
+    /// Only update the dependencies and optionalDependencies
+    #[clap(long, short = 'P', visible_alias = "production")]
+    prod: bool,
+    /// Only update the devDependencies
+    #[clap(long, short = 'D', conflicts_with = "prod")]
+    dev: bool,
+    /// Don't update the optionalDependencies
+    #[clap(long)]
+    no_optional: bool,
+    /// Run the command in this directory instead of the current working directory
+    #[clap(long, short = 'C')]
+    dir: Option<String>,
+}
+
+impl Update {
+    fn include(&self) -> IncludedDependencies {
+        IncludedDependencies {
+            dependencies: !self.dev,
+            dev_dependencies: !self.prod,
+            optional_dependencies: !self.dev && !self.no_optional,
+        }
+    }
+
+    /// The names of the outdated dependencies the user picked
+    fn select_interactively(&self, config: &Config, dir: &Path) -> Result<Vec<String>> {
+        let dir = dir.to_string_lossy().to_string();
+        let project_manifest = try_read_project_manifest(
+            &dir,
+            PackageIsInstallableOpts {
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+            },
+        )?;
+        let manifest = match &project_manifest.manifest {
+            Some(manifest) => manifest,
+            None => bail!("NO_IMPORTER_MANIFEST: No package.json found in {}", dir),
+        };
+        let InstallContext {
+            client,
+            install_opts,
+            ..
+        } = create_install_context(
+            config,
+            &dir,
+            config
+                .workspace_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().to_string()),
+            self.include(),
+        )?;
+        let patterns = self.packages.iter().map(String::as_str).collect::<Vec<_>>();
+        let outdated = outdated_dependencies(
+            &client,
+            manifest,
+            &UpdateDependenciesOptions {
+                patterns: &patterns,
+                depth: 0,
+                latest: self.latest,
+                save_prefix: config.save_prefix.clone(),
+            },
+            &install_opts,
+        )?;
+        if outdated.is_empty() {
+            println!("All of your dependencies are already up to date");
+            return Ok(vec![]);
+        }
+
+        let alias_width = outdated
+            .iter()
+            .map(|dep| dep.alias.len())
+            .max()
+            .unwrap_or_default();
+        let mut selected = vec![];
+        // one list per dependency type
+        for field in DependencyField::iterator() {
+            let deps = outdated
+                .iter()
+                .filter(|dep| dep.field == field)
+                .collect::<Vec<&OutdatedDependency>>();
+            if deps.is_empty() {
+                continue;
+            }
+            let items = deps
+                .iter()
+                .map(|dep| {
+                    format!(
+                        "{:width$}  {} ❯ {}",
+                        dep.alias,
+                        dep.current.as_deref().unwrap_or("missing"),
+                        dep.wanted,
+                        width = alias_width
+                    )
+                })
+                .collect::<Vec<_>>();
+            let chosen = MultiSelect::with_theme(&ColorfulTheme::default())
+                .with_prompt(field.as_str())
+                .items(&items)
+                .interact()?;
+            selected.extend(chosen.into_iter().map(|index| deps[index].alias.clone()));
+        }
+        if selected.is_empty() {
+            println!("No dependencies were selected");
+        }
+        Ok(selected)
+    }
+}
+
+impl Command for Update {
+    fn exec(&self) -> Result<()> {
+        let dir = self
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap());
+        let config = get_config(GetConfigOptions::new(&dir)?)?;
+
+        let packages = if self.interactive {
+            let selected = self.select_interactively(&config, &dir)?;
+            if selected.is_empty() {
+                return Ok(());
+            }
+            selected
+        } else {
+            self.packages.clone()
+        };
+
+        let packages = packages.iter().map(String::as_str).collect::<Vec<_>>();
+        install_deps(
+            InstallDepsOpts {
+                dir: dir.to_string_lossy().to_string(),
+                update: Some(true),
+                workspace: None,
+                latest: Some(self.latest),
+                update_depth: self.depth,
+                save_workspace_protocol: None,
+                link_workspace_packages: config.get_bool("link-workspace-packages")?,
+                workspace_dir: config
+                    .workspace_dir
+                    .as_ref()
+                    .map(|dir| dir.to_string_lossy().to_string()),
+                raw_local_config: RawLocalConfig::from_config(&config),
+                include_direct: Some(self.include()),
+                engine_strict: Some(config.engine_strict),
+                node_version: None,
+                target_dependency_field: None,
+                save_peer: false,
+                config: &config,
+            },
+            &packages,
+        )
+    }
+}
@@ -1,44 +1,186 @@
-use crate::recursive::{recursive, CommandFullName, RecursiveOptions};
 use anyhow::{bail, Result};
 use cli_utils::{
     package_is_installable::PackageIsInstallableOpts,
     read_project_manifest::try_read_project_manifest,
 };
-use find_workspace_packages::{slice_of_workspace_packages_to_map, ManifestOnlyPackage};
-use project::{Graph, Project, ProjectsGraph};
+use client::{create_client, Client, ClientOptions};
+use config::Config;
+use fetch::{AgentOptions, RetryTimeoutOptions};
+use find_workspace_packages::{find_workspace_packages, WorkspacePackagesOpts};
+use pnpm_core::install::add_dependencies::{add_dependencies_to_package, AddDependenciesOptions};
+use pnpm_core::install::extend_install_options::InstallOptions;
+use pnpm_core::install::install;
+use pnpm_core::install::update_dependencies::{
+    update_dependencies_of_package, UpdateDependenciesOptions,
+};
+use project::Project;
 use rayon::prelude::*;
-use relative_path::RelativePath;
-use sort_packages::sequence_graph;
-use std::collections::HashMap;
-use types::{BaseManifest, IncludedDependencies};
+use resolvers::base::{WorkspacePackage, WorkspacePackages};
+use std::path::PathBuf;
+use std::time::Duration;
+use store_connection_manager::{StoreController, StoreEntry};
+use store_path::store_path;
+use types::{BaseManifest, DependencyField, IncludedDependencies};
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct RawLocalConfig {
     save_workspace_protocol: Option<bool>,
-    user_agent: String,
     shamefully_hoist: Option<bool>,
     public_hoist_pattern: Option<String>,
     hoist_pattern: Option<String>,
     hoist: Option<bool>,
-    save_exact: Option<bool>,
-    modules_dir: Option<String>,
+}
+
+impl RawLocalConfig {
+    /// Only the settings of the workspace and project `.npmrc` files,
+    /// to know which ones were set explicitly
+    pub fn from_config(config: &Config) -> Self {
+        let raw = &config.raw_local_config;
+        let get_bool = |key: &str| raw.get(key).map(|value| value != "false");
+
+        Self {
+            save_workspace_protocol: get_bool("save-workspace-protocol"),
+            shamefully_hoist: get_bool("shamefully-hoist"),
+            public_hoist_pattern: raw.get("public-hoist-pattern").cloned(),
+            hoist_pattern: raw.get("hoist-pattern").cloned(),
+            hoist: get_bool("hoist"),
+        }
+    }
 }
 
 pub struct InstallDepsOpts<'a> {
-    dir: String,
-    update: Option<bool>,
-    workspace: Option<bool>,
-    latest: Option<bool>,
-    save_workspace_protocol: Option<bool>,
-    link_workspace_packages: bool,
+    pub dir: String,
+    pub update: Option<bool>,
+    pub workspace: Option<bool>,
+    pub latest: Option<bool>,
+    /// How deep the dependencies are updated, all the way down when it is `None`
+    pub update_depth: Option<usize>,
+    pub save_workspace_protocol: Option<bool>,
+    pub link_workspace_packages: bool,
+    pub workspace_dir: Option<String>,
+    pub raw_local_config: RawLocalConfig,
+    pub include_direct: Option<IncludedDependencies>,
+    pub engine_strict: Option<bool>,
+    pub node_version: Option<String>,
+    /// The field the added dependencies are saved to
+    pub target_dependency_field: Option<DependencyField>,
+    /// Save the added dependencies to `peerDependencies` too
+    pub save_peer: bool,
+    pub config: &'a Config,
+}
+
+pub(crate) fn parse_number<T: std::str::FromStr>(config: &Config, key: &str) -> Result<Option<T>> {
+    match config.raw_config.get(key) {
+        Some(value) => match value.parse() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => bail!(
+                "CONFIG_INVALID_VALUE: {} should be a number, got \"{}\"",
+                key,
+                value
+            ),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The network and auth settings of the client, as they are set in the `.npmrc`
+pub fn client_options(config: &Config) -> Result<ClientOptions> {
+    let raw = &config.raw_config;
+    let defaults = RetryTimeoutOptions::default();
+    let millis = |key: &str| -> Result<Option<Duration>> {
+        Ok(parse_number(config, key)?.map(Duration::from_millis))
+    };
+
+    Ok(ClientOptions {
+        auth_config: raw.clone(),
+        retry: Some(RetryTimeoutOptions {
+            retries: parse_number(config, "fetch-retries")?.unwrap_or(defaults.retries),
+            factor: parse_number(config, "fetch-retry-factor")?.unwrap_or(defaults.factor),
+            min_timeout: millis("fetch-retry-mintimeout")?.unwrap_or(defaults.min_timeout),
+            max_timeout: millis("fetch-retry-maxtimeout")?.unwrap_or(defaults.max_timeout),
+        }),
+        timeout: parse_number(config, "fetch-timeout")?,
+        connect_timeout: None,
+        user_agent: raw.get("user-agent").cloned(),
+        user_config: Some(config.user_config.clone()),
+        agent: AgentOptions {
+            ca: raw
+                .get("ca")
+                .map(|ca| ca.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            cafile: raw.get("cafile").map(PathBuf::from),
+            cert: raw.get("cert").cloned(),
+            key: raw.get("key").cloned(),
+            strict_ssl: Some(config.strict_ssl),
+            http_proxy: raw.get("http-proxy").or_else(|| raw.get("proxy")).cloned(),
+            https_proxy: raw.get("https-proxy").or_else(|| raw.get("proxy")).cloned(),
+            no_proxy: raw.get("no-proxy").cloned(),
+        },
+    })
+}
+
+/// What is needed to install the dependencies of a project
+pub struct InstallContext {
+    pub store: StoreEntry,
+    pub client: Client,
+    pub install_opts: InstallOptions,
+}
+
+pub fn create_install_context(
+    config: &Config,
+    dir: &str,
     workspace_dir: Option<String>,
-    raw_local_config: RawLocalConfig,
-    include_direct: Option<IncludedDependencies>,
-    all_projects: Option<Vec<Project>>,
-    selected_projects_graph: Option<ProjectsGraph<'a>>,
-    engine_strict: Option<bool>,
-    node_version: Option<String>,
-    sort: bool,
+    include: IncludedDependencies,
+) -> Result<InstallContext> {
+    // the store is used directly, as there is no store server to connect to yet
+    let store_dir = store_path(
+        workspace_dir.as_deref().unwrap_or(dir),
+        config.store_dir.as_deref(),
+    )?;
+    let store = StoreEntry {
+        ctrl: StoreController::new(&store_dir),
+        dir: store_dir.to_string_lossy().to_string(),
+    };
+    let workspace_packages = match &workspace_dir {
+        Some(workspace_dir) => Some(workspace_packages(&find_workspace_packages(
+            workspace_dir,
+            WorkspacePackagesOpts {
+                engine_strict: Some(config.engine_strict),
+                ..Default::default()
+            },
+        )?)),
+        None => None,
+    };
+    let client = create_client(client_options(config)?)?;
+    let dir = PathBuf::from(dir);
+    let install_opts = InstallOptions {
+        modules_dir: dir.join(&config.modules_dir),
+        virtual_store_dir: config.virtual_store_dir.clone(),
+        store_dir: PathBuf::from(&store.dir),
+        hoist_pattern: config.hoist_pattern.clone(),
+        public_hoist_pattern: config.public_hoist_pattern.clone(),
+        force_hoist_pattern: false,
+        force_public_hoist_pattern: false,
+        node_linker: config.node_linker,
+        cache_dir: config.cache_dir.clone(),
+        registries: config.registries.clone(),
+        workspace_packages,
+        include,
+        frozen_lockfile: config.get_bool("frozen-lockfile")?,
+        prefer_frozen_lockfile: config.get_bool("prefer-frozen-lockfile")?,
+        lockfile_only: config.get_bool("lockfile-only")?,
+        ignore_scripts: config.get_bool("ignore-scripts")?,
+        side_effects_cache: config.get_bool("side-effects-cache")?,
+        offline: config.get_bool("offline")?,
+        confirm_modules_purge: config.get_bool("confirm-modules-purge")?,
+        dir,
+    };
+
+    Ok(InstallContext {
+        store,
+        client,
+        install_opts,
+    })
 }
 
 pub fn install_deps<'a>(mut opts: InstallDepsOpts<'a>, params: &[&str]) -> Result<()> {
@@ -46,7 +188,7 @@ pub fn install_deps<'a>(mut opts: InstallDepsOpts<'a>, params: &[&str]) -> Resul
         if opts.latest.unwrap_or(false) {
             bail!("BAD_OPTIONS: Cannot use --latest with --workspace simultaneously");
         }
-        if opts.workspace.is_none() {
+        if opts.workspace_dir.is_none() {
             bail!("WORKSPACE_OPTION_OUTSIDE_WORKSPACE: --workspace can only be used inside a workspace")
         }
         if !opts.link_workspace_packages && !opts.save_workspace_protocol.unwrap_or(false) {
@@ -59,142 +201,133 @@ pub fn install_deps<'a>(mut opts: InstallDepsOpts<'a>, params: &[&str]) -> Resul
                 opts.save_workspace_protocol = Some(true)
             }
         }
+    }
 
-        // opts['preserveWorkspaceProtocol'] = !opts.linkWorkspacePackages
-        let _include_direct = opts.include_direct.unwrap_or(IncludedDependencies {
-            dependencies: true,
-            dev_dependencies: true,
-            optional_dependencies: true,
-        });
-        let _force_hoist_pattern =
-            opts.raw_local_config.hoist_pattern.is_some() || opts.raw_local_config.hoist.is_some();
-        let _force_public_hoist_pattern = opts.raw_local_config.shamefully_hoist.is_some()
-            || opts.raw_local_config.public_hoist_pattern.is_some();
-        let all_projects = match opts.all_projects {
-            Some(all_projects) => all_projects,
-            None => opts
-                .workspace_dir
-                .as_ref()
-                .map(|workspace_dir| find_workspace_packages(workspace_dir))
-                .unwrap_or_default(),
-        };
-
-        if let Some(_workspace_dir) = &opts.workspace_dir {
-            if let Some(selected_projects_graph) = match opts.selected_projects_graph {
-                Some(graph) => Some(graph),
-                None => select_project_by_dir(&all_projects, &opts.dir),
-            } {
-                let sequenced_graph = sequence_graph(&selected_projects_graph);
-
-                if !sequenced_graph.safe {
-                    let _cyclic_dependencies_info = if sequenced_graph.cycles.is_empty() {
-                        format!("")
-                    } else {
-                        sequenced_graph
-                            .cycles
-                            .par_iter()
-                            .map(|deps| {
-                                deps.par_iter()
-                                    .map(|&x| x.clone())
-                                    .collect::<Vec<_>>()
-                                    .join("-")
-                            })
-                            .collect::<Vec<String>>()
-                            .join("; ")
-                    };
-                    println!("WARN: there are cyclic workspace dependencies")
-                }
-
-                recursive(
-                    &all_projects,
-                    params,
-                    RecursiveOptions {
-                        selected_projects_graph,
-                        sort: opts.sort,
-                    },
-                    if opts.update.unwrap_or(false) {
-                        CommandFullName::Update
-                    } else if params.is_empty() {
-                        CommandFullName::Install
-                    } else {
-                        CommandFullName::Add
-                    },
-                );
-                return Ok(());
+    // opts['preserveWorkspaceProtocol'] = !opts.linkWorkspacePackages
+    let include_direct = opts.include_direct.unwrap_or(IncludedDependencies {
+        dependencies: true,
+        dev_dependencies: true,
+        optional_dependencies: true,
+    });
+    let force_hoist_pattern =
+        opts.raw_local_config.hoist_pattern.is_some() || opts.raw_local_config.hoist.is_some();
+    let force_public_hoist_pattern = opts.raw_local_config.shamefully_hoist.is_some()
+        || opts.raw_local_config.public_hoist_pattern.is_some();
+    // `pnpm install ""` is going to be just `pnpm install`
+    let params = params
+        .par_iter()
+        .filter(|param| !param.is_empty())
+        .copied()
+        .collect::<Vec<_>>();
+
+    let project_manifest = try_read_project_manifest(
+        &opts.dir,
+        PackageIsInstallableOpts {
+            engine_strict: opts.engine_strict,
+            node_version: opts.node_version,
+        },
+    )?;
+    let temp;
+    let manifest = match &project_manifest.manifest {
+        Some(manifest) => manifest,
+        None => {
+            if opts.update.unwrap_or(false) {
+                bail!("NO_IMPORTER_MANIFEST: No package.json found");
             }
+            temp = Default::default();
+            &temp
         }
+    };
+    let write_project_manifest = |updated_manifest: &BaseManifest| {
+        // a new package.json is written when there was none
+        project_manifest
+            .write_project_manifest(updated_manifest, project_manifest.manifest.is_none())
+    };
 
-        // `pnpm install ""` is going to be just `pnpm install`
-        let params = params
-            .par_iter()
-            .filter(|param| !param.is_empty())
-            .copied()
-            .collect::<Vec<_>>();
-        let dir = &opts.dir;
-        // .unwrap_or_else(|| {
-        //     std::env::current_dir()
-        //         .unwrap()
-        //         .to_string_lossy()
-        //         .to_string()
-        // });
-        let packages = all_projects
-            .iter()
-            .map(|project| ManifestOnlyPackage {
-                manifest: &project.manifest,
-            })
-            .collect::<Vec<_>>();
-        let _workspace_packages = match opts.workspace_dir {
-            Some(_) => Some(slice_of_workspace_packages_to_map(&packages)),
-            None => None,
-        };
-
-        let project_manifest = try_read_project_manifest(
-            &opts.dir,
-            PackageIsInstallableOpts {
-                engine_strict: opts.engine_strict,
-                node_version: opts.node_version,
+    let config = opts.config;
+    let InstallContext {
+        store,
+        client,
+        mut install_opts,
+    } = create_install_context(
+        config,
+        &opts.dir,
+        opts.workspace_dir.clone(),
+        include_direct,
+    )?;
+    install_opts.force_hoist_pattern = force_hoist_pattern;
+    install_opts.force_public_hoist_pattern = force_public_hoist_pattern;
+
+    if opts.update.unwrap_or(false) {
+        let (updated_manifest, _) = update_dependencies_of_package(
+            &client,
+            &store.ctrl,
+            manifest,
+            &UpdateDependenciesOptions {
+                patterns: &params,
+                depth: opts.update_depth.unwrap_or(usize::MAX),
+                latest: opts.latest.unwrap_or(false),
+                save_prefix: config.save_prefix.clone(),
             },
+            &install_opts,
         )?;
-        let temp;
-        let manifest = match &project_manifest.manifest {
-            Some(manifest) => manifest,
-            None => {
-                if opts.update.unwrap_or(false) {
-                    bail!("NO_IMPORTER_MANIFEST: No package.json found");
-                }
-                temp = Default::default();
-                &temp
-            }
-        };
-        let write_project_manifest = |updated_manifest: &BaseManifest, force: bool| {
-            project_manifest.write_project_manifest(updated_manifest, force)
-        };
-
-        let store = create_or_connect_store_controller();
+        write_project_manifest(&updated_manifest)?;
+        return Ok(());
     }
 
-    Ok(())
-}
+    if params.is_empty() {
+        install(&client, &store.ctrl, manifest, &install_opts)?;
+        return Ok(());
+    }
 
-fn select_project_by_dir<'a>(
-    all_projects: &'a [Project],
-    search_dir: &str,
-) -> Option<ProjectsGraph<'a>> {
-    let project = all_projects.iter().find(|Project { dir, .. }| {
-        RelativePath::new(dir).to_path(search_dir).to_string_lossy() == ""
+    let workspace_packages = opts.workspace.unwrap_or(false).then(|| {
+        install_opts
+            .workspace_packages
+            .iter()
+            .flatten()
+            .flat_map(|(name, versions)| {
+                versions
+                    .values()
+                    .filter_map(|package| Some((name.clone(), package.manifest.version.clone()?)))
+            })
+            .collect()
     });
+    let (updated_manifest, _) = add_dependencies_to_package(
+        &client,
+        &store.ctrl,
+        manifest,
+        &params,
+        &AddDependenciesOptions {
+            target_dependency_field: opts.target_dependency_field,
+            peer: opts.save_peer,
+            save_prefix: config.save_prefix.clone(),
+            workspace_packages,
+            save_workspace_protocol: opts.save_workspace_protocol.unwrap_or(false),
+        },
+        &install_opts,
+    )?;
+    write_project_manifest(&updated_manifest)?;
 
-    project.map(|project| {
-        HashMap::from_iter([(
-            search_dir.to_string(),
-            Graph {
-                dependencies: vec![],
-                package: project,
-            },
-        )])
-    })
+    Ok(())
 }
 
-fn find_workspace_packages(_workspace_dir: &str) -> Vec<Project> {
-    vec![]
+/// The projects of the workspace by name and version
+fn workspace_packages(all_projects: &[Project]) -> WorkspacePackages {
+    let mut workspace_packages = WorkspacePackages::new();
+    for project in all_projects {
+        let manifest = &project.manifest;
+        if let Some(name) = &manifest.name {
+            workspace_packages.entry(name.clone()).or_default().insert(
+                manifest
+                    .version
+                    .clone()
+                    .unwrap_or_else(|| "0.0.0".to_string()),
+                WorkspacePackage {
+                    dir: project.dir.clone(),
+                    manifest: manifest.clone(),
+                },
+            );
+        }
+    }
+    workspace_packages
 }
@@ -1,7 +1,8 @@
 use clap::{AppSettings, IntoApp, Parser, Subcommand};
 mod commands;
 mod recursive;
-use commands::{add, install};
+use anyhow::Result;
+use commands::{add, config, dlx, exec, install, remove, run, store, update};
 mod install_deps;
 
 #[derive(Parser, Debug)]
@@ -18,23 +19,50 @@ struct Args {
 enum Commands {
     /// Installs a package and any packages that it depends on. By default, any new package is installed as a prod dependency add
     Add(add::Add),
+    /// Manages the settings in the `.npmrc` files
+    Config(config::Config),
+    /// Fetches a package from the registry without installing it as a dependency and runs its command
+    Dlx(dlx::Dlx),
+    /// Executes a shell command in the scope of a project
+    Exec(exec::Exec),
     /// Install all dependencies for a project
     Install(install::Install),
-    Remove,
-    Update,
-    Run,
+    /// Removes packages from node_modules and from the project's package.json
+    #[clap(visible_aliases = &["rm", "uninstall", "un"])]
+    Remove(remove::Remove),
+    /// Updates packages to their latest version based on the specified range
+    #[clap(visible_aliases = &["up", "upgrade"])]
+    Update(update::Update),
+    /// Runs a script defined in the package's manifest file
+    #[clap(visible_alias = "run-script")]
+    Run(run::Run),
+    /// Runs the `start` script, or `node server.js` when there is none
+    Start(run::Start),
+    /// Reads and performs actions on pnpm store that is on the current filesystem
+    Store(store::Store),
+    /// Runs the `test` script
+    #[clap(visible_aliases = &["t", "tst"])]
+    Test(run::Test),
 }
 
 pub trait Command {
-    fn exec(&self) {}
+    fn exec(&self) -> Result<()>;
 }
 
 impl Commands {
-    fn exec(&self) {
+    fn exec(&self) -> Result<()> {
         match &self {
             Self::Add(x) => x.exec(),
+            Self::Config(x) => x.exec(),
+            Self::Dlx(x) => x.exec(),
+            Self::Exec(x) => x.exec(),
             Self::Install(x) => x.exec(),
-            _ => {}
+            Self::Remove(x) => x.exec(),
+            Self::Run(x) => x.exec(),
+            Self::Start(x) => x.exec(),
+            Self::Store(x) => x.exec(),
+            Self::Test(x) => x.exec(),
+            Self::Update(x) => x.exec(),
         }
     }
 }
@@ -43,6 +71,14 @@ fn main() {
     let args = Args::parse();
 
     if !args.npm.is_empty() {
+        // `pnpm foo` runs the foo script of the project, when it has one
+        if let Some(run) = run::Run::implicit(&args.npm) {
+            if let Err(error) = run.exec() {
+                eprintln!("ERROR {}", error);
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
         std::process::Command::new("npm")
             .args(args.npm)
             .spawn()
@@ -52,7 +88,11 @@ fn main() {
         std::process::exit(0);
     }
 
-    if let Some(_command) = args.command {
+    if let Some(command) = args.command {
+        if let Err(error) = command.exec() {
+            eprintln!("ERROR {}", error);
+            std::process::exit(1);
+        }
     } else {
         Args::into_app().print_help().unwrap();
     }
@@ -1,58 +1,313 @@
+use anyhow::{bail, Result};
 use project::{Project, ProjectsGraph};
 use sort_packages::sort_packages;
-use std::collections::HashMap;
-// use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The work of a command in one project. Jobs are created on the main thread,
+/// because projects can't be shared between threads, and run on the workers.
+pub type Job<'a> = Box<dyn FnOnce() -> Result<()> + Send + 'a>;
 
 pub struct RecursiveOptions<'a> {
     pub selected_projects_graph: ProjectsGraph<'a>,
+    /// Run the projects after the projects they depend on
     pub sort: bool,
+    /// Run the projects before the projects they depend on
+    pub reverse: bool,
+    /// How many projects of a chunk run at the same time
+    pub workspace_concurrency: usize,
+    /// Stop after the first failure instead of running every project
+    pub bail: bool,
+}
+
+#[derive(Debug)]
+pub enum ProjectStatus {
+    Passed,
+    Failed(anyhow::Error),
+    /// The command has nothing to do in the project, or it did not run because another project failed
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct ProjectResult {
+    pub dir: String,
+    pub name: Option<String>,
+    pub status: ProjectStatus,
+    pub duration: Duration,
 }
 
-pub enum CommandFullName {
-    Install,
-    Add,
-    Remove,
-    Unlink,
-    Update,
-    Import,
+#[derive(Debug)]
+pub struct RecursiveSummary {
+    pub results: Vec<ProjectResult>,
 }
 
-pub fn recursive<'a>(
-    all_projects: &[Project],
-    params: &[&str],
-    opts: RecursiveOptions<'a>,
-    cmd_full_name: CommandFullName,
-) -> bool {
-    if all_projects.is_empty() {
-        return false;
+impl RecursiveSummary {
+    fn count(&self, matches: fn(&ProjectStatus) -> bool) -> usize {
+        self.results
+            .iter()
+            .filter(|result| matches(&result.status))
+            .count()
     }
 
-    let pkgs = opts
-        .selected_projects_graph
-        .iter()
-        .map(|(_, ws_pkg)| ws_pkg.package)
-        .collect::<Vec<_>>();
+    pub fn passed(&self) -> usize {
+        self.count(|status| matches!(status, ProjectStatus::Passed))
+    }
 
-    if pkgs.is_empty() {
-        return false;
+    pub fn failed(&self) -> usize {
+        self.count(|status| matches!(status, ProjectStatus::Failed(_)))
     }
 
-    let manifests_by_path = all_projects
-        .iter()
-        .map(|project| (project.dir.clone(), project))
-        .collect::<HashMap<_, _>>();
-    let chunks = if opts.sort {
-        sort_packages(&opts.selected_projects_graph)
-    } else {
-        let mut vec = opts
-            .selected_projects_graph
+    pub fn skipped(&self) -> usize {
+        self.count(|status| matches!(status, ProjectStatus::Skipped))
+    }
+
+    pub fn print(&self) {
+        println!(
+            "\nSummary: {} passed, {} failed, {} skipped",
+            self.passed(),
+            self.failed(),
+            self.skipped()
+        );
+        let names = self
+            .results
             .iter()
-            .map(|(k, _)| k)
+            .map(|result| result.name.as_deref().unwrap_or(&result.dir))
             .collect::<Vec<_>>();
-        vec.sort();
-        vec![vec]
+        let width = names
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or_default();
+        for (name, result) in names.iter().zip(&self.results) {
+            match &result.status {
+                ProjectStatus::Passed => {
+                    println!("  {:width$}  passed  {:.1?}", name, result.duration)
+                }
+                ProjectStatus::Failed(error) => println!(
+                    "  {:width$}  failed  {:.1?}  {}",
+                    name, result.duration, error
+                ),
+                ProjectStatus::Skipped => println!("  {:width$}  skipped", name),
+            }
+        }
+    }
+}
+
+/// The selected projects in the chunks they run in, one chunk after another
+fn chunks<'a>(opts: &RecursiveOptions<'a>) -> Vec<Vec<&'a Project>> {
+    let graph = &opts.selected_projects_graph;
+    let mut chunks = if opts.sort {
+        sort_packages(graph)
+            .into_iter()
+            .map(|chunk| chunk.into_iter().map(|dir| graph[dir].package).collect())
+            .collect()
+    } else {
+        vec![graph.values().map(|node| node.package).collect::<Vec<_>>()]
     };
-    // let store =
+    for chunk in &mut chunks {
+        chunk.sort_by(|project_1, project_2| project_1.dir.cmp(&project_2.dir));
+    }
+    if opts.reverse {
+        chunks.reverse();
+    }
+    chunks
+}
+
+/// Runs the jobs on up to `concurrency` threads. Once a job failed and `bail` is set,
+/// the jobs that did not start yet are skipped.
+fn run_chunk<'j>(
+    jobs: Vec<(usize, Job<'j>)>,
+    concurrency: usize,
+    bail: bool,
+    failed: &AtomicBool,
+) -> Vec<(usize, ProjectStatus, Duration)> {
+    let workers = concurrency.clamp(1, jobs.len().max(1));
+    let queue = Mutex::new(jobs.into_iter());
+    let results = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if bail && failed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let next = queue.lock().unwrap().next();
+                let (index, job) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let start = Instant::now();
+                let status = match job() {
+                    Ok(()) => ProjectStatus::Passed,
+                    Err(error) => {
+                        failed.store(true, Ordering::SeqCst);
+                        ProjectStatus::Failed(error)
+                    }
+                };
+                results
+                    .lock()
+                    .unwrap()
+                    .push((index, status, start.elapsed()));
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+/// Runs a command in each selected project, chunk by chunk, and prints a summary.
+/// `create_job` returns `None` for the projects the command has nothing to do in.
+pub fn recursive<'a, 'j>(
+    opts: &RecursiveOptions<'a>,
+    mut create_job: impl FnMut(&'a Project) -> Result<Option<Job<'j>>>,
+) -> Result<RecursiveSummary> {
+    let chunks = chunks(opts);
+    let mut results = chunks
+        .iter()
+        .flatten()
+        .map(|project| ProjectResult {
+            dir: project.dir.clone(),
+            name: project.manifest.name.clone(),
+            status: ProjectStatus::Skipped,
+            duration: Duration::ZERO,
+        })
+        .collect::<Vec<_>>();
+
+    let failed = AtomicBool::new(false);
+    let mut offset = 0;
+    for chunk in chunks {
+        if opts.bail && failed.load(Ordering::SeqCst) {
+            break;
+        }
+        let mut jobs = vec![];
+        for (index, project) in chunk.iter().enumerate() {
+            if let Some(job) = create_job(project)? {
+                jobs.push((offset + index, job));
+            }
+        }
+        offset += chunk.len();
+        for (index, status, duration) in
+            run_chunk(jobs, opts.workspace_concurrency, opts.bail, &failed)
+        {
+            results[index].status = status;
+            results[index].duration = duration;
+        }
+    }
+
+    let summary = RecursiveSummary { results };
+    // a command that ran in a single project doesn't need a summary
+    if summary.results.len() > 1 && summary.skipped() < summary.results.len() {
+        summary.print();
+    }
+    if summary.failed() > 0 {
+        if opts.bail {
+            let error = summary
+                .results
+                .into_iter()
+                .find_map(|result| match result.status {
+                    ProjectStatus::Failed(error) => Some(error),
+                    _ => None,
+                })
+                .unwrap();
+            return Err(error);
+        }
+        bail!(
+            "RECURSIVE_FAIL: {} of {} projects failed",
+            summary.failed(),
+            summary.results.len()
+        );
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use project::Graph;
+    use read_project_manifest::WriterOptions;
+    use std::sync::atomic::AtomicUsize;
+    use types::BaseManifest;
+
+    fn project(dir: &str) -> Project {
+        Project {
+            dir: dir.to_string(),
+            manifest: BaseManifest {
+                name: Some(dir.trim_start_matches('/').to_string()),
+                ..Default::default()
+            },
+            writer_options: WriterOptions {
+                insert_final_newline: None,
+                manifest_path: format!("{}/package.json", dir),
+            },
+        }
+    }
 
-    todo!()
+    fn options(projects: &[Project], bail: bool) -> RecursiveOptions<'_> {
+        // b depends on a, c depends on b
+        let dependencies = [vec![], vec!["/a"], vec!["/b"]];
+        RecursiveOptions {
+            selected_projects_graph: projects
+                .iter()
+                .zip(dependencies)
+                .map(|(project, dependencies)| {
+                    (
+                        project.dir.clone(),
+                        Graph {
+                            dependencies: dependencies.into_iter().map(String::from).collect(),
+                            package: project,
+                        },
+                    )
+                })
+                .collect(),
+            sort: true,
+            reverse: false,
+            workspace_concurrency: 4,
+            bail,
+        }
+    }
+
+    #[test]
+    fn run_in_topological_order_and_stop_on_failure() {
+        let projects = [project("/a"), project("/b"), project("/c")];
+        let order = Mutex::new(vec![]);
+        let summary = recursive(&options(&projects, true), |project| {
+            let dir = project.dir.clone();
+            let order = &order;
+            Ok(Some(Box::new(move || {
+                order.lock().unwrap().push(dir);
+                Ok(())
+            }) as Job))
+        })
+        .unwrap();
+        assert_eq!(*order.lock().unwrap(), ["/a", "/b", "/c"]);
+        assert_eq!(summary.passed(), 3);
+
+        let ran = AtomicUsize::new(0);
+        let error = recursive(&options(&projects, true), |project| {
+            let ran = &ran;
+            let fails = project.dir == "/b";
+            Ok(Some(Box::new(move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+                if fails {
+                    bail!("TEST_ERROR: b failed");
+                }
+                Ok(())
+            }) as Job))
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "TEST_ERROR: b failed");
+        assert_eq!(ran.load(Ordering::SeqCst), 2);
+
+        let error = recursive(&options(&projects, false), |project| {
+            let fails = project.dir == "/b";
+            Ok(Some(Box::new(move || {
+                if fails {
+                    bail!("TEST_ERROR: b failed");
+                }
+                Ok(())
+            }) as Job))
+        })
+        .unwrap_err();
+        assert_eq!(error.to_string(), "RECURSIVE_FAIL: 1 of 3 projects failed");
+    }
 }
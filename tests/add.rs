@@ -0,0 +1,151 @@
+mod common;
+
+use common::{
+    assert_success, is_symlink, mock_registry, packages, pnpm, pnpm_in, project, workspace, Project,
+};
+use pretty_assertions::assert_eq;
+use std::fs;
+
+fn empty_project(registry: &str) -> Project {
+    let project = project(registry);
+    fs::write(
+        project.dir.join("package.json"),
+        "{\n  \"name\": \"project\",\n  \"license\": \"MIT\"\n}\n",
+    )
+    .unwrap();
+    project
+}
+
+fn read_manifest(project: &Project) -> String {
+    fs::read_to_string(project.dir.join("package.json")).unwrap()
+}
+
+#[test]
+fn add_to_dependency_fields() {
+    let registry = mock_registry(packages());
+    let project = empty_project(&registry.url);
+
+    assert_success(&pnpm(&project, &["add", "foo"]));
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+    assert_eq!(
+        read_manifest(&project),
+        "{
+  \"name\": \"project\",
+  \"license\": \"MIT\",
+  \"dependencies\": {
+    \"foo\": \"^1.0.0\"
+  }
+}
+"
+    );
+
+    assert_success(&pnpm(&project, &["add", "-D", "baz", "--save-prefix", "~"]));
+    assert_success(&pnpm(&project, &["add", "-O", "-E", "bar"]));
+    assert!(is_symlink(&project.dir.join("node_modules/bar")));
+    assert_eq!(
+        read_manifest(&project),
+        "{
+  \"name\": \"project\",
+  \"license\": \"MIT\",
+  \"dependencies\": {
+    \"foo\": \"^1.0.0\"
+  },
+  \"devDependencies\": {
+    \"baz\": \"~1.0.0\"
+  },
+  \"optionalDependencies\": {
+    \"bar\": \"1.1.0\"
+  }
+}
+"
+    );
+
+    // the wanted range is saved as it is and the dependency stays in its field
+    assert_success(&pnpm(&project, &["add", "bar@~1.0.0"]));
+    assert!(read_manifest(&project).contains(
+        "\"optionalDependencies\": {
+    \"bar\": \"~1.0.0\"
+  }"
+    ));
+    assert_eq!(
+        fs::read_link(project.dir.join("node_modules/bar")).unwrap(),
+        std::path::Path::new(".pnpm/bar@1.0.0/node_modules/bar")
+    );
+}
+
+#[test]
+fn add_peer_dependency() {
+    let registry = mock_registry(packages());
+    let project = empty_project(&registry.url);
+
+    assert_success(&pnpm(&project, &["add", "--save-peer", "bar"]));
+
+    let manifest = read_manifest(&project);
+    assert!(manifest.contains("\"devDependencies\": {\n    \"bar\": \"^1.1.0\"\n  }"));
+    assert!(manifest.contains("\"peerDependencies\": {\n    \"bar\": \"^1.1.0\"\n  }"));
+}
+
+#[test]
+fn add_from_workspace_outside_of_workspace() {
+    let registry = mock_registry(packages());
+    let project = empty_project(&registry.url);
+
+    let output = pnpm(&project, &["add", "--workspace", "foo"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("WORKSPACE_OPTION_OUTSIDE_WORKSPACE"));
+    assert!(!project.dir.join("node_modules").exists());
+}
+
+#[test]
+fn add_to_the_root_of_a_workspace() {
+    let registry = mock_registry(packages());
+    let project = empty_project(&registry.url);
+    fs::write(
+        project.dir.join("pnpm-workspace.yaml"),
+        "packages:\n  - packages/*\n",
+    )
+    .unwrap();
+
+    let output = pnpm(&project, &["add", "foo"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ADDING_TO_ROOT"));
+    assert!(!project.dir.join("node_modules").exists());
+}
+
+#[test]
+fn add_from_workspace() {
+    let registry = mock_registry(packages());
+    let workspace = workspace(&registry.url);
+    let app_dir = workspace.dir.join("packages/app");
+
+    assert_success(&pnpm_in(
+        &workspace,
+        &app_dir,
+        &["add", "--workspace", "lib"],
+    ));
+
+    assert_eq!(
+        fs::read_to_string(app_dir.join("package.json")).unwrap(),
+        "{
+  \"name\": \"app\",
+  \"version\": \"1.0.0\",
+  \"dependencies\": {
+    \"lib\": \"workspace:^1.0.0\"
+  }
+}"
+    );
+    assert_eq!(
+        fs::read_link(app_dir.join("node_modules/lib")).unwrap(),
+        std::path::Path::new("../../lib")
+    );
+    assert!(fs::read_to_string(app_dir.join("pnpm-lock.yaml"))
+        .unwrap()
+        .contains("dependencies:\n  lib: \"link:../lib\"\n"));
+
+    // only the projects of the workspace can be added with --workspace
+    let output = pnpm_in(&workspace, &app_dir, &["add", "--workspace", "foo"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("WORKSPACE_PACKAGE_NOT_FOUND"));
+}
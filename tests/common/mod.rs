@@ -0,0 +1,221 @@
+#![allow(dead_code)]
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http_stub::{serve_on, Reply};
+use serde_json::json;
+use ssri::{Algorithm, IntegrityOpts};
+use std::collections::HashMap;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tar::{Builder, Header};
+use tempfile::{tempdir, TempDir};
+
+pub struct Package {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub manifest: serde_json::Value,
+    pub files: &'static [(&'static str, &'static str)],
+}
+
+pub fn create_tarball(package: &Package) -> Vec<u8> {
+    let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+    let manifest = package.manifest.to_string();
+    let files = [("package.json", manifest.as_str())]
+        .into_iter()
+        .chain(package.files.iter().copied());
+    for (path, content) in files {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, format!("package/{}", path), content.as_bytes())
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+pub struct MockRegistry {
+    pub url: String,
+    /// By `name@version`
+    pub integrities: HashMap<String, String>,
+    pub requests: Arc<AtomicUsize>,
+}
+
+/// A registry that serves the metadata and the tarballs of the packages
+pub fn mock_registry(packages: Vec<Package>) -> MockRegistry {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let registry = format!("http://{}/", listener.local_addr().unwrap());
+
+    let mut documents = HashMap::new();
+    let mut integrities = HashMap::new();
+    let mut metadata = HashMap::<&str, serde_json::Value>::new();
+    for package in packages {
+        let tarball = create_tarball(&package);
+        let integrity = IntegrityOpts::new()
+            .algorithm(Algorithm::Sha512)
+            .chain(&tarball)
+            .result()
+            .to_string();
+        let tarball_path = format!("/{0}/-/{0}-{1}.tgz", package.name, package.version);
+
+        let meta = metadata
+            .entry(package.name)
+            .or_insert_with(|| json!({ "name": package.name, "dist-tags": {}, "versions": {} }));
+        meta["dist-tags"]["latest"] = json!(package.version);
+        let mut manifest = package.manifest.clone();
+        manifest["dist"] = json!({
+            "integrity": integrity,
+            "tarball": format!("{}{}", registry.trim_end_matches('/'), tarball_path),
+        });
+        meta["versions"][package.version] = manifest;
+
+        integrities.insert(format!("{}@{}", package.name, package.version), integrity);
+        documents.insert(tarball_path, tarball);
+    }
+    for (name, meta) in metadata {
+        documents.insert(format!("/{}", name), meta.to_string().into_bytes());
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    serve_on(listener, move |request| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        match documents.get(request.path()) {
+            Some(body) => Reply::body(body.clone()),
+            None => Reply::status(404),
+        }
+    });
+
+    MockRegistry {
+        url: registry,
+        integrities,
+        requests,
+    }
+}
+
+pub fn packages() -> Vec<Package> {
+    vec![
+        Package {
+            name: "foo",
+            version: "1.0.0",
+            manifest: json!({
+                "name": "foo",
+                "version": "1.0.0",
+                "bin": "cli.js",
+                "dependencies": { "bar": "^1.0.0" },
+            }),
+            files: &[("cli.js", "#!/usr/bin/env node\n")],
+        },
+        Package {
+            name: "bar",
+            version: "1.0.0",
+            manifest: json!({ "name": "bar", "version": "1.0.0" }),
+            files: &[],
+        },
+        Package {
+            name: "bar",
+            version: "1.1.0",
+            manifest: json!({ "name": "bar", "version": "1.1.0" }),
+            files: &[],
+        },
+        Package {
+            name: "baz",
+            version: "1.0.0",
+            manifest: json!({
+                "name": "baz",
+                "version": "1.0.0",
+                "scripts": { "postinstall": "echo built > built.txt" },
+            }),
+            files: &[],
+        },
+    ]
+}
+
+pub struct Project {
+    pub home: TempDir,
+    pub dir: PathBuf,
+}
+
+pub fn project(registry: &str) -> Project {
+    let home = tempdir().unwrap();
+    let dir = home.path().join("project");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("package.json"),
+        json!({
+            "name": "project",
+            "version": "1.0.0",
+            "scripts": { "postinstall": "echo done > postinstall.txt" },
+            "dependencies": { "foo": "^1.0.0" },
+            "devDependencies": { "baz": "^1.0.0" },
+        })
+        .to_string(),
+    )
+    .unwrap();
+    fs::write(
+        dir.join(".npmrc"),
+        format!(
+            "registry={}\nstore-dir={}\n",
+            registry,
+            home.path().join("store").display()
+        ),
+    )
+    .unwrap();
+    Project { home, dir }
+}
+
+/// A workspace in the directory of the project, with the `lib` and `app` projects in `packages`
+pub fn workspace(registry: &str) -> Project {
+    let project = project(registry);
+    fs::write(
+        project.dir.join("pnpm-workspace.yaml"),
+        "packages:\n  - packages/*\n",
+    )
+    .unwrap();
+    for (name, manifest) in [
+        ("lib", json!({ "name": "lib", "version": "1.0.0" })),
+        ("app", json!({ "name": "app", "version": "1.0.0" })),
+    ] {
+        let dir = project.dir.join("packages").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), manifest.to_string()).unwrap();
+    }
+    project
+}
+
+pub fn pnpm(project: &Project, args: &[&str]) -> Output {
+    pnpm_in(project, &project.dir, args)
+}
+
+/// Runs pnpm in a directory of the project, like a project of a workspace
+pub fn pnpm_in(project: &Project, dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pnpm-rust"))
+        .args(args)
+        .current_dir(dir)
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("HOME", project.home.path())
+        .output()
+        .unwrap()
+}
+
+pub fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
@@ -0,0 +1,444 @@
+mod common;
+
+use common::{
+    assert_success, is_symlink, mock_registry, packages, pnpm, pnpm_in, project, workspace, Package,
+};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+#[test]
+fn install_from_registry() {
+    let registry = mock_registry(packages());
+    let integrities = registry.integrities;
+    let project = project(&registry.url);
+
+    assert_success(&pnpm(&project, &["install"]));
+
+    let modules_dir = project.dir.join("node_modules");
+    assert!(is_symlink(&modules_dir.join("foo")));
+    assert!(is_symlink(&modules_dir.join("baz")));
+    assert!(!modules_dir.join("bar").exists());
+    assert_eq!(
+        fs::read_link(modules_dir.join(".pnpm/foo@1.0.0/node_modules/bar")).unwrap(),
+        Path::new("../../bar@1.1.0/node_modules/bar")
+    );
+    assert!(modules_dir.join(".bin/foo").exists());
+    assert_eq!(
+        fs::read_to_string(modules_dir.join(".pnpm/baz@1.0.0/node_modules/baz/built.txt")).unwrap(),
+        "built\n"
+    );
+    assert_eq!(
+        fs::read_to_string(project.dir.join("postinstall.txt")).unwrap(),
+        "done\n"
+    );
+    assert!(modules_dir.join(".pnpm/lock.yaml").exists());
+    assert_eq!(
+        fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap(),
+        format!(
+            "lockfileVersion: 5.3
+specifiers:
+  baz: ^1.0.0
+  foo: ^1.0.0
+dependencies:
+  foo: 1.0.0
+devDependencies:
+  baz: 1.0.0
+packages:
+  /bar/1.1.0:
+    resolution:
+      integrity: {}
+    dev: false
+  /baz/1.0.0:
+    resolution:
+      integrity: {}
+    requiresBuild: true
+    dev: true
+  /foo/1.0.0:
+    resolution:
+      integrity: {}
+    hasBin: true
+    dependencies:
+      bar: 1.1.0
+    dev: false
+",
+            integrities["bar@1.1.0"], integrities["baz@1.0.0"], integrities["foo@1.0.0"]
+        )
+    );
+}
+
+#[test]
+fn install_only_prod_dependencies_without_scripts() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+
+    assert_success(&pnpm(&project, &["install", "--prod", "--ignore-scripts"]));
+
+    let modules_dir = project.dir.join("node_modules");
+    assert!(is_symlink(&modules_dir.join("foo")));
+    assert!(!modules_dir.join("baz").exists());
+    assert!(!modules_dir.join(".pnpm/baz@1.0.0").exists());
+    assert!(!project.dir.join("postinstall.txt").exists());
+    // the lockfile has every dependency
+    assert!(fs::read_to_string(project.dir.join("pnpm-lock.yaml"))
+        .unwrap()
+        .contains("/baz/1.0.0"));
+}
+
+#[test]
+fn frozen_lockfile() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+
+    let output = pnpm(&project, &["install", "--frozen-lockfile"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NO_LOCKFILE"));
+
+    assert_success(&pnpm(&project, &["install", "--lockfile-only"]));
+    assert!(project.dir.join("pnpm-lock.yaml").exists());
+    assert!(!project.dir.join("node_modules").exists());
+
+    assert_success(&pnpm(&project, &["install", "--frozen-lockfile"]));
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+
+    let manifest = fs::read_to_string(project.dir.join("package.json")).unwrap();
+    fs::write(
+        project.dir.join("package.json"),
+        manifest.replace("\"foo\":\"^1.0.0\"", "\"foo\":\"^1.0.0\",\"bar\":\"1.0.0\""),
+    )
+    .unwrap();
+    let output = pnpm(&project, &["install", "--frozen-lockfile"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "OUTDATED_LOCKFILE: Cannot install with \"frozen-lockfile\" because pnpm-lock.yaml is not up-to-date with package.json"
+    ));
+}
+
+#[test]
+fn install_only_what_changed_since_the_previous_install() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+    fs::remove_dir_all(project.home.path().join("store")).unwrap();
+    fs::remove_dir_all(project.dir.join("node_modules/.pnpm/bar@1.1.0")).unwrap();
+    let requests = registry.requests.load(Ordering::SeqCst);
+
+    // only the tarball of the removed package is downloaded, without its metadata
+    assert_success(&pnpm(&project, &["install", "--frozen-lockfile"]));
+    assert!(project
+        .dir
+        .join("node_modules/.pnpm/bar@1.1.0/node_modules/bar/package.json")
+        .exists());
+    assert_eq!(registry.requests.load(Ordering::SeqCst), requests + 1);
+}
+
+#[test]
+fn install_offline_from_store() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+    fs::remove_dir_all(project.dir.join("node_modules")).unwrap();
+    fs::remove_file(project.dir.join("pnpm-lock.yaml")).unwrap();
+    let requests = registry.requests.load(Ordering::SeqCst);
+
+    // the metadata comes from the cache and the tarballs from the store
+    assert_success(&pnpm(&project, &["install", "--offline"]));
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+    assert_eq!(registry.requests.load(Ordering::SeqCst), requests);
+}
+
+#[test]
+fn prefer_the_versions_of_shallower_dependencies() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    fs::write(
+        project.dir.join("package.json"),
+        r#"{ "name": "project", "dependencies": { "bar": "1.0.0", "foo": "^1.0.0" } }"#,
+    )
+    .unwrap();
+
+    assert_success(&pnpm(&project, &["install", "--lockfile-only"]));
+
+    let lockfile = fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap();
+    // foo depends on bar@^1.0.0, which the direct dependency on bar already satisfies
+    assert!(lockfile.contains("  /foo/1.0.0:\n"));
+    assert!(lockfile.contains("    dependencies:\n      bar: 1.0.0\n"));
+    assert!(!lockfile.contains("/bar/1.1.0"));
+}
+
+#[test]
+fn custom_virtual_store_and_modules_dirs() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    fs::write(
+        project.dir.join("package.json"),
+        r#"{ "name": "project", "dependencies": { "foo": "^1.0.0", "qar": "npm:bar@1.0.0" } }"#,
+    )
+    .unwrap();
+    let npmrc = fs::read_to_string(project.dir.join(".npmrc")).unwrap();
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!("{}modules-dir=deps\nvirtual-store-dir=.store\n", npmrc),
+    )
+    .unwrap();
+
+    assert_success(&pnpm(&project, &["install"]));
+
+    let modules_dir = project.dir.join("deps");
+    assert!(!project.dir.join("node_modules").exists());
+    assert_eq!(
+        fs::read_link(modules_dir.join("foo")).unwrap(),
+        Path::new("../.store/foo@1.0.0/node_modules/foo")
+    );
+    assert_eq!(
+        fs::read_link(modules_dir.join("qar")).unwrap(),
+        Path::new("../.store/bar@1.0.0/node_modules/bar")
+    );
+    assert_eq!(
+        fs::read_link(project.dir.join(".store/foo@1.0.0/node_modules/bar")).unwrap(),
+        Path::new("../../bar@1.0.0/node_modules/bar")
+    );
+    assert!(modules_dir.join(".bin/foo").exists());
+    assert!(project.dir.join(".store/lock.yaml").exists());
+}
+
+#[test]
+fn hoist_dependencies() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    let npmrc = fs::read_to_string(project.dir.join(".npmrc")).unwrap();
+
+    assert_success(&pnpm(&project, &["install"]));
+    let modules_dir = project.dir.join("node_modules");
+    assert_eq!(
+        fs::read_link(modules_dir.join(".pnpm/node_modules/bar")).unwrap(),
+        Path::new("../bar@1.1.0/node_modules/bar")
+    );
+    assert!(!modules_dir.join("bar").exists());
+
+    // the modules directory is recreated when a pattern is set to another value
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!(
+            "{}public-hoist-pattern=bar\nconfirm-modules-purge=false\n",
+            npmrc
+        ),
+    )
+    .unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+    assert_eq!(
+        fs::read_link(modules_dir.join("bar")).unwrap(),
+        Path::new(".pnpm/bar@1.1.0/node_modules/bar")
+    );
+    assert!(!is_symlink(&modules_dir.join(".pnpm/node_modules/bar")));
+
+    // the public-hoist-pattern of the previous install is kept, as it is not set anymore
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!("{}confirm-modules-purge=false\n", npmrc),
+    )
+    .unwrap();
+    let output = pnpm(&project, &["install"]);
+    assert_success(&output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Recreating"));
+    assert!(is_symlink(&modules_dir.join("bar")));
+
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!("{}hoist=false\nconfirm-modules-purge=false\n", npmrc),
+    )
+    .unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+    assert!(!is_symlink(&modules_dir.join("bar")));
+    assert!(!is_symlink(&modules_dir.join(".pnpm/node_modules/bar")));
+    assert!(is_symlink(&modules_dir.join("foo")));
+}
+
+#[test]
+fn hoisted_node_linker() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    let npmrc = fs::read_to_string(project.dir.join(".npmrc")).unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+
+    // bar@1.1.0 that foo depends on conflicts with the bar of the project
+    fs::write(
+        project.dir.join("package.json"),
+        json!({
+            "name": "project",
+            "version": "1.0.0",
+            "dependencies": { "bar": "1.0.0", "foo": "^1.0.0" },
+            "devDependencies": { "baz": "^1.0.0" },
+        })
+        .to_string(),
+    )
+    .unwrap();
+    // the modules directory of the isolated node-linker is recreated
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!(
+            "{}node-linker=hoisted\nconfirm-modules-purge=false\n",
+            npmrc
+        ),
+    )
+    .unwrap();
+    let output = pnpm(&project, &["install"]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Recreating"));
+    let modules_dir = project.dir.join("node_modules");
+    for dir in ["foo", "bar", "baz", "foo/node_modules/bar"] {
+        assert!(modules_dir.join(dir).is_dir() && !is_symlink(&modules_dir.join(dir)));
+    }
+    let version = |dir: &str| {
+        let manifest = fs::read_to_string(modules_dir.join(dir).join("package.json")).unwrap();
+        serde_json::from_str::<serde_json::Value>(&manifest).unwrap()["version"].clone()
+    };
+    assert_eq!(version("bar"), "1.0.0");
+    assert_eq!(version("foo/node_modules/bar"), "1.1.0");
+    assert!(modules_dir.join("baz/built.txt").exists());
+    assert!(modules_dir.join(".bin/foo").exists());
+
+    // the bar of the project is removed, so the one of foo goes to the top
+    fs::write(
+        project.dir.join("package.json"),
+        json!({
+            "name": "project",
+            "version": "1.0.0",
+            "dependencies": { "foo": "^1.0.0" },
+        })
+        .to_string(),
+    )
+    .unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+    assert_eq!(version("bar"), "1.1.0");
+    assert!(!modules_dir.join("foo/node_modules/bar").exists());
+    assert!(!modules_dir.join("baz").exists());
+}
+
+#[test]
+fn record_the_modules_state_and_purge_incompatible_modules() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    let npmrc = fs::read_to_string(project.dir.join(".npmrc")).unwrap();
+    let modules_yaml = project.dir.join("node_modules/.modules.yaml");
+
+    assert_success(&pnpm(&project, &["install", "--ignore-scripts"]));
+    let modules = fs::read_to_string(&modules_yaml).unwrap();
+    assert!(modules.contains("layoutVersion: 5\n"));
+    assert!(modules.contains("virtualStoreDir: \".pnpm\"\n"));
+    assert!(modules.contains("pendingBuilds:\n  - /baz/1.0.0\n"));
+    assert!(modules.contains(&format!(
+        "storeDir: {}",
+        project.home.path().join("store").display()
+    )));
+
+    let other_store = project.home.path().join("other-store");
+    fs::write(
+        project.dir.join(".npmrc"),
+        npmrc.replace("/store\n", "/other-store\n"),
+    )
+    .unwrap();
+    let output = pnpm(&project, &["install"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("ABORTED_REMOVE_MODULES_DIR_NO_TTY"));
+    assert!(project.dir.join("node_modules/foo").exists());
+
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!(
+            "{}confirm-modules-purge=false\n",
+            npmrc.replace("/store\n", "/other-store\n")
+        ),
+    )
+    .unwrap();
+    let output = pnpm(&project, &["install"]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Recreating"));
+    let modules = fs::read_to_string(&modules_yaml).unwrap();
+    assert!(modules.contains(&format!("storeDir: {}", other_store.display())));
+    assert!(!modules.contains("pendingBuilds:\n  -"));
+    assert!(project.dir.join("node_modules/baz/built.txt").exists());
+}
+
+#[test]
+fn reuse_the_side_effects_of_builds() {
+    let mut packages = packages();
+    packages.push(Package {
+        name: "qux",
+        version: "1.0.0",
+        manifest: json!({
+            "name": "qux",
+            "version": "1.0.0",
+            "scripts": {
+                "postinstall": "echo built > built.txt && echo qux >> \"$INIT_CWD/builds.txt\"",
+            },
+        }),
+        files: &[],
+    });
+    let registry = mock_registry(packages);
+    let project = project(&registry.url);
+    fs::write(
+        project.dir.join("package.json"),
+        json!({ "name": "project", "version": "1.0.0", "dependencies": { "qux": "^1.0.0" } })
+            .to_string(),
+    )
+    .unwrap();
+    let builds = || fs::read_to_string(project.dir.join("builds.txt")).unwrap();
+
+    assert_success(&pnpm(&project, &["install"]));
+    assert_eq!(builds(), "qux\n");
+
+    // the package is imported from the store as it was after its build
+    fs::remove_dir_all(project.dir.join("node_modules")).unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+    assert_eq!(builds(), "qux\n");
+    assert!(project.dir.join("node_modules/qux/built.txt").exists());
+
+    // without the side effects cache, the package is built again
+    fs::remove_dir_all(project.dir.join("node_modules")).unwrap();
+    let npmrc = fs::read_to_string(project.dir.join(".npmrc")).unwrap();
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!("{}side-effects-cache=false\n", npmrc),
+    )
+    .unwrap();
+    assert_success(&pnpm(&project, &["install"]));
+    assert_eq!(builds(), "qux\nqux\n");
+}
+
+#[test]
+fn link_the_projects_of_the_workspace() {
+    let registry = mock_registry(packages());
+    let workspace = workspace(&registry.url);
+    let app_dir = workspace.dir.join("packages/app");
+    fs::write(
+        app_dir.join("package.json"),
+        json!({
+            "name": "app",
+            "version": "1.0.0",
+            "dependencies": { "lib": "workspace:*", "foo": "^1.0.0" },
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    assert_success(&pnpm_in(&workspace, &app_dir, &["install"]));
+
+    assert_eq!(
+        fs::read_link(app_dir.join("node_modules/lib")).unwrap(),
+        Path::new("../../lib")
+    );
+    assert!(is_symlink(&app_dir.join("node_modules/foo")));
+    let lockfile = fs::read_to_string(app_dir.join("pnpm-lock.yaml")).unwrap();
+    assert!(lockfile.contains("specifiers:\n  foo: ^1.0.0\n  lib: \"workspace:*\"\n"));
+    assert!(lockfile.contains("dependencies:\n  foo: 1.0.0\n  lib: \"link:../lib\"\n"));
+
+    // the lockfile is up to date as long as the linked project satisfies the range
+    assert_success(&pnpm_in(
+        &workspace,
+        &app_dir,
+        &["install", "--frozen-lockfile"],
+    ));
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{assert_success, is_symlink, mock_registry, packages, pnpm, project};
+use std::fs;
+use std::sync::atomic::Ordering;
+
+#[test]
+fn remove_dependency() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+    let requests = registry.requests.load(Ordering::SeqCst);
+
+    assert_success(&pnpm(&project, &["remove", "foo"]));
+
+    let modules_dir = project.dir.join("node_modules");
+    assert!(!modules_dir.join("foo").exists());
+    assert!(!is_symlink(&modules_dir.join(".bin/foo")));
+    assert!(!modules_dir.join(".pnpm/foo@1.0.0").exists());
+    assert!(!modules_dir.join(".pnpm/bar@1.1.0").exists());
+    assert!(is_symlink(&modules_dir.join("baz")));
+
+    let manifest = fs::read_to_string(project.dir.join("package.json")).unwrap();
+    assert!(!manifest.contains("\"foo\""));
+    assert!(manifest.contains("\"baz\""));
+    let lockfile = fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap();
+    assert!(!lockfile.contains("foo"));
+    assert!(!lockfile.contains("/bar/1.1.0"));
+    assert!(lockfile.contains("/baz/1.0.0"));
+    // the lockfile was pruned, so nothing was resolved again
+    assert_eq!(registry.requests.load(Ordering::SeqCst), requests);
+}
+
+#[test]
+fn remove_from_dependency_field() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+
+    let output = pnpm(&project, &["remove", "--save-dev", "foo"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "CANNOT_REMOVE_MISSING_DEPS: Cannot remove 'foo': no such dependency found. Available dependencies: baz"
+    ));
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+
+    assert_success(&pnpm(&project, &["remove", "-D", "baz"]));
+    assert!(!project.dir.join("node_modules/baz").exists());
+    assert!(!project.dir.join("node_modules/.pnpm/baz@1.0.0").exists());
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+}
+
+#[test]
+fn keep_the_lockfile_when_the_install_fails() {
+    let registry = mock_registry(packages());
+    let project = project(&registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+    let manifest_path = project.dir.join("package.json");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    fs::write(
+        &manifest_path,
+        manifest.replace("echo done > postinstall.txt", "exit 1"),
+    )
+    .unwrap();
+    let lockfile = fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap();
+
+    assert!(!pnpm(&project, &["remove", "foo"]).status.success());
+
+    assert_eq!(
+        fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap(),
+        lockfile
+    );
+}
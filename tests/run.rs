@@ -0,0 +1,162 @@
+mod common;
+
+use common::{assert_success, mock_registry, pnpm, project, workspace, Package, Project};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::fs;
+
+/// A project with scripts, that doesn't need a registry
+fn project_with_scripts(scripts: serde_json::Value) -> Project {
+    let project = project("http://localhost:1/");
+    fs::write(
+        project.dir.join("package.json"),
+        json!({ "name": "project", "version": "1.0.0", "scripts": scripts }).to_string(),
+    )
+    .unwrap();
+    project
+}
+
+fn read(project: &Project, file: &str) -> String {
+    fs::read_to_string(project.dir.join(file)).unwrap()
+}
+
+#[test]
+fn run_script_with_args_and_env() {
+    let project = project_with_scripts(json!({
+        "build": "printf '%s|' \"$npm_package_name\" \"$npm_lifecycle_event\" > out.txt",
+    }));
+
+    assert_success(&pnpm(&project, &["run", "build", "--watch", "it's"]));
+    assert_eq!(read(&project, "out.txt"), "project|build|--watch|it's|");
+
+    // scripts may be run without `run`
+    assert_success(&pnpm(&project, &["build", "again"]));
+    assert_eq!(read(&project, "out.txt"), "project|build|again|");
+}
+
+#[test]
+fn run_pre_and_post_scripts_when_enabled() {
+    let project = project_with_scripts(json!({
+        "prebuild": "echo pre >> log.txt",
+        "build": "echo build >> log.txt",
+        "postbuild": "echo post >> log.txt",
+    }));
+
+    assert_success(&pnpm(&project, &["run", "build"]));
+    assert_eq!(read(&project, "log.txt"), "build\n");
+
+    fs::remove_file(project.dir.join("log.txt")).unwrap();
+    let npmrc = read(&project, ".npmrc");
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!("{}enable-pre-post-scripts=true\n", npmrc),
+    )
+    .unwrap();
+    assert_success(&pnpm(&project, &["run", "build"]));
+    assert_eq!(read(&project, "log.txt"), "pre\nbuild\npost\n");
+}
+
+#[test]
+fn fail_on_missing_script_unless_if_present() {
+    let project = project_with_scripts(json!({ "test": "exit 3" }));
+
+    let output = pnpm(&project, &["run", "lint"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NO_SCRIPT: Missing script: lint"));
+
+    assert_success(&pnpm(&project, &["run", "--if-present", "lint"]));
+
+    let output = pnpm(&project, &["t"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("LIFECYCLE_ERROR: project@1.0.0 test"));
+
+    let output = pnpm(&project, &["start"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("NO_SCRIPT_OR_SERVER: Missing script start or file server.js"));
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_with_the_bins_of_the_project() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let project = project_with_scripts(json!({}));
+    let bin_dir = project.dir.join("node_modules/.bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let bin = bin_dir.join("hello");
+    fs::write(&bin, "#!/bin/sh\necho \"hello $1\" > hello.txt\n").unwrap();
+    fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert_success(&pnpm(&project, &["exec", "hello", "world"]));
+    assert_eq!(read(&project, "hello.txt"), "hello world\n");
+
+    assert_success(&pnpm(
+        &project,
+        &["exec", "-c", "echo $PNPM_PACKAGE_NAME | tee name.txt"],
+    ));
+    assert_eq!(read(&project, "name.txt"), "project\n");
+
+    let output = pnpm(&project, &["exec", "not-a-command"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("EXEC_ERROR"));
+}
+
+#[cfg(unix)]
+#[test]
+fn dlx_runs_the_bin_of_a_package() {
+    let registry = mock_registry(vec![Package {
+        name: "hello-cli",
+        version: "1.0.0",
+        manifest: json!({ "name": "hello-cli", "version": "1.0.0", "bin": "cli.sh" }),
+        files: &[("cli.sh", "#!/bin/sh\necho \"hello $1\" > dlx.txt\n")],
+    }]);
+    let project = project(&registry.url);
+
+    assert_success(&pnpm(&project, &["dlx", "hello-cli", "world"]));
+    assert_eq!(read(&project, "dlx.txt"), "hello world\n");
+    // nothing is installed in the project
+    assert!(!project.dir.join("node_modules").exists());
+
+    assert_success(&pnpm(
+        &project,
+        &["dlx", "--package", "hello-cli@1", "hello-cli", "again"],
+    ));
+    assert_eq!(read(&project, "dlx.txt"), "hello again\n");
+}
+
+#[test]
+fn run_in_the_workspace_root_only_when_included() {
+    let project = workspace("http://localhost:1/");
+    let script = json!({ "hello": "touch ran.txt" });
+    for (dir, name) in [
+        (".", "project"),
+        ("packages/lib", "lib"),
+        ("packages/app", "app"),
+    ] {
+        fs::write(
+            project.dir.join(dir).join("package.json"),
+            json!({ "name": name, "version": "1.0.0", "scripts": script }).to_string(),
+        )
+        .unwrap();
+    }
+    let ran = |dir: &str| {
+        let file = project.dir.join(dir).join("ran.txt");
+        let ran = file.exists();
+        fs::remove_file(file).ok();
+        ran
+    };
+
+    assert_success(&pnpm(&project, &["run", "-r", "hello"]));
+    assert!(ran("packages/lib") && ran("packages/app"));
+    assert!(!ran("."));
+
+    assert_success(&pnpm(
+        &project,
+        &["run", "-r", "--include-workspace-root", "hello"],
+    ));
+    assert!(ran("packages/lib") && ran("packages/app") && ran("."));
+
+    assert_success(&pnpm(&project, &["run", "--filter", "project", "hello"]));
+    assert!(ran("."));
+    assert!(!ran("packages/lib"));
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::{
+    assert_success, is_symlink, mock_registry, packages, pnpm, project, MockRegistry, Package,
+    Project,
+};
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use std::fs;
+
+/// The packages of `packages()`, except that bar only has its first version
+fn old_packages() -> Vec<Package> {
+    packages()
+        .into_iter()
+        .filter(|package| (package.name, package.version) != ("bar", "1.1.0"))
+        .collect()
+}
+
+/// The packages of `packages()` with newer versions of foo and baz
+fn new_packages() -> Vec<Package> {
+    let mut packages = packages();
+    packages.push(Package {
+        name: "foo",
+        version: "1.1.0",
+        manifest: json!({
+            "name": "foo",
+            "version": "1.1.0",
+            "dependencies": { "bar": "^1.0.0" },
+        }),
+        files: &[],
+    });
+    packages.push(Package {
+        name: "baz",
+        version: "2.0.0",
+        manifest: json!({ "name": "baz", "version": "2.0.0" }),
+        files: &[],
+    });
+    packages
+}
+
+/// A project installed from the old packages, that then uses a registry with the new ones
+fn installed_project() -> (Project, MockRegistry) {
+    let old_registry = mock_registry(old_packages());
+    let project = project(&old_registry.url);
+    assert_success(&pnpm(&project, &["install"]));
+
+    let registry = mock_registry(new_packages());
+    fs::write(
+        project.dir.join(".npmrc"),
+        format!(
+            "registry={}\nstore-dir={}\n",
+            registry.url,
+            project.home.path().join("store").display()
+        ),
+    )
+    .unwrap();
+    (project, registry)
+}
+
+fn read_lockfile(project: &Project) -> String {
+    fs::read_to_string(project.dir.join("pnpm-lock.yaml")).unwrap()
+}
+
+fn read_manifest(project: &Project) -> serde_json::Value {
+    serde_json::from_str(&fs::read_to_string(project.dir.join("package.json")).unwrap()).unwrap()
+}
+
+fn is_installed(project: &Project, dir_name: &str) -> bool {
+    project
+        .dir
+        .join("node_modules/.pnpm")
+        .join(dir_name)
+        .exists()
+}
+
+#[test]
+fn update_within_ranges() {
+    let (project, _registry) = installed_project();
+
+    assert_success(&pnpm(&project, &["update", "--depth", "0"]));
+    let lockfile = read_lockfile(&project);
+    assert!(lockfile.contains("/foo/1.1.0"));
+    // the dependencies of foo keep their locked versions
+    assert!(lockfile.contains("/bar/1.0.0"));
+    assert!(!lockfile.contains("/bar/1.1.0"));
+    // baz 2.0.0 is not in the range
+    assert!(lockfile.contains("/baz/1.0.0"));
+    assert!(is_installed(&project, "foo@1.1.0"));
+    assert!(!is_installed(&project, "foo@1.0.0"));
+
+    assert_success(&pnpm(&project, &["update"]));
+    let lockfile = read_lockfile(&project);
+    assert!(lockfile.contains("/bar/1.1.0"));
+    assert!(!lockfile.contains("/bar/1.0.0"));
+    assert!(is_installed(&project, "bar@1.1.0"));
+
+    let manifest = read_manifest(&project);
+    assert_eq!(manifest["dependencies"], json!({ "foo": "^1.0.0" }));
+    assert_eq!(manifest["devDependencies"], json!({ "baz": "^1.0.0" }));
+}
+
+#[test]
+fn update_matching_packages() {
+    let (project, _registry) = installed_project();
+
+    assert_success(&pnpm(&project, &["up", "ba*"]));
+
+    let lockfile = read_lockfile(&project);
+    assert!(lockfile.contains("/foo/1.0.0"));
+    assert!(lockfile.contains("/bar/1.1.0"));
+    assert!(!lockfile.contains("/bar/1.0.0"));
+    assert!(is_symlink(&project.dir.join("node_modules/foo")));
+
+    let output = pnpm(&project, &["update", "qar"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "NO_PACKAGE_IN_DEPENDENCIES: None of the specified packages were found in the dependencies"
+    ));
+}
+
+#[test]
+fn update_to_latest() {
+    let (project, _registry) = installed_project();
+
+    assert_success(&pnpm(&project, &["update", "--latest", "baz"]));
+
+    let manifest = read_manifest(&project);
+    assert_eq!(manifest["dependencies"], json!({ "foo": "^1.0.0" }));
+    assert_eq!(manifest["devDependencies"], json!({ "baz": "^2.0.0" }));
+    let lockfile = read_lockfile(&project);
+    assert!(lockfile.contains("/baz/2.0.0"));
+    assert!(lockfile.contains("/foo/1.0.0"));
+    assert!(is_installed(&project, "baz@2.0.0"));
+}